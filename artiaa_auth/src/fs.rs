@@ -13,7 +13,7 @@ use std::{
 pub fn try_read<P: AsRef<Path>>(path: P) -> ArtifactoryAuthResult<Option<Vec<u8>>> {
     let path = path.as_ref();
 
-    match fs::read(&path).map(Some) {
+    match fs::read(path).map(Some) {
         Ok(contents) => Ok(contents),
         Err(err) => {
             if err.kind() == io::ErrorKind::NotFound {
@@ -1,6 +1,8 @@
 use std::{
     env::{self, consts::EXE_SUFFIX},
-    path::Path,
+    io::{self, Read},
+    path::{Path, PathBuf},
+    str::FromStr,
 };
 
 use crate::{
@@ -8,12 +10,311 @@ use crate::{
     fs,
 };
 
-pub fn add_self_alias(name: &str, bin_path: &Path) -> ForemanResult<()> {
+/// First line after the interpreter line of every launcher script, used to
+/// recognize launchers among the aliases.
+const LAUNCHER_MARKER: &str = "Launcher generated by Foreman";
+
+#[cfg(windows)]
+const SCRIPT_SUFFIX: &str = ".cmd";
+#[cfg(not(windows))]
+const SCRIPT_SUFFIX: &str = "";
+
+/// How the alias of a tool in the bin directory invokes Foreman.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum AliasStyle {
+    /// A full copy of the Foreman executable.
+    Copy,
+    /// A symbolic link to the Foreman executable.
+    Symlink,
+    /// A small script running `foreman run <tool>`.
+    Script,
+}
+
+impl AliasStyle {
+    pub const VARIANTS: &'static [&'static str] = &["copy", "symlink", "script"];
+}
+
+impl FromStr for AliasStyle {
+    type Err = String;
+
+    fn from_str(value: &str) -> Result<Self, Self::Err> {
+        match value {
+            "copy" => Ok(Self::Copy),
+            "symlink" => Ok(Self::Symlink),
+            "script" => Ok(Self::Script),
+            _ => Err(format!(
+                "unknown alias style `{}`, expected one of: {}",
+                value,
+                Self::VARIANTS.join(", ")
+            )),
+        }
+    }
+}
+
+pub fn add_self_alias(name: &str, bin_path: &Path, style: AliasStyle) -> ForemanResult<()> {
     let foreman_path = env::current_exe().map_err(|err| {
         ForemanError::io_error_with_context(err, "unable to obtain foreman executable location")
     })?;
-    let mut alias_path = bin_path.to_owned();
-    alias_path.push(format!("{}{}", name, EXE_SUFFIX));
+    write_alias(&foreman_path, name, bin_path, style)
+}
+
+/// Removes the alias `name` from `bin_path`, whatever its style. Missing
+/// aliases are not an error.
+pub fn remove_self_alias(name: &str, bin_path: &Path) -> ForemanResult<()> {
+    remove_alias(&bin_path.join(format!("{}{}", name, EXE_SUFFIX)))?;
+    remove_alias(&bin_path.join(format!("{}{}", name, SCRIPT_SUFFIX)))
+}
+
+/// The path of the alias `name` in `bin_path`, whatever its style, or `None`
+/// when there is none.
+pub fn find_alias(name: &str, bin_path: &Path) -> Option<PathBuf> {
+    [EXE_SUFFIX, SCRIPT_SUFFIX]
+        .iter()
+        .map(|suffix| bin_path.join(format!("{}{}", name, suffix)))
+        .find(|path| path.is_file())
+}
+
+pub(crate) fn write_alias(
+    foreman_path: &Path,
+    name: &str,
+    bin_path: &Path,
+    style: AliasStyle,
+) -> ForemanResult<()> {
+    let exe_path = bin_path.join(format!("{}{}", name, EXE_SUFFIX));
+    let script_path = bin_path.join(format!("{}{}", name, SCRIPT_SUFFIX));
+
+    // Remove aliases of every style first: copying onto a symbolic link would
+    // overwrite Foreman itself, and on Windows a leftover `.exe` would shadow a
+    // new `.cmd` launcher.
+    remove_alias(&exe_path)?;
+    remove_alias(&script_path)?;
+
+    match style {
+        AliasStyle::Copy => {
+            fs::copy(foreman_path, &exe_path)?;
+            verify_alias(foreman_path, &exe_path)
+        }
+        AliasStyle::Symlink => {
+            symlink(foreman_path, &exe_path).map_err(|err| {
+                ForemanError::io_error_with_context(
+                    err,
+                    format!("unable to create symbolic link {}", exe_path.display()),
+                )
+            })?;
+            verify_alias(foreman_path, &exe_path)
+        }
+        AliasStyle::Script => write_launcher(foreman_path, name, &script_path),
+    }
+}
+
+fn remove_alias(path: &Path) -> ForemanResult<()> {
+    match std::fs::remove_file(path) {
+        Ok(()) => Ok(()),
+        Err(err) if err.kind() == io::ErrorKind::NotFound => Ok(()),
+        Err(err) => Err(ForemanError::io_error_with_context(
+            err,
+            format!("unable to remove alias {}", path.display()),
+        )),
+    }
+}
+
+#[cfg(unix)]
+fn symlink(original: &Path, link: &Path) -> io::Result<()> {
+    std::os::unix::fs::symlink(original, link)
+}
+
+#[cfg(windows)]
+fn symlink(original: &Path, link: &Path) -> io::Result<()> {
+    std::os::windows::fs::symlink_file(original, link)
+}
+
+fn write_launcher(foreman_path: &Path, name: &str, script_path: &Path) -> ForemanResult<()> {
+    fs::write(script_path, launcher_script(foreman_path, name))?;
+
+    #[cfg(unix)]
+    {
+        use std::os::unix::fs::PermissionsExt;
+
+        fs::set_permissions(script_path, fs::Permissions::from_mode(0o755))?;
+    }
+
+    Ok(())
+}
+
+#[cfg(not(windows))]
+fn launcher_script(foreman_path: &Path, name: &str) -> String {
+    fn quote(value: &str) -> String {
+        format!("'{}'", value.replace('\'', "'\\''"))
+    }
+
+    format!(
+        "#!/bin/sh\n# {}\nexec {} run {} -- \"$@\"\n",
+        LAUNCHER_MARKER,
+        quote(&foreman_path.to_string_lossy()),
+        quote(name)
+    )
+}
+
+#[cfg(windows)]
+fn launcher_script(foreman_path: &Path, name: &str) -> String {
+    format!(
+        "@echo off\r\nrem {}\r\n\"{}\" run \"{}\" -- %*\r\n",
+        LAUNCHER_MARKER,
+        foreman_path.display(),
+        name
+    )
+}
+
+/// Whether the file at `path` is a launcher script written by Foreman rather
+/// than a copy of the Foreman executable.
+pub fn is_launcher(path: &Path) -> bool {
+    let mut header = Vec::new();
+    let read = std::fs::File::open(path).and_then(|file| file.take(128).read_to_end(&mut header));
+
+    read.is_ok() && String::from_utf8_lossy(&header).contains(LAUNCHER_MARKER)
+}
+
+/// Checks that the alias written at `alias_path` is a full copy of
+/// `foreman_path`. Antivirus software or a full disk can leave a truncated or
+/// empty file behind, which would then fail in confusing ways when invoked, so
+/// an incomplete alias is removed and reported instead.
+fn verify_alias(foreman_path: &Path, alias_path: &Path) -> ForemanResult<()> {
+    let expected_size = std::fs::metadata(foreman_path)
+        .map_err(|err| ForemanError::read_error(err, foreman_path))?
+        .len();
+    let actual_size = match std::fs::metadata(alias_path) {
+        Ok(metadata) => metadata.len(),
+        Err(err) if err.kind() == io::ErrorKind::NotFound => 0,
+        Err(err) => return Err(ForemanError::read_error(err, alias_path)),
+    };
+
+    if actual_size == expected_size {
+        return Ok(());
+    }
+
+    if let Err(err) = std::fs::remove_file(alias_path) {
+        log::debug!(
+            "unable to remove incomplete alias {}: {}",
+            alias_path.display(),
+            err
+        );
+    }
+
+    Err(ForemanError::IncompleteAlias {
+        path: alias_path.to_owned(),
+        expected_size,
+        actual_size,
+    })
+}
+
+#[cfg(test)]
+mod test {
+    use tempfile::tempdir;
+
+    use super::*;
+
+    #[test]
+    fn complete_alias_is_kept() {
+        let directory = tempdir().unwrap();
+        let foreman_path = directory.path().join("foreman");
+        let alias_path = directory.path().join("tool");
+        std::fs::write(&foreman_path, b"foreman binary").unwrap();
+        std::fs::write(&alias_path, b"foreman binary").unwrap();
+
+        verify_alias(&foreman_path, &alias_path).unwrap();
+        assert!(alias_path.exists());
+    }
+
+    #[test]
+    fn truncated_alias_is_removed_and_reported() {
+        let directory = tempdir().unwrap();
+        let foreman_path = directory.path().join("foreman");
+        let alias_path = directory.path().join("tool");
+        std::fs::write(&foreman_path, b"foreman binary").unwrap();
+        std::fs::write(&alias_path, b"").unwrap();
+
+        let err = verify_alias(&foreman_path, &alias_path).unwrap_err();
+        assert!(matches!(
+            err,
+            ForemanError::IncompleteAlias {
+                expected_size: 14,
+                actual_size: 0,
+                ..
+            }
+        ));
+        assert!(err.to_string().contains("antivirus"));
+        assert!(!alias_path.exists());
+    }
+
+    #[test]
+    fn alias_style_from_str() {
+        assert_eq!("copy".parse(), Ok(AliasStyle::Copy));
+        assert_eq!("symlink".parse(), Ok(AliasStyle::Symlink));
+        assert_eq!("script".parse(), Ok(AliasStyle::Script));
+        assert!("hardlink".parse::<AliasStyle>().is_err());
+    }
+
+    #[test]
+    fn launcher_script_runs_the_tool_through_foreman() {
+        let script = launcher_script(Path::new("/opt/foreman/foreman"), "rojo");
+        assert!(script.contains(LAUNCHER_MARKER));
+        #[cfg(not(windows))]
+        assert!(script.ends_with("exec '/opt/foreman/foreman' run 'rojo' -- \"$@\"\n"));
+        #[cfg(windows)]
+        assert!(script.ends_with("\"/opt/foreman/foreman\" run \"rojo\" -- %*\r\n"));
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn launcher_passes_tool_name_and_arguments_to_foreman() {
+        let directory = tempdir().unwrap();
+        let bin_path = directory.path().join("bin");
+        std::fs::create_dir(&bin_path).unwrap();
+
+        // Stands in for Foreman, printing the arguments it receives.
+        let foreman_path = directory.path().join("fake foreman");
+        std::fs::write(&foreman_path, "#!/bin/sh\necho \"$@\"\n").unwrap();
+        {
+            use std::os::unix::fs::PermissionsExt;
+
+            std::fs::set_permissions(&foreman_path, std::fs::Permissions::from_mode(0o755))
+                .unwrap();
+        }
+
+        write_alias(&foreman_path, "it's-a-tool", &bin_path, AliasStyle::Script).unwrap();
+        let alias_path = bin_path.join("it's-a-tool");
+        assert!(is_launcher(&alias_path));
+
+        let output = std::process::Command::new(&alias_path)
+            .args(["--flag", "two words"])
+            .output()
+            .unwrap();
+        assert_eq!(
+            String::from_utf8(output.stdout).unwrap(),
+            "run it's-a-tool -- --flag two words\n"
+        );
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn changing_style_replaces_the_alias_without_touching_foreman() {
+        let directory = tempdir().unwrap();
+        let foreman_path = directory.path().join("foreman");
+        std::fs::write(&foreman_path, b"foreman binary").unwrap();
+
+        write_alias(&foreman_path, "tool", directory.path(), AliasStyle::Symlink).unwrap();
+        let alias_path = directory.path().join("tool");
+        assert!(std::fs::symlink_metadata(&alias_path)
+            .unwrap()
+            .file_type()
+            .is_symlink());
+
+        write_alias(&foreman_path, "tool", directory.path(), AliasStyle::Script).unwrap();
+        assert!(is_launcher(&alias_path));
+        assert_eq!(std::fs::read(&foreman_path).unwrap(), b"foreman binary");
 
-    fs::copy(foreman_path, alias_path).map(|_| ())
+        write_alias(&foreman_path, "tool", directory.path(), AliasStyle::Copy).unwrap();
+        assert!(!is_launcher(&alias_path));
+        assert_eq!(std::fs::read(&alias_path).unwrap(), b"foreman binary");
+    }
 }
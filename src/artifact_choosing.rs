@@ -3,16 +3,26 @@
 #[cfg(all(target_os = "windows", target_arch = "x86_64"))]
 static PLATFORM_KEYWORDS: &[&str] = &["win64", "windows-x86_64", "windows"];
 
-#[cfg(all(target_os = "windows", target_arch = "i686"))]
+#[cfg(all(target_os = "windows", target_arch = "x86"))]
 static PLATFORM_KEYWORDS: &[&str] = &["win32", "windows-i686"];
 
 #[cfg(all(target_os = "windows", target_arch = "aarch64"))]
 static PLATFORM_KEYWORDS: &[&str] = &["windows-aarch64"];
 
+// Universal binaries, which run on every Mac, come after the host's own
+// architecture but before assets that only name the operating system.
 #[cfg(all(target_os = "macos", target_arch = "x86_64"))]
-static PLATFORM_KEYWORDS: &[&str] = &["macos-x86_64", "darwin-x86_64", "macos", "darwin"];
+static PLATFORM_KEYWORDS: &[&str] = &[
+    "macos-x86_64",
+    "darwin-x86_64",
+    "macos-universal2",
+    "macos-universal",
+    "darwin-universal",
+    "macos",
+    "darwin",
+];
 
-#[cfg(all(target_os = "macos", target_arch = "i686"))]
+#[cfg(all(target_os = "macos", target_arch = "x86"))]
 static PLATFORM_KEYWORDS: &[&str] = &["macos-i686", "darwin-i686"];
 
 #[cfg(all(target_os = "macos", target_arch = "aarch64"))]
@@ -21,6 +31,9 @@ static PLATFORM_KEYWORDS: &[&str] = &[
     "darwin-arm64",
     "macos-aarch64",
     "darwin-aarch64",
+    "macos-universal2",
+    "macos-universal",
+    "darwin-universal",
     "macos",
     "darwin",
 ];
@@ -28,7 +41,7 @@ static PLATFORM_KEYWORDS: &[&str] = &[
 #[cfg(all(target_os = "linux", target_arch = "x86_64"))]
 static PLATFORM_KEYWORDS: &[&str] = &["linux-x86_64", "linux"];
 
-#[cfg(all(target_os = "linux", target_arch = "i686"))]
+#[cfg(all(target_os = "linux", target_arch = "x86"))]
 static PLATFORM_KEYWORDS: &[&str] = &["linux-i686"];
 
 #[cfg(all(target_os = "linux", target_arch = "aarch64"))]
@@ -36,10 +49,15 @@ static PLATFORM_KEYWORDS: &[&str] = &["linux-arm64", "linux-aarch64"];
 
 #[cfg(all(
     target_os = "linux",
-    not(any(target_arch = "x86_64", target_arch = "aarch64", target_arch = "i686"))
+    not(any(target_arch = "x86_64", target_arch = "aarch64", target_arch = "x86"))
 ))]
 static PLATFORM_KEYWORDS: &[&str] = &["linux"];
 
+// Platforms Foreman does not recognize rely on the `platform_keywords`
+// configuration key instead.
+#[cfg(not(any(target_os = "windows", target_os = "macos", target_os = "linux")))]
+static PLATFORM_KEYWORDS: &[&str] = &[];
+
 pub fn platform_keywords() -> &'static [&'static str] {
     PLATFORM_KEYWORDS
 }
@@ -36,16 +36,39 @@ pub fn generate_artifactory_path<S: Into<String>>(
     ))
 }
 
+/// The paths for every valid operating system and architecture pair, in the
+/// order of `VALID_OS`, then `VALID_ARCH`.
+pub fn generate_all_artifactory_paths(
+    repo: &str,
+    tool_name: &str,
+    version: &str,
+) -> ForemanResult<Vec<String>> {
+    VALID_OS
+        .iter()
+        .flat_map(|operating_system| {
+            VALID_ARCH.iter().map(move |architecture| {
+                generate_artifactory_path(
+                    repo,
+                    tool_name,
+                    version,
+                    operating_system,
+                    Some(architecture),
+                )
+            })
+        })
+        .collect()
+}
+
 fn check_valid_os(operating_system: &str) -> ForemanResult<()> {
     if !VALID_OS.contains(&operating_system) {
-        return Err(ForemanError::io_error_with_context(
+        Err(ForemanError::io_error_with_context(
             Error::new(ErrorKind::InvalidInput, "Invalid Argument"),
             format!(
                 "Invalid operating system: {}. Please input a valid operating system: {}",
                 operating_system,
                 VALID_OS.join(", ")
             ),
-        ));
+        ))
     } else {
         Ok(())
     }
@@ -53,14 +76,14 @@ fn check_valid_os(operating_system: &str) -> ForemanResult<()> {
 
 fn check_valid_arch(architecture: &str) -> ForemanResult<()> {
     if !VALID_ARCH.contains(&architecture) {
-        return Err(ForemanError::io_error_with_context(
+        Err(ForemanError::io_error_with_context(
             Error::new(ErrorKind::InvalidInput, "Invalid Argument"),
             format!(
                 "Invalid architecture: {}. Please input a valid architecture: {}",
                 architecture,
                 VALID_ARCH.join(", ")
             ),
-        ));
+        ))
     } else {
         Ok(())
     }
@@ -86,7 +109,7 @@ fn check_valid_version(version: &str) -> ForemanResult<()> {
 
 #[cfg(test)]
 mod test {
-    use super::generate_artifactory_path;
+    use super::{generate_all_artifactory_paths, generate_artifactory_path};
 
     #[test]
     fn simple_path() {
@@ -147,4 +170,32 @@ mod test {
             "Invalid architecture: fake_arch. Please input a valid architecture: x86_64, arm64, aarch64, i686: Invalid Argument".to_string()
         );
     }
+
+    #[test]
+    fn all_platforms() {
+        let paths = generate_all_artifactory_paths("repo", "tool_name", "v0.1.0").unwrap();
+        let prefix = "artifactory/repo/tool_name/v0.1.0/tool_name-v0.1.0";
+        assert_eq!(
+            paths,
+            vec![
+                format!("{}-windows-x86_64.zip", prefix),
+                format!("{}-windows-arm64.zip", prefix),
+                format!("{}-windows-aarch64.zip", prefix),
+                format!("{}-windows-i686.zip", prefix),
+                format!("{}-macos-x86_64.zip", prefix),
+                format!("{}-macos-arm64.zip", prefix),
+                format!("{}-macos-aarch64.zip", prefix),
+                format!("{}-macos-i686.zip", prefix),
+                format!("{}-linux-x86_64.zip", prefix),
+                format!("{}-linux-arm64.zip", prefix),
+                format!("{}-linux-aarch64.zip", prefix),
+                format!("{}-linux-i686.zip", prefix),
+            ]
+        );
+    }
+
+    #[test]
+    fn all_platforms_invalid_version() {
+        assert!(generate_all_artifactory_paths("repo", "tool_name", "0.1.0").is_err());
+    }
 }
@@ -1,7 +1,13 @@
-use std::path::Path;
+use std::{
+    collections::{BTreeMap, HashMap},
+    fmt,
+    path::Path,
+    process::{Command, Stdio},
+    sync::{Mutex, OnceLock},
+};
 
 use serde::{Deserialize, Serialize};
-use toml_edit::{value, Document, TomlError};
+use toml_edit::{table, value, Document, TomlError};
 
 use crate::{
     error::{ForemanError, ForemanResult},
@@ -10,28 +16,72 @@ use crate::{
 pub static DEFAULT_AUTH_CONFIG: &str = include_str!("../resources/default-auth.toml");
 
 /// Contains stored user tokens that Foreman can use to download tools.
-#[derive(Debug, Default, Serialize, Deserialize)]
+#[derive(Default, Clone, PartialEq, Serialize, Deserialize)]
 pub struct AuthStore {
     pub github: Option<String>,
     pub gitlab: Option<String>,
+    /// A Bitbucket access token, or an app password written as
+    /// `username:app_password`.
+    pub bitbucket: Option<String>,
+    /// GitHub tokens scoped to an owner (`owner`) or to a single repository
+    /// (`owner/repo`). They take precedence over the `github` token.
+    #[serde(default, rename = "github-repositories")]
+    pub github_repositories: BTreeMap<String, String>,
+    /// GitLab tokens scoped to a group or to a single project, like
+    /// `github-repositories`.
+    #[serde(default, rename = "gitlab-repositories")]
+    pub gitlab_repositories: BTreeMap<String, String>,
+    /// A command printing the GitHub token, run when `github` is not set.
+    /// Only read from the auth store itself, never from a configuration's
+    /// `[auth]` table, so that cloning a project cannot make Foreman run
+    /// commands.
+    #[serde(skip)]
+    pub github_token_command: Option<Vec<String>>,
+    /// A command printing the GitLab token, like `github_token_command`.
+    #[serde(skip)]
+    pub gitlab_token_command: Option<Vec<String>>,
 }
 
 impl AuthStore {
     pub fn load(path: &Path) -> ForemanResult<Self> {
         if let Some(contents) = fs::try_read(path)? {
             log::debug!("Loading auth store");
-            let store: AuthStore = toml::from_slice(&contents)
+            let value: toml::Value = toml::from_slice(&contents)
                 .map_err(|error| ForemanError::auth_parsing(path, error.to_string()))?;
+            let warnings =
+                validate(&value).map_err(|error| ForemanError::auth_parsing(path, error))?;
+            for warning in warnings {
+                log::warn!("{}: {}", path.display(), warning);
+            }
+            let github_token_command = token_command(&value, "github");
+            let gitlab_token_command = token_command(&value, "gitlab");
+            let store = AuthStore {
+                github_token_command,
+                gitlab_token_command,
+                ..value
+                    .try_into()
+                    .map_err(|error| ForemanError::auth_parsing(path, error.to_string()))?
+            };
 
             let mut found_credentials = false;
-            if store.github.is_some() {
+            if store.github.is_some()
+                || !store.github_repositories.is_empty()
+                || store.github_token_command.is_some()
+            {
                 log::debug!("Found GitHub credentials");
                 found_credentials = true;
             }
-            if store.gitlab.is_some() {
+            if store.gitlab.is_some()
+                || !store.gitlab_repositories.is_empty()
+                || store.gitlab_token_command.is_some()
+            {
                 log::debug!("Found GitLab credentials");
                 found_credentials = true;
             }
+            if store.bitbucket.is_some() {
+                log::debug!("Found Bitbucket credentials");
+                found_credentials = true;
+            }
             if !found_credentials {
                 log::debug!("Found no credentials");
             }
@@ -43,24 +93,612 @@ impl AuthStore {
         }
     }
 
+    /// The GitHub token to use for `repo`: the token scoped to the most
+    /// specific prefix of `repo`, or the GitHub-wide token.
+    pub fn github_token(&self, repo: &str) -> Option<&str> {
+        scoped_token(&self.github_repositories, repo).or(self.github.as_deref())
+    }
+
+    /// The GitLab token to use for `repo`: the token scoped to the most
+    /// specific prefix of `repo`, or the GitLab-wide token.
+    pub fn gitlab_token(&self, repo: &str) -> Option<&str> {
+        scoped_token(&self.gitlab_repositories, repo).or(self.gitlab.as_deref())
+    }
+
+    /// Runs the GitHub token command when there is no GitHub token, and keeps
+    /// the token it prints. Each command runs at most once per invocation of
+    /// Foreman, and only once a GitHub tool needs it.
+    pub fn run_github_token_command(&mut self) -> ForemanResult<()> {
+        if self.github.is_none() {
+            if let Some(command) = &self.github_token_command {
+                self.github = Some(command_token(command)?);
+            }
+        }
+        Ok(())
+    }
+
+    /// Runs the GitLab token command, like `run_github_token_command`.
+    pub fn run_gitlab_token_command(&mut self) -> ForemanResult<()> {
+        if self.gitlab.is_none() {
+            if let Some(command) = &self.gitlab_token_command {
+                self.gitlab = Some(command_token(command)?);
+            }
+        }
+        Ok(())
+    }
+
+    /// Adds the tokens of `other` that this store does not have, so that the
+    /// tokens already in this store take precedence.
+    pub fn fill_from(&mut self, other: &AuthStore) {
+        if self.github.is_none() {
+            self.github = other.github.clone();
+        }
+        if self.gitlab.is_none() {
+            self.gitlab = other.gitlab.clone();
+        }
+        if self.bitbucket.is_none() {
+            self.bitbucket = other.bitbucket.clone();
+        }
+
+        for (repo, token) in &other.github_repositories {
+            self.github_repositories
+                .entry(repo.clone())
+                .or_insert_with(|| token.clone());
+        }
+        for (repo, token) in &other.gitlab_repositories {
+            self.gitlab_repositories
+                .entry(repo.clone())
+                .or_insert_with(|| token.clone());
+        }
+    }
+
     pub fn set_github_token(auth_file: &Path, token: &str) -> ForemanResult<()> {
-        Self::set_token(auth_file, "github", token)
+        Self::set_token(auth_file, None, "github", token)
     }
 
     pub fn set_gitlab_token(auth_file: &Path, token: &str) -> ForemanResult<()> {
-        Self::set_token(auth_file, "gitlab", token)
+        Self::set_token(auth_file, None, "gitlab", token)
+    }
+
+    pub fn set_github_repository_token(
+        auth_file: &Path,
+        repo: &str,
+        token: &str,
+    ) -> ForemanResult<()> {
+        Self::set_token(auth_file, Some("github-repositories"), repo, token)
     }
 
-    fn set_token(auth_file: &Path, key: &str, token: &str) -> ForemanResult<()> {
+    pub fn set_gitlab_repository_token(
+        auth_file: &Path,
+        repo: &str,
+        token: &str,
+    ) -> ForemanResult<()> {
+        Self::set_token(auth_file, Some("gitlab-repositories"), repo, token)
+    }
+
+    fn set_token(
+        auth_file: &Path,
+        section: Option<&str>,
+        key: &str,
+        token: &str,
+    ) -> ForemanResult<()> {
         let contents =
             fs::try_read_to_string(auth_file)?.unwrap_or_else(|| DEFAULT_AUTH_CONFIG.to_owned());
 
         let mut store: Document = contents
             .parse()
             .map_err(|err: TomlError| ForemanError::auth_parsing(auth_file, err.to_string()))?;
-        store[key] = value(token);
+        match section {
+            Some(section) => {
+                if !store.contains_key(section) {
+                    store[section] = table();
+                }
+                store[section][key] = value(token);
+            }
+            None => store[key] = value(token),
+        }
 
         let serialized = store.to_string();
         fs::write(auth_file, serialized)
     }
 }
+
+/// What a key of the auth store holds.
+#[derive(Clone, Copy)]
+enum KeyKind {
+    Token,
+    /// A table of tokens, keyed by scope.
+    ScopedTokens,
+    /// A command printing a token, as a string of whitespace-separated
+    /// arguments or as an array of arguments.
+    TokenCommand,
+}
+
+/// Keys an auth store may contain, with what each holds.
+const KNOWN_KEYS: &[(&str, KeyKind)] = &[
+    ("github", KeyKind::Token),
+    ("gitlab", KeyKind::Token),
+    ("bitbucket", KeyKind::Token),
+    ("github-repositories", KeyKind::ScopedTokens),
+    ("gitlab-repositories", KeyKind::ScopedTokens),
+    ("github-token-command", KeyKind::TokenCommand),
+    ("gitlab-token-command", KeyKind::TokenCommand),
+    ("github_token_command", KeyKind::TokenCommand),
+    ("gitlab_token_command", KeyKind::TokenCommand),
+];
+
+/// Checks `value` against the documented structure of the auth store, so that
+/// a misspelled key is reported instead of its token being silently ignored.
+/// Values of the wrong type are errors, while unknown keys, which newer
+/// versions of Foreman may know about, are returned as warnings.
+fn validate(value: &toml::Value) -> Result<Vec<String>, String> {
+    let top_level = value
+        .as_table()
+        .ok_or_else(|| "expected a table of tokens".to_owned())?;
+
+    let mut warnings = Vec::new();
+    for (key, value) in top_level {
+        let kind = match KNOWN_KEYS.iter().find(|(known, _)| known == key) {
+            Some((_, kind)) => *kind,
+            None => {
+                let mut message = format!("ignoring unknown key `{}`", key);
+                if let Some(suggestion) = suggest(key, KNOWN_KEYS.iter().map(|(known, _)| *known)) {
+                    message.push_str(&format!(", did you mean `{}`?", suggestion));
+                }
+                warnings.push(message);
+                continue;
+            }
+        };
+
+        match kind {
+            KeyKind::ScopedTokens => {
+                let tokens = value.as_table().ok_or_else(|| {
+                    format!("`{}` must be a table, found {}", key, value.type_str())
+                })?;
+                for (scope, token) in tokens {
+                    if !token.is_str() {
+                        return Err(format!(
+                            "`{}.\"{}\"` must be a string, found {}",
+                            key,
+                            scope,
+                            token.type_str()
+                        ));
+                    }
+                }
+            }
+            KeyKind::Token if !value.is_str() => {
+                return Err(format!(
+                    "`{}` must be a string, found {}",
+                    key,
+                    value.type_str()
+                ));
+            }
+            KeyKind::TokenCommand if command_arguments(value).is_none() => {
+                return Err(format!(
+                    "`{}` must be a command, as a string or an array of strings, found {}",
+                    key,
+                    value.type_str()
+                ));
+            }
+            KeyKind::Token | KeyKind::TokenCommand => {}
+        }
+    }
+
+    Ok(warnings)
+}
+
+/// The arguments of a token command, written either as a string of
+/// whitespace-separated arguments or as an array of arguments, if `value` is a
+/// non-empty command.
+fn command_arguments(value: &toml::Value) -> Option<Vec<String>> {
+    let arguments: Vec<String> = match value {
+        toml::Value::String(command) => command.split_whitespace().map(str::to_owned).collect(),
+        toml::Value::Array(arguments) => arguments
+            .iter()
+            .map(|argument| argument.as_str().map(str::to_owned))
+            .collect::<Option<_>>()?,
+        _ => return None,
+    };
+
+    (!arguments.is_empty()).then_some(arguments)
+}
+
+/// The token command of `provider` in the auth store `value`, spelled either
+/// `<provider>-token-command` or `<provider>_token_command`.
+fn token_command(value: &toml::Value, provider: &str) -> Option<Vec<String>> {
+    ["-token-command", "_token_command"]
+        .iter()
+        .find_map(|suffix| value.get(format!("{}{}", provider, suffix)))
+        .and_then(command_arguments)
+}
+
+/// Runs a token command and returns the token it prints, trimmed. The command
+/// is spawned directly rather than through a shell, so its arguments are never
+/// interpreted. Its standard error goes to the terminal, so that helpers can
+/// prompt for a password or explain why they failed.
+fn command_token(arguments: &[String]) -> ForemanResult<String> {
+    static TOKENS: OnceLock<Mutex<HashMap<Vec<String>, String>>> = OnceLock::new();
+
+    let mut tokens = TOKENS
+        .get_or_init(Default::default)
+        .lock()
+        .expect("token command cache was poisoned");
+    if let Some(token) = tokens.get(arguments) {
+        return Ok(token.clone());
+    }
+
+    let command = arguments.join(" ");
+    log::debug!("Running token command `{}`", command);
+    let output = Command::new(&arguments[0])
+        .args(&arguments[1..])
+        .stdin(Stdio::inherit())
+        .stderr(Stdio::inherit())
+        .output()
+        .map_err(|err| ForemanError::token_command_failed(&command, err.to_string()))?;
+
+    if !output.status.success() {
+        return Err(ForemanError::token_command_failed(
+            &command,
+            format!("it exited with {}", output.status),
+        ));
+    }
+    let token = String::from_utf8(output.stdout)
+        .map_err(|_| ForemanError::token_command_failed(&command, "it printed invalid UTF-8"))?
+        .trim()
+        .to_owned();
+    if token.is_empty() {
+        return Err(ForemanError::token_command_failed(
+            &command,
+            "it printed no token",
+        ));
+    }
+
+    tokens.insert(arguments.to_vec(), token.clone());
+    Ok(token)
+}
+
+/// The candidate closest to `key`, if it is close enough to be a typo.
+fn suggest<'a>(key: &str, candidates: impl Iterator<Item = &'a str>) -> Option<&'a str> {
+    candidates
+        .map(|candidate| (edit_distance(key, candidate), candidate))
+        .filter(|(distance, _)| *distance <= 2)
+        .min_by_key(|(distance, _)| *distance)
+        .map(|(_, candidate)| candidate)
+}
+
+/// The Levenshtein distance between `a` and `b`, ignoring case.
+fn edit_distance(a: &str, b: &str) -> usize {
+    let a: Vec<char> = a.to_lowercase().chars().collect();
+    let b: Vec<char> = b.to_lowercase().chars().collect();
+
+    let mut previous: Vec<usize> = (0..=b.len()).collect();
+    for (i, a_char) in a.iter().enumerate() {
+        let mut current = vec![i + 1];
+        for (j, b_char) in b.iter().enumerate() {
+            let substitution = previous[j] + usize::from(a_char != b_char);
+            current.push(substitution.min(previous[j + 1] + 1).min(current[j] + 1));
+        }
+        previous = current;
+    }
+
+    previous[b.len()]
+}
+
+/// Tokens are never printed, since configurations and stores end up in logs.
+impl fmt::Debug for AuthStore {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let redacted = |token: &Option<String>| token.as_ref().map(|_| "***");
+        let scopes = |tokens: &BTreeMap<String, String>| tokens.keys().cloned().collect::<Vec<_>>();
+
+        f.debug_struct("AuthStore")
+            .field("github", &redacted(&self.github))
+            .field("gitlab", &redacted(&self.gitlab))
+            .field("bitbucket", &redacted(&self.bitbucket))
+            .field("github_repositories", &scopes(&self.github_repositories))
+            .field("gitlab_repositories", &scopes(&self.gitlab_repositories))
+            .field("github_token_command", &self.github_token_command)
+            .field("gitlab_token_command", &self.gitlab_token_command)
+            .finish()
+    }
+}
+
+/// Finds the token whose key is the longest prefix of `repo`, comparing whole
+/// path segments case-insensitively, so that `owner` matches `owner/repo` but
+/// not `owner-2/repo`.
+fn scoped_token<'a>(tokens: &'a BTreeMap<String, String>, repo: &str) -> Option<&'a str> {
+    let repo_segments: Vec<&str> = repo.split('/').filter(|s| !s.is_empty()).collect();
+
+    tokens
+        .iter()
+        .filter_map(|(prefix, token)| {
+            let prefix_segments: Vec<&str> = prefix.split('/').filter(|s| !s.is_empty()).collect();
+            let matches = !prefix_segments.is_empty()
+                && prefix_segments.len() <= repo_segments.len()
+                && prefix_segments
+                    .iter()
+                    .zip(&repo_segments)
+                    .all(|(prefix, repo)| prefix.eq_ignore_ascii_case(repo));
+
+            matches.then_some((prefix_segments.len(), token.as_str()))
+        })
+        .max_by_key(|(specificity, _)| *specificity)
+        .map(|(_, token)| token)
+}
+
+#[cfg(test)]
+mod test {
+    use tempfile::tempdir;
+
+    use super::*;
+
+    fn store() -> AuthStore {
+        toml::from_str(
+            r#"
+            github = "global"
+
+            [github-repositories]
+            "roblox" = "owner"
+            "roblox/private-tool" = "repo"
+            "#,
+        )
+        .unwrap()
+    }
+
+    #[test]
+    fn most_specific_token_wins() {
+        assert_eq!(store().github_token("Roblox/private-tool"), Some("repo"));
+    }
+
+    #[test]
+    fn owner_token_applies_to_other_repositories() {
+        assert_eq!(store().github_token("Roblox/foreman"), Some("owner"));
+    }
+
+    #[test]
+    fn falls_back_to_provider_token() {
+        assert_eq!(store().github_token("rojo-rbx/rojo"), Some("global"));
+        assert_eq!(store().github_token("roblox-2/foreman"), Some("global"));
+        assert_eq!(store().gitlab_token("roblox/foreman"), None);
+    }
+
+    #[test]
+    fn set_repository_token() {
+        let directory = tempdir().unwrap();
+        let auth_file = directory.path().join("auth.toml");
+
+        AuthStore::set_github_token(&auth_file, "global").unwrap();
+        AuthStore::set_github_repository_token(&auth_file, "owner/repo", "repo").unwrap();
+        AuthStore::set_github_repository_token(&auth_file, "owner", "owner").unwrap();
+
+        let store = AuthStore::load(&auth_file).unwrap();
+        assert_eq!(store.github_token("owner/repo"), Some("repo"));
+        assert_eq!(store.github_token("owner/other"), Some("owner"));
+        assert_eq!(store.github_token("other/repo"), Some("global"));
+    }
+
+    #[test]
+    fn stored_tokens_take_precedence_when_filling() {
+        let mut store = store();
+        store.fill_from(
+            &toml::from_str(
+                r#"
+                github = "config"
+                gitlab = "config"
+
+                [github-repositories]
+                "roblox" = "config-owner"
+                "rojo-rbx" = "config-rojo"
+                "#,
+            )
+            .unwrap(),
+        );
+
+        assert_eq!(store.github_token("roblox/foreman"), Some("owner"));
+        assert_eq!(store.github_token("rojo-rbx/rojo"), Some("config-rojo"));
+        assert_eq!(store.github_token("other/tool"), Some("global"));
+        assert_eq!(store.gitlab_token("other/tool"), Some("config"));
+    }
+
+    #[test]
+    fn filled_tokens_are_used_without_auth_file() {
+        let directory = tempdir().unwrap();
+        let mut store = AuthStore::load(&directory.path().join("auth.toml")).unwrap();
+        store.fill_from(&toml::from_str(r#"github = "config""#).unwrap());
+
+        assert_eq!(store.github_token("rojo-rbx/rojo"), Some("config"));
+    }
+
+    #[test]
+    fn debug_output_hides_tokens() {
+        let debug = format!("{:?}", store());
+        assert!(!debug.contains("global"));
+        assert!(!debug.contains("\"repo\""));
+        assert!(debug.contains("roblox/private-tool"));
+    }
+
+    #[test]
+    fn unknown_key_is_reported_with_suggestion() {
+        let warnings = validate(&toml::from_str("githubb = \"token\"").unwrap()).unwrap();
+        assert_eq!(
+            warnings,
+            vec!["ignoring unknown key `githubb`, did you mean `github`?"]
+        );
+
+        let warnings = validate(&toml::from_str("sourcehut = \"token\"").unwrap()).unwrap();
+        assert_eq!(warnings, vec!["ignoring unknown key `sourcehut`"]);
+    }
+
+    #[test]
+    fn unknown_key_does_not_fail_loading() {
+        let directory = tempdir().unwrap();
+        let auth_file = directory.path().join("auth.toml");
+        std::fs::write(&auth_file, "github = \"token\"\nsourcehut = \"token\"").unwrap();
+
+        let store = AuthStore::load(&auth_file).unwrap();
+        assert_eq!(store.github_token("rojo-rbx/rojo"), Some("token"));
+    }
+
+    #[test]
+    fn wrong_typed_value_is_reported() {
+        let directory = tempdir().unwrap();
+        let auth_file = directory.path().join("auth.toml");
+
+        std::fs::write(&auth_file, "gitlab = 42").unwrap();
+        let message = AuthStore::load(&auth_file).unwrap_err().to_string();
+        assert!(message.contains("`gitlab` must be a string, found integer"));
+
+        std::fs::write(&auth_file, "[github-repositories]\nowner = true").unwrap();
+        let message = AuthStore::load(&auth_file).unwrap_err().to_string();
+        assert!(message.contains("`github-repositories.\"owner\"` must be a string, found boolean"));
+    }
+
+    /// Writes an executable script named `name` to `directory` and returns its
+    /// path.
+    #[cfg(unix)]
+    fn token_helper(directory: &Path, name: &str, script: &str) -> String {
+        use std::os::unix::fs::PermissionsExt;
+
+        let path = directory.join(name);
+        std::fs::write(&path, script).unwrap();
+        std::fs::set_permissions(&path, std::fs::Permissions::from_mode(0o755)).unwrap();
+        path.to_str().unwrap().to_owned()
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn tokens_are_read_from_helper_commands() {
+        let directory = tempdir().unwrap();
+        let helper = token_helper(
+            directory.path(),
+            "token-helper",
+            "#!/bin/sh\necho \"  token-for-$1  \"\n",
+        );
+        let auth_file = directory.path().join("auth.toml");
+        std::fs::write(
+            &auth_file,
+            format!(
+                "github-token-command = [{:?}, \"github\"]\ngitlab_token_command = \"{} gitlab\"\n",
+                helper, helper
+            ),
+        )
+        .unwrap();
+
+        let mut store = AuthStore::load(&auth_file).unwrap();
+        assert_eq!(store.github_token("rojo-rbx/rojo"), None);
+        store.run_github_token_command().unwrap();
+        assert_eq!(
+            store.github_token("rojo-rbx/rojo"),
+            Some("token-for-github")
+        );
+        assert_eq!(store.gitlab_token("group/project"), None);
+        store.run_gitlab_token_command().unwrap();
+        assert_eq!(
+            store.gitlab_token("group/project"),
+            Some("token-for-gitlab")
+        );
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn only_the_needed_token_command_runs() {
+        let directory = tempdir().unwrap();
+        let auth_file = directory.path().join("auth.toml");
+        std::fs::write(
+            &auth_file,
+            "github-token-command = \"echo token\"\ngitlab-token-command = \"false\"\n",
+        )
+        .unwrap();
+
+        let mut store = AuthStore::load(&auth_file).unwrap();
+        store.run_github_token_command().unwrap();
+        assert_eq!(store.github_token("rojo-rbx/rojo"), Some("token"));
+        assert!(store.run_gitlab_token_command().is_err());
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn token_commands_are_not_run_through_a_shell() {
+        let directory = tempdir().unwrap();
+        let auth_file = directory.path().join("auth.toml");
+        std::fs::write(
+            &auth_file,
+            "github-token-command = \"echo token;touch $HOME/injected\"\n",
+        )
+        .unwrap();
+
+        let mut store = AuthStore::load(&auth_file).unwrap();
+        store.run_github_token_command().unwrap();
+        assert_eq!(
+            store.github_token("rojo-rbx/rojo"),
+            Some("token;touch $HOME/injected")
+        );
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn stored_tokens_take_precedence_over_commands() {
+        let directory = tempdir().unwrap();
+        let auth_file = directory.path().join("auth.toml");
+        std::fs::write(
+            &auth_file,
+            "github = \"stored\"\ngithub-token-command = \"false\"\n",
+        )
+        .unwrap();
+
+        let mut store = AuthStore::load(&auth_file).unwrap();
+        store.run_github_token_command().unwrap();
+        assert_eq!(store.github_token("rojo-rbx/rojo"), Some("stored"));
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn failing_token_commands_are_reported() {
+        let directory = tempdir().unwrap();
+        let silent = token_helper(directory.path(), "silent-helper", "#!/bin/sh\n");
+        let auth_file = directory.path().join("auth.toml");
+
+        std::fs::write(&auth_file, "github-token-command = \"false\"\n").unwrap();
+        let message = AuthStore::load(&auth_file)
+            .unwrap()
+            .run_github_token_command()
+            .unwrap_err()
+            .to_string();
+        assert!(message.contains("`false`"), "{}", message);
+        assert!(message.contains("exited with"), "{}", message);
+
+        std::fs::write(&auth_file, format!("github-token-command = {:?}\n", silent)).unwrap();
+        let message = AuthStore::load(&auth_file)
+            .unwrap()
+            .run_github_token_command()
+            .unwrap_err()
+            .to_string();
+        assert!(message.contains("printed no token"), "{}", message);
+    }
+
+    #[test]
+    fn token_commands_must_be_commands() {
+        let directory = tempdir().unwrap();
+        let auth_file = directory.path().join("auth.toml");
+
+        std::fs::write(&auth_file, "github-token-command = []").unwrap();
+        let message = AuthStore::load(&auth_file).unwrap_err().to_string();
+        assert!(message.contains("`github-token-command` must be a command"));
+
+        std::fs::write(&auth_file, "gitlab_token_command = [\"vault\", 1]").unwrap();
+        let message = AuthStore::load(&auth_file).unwrap_err().to_string();
+        assert!(message.contains("`gitlab_token_command` must be a command"));
+    }
+
+    #[test]
+    fn configuration_tokens_never_carry_commands() {
+        let auth: AuthStore =
+            toml::from_str("github-token-command = \"vault read secret/github\"").unwrap();
+        assert_eq!(auth.github_token_command, None);
+    }
+
+    #[test]
+    fn default_auth_config_is_valid() {
+        let value: toml::Value = toml::from_str(DEFAULT_AUTH_CONFIG).unwrap();
+        validate(&value).unwrap();
+    }
+}
@@ -0,0 +1,90 @@
+//! Recognizes the executable format of a tool's binary from its first bytes,
+//! to catch release assets meant for another platform at install time rather
+//! than with an `exec format error` when the tool runs.
+
+use std::fmt;
+
+/// Executable formats, one per platform Foreman knows about.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum BinaryFormat {
+    Elf,
+    MachO,
+    Pe,
+}
+
+impl BinaryFormat {
+    /// The format of executables on the current platform, if known.
+    pub fn host() -> Option<Self> {
+        if cfg!(target_os = "windows") {
+            Some(Self::Pe)
+        } else if cfg!(target_os = "macos") {
+            Some(Self::MachO)
+        } else if cfg!(target_os = "linux") {
+            Some(Self::Elf)
+        } else {
+            None
+        }
+    }
+
+    /// The format of an executable starting with `header`, or `None` for
+    /// anything else, like scripts.
+    pub fn detect(header: &[u8]) -> Option<Self> {
+        const MACH_O_MAGICS: [[u8; 4]; 5] = [
+            [0xfe, 0xed, 0xfa, 0xce],
+            [0xfe, 0xed, 0xfa, 0xcf],
+            [0xce, 0xfa, 0xed, 0xfe],
+            [0xcf, 0xfa, 0xed, 0xfe],
+            // Universal binaries.
+            [0xca, 0xfe, 0xba, 0xbe],
+        ];
+
+        if header.starts_with(b"\x7fELF") {
+            Some(Self::Elf)
+        } else if MACH_O_MAGICS.iter().any(|magic| header.starts_with(magic)) {
+            Some(Self::MachO)
+        } else if header.starts_with(b"MZ") {
+            Some(Self::Pe)
+        } else {
+            None
+        }
+    }
+}
+
+impl fmt::Display for BinaryFormat {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(match self {
+            Self::Elf => "ELF (Linux)",
+            Self::MachO => "Mach-O (macOS)",
+            Self::Pe => "PE (Windows)",
+        })
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn detects_executable_formats() {
+        assert_eq!(
+            BinaryFormat::detect(b"\x7fELF\x02\x01\x01"),
+            Some(BinaryFormat::Elf)
+        );
+        assert_eq!(
+            BinaryFormat::detect(&[0xcf, 0xfa, 0xed, 0xfe, 0x0c]),
+            Some(BinaryFormat::MachO)
+        );
+        assert_eq!(
+            BinaryFormat::detect(&[0xca, 0xfe, 0xba, 0xbe]),
+            Some(BinaryFormat::MachO)
+        );
+        assert_eq!(BinaryFormat::detect(b"MZ\x90\x00"), Some(BinaryFormat::Pe));
+    }
+
+    #[test]
+    fn scripts_and_short_files_are_unknown() {
+        assert_eq!(BinaryFormat::detect(b"#!/bin/sh\n"), None);
+        assert_eq!(BinaryFormat::detect(b"\x7fEL"), None);
+        assert_eq!(BinaryFormat::detect(b""), None);
+    }
+}
@@ -0,0 +1,314 @@
+//! Checksums of release assets declared in `foreman.checksums.toml` files,
+//! usually generated in bulk and committed next to `foreman.toml`:
+//!
+//! ```toml
+//! ["github.com/rojo-rbx/rojo"."7.0.0"]
+//! linux-x86_64 = "<sha256 in hexadecimal>"
+//! windows-x86_64 = "<sha256 in hexadecimal>"
+//! ```
+//!
+//! Tools are keyed by their source, then by version, then by platform
+//! keyword.
+//!
+//! Releases can also publish the checksum of an asset as another asset named
+//! after it, like `tool-linux.zip.sha256`, which is used when no checksum is
+//! declared.
+
+use std::{
+    collections::{BTreeMap, HashMap},
+    fmt,
+    path::{Path, PathBuf},
+};
+
+use semver::Version;
+use sha2::{Digest, Sha256, Sha512};
+
+use crate::{
+    ci_string::CiString,
+    config::{ConfigFile, ToolSpec},
+    error::{ForemanError, ForemanResult},
+    fs,
+    paths::ForemanPaths,
+};
+
+pub static CHECKSUMS_FILE_NAME: &str = "foreman.checksums.toml";
+
+type PlatformChecksums = BTreeMap<String, String>;
+
+#[derive(Debug, Default, Clone, PartialEq)]
+pub struct Checksums {
+    tools: HashMap<CiString, BTreeMap<String, PlatformChecksums>>,
+}
+
+impl Checksums {
+    /// Reads the checksum file next to the project's `foreman.toml`, then the
+    /// one in Foreman's home directory. Entries of the former take
+    /// precedence. Missing files are skipped.
+    pub fn load(paths: &ForemanPaths) -> ForemanResult<Self> {
+        let (_, config_path) = ConfigFile::find_project(paths)?;
+        let project_path = config_path.and_then(|config_path| {
+            config_path
+                .parent()
+                .map(|directory| directory.join(CHECKSUMS_FILE_NAME))
+        });
+
+        Self::load_from(project_path.iter().chain([paths.user_checksums()].iter()))
+    }
+
+    /// Reads the given checksum files, earlier files taking precedence.
+    pub fn load_from<'a, I>(checksum_paths: I) -> ForemanResult<Self>
+    where
+        I: IntoIterator<Item = &'a PathBuf>,
+    {
+        let mut checksums = Self::default();
+
+        for checksum_path in checksum_paths {
+            if let Some(file) = Self::read_file(checksum_path)? {
+                log::debug!("Read checksums from {}", checksum_path.display());
+                checksums.fill_from(file);
+            }
+        }
+
+        Ok(checksums)
+    }
+
+    fn read_file(checksum_path: &Path) -> ForemanResult<Option<Self>> {
+        let contents = match fs::try_read(checksum_path)? {
+            Some(contents) => contents,
+            None => return Ok(None),
+        };
+
+        let tools: BTreeMap<String, BTreeMap<String, PlatformChecksums>> =
+            toml::from_slice(&contents).map_err(|err| {
+                ForemanError::config_parsing(
+                    checksum_path,
+                    format!(
+                        "expected checksums keyed by tool source, version and platform ({})",
+                        err
+                    ),
+                )
+            })?;
+
+        Ok(Some(Self {
+            tools: tools
+                .into_iter()
+                .map(|(source, versions)| (CiString(source), versions))
+                .collect(),
+        }))
+    }
+
+    /// Adds the entries of `other` that are not already present.
+    fn fill_from(&mut self, other: Self) {
+        for (source, versions) in other.tools {
+            let existing_versions = self.tools.entry(source).or_default();
+            for (version, platforms) in versions {
+                let existing_platforms = existing_versions.entry(version).or_default();
+                for (platform, sha256) in platforms {
+                    existing_platforms.entry(platform).or_insert(sha256);
+                }
+            }
+        }
+    }
+
+    /// The SHA-256 digest, in hexadecimal, declared for the release asset of
+    /// `version` of the tool on the first of `platform_keywords` that has
+    /// one.
+    pub fn get(
+        &self,
+        tool: &ToolSpec,
+        version: &Version,
+        platform_keywords: &[&str],
+    ) -> Option<&str> {
+        let platforms = self
+            .tools
+            .get(&CiString(tool.source()))?
+            .get(&version.to_string())?;
+
+        platform_keywords
+            .iter()
+            .find_map(|keyword| platforms.get(*keyword))
+            .map(String::as_str)
+    }
+}
+
+/// Hash functions of the checksums published next to release assets.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum DigestAlgorithm {
+    Sha256,
+    Sha512,
+}
+
+impl DigestAlgorithm {
+    pub const ALL: [Self; 2] = [Self::Sha256, Self::Sha512];
+
+    /// Extension of the files holding checksums of this algorithm.
+    fn extension(self) -> &'static str {
+        match self {
+            Self::Sha256 => "sha256",
+            Self::Sha512 => "sha512",
+        }
+    }
+
+    /// The algorithm of the checksum file named `name`, like
+    /// `tool-linux.zip.sha256`, or `None` for anything else.
+    pub fn of_checksum_file(name: &str) -> Option<Self> {
+        let (_, extension) = name.rsplit_once('.')?;
+        Self::ALL
+            .iter()
+            .copied()
+            .find(|algorithm| extension.eq_ignore_ascii_case(algorithm.extension()))
+    }
+
+    /// Name of the checksum file of this algorithm for the asset `name`.
+    pub fn checksum_file_name(self, name: &str) -> String {
+        format!("{}.{}", name, self.extension())
+    }
+
+    /// The digest of `bytes`, in lowercase hexadecimal.
+    pub fn hex_digest(self, bytes: &[u8]) -> String {
+        match self {
+            Self::Sha256 => format!("{:x}", Sha256::digest(bytes)),
+            Self::Sha512 => format!("{:x}", Sha512::digest(bytes)),
+        }
+    }
+
+    /// Reads the digest from the contents of a checksum file, which hold
+    /// either the digest alone or the output of `sha256sum`, the digest
+    /// followed by the file name.
+    pub fn parse_checksum_file(self, contents: &[u8]) -> Option<String> {
+        let hex_length = match self {
+            Self::Sha256 => 64,
+            Self::Sha512 => 128,
+        };
+
+        std::str::from_utf8(contents)
+            .ok()?
+            .split_whitespace()
+            .next()
+            .filter(|digest| {
+                digest.len() == hex_length && digest.chars().all(|c| c.is_ascii_hexdigit())
+            })
+            .map(str::to_ascii_lowercase)
+    }
+}
+
+impl fmt::Display for DigestAlgorithm {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(match self {
+            Self::Sha256 => "SHA-256",
+            Self::Sha512 => "SHA-512",
+        })
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use tempfile::tempdir;
+
+    use super::*;
+
+    fn rojo() -> ToolSpec {
+        ToolSpec::from_value(
+            &toml::from_str("github = \"rojo-rbx/rojo\"\nversion = \"7.0.0\"").unwrap(),
+            &ConfigFile::new_with_defaults().hosts,
+        )
+        .unwrap()
+    }
+
+    fn write(directory: &Path, name: &str, contents: &str) -> PathBuf {
+        let path = directory.join(name);
+        std::fs::write(&path, contents).unwrap();
+        path
+    }
+
+    #[test]
+    fn picks_first_matching_platform_keyword() {
+        let directory = tempdir().unwrap();
+        let path = write(
+            directory.path(),
+            CHECKSUMS_FILE_NAME,
+            "[\"GitHub.com/Rojo-Rbx/Rojo\".\"7.0.0\"]\nlinux = \"aaa\"\nlinux-x86_64 = \"bbb\"\n",
+        );
+        let checksums = Checksums::load_from(&[path]).unwrap();
+
+        let version = Version::new(7, 0, 0);
+        assert_eq!(
+            checksums.get(&rojo(), &version, &["linux-x86_64", "linux"]),
+            Some("bbb")
+        );
+        assert_eq!(checksums.get(&rojo(), &version, &["linux"]), Some("aaa"));
+        assert_eq!(checksums.get(&rojo(), &version, &["windows"]), None);
+        assert_eq!(
+            checksums.get(&rojo(), &Version::new(7, 0, 1), &["linux"]),
+            None
+        );
+    }
+
+    #[test]
+    fn earlier_files_take_precedence() {
+        let directory = tempdir().unwrap();
+        let project = write(
+            directory.path(),
+            "project.toml",
+            "[\"github.com/rojo-rbx/rojo\".\"7.0.0\"]\nlinux = \"project\"\n",
+        );
+        let home = write(
+            directory.path(),
+            "home.toml",
+            "[\"github.com/rojo-rbx/rojo\".\"7.0.0\"]\nlinux = \"home\"\nwindows = \"home\"\n",
+        );
+        let missing = directory.path().join("missing.toml");
+        let checksums = Checksums::load_from(&[missing, project, home]).unwrap();
+
+        let version = Version::new(7, 0, 0);
+        assert_eq!(
+            checksums.get(&rojo(), &version, &["linux"]),
+            Some("project")
+        );
+        assert_eq!(checksums.get(&rojo(), &version, &["windows"]), Some("home"));
+    }
+
+    #[test]
+    fn malformed_file_is_an_error() {
+        let directory = tempdir().unwrap();
+        let path = write(
+            directory.path(),
+            CHECKSUMS_FILE_NAME,
+            "\"github.com/rojo-rbx/rojo\" = \"aaa\"\n",
+        );
+        assert!(Checksums::load_from(&[path]).is_err());
+    }
+
+    #[test]
+    fn reads_published_checksum_files() {
+        let digest = "9f86d081884c7d659a2feaa0c55ad015a3bf4f1b2b0b822cd15d6c15b0f00a08";
+        let algorithm = DigestAlgorithm::of_checksum_file("tool-linux.zip.SHA256").unwrap();
+        assert_eq!(algorithm, DigestAlgorithm::Sha256);
+        assert_eq!(algorithm.hex_digest(b"test"), digest);
+
+        assert_eq!(
+            algorithm.parse_checksum_file(format!("{}\n", digest).as_bytes()),
+            Some(digest.to_owned())
+        );
+        assert_eq!(
+            algorithm.parse_checksum_file(
+                format!("{}  tool-linux.zip\n", digest.to_uppercase()).as_bytes()
+            ),
+            Some(digest.to_owned())
+        );
+        assert_eq!(
+            algorithm.parse_checksum_file(b"0123abcd  tool-linux.zip"),
+            None
+        );
+        assert_eq!(
+            DigestAlgorithm::Sha512.parse_checksum_file(digest.as_bytes()),
+            None
+        );
+
+        assert_eq!(
+            DigestAlgorithm::of_checksum_file("tool-linux.zip.sha512"),
+            Some(DigestAlgorithm::Sha512)
+        );
+        assert_eq!(DigestAlgorithm::of_checksum_file("tool-linux.zip"), None);
+    }
+}
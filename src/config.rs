@@ -1,20 +1,24 @@
 use crate::{
+    auth_store::AuthStore,
     ci_string::CiString,
-    error::{ConfigFileParseError, ConfigFileParseResult, ForemanError},
+    error::{ConfigFileParseError, ConfigFileParseResult, ForemanError, ForemanResult},
     fs,
     paths::ForemanPaths,
     tool_provider::Provider,
+    versioning::Versioning,
 };
-use semver::VersionReq;
+use semver::{Version, VersionReq};
 use std::{
     collections::{BTreeMap, HashMap},
-    env, fmt,
+    env, fmt, io,
+    path::{Path, PathBuf},
 };
 use toml::Value;
+use toml_edit::{Document, InlineTable, TomlError};
 use url::Url;
 
-const GITHUB: &'static str = "https://github.com";
-const GITLAB: &'static str = "https://gitlab.com";
+const GITHUB: &str = "https://github.com";
+const GITLAB: &str = "https://gitlab.com";
 
 #[derive(Debug, Clone, PartialEq)]
 pub struct ToolSpec {
@@ -22,12 +26,127 @@ pub struct ToolSpec {
     path: String,
     version: VersionReq,
     protocol: Protocol,
+    /// Platforms this tool is restricted to, either as an operating system
+    /// (`windows`) or as an operating system and architecture pair
+    /// (`windows-x86_64`). Empty when the tool is available everywhere.
+    platforms: Vec<String>,
+    /// Glob matching the name of the tool's binary in its release archives,
+    /// like `stylua*`. When absent, the archive's first entry is used.
+    bin: Option<String>,
+    /// Exact name of the release asset to download, compared
+    /// case-insensitively. When absent, the asset is picked by looking for
+    /// platform keywords in the asset names.
+    asset: Option<String>,
+    /// Environment variables set for the tool when it runs.
+    env: BTreeMap<String, String>,
+    /// Download the release's source tarball when none of its assets match
+    /// the platform. Only GitHub releases provide one.
+    source_fallback: bool,
+    /// The asset to download for tools declared with a `url`, instead of
+    /// looking through the releases of a provider.
+    direct: Option<DirectAsset>,
+    /// Versions never to install, even when they match `version`.
+    yanked: Vec<Version>,
+    /// How release tags, `version` and `yanked` are read.
+    versioning: Versioning,
+    /// Arguments to run a freshly installed version with, like `--version`,
+    /// to check that it works before recording it as installed.
+    verify: Option<Vec<String>>,
 }
+
+/// A release asset downloaded from the URL given by a tool's `url` key.
+#[derive(Debug, Clone, PartialEq)]
+pub struct DirectAsset {
+    pub url: Url,
+    /// The version the tool is installed as, which only names its place in
+    /// the tool cache.
+    pub version: Version,
+}
+
 #[derive(Debug, Clone, PartialEq)]
 pub enum Protocol {
     Github,
     Gitlab,
+    Bitbucket,
     Artifactory,
+    /// A directory on disk, see `tool_provider::local`.
+    Local,
+    /// Any URL outside of the configured hosts, downloaded without
+    /// authentication.
+    Direct,
+}
+
+impl Protocol {
+    /// The name of the protocol in a host's `protocol` key.
+    fn name(&self) -> &'static str {
+        match self {
+            Protocol::Github => "github",
+            Protocol::Gitlab => "gitlab",
+            Protocol::Bitbucket => "bitbucket",
+            Protocol::Artifactory => "artifactory",
+            Protocol::Local => "local",
+            Protocol::Direct => "direct",
+        }
+    }
+}
+
+/// Parses a list of yanked versions, like `["0.14.2"]`.
+fn parse_yanked(value: &Value, versioning: Versioning) -> Option<Vec<Version>> {
+    value
+        .as_array()?
+        .iter()
+        .map(|version| versioning.parse_version(version.as_str()?))
+        .collect()
+}
+
+/// Parses a tool's `verify` key into the arguments to run the tool with,
+/// either split from a string, like `"--version"`, or taken as they are from
+/// a list, like `["--eval", "print(1)"]`. There must be at least one.
+fn parse_verify(value: &Value) -> Option<Vec<String>> {
+    let arguments: Vec<String> = match value {
+        Value::String(arguments) => arguments.split_whitespace().map(str::to_owned).collect(),
+        Value::Array(arguments) => arguments
+            .iter()
+            .map(|argument| argument.as_str().map(str::to_owned))
+            .collect::<Option<_>>()?,
+        _ => return None,
+    };
+
+    (!arguments.is_empty()).then_some(arguments)
+}
+
+/// Replaces every `${VAR}` in `text` with the value of the environment
+/// variable `VAR`. Fails with the name of the first variable that is not set,
+/// or with `None` if a `${` is never closed.
+fn expand_env_vars(text: &str) -> Result<String, Option<String>> {
+    let mut expanded = String::with_capacity(text.len());
+    let mut rest = text;
+
+    while let Some(start) = rest.find("${") {
+        expanded.push_str(&rest[..start]);
+        let after = &rest[start + 2..];
+        let end = after.find('}').ok_or(None)?;
+        let variable = &after[..end];
+        let value = env::var(variable).map_err(|_| Some(variable.to_owned()))?;
+        expanded.push_str(&value);
+        rest = &after[end + 1..];
+    }
+    expanded.push_str(rest);
+
+    Ok(expanded)
+}
+
+/// Replaces the `{version}`, `{platform}` and `{arch}` placeholders of a
+/// tool's `url` with `version`, the operating system `os` and the
+/// architecture `arch`, named like in the `platforms` key. Fails on any other
+/// placeholder, which is most likely a typo.
+fn expand_url_template(template: &str, version: &Version, os: &str, arch: &str) -> Option<String> {
+    let expanded = template
+        .replace("{version}", &version.to_string())
+        .replace("{platform}", os)
+        .replace("{arch}", arch);
+
+    (!expanded.contains('{')).then_some(expanded)
 }
 
 impl ToolSpec {
@@ -46,24 +165,123 @@ impl ToolSpec {
                 .ok_or_else(|| ConfigFileParseError::Tool {
                     tool: value.to_string(),
                 })?;
-            let version =
-                VersionReq::parse(version_str).map_err(|_| ConfigFileParseError::Tool {
+
+            if let Some(url) = map.remove("url") {
+                return Self::direct_from_value(value, map, url, version_str, host_map);
+            }
+
+            let versioning = match map.remove("versioning") {
+                Some(Value::String(name)) => {
+                    Versioning::from_name(&name).ok_or_else(|| ConfigFileParseError::Tool {
+                        tool: value.to_string(),
+                    })?
+                }
+                Some(_) => {
+                    return Err(ConfigFileParseError::Tool {
+                        tool: value.to_string(),
+                    })
+                }
+                None => Versioning::Semver,
+            };
+
+            let version = versioning.parse_requirement(version_str).ok_or_else(|| {
+                ConfigFileParseError::Tool {
                     tool: value.to_string(),
-                })?;
+                }
+            })?;
 
             let (path_val, host_source) = host_map
                 .iter()
                 .find_map(|(potential_host, host_source)| {
-                    if let Some(path) = map.remove(potential_host) {
-                        Some((path, host_source))
-                    } else {
-                        None
-                    }
+                    map.remove(potential_host).map(|path| (path, host_source))
                 })
                 .ok_or_else(|| ConfigFileParseError::Tool {
                     tool: value.to_string(),
                 })?;
 
+            let platforms = match map.remove("platforms") {
+                Some(Value::Array(platforms)) => platforms
+                    .iter()
+                    .map(|platform| platform.as_str().map(str::to_owned))
+                    .collect::<Option<Vec<_>>>()
+                    .ok_or_else(|| ConfigFileParseError::Tool {
+                        tool: value.to_string(),
+                    })?,
+                Some(_) => {
+                    return Err(ConfigFileParseError::Tool {
+                        tool: value.to_string(),
+                    })
+                }
+                None => Vec::new(),
+            };
+
+            let bin = match map.remove("bin") {
+                Some(Value::String(bin)) if glob::Pattern::new(&bin).is_ok() => Some(bin),
+                Some(_) => {
+                    return Err(ConfigFileParseError::Tool {
+                        tool: value.to_string(),
+                    })
+                }
+                None => None,
+            };
+
+            let env = match map.remove("env") {
+                Some(Value::Table(env)) => env
+                    .into_iter()
+                    .map(|(key, env_value)| match env_value {
+                        Value::String(env_value) => Some((key, env_value)),
+                        _ => None,
+                    })
+                    .collect::<Option<BTreeMap<_, _>>>()
+                    .ok_or_else(|| ConfigFileParseError::Tool {
+                        tool: value.to_string(),
+                    })?,
+                Some(_) => {
+                    return Err(ConfigFileParseError::Tool {
+                        tool: value.to_string(),
+                    })
+                }
+                None => BTreeMap::new(),
+            };
+
+            let asset = match map.remove("asset") {
+                Some(Value::String(asset)) => Some(asset),
+                Some(_) => {
+                    return Err(ConfigFileParseError::Tool {
+                        tool: value.to_string(),
+                    })
+                }
+                None => None,
+            };
+
+            let source_fallback = match map.remove("source_fallback") {
+                Some(Value::Boolean(source_fallback)) => source_fallback,
+                Some(_) => {
+                    return Err(ConfigFileParseError::Tool {
+                        tool: value.to_string(),
+                    })
+                }
+                None => false,
+            };
+
+            let yanked = match map.remove("yanked") {
+                Some(yanked) => {
+                    parse_yanked(&yanked, versioning).ok_or_else(|| ConfigFileParseError::Tool {
+                        tool: value.to_string(),
+                    })?
+                }
+                None => Vec::new(),
+            };
+
+            let verify = map
+                .remove("verify")
+                .map(|verify| {
+                    parse_verify(&verify).ok_or_else(|| ConfigFileParseError::Tool {
+                        tool: value.to_string(),
+                    })
+                })
+                .transpose()?;
+
             // Extraneous fields in a tool spec definition should not be allowed
             if !map.is_empty() {
                 return Err(ConfigFileParseError::Tool {
@@ -76,8 +294,16 @@ impl ToolSpec {
                 .as_str()
                 .ok_or_else(|| ConfigFileParseError::Tool {
                     tool: value.to_string(),
-                })?
-                .to_string();
+                })?;
+            let path = expand_env_vars(path).map_err(|variable| match variable {
+                Some(variable) => ConfigFileParseError::UndefinedEnvVar {
+                    variable,
+                    tool: value.to_string(),
+                },
+                None => ConfigFileParseError::Tool {
+                    tool: value.to_string(),
+                },
+            })?;
 
             let protocol = host_source.protocol.clone();
 
@@ -86,6 +312,15 @@ impl ToolSpec {
                 path,
                 version,
                 protocol,
+                platforms,
+                bin,
+                asset,
+                env,
+                source_fallback,
+                direct: None,
+                yanked,
+                versioning,
+                verify,
             })
         } else {
             Err(ConfigFileParseError::Tool {
@@ -94,19 +329,103 @@ impl ToolSpec {
         }
     }
 
+    /// Parses a tool declared with a `url`, like `{ url = "https://host/tool.zip",
+    /// version = "1.0.0" }`, whose `version` must be exact. The URL can name
+    /// the version and the platform with placeholders, see
+    /// `expand_url_template`. It is downloaded with the authentication of the
+    /// configured host it belongs to, if any.
+    fn direct_from_value(
+        value: &Value,
+        mut map: toml::value::Table,
+        url: Value,
+        version_str: &str,
+        host_map: &HashMap<String, Host>,
+    ) -> ConfigFileParseResult<Self> {
+        let invalid = || ConfigFileParseError::Tool {
+            tool: value.to_string(),
+        };
+
+        let version = Version::parse(version_str.strip_prefix('=').unwrap_or(version_str))
+            .map_err(|_| invalid())?;
+        // The tool is known by its URL as written, so that a template names
+        // the same tool whatever the version and platform it expands to.
+        let template = url.as_str().ok_or_else(invalid)?.to_owned();
+        let url = expand_url_template(&template, &version, env::consts::OS, env::consts::ARCH)
+            .and_then(|url| Url::parse(&url).ok())
+            .ok_or_else(invalid)?;
+
+        let bin = match map.remove("bin") {
+            Some(Value::String(bin)) if glob::Pattern::new(&bin).is_ok() => Some(bin),
+            Some(_) => return Err(invalid()),
+            None => None,
+        };
+
+        let verify = map
+            .remove("verify")
+            .map(|verify| parse_verify(&verify).ok_or_else(invalid))
+            .transpose()?;
+
+        // Only `bin` and `verify` apply to a single asset: there are no
+        // releases to pick it from.
+        if !map.is_empty() {
+            return Err(invalid());
+        }
+
+        let host_source = host_map
+            .values()
+            .filter(|host_source| url.as_str().starts_with(host_source.source.as_str()))
+            .max_by_key(|host_source| host_source.source.as_str().len());
+        let (host, protocol) = match host_source {
+            Some(host_source) => (host_source.source.clone(), host_source.protocol.clone()),
+            None => (
+                url.join("/").unwrap_or_else(|_| url.clone()),
+                Protocol::Direct,
+            ),
+        };
+
+        Ok(Self {
+            host,
+            path: template,
+            version: VersionReq::parse(&format!("={}", version)).map_err(|_| invalid())?,
+            protocol,
+            platforms: Vec::new(),
+            bin,
+            asset: None,
+            env: BTreeMap::new(),
+            source_fallback: false,
+            direct: Some(DirectAsset { url, version }),
+            yanked: Vec::new(),
+            versioning: Versioning::Semver,
+            verify,
+        })
+    }
+
     pub fn cache_key(&self) -> CiString {
+        if self.direct.is_some() {
+            return CiString(format!("url@{}", self.path));
+        }
+
         match self.protocol {
-            Protocol::Github => CiString(format!("{}", self.path)),
+            Protocol::Github => CiString(self.path.to_string()),
             Protocol::Gitlab => CiString(format!("gitlab@{}", self.path)),
-            Protocol::Artifactory => CiString(format!("{}@{}", self.host, self.path)),
+            Protocol::Bitbucket => CiString(format!("bitbucket@{}", self.path)),
+            Protocol::Artifactory | Protocol::Local | Protocol::Direct => {
+                CiString(format!("{}@{}", self.host, self.path))
+            }
         }
     }
 
     pub fn source(&self) -> String {
+        if self.direct.is_some() {
+            return self.path.clone();
+        }
+
         let provider = match self.protocol {
             Protocol::Github => "github.com",
             Protocol::Gitlab => "gitlab.com",
+            Protocol::Bitbucket => "bitbucket.org",
             Protocol::Artifactory => "artifactory.com",
+            Protocol::Local | Protocol::Direct => self.host.as_str().trim_end_matches('/'),
         };
 
         format!("{}/{}", provider, self.path)
@@ -124,13 +443,204 @@ impl ToolSpec {
         match self.protocol {
             Protocol::Github => Provider::Github,
             Protocol::Gitlab => Provider::Gitlab,
+            Protocol::Bitbucket => Provider::Bitbucket,
             Protocol::Artifactory => Provider::Artifactory,
+            Protocol::Local => Provider::Local,
+            Protocol::Direct => Provider::Direct,
         }
     }
 
     pub fn host(&self) -> &Url {
         &self.host
     }
+
+    pub fn bin(&self) -> Option<&str> {
+        self.bin.as_deref()
+    }
+
+    pub fn asset(&self) -> Option<&str> {
+        self.asset.as_deref()
+    }
+
+    pub fn source_fallback(&self) -> bool {
+        self.source_fallback
+    }
+
+    pub fn yanked(&self) -> &[Version] {
+        &self.yanked
+    }
+
+    pub fn versioning(&self) -> Versioning {
+        self.versioning
+    }
+
+    pub fn direct(&self) -> Option<&DirectAsset> {
+        self.direct.as_ref()
+    }
+
+    pub fn env(&self) -> &BTreeMap<String, String> {
+        &self.env
+    }
+
+    pub fn verify(&self) -> Option<&[String]> {
+        self.verify.as_deref()
+    }
+
+    pub fn platforms(&self) -> &[String] {
+        &self.platforms
+    }
+
+    /// Whether this tool should be installed on the platform Foreman is
+    /// running on.
+    pub fn is_available(&self) -> bool {
+        self.is_available_on(env::consts::OS, env::consts::ARCH)
+    }
+
+    fn is_available_on(&self, os: &str, arch: &str) -> bool {
+        self.platforms.is_empty()
+            || self.platforms.iter().any(|platform| {
+                let platform = platform.to_lowercase();
+                platform == os || platform == format!("{}-{}", os, arch)
+            })
+    }
+}
+
+/// A tool written in its short form, `[host:]path@version`, like
+/// `rojo-rbx/rojo@7.3.0` or `gitlab:seaofvoices/darklua@0.7.0`. When the host
+/// is omitted, the tool is assumed to come from GitHub.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ToolShorthand {
+    pub host: String,
+    pub path: String,
+    pub version: String,
+}
+
+impl ToolShorthand {
+    pub fn parse(shorthand: &str) -> ForemanResult<Self> {
+        let invalid = || ForemanError::InvalidToolShorthand {
+            shorthand: shorthand.to_owned(),
+        };
+
+        let (source, version) = shorthand.rsplit_once('@').ok_or_else(invalid)?;
+        let (host, path) = match source.split_once(':') {
+            Some((host, path)) => (host, path),
+            None => ("github", source),
+        };
+
+        if host.is_empty() || path.is_empty() || VersionReq::parse(version).is_err() {
+            return Err(invalid());
+        }
+
+        Ok(Self {
+            host: host.to_owned(),
+            path: path.to_owned(),
+            version: version.to_owned(),
+        })
+    }
+
+    /// The value of this tool as it would be written in a `[tools]` table.
+    pub fn to_value(&self) -> Value {
+        let mut map = toml::map::Map::new();
+        map.insert(self.host.clone(), Value::String(self.path.clone()));
+        map.insert("version".to_owned(), Value::String(self.version.clone()));
+        Value::Table(map)
+    }
+}
+
+/// Writes a tool entry into the `[tools]` table of the configuration file at
+/// `config_path`, creating the file or the table if they do not exist yet.
+/// Existing formatting and comments are preserved.
+pub fn add_tool(
+    config_path: &Path,
+    alias: &str,
+    tool: &ToolShorthand,
+    force: bool,
+) -> ForemanResult<()> {
+    let contents = fs::try_read_to_string(config_path)?.unwrap_or_default();
+    let mut document: Document = contents
+        .parse()
+        .map_err(|err: TomlError| ForemanError::config_parsing(config_path, err.to_string()))?;
+
+    let tools = document
+        .as_table_mut()
+        .entry("tools")
+        .or_insert_with(toml_edit::table)
+        .as_table_like_mut()
+        .ok_or_else(|| {
+            ForemanError::config_parsing(config_path, "the `tools` field must be a table")
+        })?;
+
+    if tools.contains_key(alias) && !force {
+        return Err(ForemanError::ToolAlreadyDeclared {
+            name: alias.to_owned(),
+            path: config_path.to_owned(),
+        });
+    }
+
+    let mut entry = InlineTable::new();
+    entry.insert(&tool.host, tool.path.as_str().into());
+    entry.insert("version", tool.version.as_str().into());
+    entry.fmt();
+    tools.insert(alias, toml_edit::value(entry));
+
+    fs::write(config_path, document.to_string())
+}
+
+/// Rewrites the version of every tool declared in the configuration file at
+/// `config_path` that has an entry in `versions` to exactly that version, in
+/// both `[tools]` and `[dev-tools]`. Existing formatting and comments are
+/// preserved.
+///
+/// Returns the aliases of the declared tools that were rewritten, then those
+/// of the declared tools that were left untouched. Entries of `versions` for
+/// tools the file does not declare are in neither.
+pub fn freeze_tools(
+    config_path: &Path,
+    versions: &BTreeMap<String, Version>,
+) -> ForemanResult<(Vec<String>, Vec<String>)> {
+    let contents = fs::try_read_to_string(config_path)?.unwrap_or_default();
+    let mut document: Document = contents
+        .parse()
+        .map_err(|err: TomlError| ForemanError::config_parsing(config_path, err.to_string()))?;
+
+    let mut frozen = Vec::new();
+    let mut not_frozen = Vec::new();
+
+    for section in ["tools", "dev-tools"] {
+        let tools = match document
+            .as_table_mut()
+            .get_mut(section)
+            .and_then(|item| item.as_table_like_mut())
+        {
+            Some(tools) => tools,
+            None => continue,
+        };
+
+        for (alias, entry) in tools.iter_mut() {
+            let version_item = entry
+                .as_table_like_mut()
+                .and_then(|entry| entry.get_mut("version"));
+            let (version, version_item) = match (versions.get(alias.get()), version_item) {
+                (Some(version), Some(version_item)) => (version, version_item),
+                _ => {
+                    not_frozen.push(alias.get().to_owned());
+                    continue;
+                }
+            };
+
+            let decor = version_item.as_value().map(|value| value.decor().clone());
+            let mut exact_version = toml_edit::Value::from(format!("={}", version));
+            if let Some(decor) = decor {
+                *exact_version.decor_mut() = decor;
+            }
+            *version_item = toml_edit::Item::Value(exact_version);
+            frozen.push(alias.get().to_owned());
+        }
+    }
+
+    fs::write(config_path, document.to_string())?;
+
+    Ok((frozen, not_frozen))
 }
 
 impl fmt::Display for ToolSpec {
@@ -142,18 +652,109 @@ impl fmt::Display for ToolSpec {
 #[derive(Debug, PartialEq)]
 pub struct ConfigFile {
     pub tools: BTreeMap<String, ToolSpec>,
+    /// Tools only needed during development, declared under `[dev-tools]`.
+    /// They are only installed by `foreman install --dev`.
+    pub dev_tools: BTreeMap<String, ToolSpec>,
     pub hosts: HashMap<String, Host>,
+    /// Asset name keywords that replace the ones Foreman was built with, for
+    /// platforms it does not recognize.
+    pub platform_keywords: Option<Vec<String>>,
+    /// Tokens from an `[auth]` table, used when the auth store has none.
+    pub auth: Option<AuthStore>,
+    /// Versions never to install, keyed by tool source, from a `[yanked]`
+    /// table. They add up to the ones yanked by each tool.
+    pub yanked: HashMap<CiString, Vec<Version>>,
+    /// Whether invoking an alias of a tool that no configuration declares
+    /// runs the first executable with that name on `PATH` instead of
+    /// failing.
+    pub passthrough: Option<bool>,
 }
 
 #[derive(Debug, PartialEq)]
 pub struct Host {
     source: Url,
     protocol: Protocol,
+    /// Where requests to an Artifactory host carry its token.
+    auth_in: AuthPlacement,
+}
+
+/// The canonical spelling of a host's URL, so that equivalent spellings give
+/// tools the same cache key. Parsing already lowercases the host name and
+/// drops the default port; this makes the path always end with a slash.
+fn canonical_source(mut source: Url) -> Url {
+    if !source.path().ends_with('/') {
+        let path = format!("{}/", source.path());
+        source.set_path(&path);
+    }
+    source
+}
+
+/// The spelling `ToolSpec::cache_key` gives today to the tool `cache_key`
+/// refers to. Keys written before host URLs were canonicalized, like
+/// `https://ART.example.com:443/tools@team/tool`, are respelled; other keys
+/// are already canonical.
+///
+/// The host URL ends at the first `@` after its authority, since the tool's
+/// path may contain `@` too, like `@scope/tool` packages on Artifactory.
+pub fn canonical_cache_key(cache_key: &CiString) -> CiString {
+    if cache_key.0.starts_with("url@") {
+        return cache_key.clone();
+    }
+
+    let split = cache_key.0.find("://").and_then(|scheme_end| {
+        let authority_start = scheme_end + "://".len();
+        let path_start = authority_start + cache_key.0[authority_start..].find('/')?;
+        let at = path_start + cache_key.0[path_start..].find('@')?;
+        Some((&cache_key.0[..at], &cache_key.0[at + 1..]))
+    });
+
+    match split {
+        Some((host, path)) => match Url::parse(host) {
+            Ok(host) => CiString(format!("{}@{}", canonical_source(host), path)),
+            Err(_) => cache_key.clone(),
+        },
+        None => cache_key.clone(),
+    }
+}
+
+/// Where a request carries the token of an Artifactory host, as set by the
+/// host's `auth_in` key.
+#[derive(Debug, Clone, PartialEq, Eq, Default)]
+pub enum AuthPlacement {
+    /// An `Authorization: Bearer <token>` header, written `bearer`.
+    #[default]
+    Bearer,
+    /// A header with the given name, written `header:<name>`, like
+    /// `header:X-JFrog-Art-Api`.
+    Header(String),
+    /// A query parameter with the given name, written `query:<name>`.
+    Query(String),
+}
+
+impl AuthPlacement {
+    fn parse(text: &str) -> Option<Self> {
+        if text == "bearer" {
+            return Some(Self::Bearer);
+        }
+
+        let (kind, name) = text.split_once(':')?;
+        match kind {
+            "header" if reqwest::header::HeaderName::from_bytes(name.as_bytes()).is_ok() => {
+                Some(Self::Header(name.to_owned()))
+            }
+            "query" if !name.is_empty() => Some(Self::Query(name.to_owned())),
+            _ => None,
+        }
+    }
 }
 
 impl Host {
     pub fn new(source: Url, protocol: Protocol) -> Self {
-        Self { source, protocol }
+        Self {
+            source: canonical_source(source),
+            protocol,
+            auth_in: AuthPlacement::Bearer,
+        }
     }
 
     pub fn from_value(value: &Value) -> ConfigFileParseResult<Self> {
@@ -177,6 +778,7 @@ impl Host {
                     .ok_or_else(|| ConfigFileParseError::Host {
                         host: value.to_string(),
                     })?;
+            let auth_in_value = map.remove("auth_in");
 
             if !map.is_empty() {
                 return Err(ConfigFileParseError::Host {
@@ -194,7 +796,9 @@ impl Host {
             let protocol = match protocol_str {
                 "github" => Protocol::Github,
                 "gitlab" => Protocol::Gitlab,
+                "bitbucket" => Protocol::Bitbucket,
                 "artifactory" => Protocol::Artifactory,
+                "local" => Protocol::Local,
                 _ => {
                     return Err(ConfigFileParseError::InvalidProtocol {
                         protocol: protocol_str.to_string(),
@@ -202,7 +806,35 @@ impl Host {
                 }
             };
 
-            Ok(Self { source, protocol })
+            // Only the `local` protocol reads from disk, and it cannot read
+            // anything else.
+            if (protocol == Protocol::Local) != (source.scheme() == "file") {
+                return Err(ConfigFileParseError::ProtocolMismatch {
+                    protocol: protocol_str.to_string(),
+                    source: source_string,
+                });
+            }
+
+            // Only Artifactory hosts have their tokens placed differently.
+            let auth_in = match auth_in_value {
+                Some(Value::String(auth_in)) if protocol == Protocol::Artifactory => {
+                    AuthPlacement::parse(&auth_in).ok_or_else(|| ConfigFileParseError::Host {
+                        host: value.to_string(),
+                    })?
+                }
+                Some(_) => {
+                    return Err(ConfigFileParseError::Host {
+                        host: value.to_string(),
+                    })
+                }
+                None => AuthPlacement::Bearer,
+            };
+
+            Ok(Self {
+                source: canonical_source(source),
+                protocol,
+                auth_in,
+            })
         } else {
             Err(ConfigFileParseError::Host {
                 host: value.to_string(),
@@ -211,10 +843,50 @@ impl Host {
     }
 }
 
+impl Host {
+    /// The protocol that the host's URL suggests when it differs from the
+    /// declared one, like `gitlab` for `https://gitlab.example.com` declared
+    /// with `protocol = "github"`. This is only a guess from the names of
+    /// well-known services in the host name, so it is reported as a warning.
+    pub fn suggested_protocol(&self) -> Option<&'static str> {
+        const SERVICES: &[(&str, Protocol)] = &[
+            ("github", Protocol::Github),
+            ("gitlab", Protocol::Gitlab),
+            ("bitbucket", Protocol::Bitbucket),
+            ("artifactory", Protocol::Artifactory),
+            ("jfrog", Protocol::Artifactory),
+        ];
+
+        let host = self.source.host_str()?;
+        let named: Vec<&Protocol> = SERVICES
+            .iter()
+            .filter(|(service, _)| host.contains(service))
+            .map(|(_, protocol)| protocol)
+            .collect();
+
+        if named.is_empty() || named.contains(&&self.protocol) {
+            None
+        } else {
+            Some(named[0].name())
+        }
+    }
+}
+
 impl ConfigFile {
+    /// Where requests to each Artifactory host carry its token, keyed by the
+    /// host's URL.
+    pub fn auth_placements(&self) -> HashMap<Url, AuthPlacement> {
+        self.hosts
+            .values()
+            .filter(|host| host.protocol == Protocol::Artifactory)
+            .map(|host| (host.source.clone(), host.auth_in.clone()))
+            .collect()
+    }
+
     pub fn new_with_defaults() -> Self {
         Self {
             tools: BTreeMap::new(),
+            dev_tools: BTreeMap::new(),
             hosts: HashMap::from([
                 (
                     "source".to_string(),
@@ -229,6 +901,10 @@ impl ConfigFile {
                     Host::new(Url::parse(GITLAB).unwrap(), Protocol::Gitlab),
                 ),
             ]),
+            platform_keywords: None,
+            auth: None,
+            yanked: HashMap::new(),
+            passthrough: None,
         }
     }
 
@@ -236,15 +912,15 @@ impl ConfigFile {
         let mut config = ConfigFile::new_with_defaults();
 
         if let Value::Table(top_level) = &value {
-            if let Some(hosts) = &top_level.get("hosts") {
-                if let Value::Table(hosts) = hosts {
-                    for (host, toml) in hosts {
-                        let host_source =
-                            Host::from_value(&toml).map_err(|_| ConfigFileParseError::Tool {
-                                tool: value.to_string(),
-                            })?;
-                        config.hosts.insert(host.to_owned(), host_source);
-                    }
+            if let Some(Value::Table(hosts)) = top_level.get("hosts") {
+                for (host, toml) in hosts {
+                    let host_source = Host::from_value(toml).map_err(|err| match err {
+                        ConfigFileParseError::ProtocolMismatch { .. } => err,
+                        _ => ConfigFileParseError::Tool {
+                            tool: value.to_string(),
+                        },
+                    })?;
+                    config.hosts.insert(host.to_owned(), host_source);
                 }
             }
 
@@ -252,7 +928,7 @@ impl ConfigFile {
                 if let Value::Table(tools) = tools {
                     for (tool, toml) in tools {
                         let tool_spec =
-                            ToolSpec::from_value(&toml, &config.hosts).map_err(|_| {
+                            ToolSpec::from_value(toml, &config.hosts).map_err(|_| {
                                 ConfigFileParseError::Tool {
                                     tool: value.to_string(),
                                 }
@@ -265,6 +941,67 @@ impl ConfigFile {
                     field: "tools".to_string(),
                 });
             }
+
+            if let Some(Value::Table(dev_tools)) = top_level.get("dev-tools") {
+                for (tool, toml) in dev_tools {
+                    let tool_spec = ToolSpec::from_value(toml, &config.hosts).map_err(|_| {
+                        ConfigFileParseError::Tool {
+                            tool: value.to_string(),
+                        }
+                    })?;
+                    config.dev_tools.insert(tool.to_owned(), tool_spec);
+                }
+            }
+
+            if let Some(keywords) = top_level.get("platform_keywords") {
+                let keywords = keywords
+                    .as_array()
+                    .and_then(|keywords| {
+                        keywords
+                            .iter()
+                            .map(|keyword| keyword.as_str().map(str::to_owned))
+                            .collect::<Option<Vec<_>>>()
+                    })
+                    .ok_or_else(|| ConfigFileParseError::InvalidField {
+                        field: "platform_keywords".to_string(),
+                    })?;
+                config.platform_keywords = Some(keywords);
+            }
+
+            if let Some(yanked) = top_level.get("yanked") {
+                let invalid = || ConfigFileParseError::InvalidField {
+                    field: "yanked".to_string(),
+                };
+                for (source, versions) in yanked.as_table().ok_or_else(invalid)? {
+                    // The table does not know how the tool is versioned, but
+                    // dates, with their leading zeros, are not semantic
+                    // versions.
+                    let versions = parse_yanked(versions, Versioning::Semver)
+                        .or_else(|| parse_yanked(versions, Versioning::Calver))
+                        .ok_or_else(invalid)?;
+                    config.yanked.insert(CiString(source.clone()), versions);
+                }
+            }
+
+            if let Some(passthrough) = top_level.get("passthrough") {
+                let passthrough =
+                    passthrough
+                        .as_bool()
+                        .ok_or_else(|| ConfigFileParseError::InvalidField {
+                            field: "passthrough".to_string(),
+                        })?;
+                config.passthrough = Some(passthrough);
+            }
+
+            if let Some(auth) = top_level.get("auth") {
+                let auth =
+                    auth.clone()
+                        .try_into()
+                        .map_err(|_| ConfigFileParseError::InvalidField {
+                            field: "auth".to_string(),
+                        })?;
+                config.auth = Some(auth);
+            }
             Ok(config)
         } else {
             Err(ConfigFileParseError::Tool {
@@ -278,87 +1015,388 @@ impl ConfigFile {
             self.tools.entry(tool_name).or_insert(tool_source);
         }
 
+        for (tool_name, tool_source) in other.dev_tools {
+            self.dev_tools.entry(tool_name).or_insert(tool_source);
+        }
+
         for (host_name, host_source) in other.hosts {
             self.hosts.entry(host_name).or_insert(host_source);
         }
-    }
-
-    pub fn aggregate(paths: &ForemanPaths) -> Result<ConfigFile, ForemanError> {
-        let mut config = ConfigFile::new_with_defaults();
-
-        let base_dir = env::current_dir().map_err(|err| {
-            ForemanError::io_error_with_context(
-                err,
-                "unable to obtain the current working directory",
-            )
-        })?;
-        let mut current_dir = base_dir.as_path();
 
-        loop {
-            let config_path = current_dir.join("foreman.toml");
+        if self.platform_keywords.is_none() {
+            self.platform_keywords = other.platform_keywords;
+        }
 
-            if let Some(contents) = fs::try_read(&config_path)? {
-                let config_source = toml::from_slice(&contents)
-                    .map_err(|err| ForemanError::config_parsing(&config_path, err.to_string()))?;
-                let new_config = ConfigFile::from_value(config_source)
-                    .map_err(|err| ForemanError::config_parsing(&config_path, err.to_string()))?;
-                config.fill_from(new_config);
-            }
+        if self.auth.is_none() {
+            self.auth = other.auth;
+        }
 
-            if let Some(parent) = current_dir.parent() {
-                current_dir = parent;
-            } else {
-                break;
-            }
+        if self.passthrough.is_none() {
+            self.passthrough = other.passthrough;
         }
 
-        let home_config_path = paths.user_config();
-        if let Some(contents) = fs::try_read(&home_config_path)? {
-            let config_source = toml::from_slice(&contents)
-                .map_err(|err| ForemanError::config_parsing(&home_config_path, err.to_string()))?;
-            let new_config = ConfigFile::from_value(config_source)
-                .map_err(|err| ForemanError::config_parsing(&home_config_path, err.to_string()))?;
-            log::debug!(
-                "aggregating content from config file at {}",
-                home_config_path.display()
-            );
-            config.fill_from(new_config);
+        // A version yanked anywhere stays yanked.
+        for (source, versions) in other.yanked {
+            self.yanked.entry(source).or_default().extend(versions);
         }
+    }
 
-        Ok(config)
+    /// Looks up a tool by its alias, in both `[tools]` and `[dev-tools]`.
+    pub fn tool(&self, name: &str) -> Option<&ToolSpec> {
+        self.tools.get(name).or_else(|| self.dev_tools.get(name))
     }
-}
 
-impl fmt::Display for ConfigFile {
-    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
-        writeln!(f, "Available Tools:")?;
-        for (name, spec) in self.tools.iter() {
-            writeln!(f, "\t {} => {}", name, spec)?;
-        }
-        Ok(())
+    /// The aliases of every declared tool that start with `prefix`, in
+    /// alphabetical order.
+    pub fn aliases_starting_with(&self, prefix: &str) -> Vec<&str> {
+        let mut aliases: Vec<&str> = self
+            .tools
+            .keys()
+            .chain(self.dev_tools.keys())
+            .map(String::as_str)
+            .filter(|alias| alias.starts_with(prefix))
+            .collect();
+        aliases.sort_unstable();
+        aliases.dedup();
+        aliases
     }
-}
 
-#[cfg(test)]
-mod test {
-    const ARTIFACTORY: &'static str = "https://artifactory.com";
-    use super::*;
+    /// Finds the closest `foreman.toml` file, looking in `start` and then in
+    /// each of its parent directories.
+    pub fn find_nearest(start: &Path) -> Option<PathBuf> {
+        start
+            .ancestors()
+            .map(|directory| directory.join("foreman.toml"))
+            .find(|config_path| config_path.is_file())
+    }
 
-    fn new_github<S: Into<String>>(github: S, version: VersionReq) -> ToolSpec {
-        ToolSpec {
-            host: Url::parse(GITHUB).unwrap(),
-            path: github.into(),
-            version: version,
-            protocol: Protocol::Github,
-        }
+    /// The `foreman.toml` that commands editing the project configuration
+    /// act on: the one in the config root when it is set, otherwise the
+    /// closest one to the current directory. Returns the directory it was
+    /// searched from along with it.
+    pub fn find_project(paths: &ForemanPaths) -> ForemanResult<(PathBuf, Option<PathBuf>)> {
+        let base_dir = Self::base_dir(paths)?;
+        let config_path = match paths.config_root() {
+            Some(_) => Some(base_dir.join("foreman.toml")).filter(|path| path.is_file()),
+            None => Self::find_nearest(&base_dir),
+        };
+        Ok((base_dir, config_path))
     }
 
-    fn new_gitlab<S: Into<String>>(gitlab: S, version: VersionReq) -> ToolSpec {
-        ToolSpec {
-            host: Url::parse(GITLAB).unwrap(),
-            path: gitlab.into(),
-            version: version,
+    /// Gathers the configuration from every `foreman.toml` file between the
+    /// current directory and the root, then from the user's configuration.
+    /// Files closer to the current directory take precedence. With a config
+    /// root, only its `foreman.toml` and the user's configuration are read.
+    ///
+    /// Problems that do not prevent Foreman from running are returned as
+    /// warnings for the caller to report.
+    pub fn aggregate(paths: &ForemanPaths) -> ForemanResult<(ConfigFile, Vec<ConfigWarning>)> {
+        let base_dir = Self::base_dir(paths)?;
+
+        Self::aggregate_from(
+            &base_dir,
+            &paths.user_config(),
+            paths.config_root().is_none(),
+        )
+    }
+
+    /// The configuration files `aggregate` reads when they exist, from the
+    /// highest precedence to the lowest.
+    pub fn candidate_paths(paths: &ForemanPaths) -> ForemanResult<Vec<PathBuf>> {
+        let base_dir = Self::base_dir(paths)?;
+
+        Ok(Self::candidate_paths_from(
+            &base_dir,
+            &paths.user_config(),
+            paths.config_root().is_none(),
+        ))
+    }
+
+    /// The directory project configuration files are looked up from: the
+    /// config root when it is set, otherwise the current directory.
+    fn base_dir(paths: &ForemanPaths) -> ForemanResult<PathBuf> {
+        match paths.config_root() {
+            Some(config_root) if config_root.is_dir() => Ok(config_root.to_owned()),
+            Some(config_root) => Err(ForemanError::io_error_with_context(
+                io::ErrorKind::NotFound.into(),
+                format!("config root {} is not a directory", config_root.display()),
+            )),
+            None => env::current_dir().map_err(|err| {
+                ForemanError::io_error_with_context(
+                    err,
+                    "unable to obtain the current working directory",
+                )
+            }),
+        }
+    }
+
+    /// Reads every configuration file that `aggregate` gathers without merging
+    /// them, so that tools shadowed by closer files are kept.
+    pub fn load_each(paths: &ForemanPaths) -> ForemanResult<Vec<ConfigFile>> {
+        Self::candidate_paths(paths)?
+            .iter()
+            .filter_map(|config_path| Self::load_file(config_path).transpose())
+            .collect()
+    }
+
+    /// Reads the configuration file at `config_path`, or `None` when it does
+    /// not exist.
+    pub fn load_file(config_path: &Path) -> ForemanResult<Option<ConfigFile>> {
+        match Self::read_value(config_path)? {
+            Some(config_source) => ConfigFile::from_value(config_source)
+                .map(Some)
+                .map_err(|err| ForemanError::config_parsing(config_path, err.to_string())),
+            None => Ok(None),
+        }
+    }
+
+    fn read_value(config_path: &Path) -> ForemanResult<Option<Value>> {
+        match fs::try_read(config_path)? {
+            Some(contents) => toml::from_slice(&contents)
+                .map(Some)
+                .map_err(|err| ForemanError::config_parsing(config_path, err.to_string())),
+            None => Ok(None),
+        }
+    }
+
+    fn candidate_paths_from(
+        base_dir: &Path,
+        home_config_path: &Path,
+        walk_parents: bool,
+    ) -> Vec<PathBuf> {
+        base_dir
+            .ancestors()
+            .take(if walk_parents { usize::MAX } else { 1 })
+            .map(|directory| directory.join("foreman.toml"))
+            .chain(std::iter::once(home_config_path.to_owned()))
+            .collect()
+    }
+
+    fn aggregate_from(
+        base_dir: &Path,
+        home_config_path: &Path,
+        walk_parents: bool,
+    ) -> ForemanResult<(ConfigFile, Vec<ConfigWarning>)> {
+        let mut config = ConfigFile::new_with_defaults();
+        let mut warnings = Vec::new();
+        let mut tool_origins: HashMap<String, PathBuf> = HashMap::new();
+
+        let project_config_path = base_dir.join("foreman.toml");
+        for config_path in Self::candidate_paths_from(base_dir, home_config_path, walk_parents) {
+            let is_ancestor = config_path != project_config_path && config_path != home_config_path;
+            let read = Self::read_value(&config_path);
+            let config_source =
+                match skip_unreadable_ancestor(read, &config_path, is_ancestor, &mut warnings)? {
+                    Some(config_source) => config_source,
+                    None => continue,
+                };
+            log::debug!(
+                "aggregating content from config file at {}",
+                config_path.display()
+            );
+
+            if let Value::Table(top_level) = &config_source {
+                for key in top_level.keys() {
+                    if !KNOWN_KEYS.contains(&key.as_str()) {
+                        warnings.push(ConfigWarning::UnknownKey {
+                            key: key.clone(),
+                            path: config_path.clone(),
+                        });
+                    }
+                }
+            }
+
+            let new_config = ConfigFile::from_value(config_source)
+                .map_err(|err| ForemanError::config_parsing(&config_path, err.to_string()))?;
+
+            let mut hosts: Vec<(&String, &Host)> = new_config.hosts.iter().collect();
+            hosts.sort_by_key(|(name, _)| *name);
+            for (name, host) in hosts {
+                if let Some(suggested) = host.suggested_protocol() {
+                    warnings.push(ConfigWarning::ProtocolMismatch {
+                        host: name.clone(),
+                        protocol: host.protocol.name(),
+                        suggested,
+                        path: config_path.clone(),
+                    });
+                }
+            }
+
+            for (name, tool) in new_config.tools.iter().chain(&new_config.dev_tools) {
+                match tool_origins.get(name) {
+                    Some(origin) => {
+                        if config.tool(name) != Some(tool) {
+                            warnings.push(ConfigWarning::ShadowedTool {
+                                name: name.clone(),
+                                path: origin.clone(),
+                                shadowed_path: config_path.clone(),
+                            });
+                        }
+                    }
+                    None => {
+                        tool_origins.insert(name.clone(), config_path.clone());
+                    }
+                }
+            }
+
+            config.fill_from(new_config);
+        }
+
+        Ok((config, warnings))
+    }
+}
+
+/// Turns a failure to read the configuration file of a parent directory for
+/// lack of permission into a warning. These files often belong to someone
+/// else on shared filesystems, so they should not fail the whole command.
+fn skip_unreadable_ancestor(
+    read: ForemanResult<Option<Value>>,
+    config_path: &Path,
+    is_ancestor: bool,
+    warnings: &mut Vec<ConfigWarning>,
+) -> ForemanResult<Option<Value>> {
+    match read {
+        Err(ForemanError::Read { source, .. })
+            if is_ancestor && source.kind() == io::ErrorKind::PermissionDenied =>
+        {
+            log::debug!("skipping unreadable {}: {}", config_path.display(), source);
+            warnings.push(ConfigWarning::UnreadableConfig {
+                path: config_path.to_owned(),
+            });
+            Ok(None)
+        }
+        read => read,
+    }
+}
+
+/// Top-level keys Foreman understands in a configuration file.
+const KNOWN_KEYS: &[&str] = &[
+    "tools",
+    "dev-tools",
+    "hosts",
+    "platform_keywords",
+    "auth",
+    "yanked",
+    "passthrough",
+];
+
+/// A problem found while gathering configuration files that does not prevent
+/// Foreman from running.
+#[derive(Debug, PartialEq)]
+pub enum ConfigWarning {
+    /// A tool declared in `path` hides a different declaration of the same
+    /// tool in `shadowed_path`.
+    ShadowedTool {
+        name: String,
+        path: PathBuf,
+        shadowed_path: PathBuf,
+    },
+    /// A top-level key that Foreman does not know about and ignores.
+    UnknownKey { key: String, path: PathBuf },
+    /// A configuration file of a parent directory that Foreman is not
+    /// allowed to read, and skips.
+    UnreadableConfig { path: PathBuf },
+    /// A host whose URL looks like it belongs to another protocol than the
+    /// one it declares.
+    ProtocolMismatch {
+        host: String,
+        protocol: &'static str,
+        suggested: &'static str,
+        path: PathBuf,
+    },
+}
+
+impl fmt::Display for ConfigWarning {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::ShadowedTool {
+                name,
+                path,
+                shadowed_path,
+            } => write!(
+                f,
+                "tool '{}' from {} takes precedence over its declaration in {}",
+                name,
+                path.display(),
+                shadowed_path.display()
+            ),
+            Self::UnknownKey { key, path } => {
+                write!(f, "unknown key `{}` in {} is ignored", key, path.display())
+            }
+            Self::UnreadableConfig { path } => write!(
+                f,
+                "{} is skipped, permission to read it was denied",
+                path.display()
+            ),
+            Self::ProtocolMismatch {
+                host,
+                protocol,
+                suggested,
+                path,
+            } => write!(
+                f,
+                "host `{}` in {} uses protocol `{}`, but its source looks like a {} host, did you mean `protocol = \"{}\"`?",
+                host,
+                path.display(),
+                protocol,
+                suggested,
+                suggested
+            ),
+        }
+    }
+}
+
+impl fmt::Display for ConfigFile {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        writeln!(f, "Available Tools:")?;
+        for (name, spec) in self.tools.iter() {
+            writeln!(f, "\t {} => {}", name, spec)?;
+        }
+        for (name, spec) in self.dev_tools.iter() {
+            writeln!(f, "\t {} => {} (dev)", name, spec)?;
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod test {
+    const ARTIFACTORY: &str = "https://artifactory.com";
+    use super::*;
+
+    fn new_github<S: Into<String>>(github: S, version: VersionReq) -> ToolSpec {
+        ToolSpec {
+            host: Url::parse(GITHUB).unwrap(),
+            path: github.into(),
+            version,
+            protocol: Protocol::Github,
+            platforms: Vec::new(),
+            bin: None,
+            asset: None,
+            env: BTreeMap::new(),
+            source_fallback: false,
+            direct: None,
+            yanked: Vec::new(),
+            versioning: Versioning::Semver,
+            verify: None,
+        }
+    }
+
+    fn new_gitlab<S: Into<String>>(gitlab: S, version: VersionReq) -> ToolSpec {
+        ToolSpec {
+            host: Url::parse(GITLAB).unwrap(),
+            path: gitlab.into(),
+            version,
             protocol: Protocol::Gitlab,
+            platforms: Vec::new(),
+            bin: None,
+            asset: None,
+            env: BTreeMap::new(),
+            source_fallback: false,
+            direct: None,
+            yanked: Vec::new(),
+            versioning: Versioning::Semver,
+            verify: None,
         }
     }
 
@@ -366,14 +1404,31 @@ mod test {
         ToolSpec {
             host: Url::parse(host.into().as_str()).unwrap(),
             path: path.into(),
-            version: version,
+            version,
             protocol: Protocol::Artifactory,
+            platforms: Vec::new(),
+            bin: None,
+            asset: None,
+            env: BTreeMap::new(),
+            source_fallback: false,
+            direct: None,
+            yanked: Vec::new(),
+            versioning: Versioning::Semver,
+            verify: None,
         }
     }
 
     fn new_config(tools: BTreeMap<String, ToolSpec>, hosts: HashMap<String, Host>) -> ConfigFile {
         let mut config = ConfigFile::new_with_defaults();
-        config.fill_from(ConfigFile { tools, hosts });
+        config.fill_from(ConfigFile {
+            tools,
+            dev_tools: BTreeMap::new(),
+            hosts,
+            platform_keywords: None,
+            auth: None,
+            yanked: HashMap::new(),
+            passthrough: None,
+        });
         config
     }
 
@@ -382,7 +1437,7 @@ mod test {
     }
 
     fn new_host(source: Url, protocol: Protocol) -> Host {
-        Host { source, protocol }
+        Host::new(source, protocol)
     }
 
     fn default_hosts() -> HashMap<String, Host> {
@@ -467,115 +1522,1241 @@ mod test {
         }
 
         #[test]
-        fn host_artifactory() {
+        fn tool_with_platforms() {
             let value: Value = toml::from_str(
                 &[
-                    r#"source = "https://artifactory.com""#,
-                    r#"protocol = "artifactory""#,
+                    r#"github = "user/repo""#,
+                    r#"version = "0.1.0""#,
+                    r#"platforms = ["windows", "macos-aarch64"]"#,
                 ]
                 .join("\n"),
             )
             .unwrap();
 
-            let host = Host::from_value(&value).unwrap();
-            assert_eq!(
-                host,
-                new_host(
-                    Url::parse("https://artifactory.com").unwrap(),
-                    Protocol::Artifactory
-                )
+            let tool = ToolSpec::from_value(&value, &default_hosts()).unwrap();
+            assert_eq!(tool.platforms(), ["windows", "macos-aarch64"]);
+        }
+
+        #[test]
+        fn tool_with_verify_arguments() {
+            let value: Value = toml::from_str(
+                &[
+                    r#"github = "user/repo""#,
+                    r#"version = "0.1.0""#,
+                    r#"verify = "--version --verbose""#,
+                ]
+                .join("\n"),
             )
+            .unwrap();
+
+            let tool = ToolSpec::from_value(&value, &default_hosts()).unwrap();
+            assert_eq!(
+                tool.verify(),
+                Some(["--version".to_owned(), "--verbose".to_owned()].as_slice())
+            );
+
+            for verify in ["true", "\"\"", "[]", "[\"--version\", 1]"] {
+                let value: Value = toml::from_str(&format!(
+                    "github = \"user/repo\"\nversion = \"0.1.0\"\nverify = {}",
+                    verify
+                ))
+                .unwrap();
+                assert!(
+                    ToolSpec::from_value(&value, &default_hosts()).is_err(),
+                    "verify = {} should be rejected",
+                    verify
+                );
+            }
         }
 
         #[test]
-        fn extraneous_fields_tools() {
+        fn tool_with_verify_argument_list() {
             let value: Value = toml::from_str(
                 &[
-                    r#"rbx_artifactory = "generic-rbx-local-tools/rotriever/""#,
-                    r#"path = "generic-rbx-local-tools/rotriever/""#,
-                    r#"version = "0.5.4""#,
+                    r#"github = "user/repo""#,
+                    r#"version = "0.1.0""#,
+                    r#"verify = ["--eval", "print('hello world')"]"#,
                 ]
                 .join("\n"),
             )
             .unwrap();
 
-            let artifactory = ToolSpec::from_value(&value, &artifactory_host()).unwrap_err();
+            let tool = ToolSpec::from_value(&value, &default_hosts()).unwrap();
             assert_eq!(
-                artifactory,
-                ConfigFileParseError::Tool {
-                    tool: [
-                        r#"path = "generic-rbx-local-tools/rotriever/""#,
-                        r#"rbx_artifactory = "generic-rbx-local-tools/rotriever/""#,
-                        r#"version = "0.5.4""#,
-                        r#""#,
-                    ]
-                    .join("\n")
-                    .to_string()
-                }
+                tool.verify(),
+                Some(["--eval".to_owned(), "print('hello world')".to_owned()].as_slice())
+            );
+        }
+
+        #[test]
+        fn tool_with_invalid_platforms() {
+            let value: Value = toml::from_str(
+                &[
+                    r#"github = "user/repo""#,
+                    r#"version = "0.1.0""#,
+                    r#"platforms = "windows""#,
+                ]
+                .join("\n"),
             )
+            .unwrap();
+
+            assert!(ToolSpec::from_value(&value, &default_hosts()).is_err());
         }
 
         #[test]
-        fn extraneous_fields_host() {
+        fn host_artifactory() {
             let value: Value = toml::from_str(
                 &[
                     r#"source = "https://artifactory.com""#,
                     r#"protocol = "artifactory""#,
-                    r#"extra = "field""#,
                 ]
                 .join("\n"),
             )
             .unwrap();
 
-            let err = Host::from_value(&value).unwrap_err();
+            let host = Host::from_value(&value).unwrap();
             assert_eq!(
-                err,
-                ConfigFileParseError::Host {
-                    host: [
-                        r#"extra = "field""#,
-                        r#"protocol = "artifactory""#,
-                        r#"source = "https://artifactory.com""#,
-                        r#""#,
-                    ]
-                    .join("\n")
-                    .to_string()
-                }
+                host,
+                new_host(
+                    Url::parse("https://artifactory.com").unwrap(),
+                    Protocol::Artifactory
+                )
             )
         }
+
+        fn artifactory_host_with_auth_in(auth_in: &str) -> ConfigFileParseResult<Host> {
+            let value: Value = toml::from_str(&format!(
+                "source = \"https://artifactory.com\"\nprotocol = \"artifactory\"\nauth_in = \"{}\"",
+                auth_in
+            ))
+            .unwrap();
+            Host::from_value(&value)
+        }
+
         #[test]
-        fn config_file_with_hosts() {
-            let value: Value = toml::from_str(&[
-                r#"[hosts]"#,
-                r#"artifactory = {source = "https://artifactory.com", protocol = "artifactory"}"#,
-                r#""#,
-                r#"[tools]"#,
-                r#"tool = {artifactory = "path/to/tool", version = "1.0.0"}"#,
-            ].join("\n"))
+        fn tool_yanked_versions() {
+            let hosts = ConfigFile::new_with_defaults().hosts;
+            let tool = ToolSpec::from_value(
+                &toml::from_str(
+                    "github = \"JohnnyMorganz/StyLua\"\nversion = \"^0.14\"\nyanked = [\"0.14.2\"]",
+                )
+                .unwrap(),
+                &hosts,
+            )
             .unwrap();
+            assert_eq!(tool.yanked(), &[Version::new(0, 14, 2)]);
 
-            let config = ConfigFile::from_value(value).unwrap();
-            assert_eq!(
-                config,
-                new_config(
-                    BTreeMap::from([(
-                        "tool".to_string(),
-                        ToolSpec {
-                            host: Url::parse("https://artifactory.com").unwrap(),
-                            path: "path/to/tool".to_string(),
-                            version: VersionReq::parse("1.0.0").unwrap(),
-                            protocol: Protocol::Artifactory
-                        }
-                    )]),
-                    HashMap::from([(
-                        "artifactory".to_string(),
-                        Host {
-                            source: Url::parse("https://artifactory.com").unwrap(),
-                            protocol: Protocol::Artifactory
-                        }
-                    )])
+            assert!(ToolSpec::from_value(
+                &toml::from_str(
+                    "github = \"JohnnyMorganz/StyLua\"\nversion = \"^0.14\"\nyanked = [\"^0.14\"]",
                 )
+                .unwrap(),
+                &hosts,
             )
+            .is_err());
+        }
+
+        #[test]
+        fn tool_calver_versioning() {
+            let hosts = ConfigFile::new_with_defaults().hosts;
+            let tool = |versioning: &str| {
+                ToolSpec::from_value(
+                    &toml::from_str(&format!(
+                        "github = \"user/tool\"\nversion = \">=2024.01.01\"\n\
+                        yanked = [\"2024.03.01\"]\nversioning = \"{}\"",
+                        versioning
+                    ))
+                    .unwrap(),
+                    &hosts,
+                )
+            };
+
+            let calver = tool("calver").unwrap();
+            assert_eq!(calver.versioning(), Versioning::Calver);
+            assert_eq!(calver.version(), &version(">=2024.1.1"));
+            assert_eq!(calver.yanked(), &[Version::new(2024, 3, 1)]);
+
+            // Dates have leading zeros that semantic versions reject.
+            assert!(tool("semver").is_err());
+            assert!(tool("date").is_err());
+        }
+
+        #[test]
+        fn yanked_table_adds_up_across_files() {
+            let config = |yanked: &str| {
+                ConfigFile::from_value(
+                    toml::from_str(&format!(
+                        "[tools]\n\n[yanked]\n\"github.com/JohnnyMorganz/StyLua\" = {}",
+                        yanked
+                    ))
+                    .unwrap(),
+                )
+                .unwrap()
+            };
+
+            let mut aggregated = config("[\"0.14.2\"]");
+            aggregated.fill_from(config("[\"0.14.0\"]"));
+            assert_eq!(
+                aggregated.yanked[&CiString::from("github.com/JohnnyMorganz/StyLua")],
+                vec![Version::new(0, 14, 2), Version::new(0, 14, 0)]
+            );
+
+            assert_eq!(
+                ConfigFile::from_value(
+                    toml::from_str("tools = {}\nyanked = [\"0.14.2\"]").unwrap()
+                ),
+                Err(ConfigFileParseError::InvalidField {
+                    field: "yanked".to_string()
+                })
+            );
+        }
+
+        #[test]
+        fn equivalent_host_spellings_share_cache_key() {
+            let cache_key = |source: &str| {
+                let host = Host::from_value(
+                    &toml::from_str(&format!(
+                        "source = \"{}\"\nprotocol = \"artifactory\"",
+                        source
+                    ))
+                    .unwrap(),
+                )
+                .unwrap();
+                let hosts = HashMap::from([("art".to_owned(), host)]);
+                ToolSpec::from_value(
+                    &toml::from_str("art = \"team/tool\"\nversion = \"1.0.0\"").unwrap(),
+                    &hosts,
+                )
+                .unwrap()
+                .cache_key()
+            };
+
+            let canonical = cache_key("https://art.example.com/tools/");
+            assert_eq!(canonical.0, "https://art.example.com/tools/@team/tool");
+            assert_eq!(cache_key("https://art.example.com/tools"), canonical);
+            assert_eq!(cache_key("https://ART.example.com:443/tools"), canonical);
+            assert_eq!(
+                cache_key("https://art.example.com"),
+                cache_key("https://art.example.com/")
+            );
+        }
+
+        #[test]
+        fn old_host_spellings_have_canonical_cache_keys() {
+            let canonical = |cache_key: &str| canonical_cache_key(&CiString::from(cache_key)).0;
+
+            assert_eq!(
+                canonical("https://ART.example.com:443/tools@team/tool"),
+                "https://art.example.com/tools/@team/tool"
+            );
+            assert_eq!(
+                canonical("https://art.example.com/tools/@team/tool"),
+                "https://art.example.com/tools/@team/tool"
+            );
+            assert_eq!(
+                canonical("https://ART.example.com/tools@team/@scope/tool@2"),
+                "https://art.example.com/tools/@team/@scope/tool@2"
+            );
+            assert_eq!(
+                canonical("https://user@ART.example.com/tools@team/tool"),
+                "https://user@art.example.com/tools/@team/tool"
+            );
+            assert_eq!(canonical("rojo-rbx/rojo"), "rojo-rbx/rojo");
+            assert_eq!(
+                canonical("gitlab@seaofvoices/darklua"),
+                "gitlab@seaofvoices/darklua"
+            );
+            assert_eq!(
+                canonical("url@https://example.com/tool.zip"),
+                "url@https://example.com/tool.zip"
+            );
+        }
+
+        #[test]
+        fn host_auth_in() {
+            assert_eq!(
+                artifactory_host_with_auth_in("bearer").unwrap().auth_in,
+                AuthPlacement::Bearer
+            );
+            assert_eq!(
+                artifactory_host_with_auth_in("header:X-JFrog-Art-Api")
+                    .unwrap()
+                    .auth_in,
+                AuthPlacement::Header("X-JFrog-Art-Api".to_owned())
+            );
+            assert_eq!(
+                artifactory_host_with_auth_in("query:token")
+                    .unwrap()
+                    .auth_in,
+                AuthPlacement::Query("token".to_owned())
+            );
+
+            for invalid in ["basic", "header:", "header:not a header", "query:"] {
+                assert!(
+                    artifactory_host_with_auth_in(invalid).is_err(),
+                    "{}",
+                    invalid
+                );
+            }
         }
+
+        #[test]
+        fn host_auth_in_only_for_artifactory() {
+            let value: Value = toml::from_str(
+                &[
+                    r#"source = "https://github.com""#,
+                    r#"protocol = "github""#,
+                    r#"auth_in = "bearer""#,
+                ]
+                .join("\n"),
+            )
+            .unwrap();
+
+            assert!(Host::from_value(&value).is_err());
+        }
+
+        #[test]
+        fn tool_with_direct_url() {
+            let value: Value = toml::from_str(
+                r#"url = "https://cdn.example.com/tools/tool-linux.zip"
+version = "1.0.0"
+bin = "tool""#,
+            )
+            .unwrap();
+
+            let tool = ToolSpec::from_value(&value, &default_hosts()).unwrap();
+            assert_eq!(
+                tool.direct(),
+                Some(&DirectAsset {
+                    url: Url::parse("https://cdn.example.com/tools/tool-linux.zip").unwrap(),
+                    version: Version::new(1, 0, 0),
+                })
+            );
+            assert_eq!(tool.provider(), Provider::Direct);
+            assert_eq!(tool.bin(), Some("tool"));
+            assert_eq!(
+                tool.to_string(),
+                "https://cdn.example.com/tools/tool-linux.zip@=1.0.0"
+            );
+        }
+
+        #[test]
+        fn direct_url_template() {
+            let version = Version::new(1, 2, 0);
+            assert_eq!(
+                expand_url_template(
+                    "https://example.com/tools/mytool-{version}-{platform}-{arch}.zip",
+                    &version,
+                    "linux",
+                    "x86_64"
+                ),
+                Some("https://example.com/tools/mytool-1.2.0-linux-x86_64.zip".to_owned())
+            );
+            assert_eq!(
+                expand_url_template(
+                    "https://example.com/{verison}.zip",
+                    &version,
+                    "linux",
+                    "x86_64"
+                ),
+                None
+            );
+
+            let value: Value = toml::from_str(
+                r#"url = "https://cdn.example.com/{version}/tool-{platform}.zip"
+version = "=1.2.0""#,
+            )
+            .unwrap();
+            let tool = ToolSpec::from_value(&value, &default_hosts()).unwrap();
+            assert_eq!(
+                tool.direct().unwrap().url.as_str(),
+                format!("https://cdn.example.com/1.2.0/tool-{}.zip", env::consts::OS)
+            );
+            assert_eq!(
+                tool.cache_key(),
+                CiString("url@https://cdn.example.com/{version}/tool-{platform}.zip".to_owned())
+            );
+
+            let value: Value = toml::from_str(
+                r#"url = "https://cdn.example.com/{version}/tool-{platform}.zip"
+version = "=1.3.0""#,
+            )
+            .unwrap();
+            let newer = ToolSpec::from_value(&value, &default_hosts()).unwrap();
+            assert_eq!(newer.cache_key(), tool.cache_key());
+        }
+
+        #[test]
+        fn direct_url_of_configured_host_uses_its_provider() {
+            let value: Value = toml::from_str(
+                r#"url = "https://artifactory.com/artifactory/tools/tool.zip"
+version = "=1.0.0""#,
+            )
+            .unwrap();
+
+            let tool = ToolSpec::from_value(&value, &artifactory_host()).unwrap();
+            assert_eq!(tool.provider(), Provider::Artifactory);
+            assert_eq!(tool.direct().unwrap().version, Version::new(1, 0, 0));
+        }
+
+        #[test]
+        fn direct_url_requires_exact_version() {
+            for tool in [
+                r#"url = "https://cdn.example.com/tool.zip"
+version = "^1.0.0""#,
+                r#"url = "not a url"
+version = "1.0.0""#,
+                r#"url = "https://cdn.example.com/tool.zip"
+version = "1.0.0"
+platforms = ["linux"]"#,
+            ] {
+                let value: Value = toml::from_str(tool).unwrap();
+                assert!(
+                    ToolSpec::from_value(&value, &default_hosts()).is_err(),
+                    "{}",
+                    tool
+                );
+            }
+        }
+
+        #[test]
+        fn host_local() {
+            let value: Value = toml::from_str(
+                &[r#"source = "file:///mnt/tools""#, r#"protocol = "local""#].join("\n"),
+            )
+            .unwrap();
+
+            let host = Host::from_value(&value).unwrap();
+            assert_eq!(
+                host,
+                new_host(Url::parse("file:///mnt/tools").unwrap(), Protocol::Local)
+            )
+        }
+
+        fn parse_host(source: &str, protocol: &str) -> ConfigFileParseResult<Host> {
+            let value: Value = toml::from_str(&format!(
+                "source = \"{}\"\nprotocol = \"{}\"",
+                source, protocol
+            ))
+            .unwrap();
+            Host::from_value(&value)
+        }
+
+        #[test]
+        fn host_named_after_another_service_suggests_its_protocol() {
+            let host = parse_host("https://gitlab.example.com", "github").unwrap();
+            assert_eq!(host.suggested_protocol(), Some("gitlab"));
+
+            let host = parse_host("https://github.com", "gitlab").unwrap();
+            assert_eq!(host.suggested_protocol(), Some("github"));
+        }
+
+        #[test]
+        fn consistent_or_unknown_hosts_suggest_nothing() {
+            for (source, protocol) in [
+                ("https://github.example.com", "github"),
+                ("https://tools.example.com", "gitlab"),
+                ("https://gitlab-artifactory.example.com", "artifactory"),
+            ] {
+                let host = parse_host(source, protocol).unwrap();
+                assert_eq!(host.suggested_protocol(), None, "{}", source);
+            }
+        }
+
+        #[test]
+        fn local_protocol_only_reads_file_urls() {
+            assert_eq!(
+                parse_host("https://github.com", "local"),
+                Err(ConfigFileParseError::ProtocolMismatch {
+                    protocol: "local".to_string(),
+                    source: "https://github.com".to_string(),
+                })
+            );
+            assert!(parse_host("file:///mnt/tools", "github").is_err());
+        }
+
+        #[test]
+        fn host_bitbucket() {
+            let value: Value = toml::from_str(
+                &[
+                    r#"source = "https://bitbucket.org""#,
+                    r#"protocol = "bitbucket""#,
+                ]
+                .join("\n"),
+            )
+            .unwrap();
+
+            let host = Host::from_value(&value).unwrap();
+            assert_eq!(
+                host,
+                new_host(
+                    Url::parse("https://bitbucket.org").unwrap(),
+                    Protocol::Bitbucket
+                )
+            )
+        }
+
+        #[test]
+        fn extraneous_fields_tools() {
+            let value: Value = toml::from_str(
+                &[
+                    r#"rbx_artifactory = "generic-rbx-local-tools/rotriever/""#,
+                    r#"path = "generic-rbx-local-tools/rotriever/""#,
+                    r#"version = "0.5.4""#,
+                ]
+                .join("\n"),
+            )
+            .unwrap();
+
+            let artifactory = ToolSpec::from_value(&value, &artifactory_host()).unwrap_err();
+            assert_eq!(
+                artifactory,
+                ConfigFileParseError::Tool {
+                    tool: [
+                        r#"path = "generic-rbx-local-tools/rotriever/""#,
+                        r#"rbx_artifactory = "generic-rbx-local-tools/rotriever/""#,
+                        r#"version = "0.5.4""#,
+                        r#""#,
+                    ]
+                    .join("\n")
+                    .to_string()
+                }
+            )
+        }
+
+        #[test]
+        fn extraneous_fields_host() {
+            let value: Value = toml::from_str(
+                &[
+                    r#"source = "https://artifactory.com""#,
+                    r#"protocol = "artifactory""#,
+                    r#"extra = "field""#,
+                ]
+                .join("\n"),
+            )
+            .unwrap();
+
+            let err = Host::from_value(&value).unwrap_err();
+            assert_eq!(
+                err,
+                ConfigFileParseError::Host {
+                    host: [
+                        r#"extra = "field""#,
+                        r#"protocol = "artifactory""#,
+                        r#"source = "https://artifactory.com""#,
+                        r#""#,
+                    ]
+                    .join("\n")
+                    .to_string()
+                }
+            )
+        }
+        #[test]
+        fn config_file_with_hosts() {
+            let value: Value = toml::from_str(&[
+                r#"[hosts]"#,
+                r#"artifactory = {source = "https://artifactory.com", protocol = "artifactory"}"#,
+                r#""#,
+                r#"[tools]"#,
+                r#"tool = {artifactory = "path/to/tool", version = "1.0.0"}"#,
+            ].join("\n"))
+            .unwrap();
+
+            let config = ConfigFile::from_value(value).unwrap();
+            assert_eq!(
+                config,
+                new_config(
+                    BTreeMap::from([(
+                        "tool".to_string(),
+                        ToolSpec {
+                            host: Url::parse("https://artifactory.com").unwrap(),
+                            path: "path/to/tool".to_string(),
+                            version: VersionReq::parse("1.0.0").unwrap(),
+                            protocol: Protocol::Artifactory,
+                            platforms: Vec::new(),
+                            bin: None,
+                            asset: None,
+                            env: BTreeMap::new(),
+                            source_fallback: false,
+                            direct: None,
+                            yanked: Vec::new(),
+                            versioning: Versioning::Semver,
+                            verify: None,
+                        }
+                    )]),
+                    HashMap::from([(
+                        "artifactory".to_string(),
+                        Host::new(
+                            Url::parse("https://artifactory.com").unwrap(),
+                            Protocol::Artifactory
+                        )
+                    )])
+                )
+            )
+        }
+    }
+
+    mod freeze {
+        use super::*;
+        use tempfile::tempdir;
+
+        #[test]
+        fn freezing_pins_exact_versions() {
+            let directory = tempdir().unwrap();
+            let config_path = directory.path().join("foreman.toml");
+            std::fs::write(
+                &config_path,
+                [
+                    "# my tools",
+                    "[tools]",
+                    "stylua = { github = \"JohnnyMorganz/StyLua\", version = \"^0.14\" } # formatter",
+                    "rojo = { github = \"rojo-rbx/rojo\", version = \"7.3.0\" }",
+                    "",
+                    "[dev-tools]",
+                    "selene = { github = \"Kampfkarren/selene\", version = \"0.25\" }",
+                    "",
+                ]
+                .join("\n"),
+            )
+            .unwrap();
+
+            let versions = BTreeMap::from([
+                ("stylua".to_owned(), Version::parse("0.14.3").unwrap()),
+                ("selene".to_owned(), Version::parse("0.25.0").unwrap()),
+                // Only declared by the user's configuration.
+                ("remodel".to_owned(), Version::parse("0.11.0").unwrap()),
+            ]);
+            let (frozen, not_frozen) = freeze_tools(&config_path, &versions).unwrap();
+            assert_eq!(frozen, vec!["stylua".to_owned(), "selene".to_owned()]);
+            assert_eq!(not_frozen, vec!["rojo".to_owned()]);
+
+            let contents = std::fs::read_to_string(&config_path).unwrap();
+            assert!(contents.starts_with("# my tools\n"));
+            assert!(contents.contains(
+                "stylua = { github = \"JohnnyMorganz/StyLua\", version = \"=0.14.3\" } # formatter"
+            ));
+
+            let config = ConfigFile::from_value(toml::from_str(&contents).unwrap()).unwrap();
+            assert_eq!(
+                config.tools.get("stylua"),
+                Some(&new_github("JohnnyMorganz/StyLua", version("=0.14.3")))
+            );
+            assert_eq!(
+                config.tools.get("rojo"),
+                Some(&new_github("rojo-rbx/rojo", version("7.3.0")))
+            );
+            assert_eq!(
+                config.dev_tools.get("selene"),
+                Some(&new_github("Kampfkarren/selene", version("=0.25.0")))
+            );
+
+            let stylua = config.tools.get("stylua").unwrap().version();
+            assert!(stylua.matches(&Version::parse("0.14.3").unwrap()));
+            assert!(!stylua.matches(&Version::parse("0.14.4").unwrap()));
+        }
+    }
+
+    mod shorthand {
+        use super::*;
+        use tempfile::tempdir;
+
+        #[test]
+        fn defaults_to_github() {
+            let shorthand = ToolShorthand::parse("rojo-rbx/rojo@7.3.0").unwrap();
+            assert_eq!(
+                shorthand,
+                ToolShorthand {
+                    host: "github".to_string(),
+                    path: "rojo-rbx/rojo".to_string(),
+                    version: "7.3.0".to_string(),
+                }
+            );
+        }
+
+        #[test]
+        fn with_explicit_host() {
+            let shorthand = ToolShorthand::parse("gitlab:seaofvoices/darklua@0.7.0").unwrap();
+            assert_eq!(shorthand.host, "gitlab");
+            assert_eq!(shorthand.path, "seaofvoices/darklua");
+        }
+
+        #[test]
+        fn rejects_missing_version() {
+            assert!(ToolShorthand::parse("rojo-rbx/rojo").is_err());
+            assert!(ToolShorthand::parse("rojo-rbx/rojo@latest").is_err());
+        }
+
+        #[test]
+        fn add_tool_round_trip() {
+            let directory = tempdir().unwrap();
+            let config_path = directory.path().join("foreman.toml");
+            std::fs::write(
+                &config_path,
+                "# my tools\n[tools]\nstylua = { github = \"JohnnyMorganz/StyLua\", version = \"0.11.3\" }\n",
+            )
+            .unwrap();
+
+            let shorthand = ToolShorthand::parse("rojo-rbx/rojo@7.3.0").unwrap();
+            add_tool(&config_path, "rojo", &shorthand, false).unwrap();
+
+            let contents = std::fs::read_to_string(&config_path).unwrap();
+            assert!(contents.starts_with("# my tools\n"));
+
+            let config = ConfigFile::from_value(toml::from_str(&contents).unwrap()).unwrap();
+            assert_eq!(
+                config.tools.get("rojo"),
+                Some(&new_github("rojo-rbx/rojo", version("7.3.0")))
+            );
+            assert_eq!(
+                config.tools.get("stylua"),
+                Some(&new_github("JohnnyMorganz/StyLua", version("0.11.3")))
+            );
+        }
+
+        #[test]
+        fn add_tool_creates_tools_table() {
+            let directory = tempdir().unwrap();
+            let config_path = directory.path().join("foreman.toml");
+
+            let shorthand = ToolShorthand::parse("gitlab:seaofvoices/darklua@0.7.0").unwrap();
+            add_tool(&config_path, "darklua", &shorthand, false).unwrap();
+
+            let contents = std::fs::read_to_string(&config_path).unwrap();
+            let config = ConfigFile::from_value(toml::from_str(&contents).unwrap()).unwrap();
+            assert_eq!(
+                config.tools.get("darklua"),
+                Some(&new_gitlab("seaofvoices/darklua", version("0.7.0")))
+            );
+        }
+
+        #[test]
+        fn add_tool_refuses_to_overwrite_without_force() {
+            let directory = tempdir().unwrap();
+            let config_path = directory.path().join("foreman.toml");
+
+            let old = ToolShorthand::parse("rojo-rbx/rojo@7.2.0").unwrap();
+            let new = ToolShorthand::parse("rojo-rbx/rojo@7.3.0").unwrap();
+            add_tool(&config_path, "rojo", &old, false).unwrap();
+
+            assert!(add_tool(&config_path, "rojo", &new, false).is_err());
+            add_tool(&config_path, "rojo", &new, true).unwrap();
+
+            let contents = std::fs::read_to_string(&config_path).unwrap();
+            let config = ConfigFile::from_value(toml::from_str(&contents).unwrap()).unwrap();
+            assert_eq!(
+                config.tools.get("rojo"),
+                Some(&new_github("rojo-rbx/rojo", version("7.3.0")))
+            );
+        }
+    }
+
+    #[test]
+    fn config_file_with_dev_tools() {
+        let value: Value = toml::from_str(
+            &[
+                r#"[tools]"#,
+                r#"rojo = { github = "rojo-rbx/rojo", version = "7.3.0" }"#,
+                r#""#,
+                r#"[dev-tools]"#,
+                r#"stylua = { github = "JohnnyMorganz/StyLua", version = "0.11.3" }"#,
+            ]
+            .join("\n"),
+        )
+        .unwrap();
+
+        let config = ConfigFile::from_value(value).unwrap();
+        assert_eq!(config.tools.len(), 1);
+        assert_eq!(
+            config.dev_tools.get("stylua"),
+            Some(&new_github("JohnnyMorganz/StyLua", version("0.11.3")))
+        );
+        assert_eq!(
+            config.tool("stylua"),
+            Some(&new_github("JohnnyMorganz/StyLua", version("0.11.3")))
+        );
+    }
+
+    #[test]
+    fn dev_tools_are_merged_like_tools() {
+        let mut config = ConfigFile::new_with_defaults();
+        let mut project = ConfigFile::new_with_defaults();
+        project.dev_tools.insert(
+            "stylua".to_string(),
+            new_github("JohnnyMorganz/StyLua", version("0.11.3")),
+        );
+        let mut system = ConfigFile::new_with_defaults();
+        system.dev_tools.insert(
+            "stylua".to_string(),
+            new_github("JohnnyMorganz/StyLua", version("0.10.0")),
+        );
+
+        config.fill_from(project);
+        config.fill_from(system);
+        assert_eq!(
+            config.dev_tools.get("stylua"),
+            Some(&new_github("JohnnyMorganz/StyLua", version("0.11.3")))
+        );
+    }
+
+    mod aggregate {
+        use tempfile::tempdir;
+
+        use super::*;
+
+        /// Makes `path` unreadable, or returns `false` when the current user
+        /// can read it anyway, like root.
+        #[cfg(unix)]
+        fn make_unreadable(path: &Path) -> bool {
+            use std::os::unix::fs::PermissionsExt;
+
+            std::fs::set_permissions(path, std::fs::Permissions::from_mode(0o000)).unwrap();
+            std::fs::read(path).is_err()
+        }
+
+        #[cfg(unix)]
+        #[test]
+        fn skips_unreadable_ancestor_config() {
+            let root = tempdir().unwrap();
+            let project = root.path().join("project");
+            std::fs::create_dir(&project).unwrap();
+            let home_config = root.path().join("home.toml");
+            std::fs::write(
+                project.join("foreman.toml"),
+                "[tools]\nrojo = { github = \"rojo-rbx/rojo\", version = \"7.3.0\" }\n",
+            )
+            .unwrap();
+            let ancestor_config = root.path().join("foreman.toml");
+            std::fs::write(&ancestor_config, "[tools]\n").unwrap();
+            if !make_unreadable(&ancestor_config) {
+                return;
+            }
+
+            let (config, warnings) =
+                ConfigFile::aggregate_from(&project, &home_config, true).unwrap();
+
+            assert!(config.tools.contains_key("rojo"));
+            assert_eq!(
+                warnings,
+                vec![ConfigWarning::UnreadableConfig {
+                    path: ancestor_config
+                }]
+            );
+        }
+
+        #[test]
+        fn config_root_ignores_parent_configs() {
+            let root = tempdir().unwrap();
+            let project = root.path().join("project");
+            std::fs::create_dir(&project).unwrap();
+            std::fs::write(
+                project.join("foreman.toml"),
+                "[tools]\nrojo = { github = \"rojo-rbx/rojo\", version = \"7.3.0\" }\n",
+            )
+            .unwrap();
+            std::fs::write(
+                root.path().join("foreman.toml"),
+                "[tools]\nselene = { github = \"Kampfkarren/selene\", version = \"0.25.0\" }\n",
+            )
+            .unwrap();
+            let home_config = root.path().join("home.toml");
+            std::fs::write(
+                &home_config,
+                "[tools]\nstylua = { github = \"JohnnyMorganz/StyLua\", version = \"0.11.3\" }\n",
+            )
+            .unwrap();
+
+            assert_eq!(
+                ConfigFile::candidate_paths_from(&project, &home_config, false),
+                vec![project.join("foreman.toml"), home_config.clone()]
+            );
+
+            let (config, _) = ConfigFile::aggregate_from(&project, &home_config, false).unwrap();
+            let mut aliases: Vec<&String> = config.tools.keys().collect();
+            aliases.sort();
+            assert_eq!(aliases, vec!["rojo", "stylua"]);
+        }
+
+        #[test]
+        fn only_ancestor_permission_errors_are_skipped() {
+            let path = Path::new("/shared/foreman.toml");
+            let denied = || {
+                Err(ForemanError::read_error(
+                    io::ErrorKind::PermissionDenied.into(),
+                    path,
+                ))
+            };
+            let mut warnings = Vec::new();
+
+            assert!(matches!(
+                skip_unreadable_ancestor(denied(), path, true, &mut warnings),
+                Ok(None)
+            ));
+            assert_eq!(
+                warnings,
+                vec![ConfigWarning::UnreadableConfig {
+                    path: path.to_owned()
+                }]
+            );
+
+            assert!(skip_unreadable_ancestor(denied(), path, false, &mut warnings).is_err());
+            let other_error = Err(ForemanError::read_error(io::ErrorKind::Other.into(), path));
+            assert!(skip_unreadable_ancestor(other_error, path, true, &mut warnings).is_err());
+            assert_eq!(warnings.len(), 1);
+        }
+
+        #[cfg(unix)]
+        #[test]
+        fn unreadable_project_config_fails() {
+            let root = tempdir().unwrap();
+            let home_config = root.path().join("home.toml");
+            let project_config = root.path().join("foreman.toml");
+            std::fs::write(&project_config, "[tools]\n").unwrap();
+            if !make_unreadable(&project_config) {
+                return;
+            }
+
+            assert!(matches!(
+                ConfigFile::aggregate_from(root.path(), &home_config, true),
+                Err(ForemanError::Read { .. })
+            ));
+        }
+
+        #[test]
+        fn reports_shadowed_tools() {
+            let root = tempdir().unwrap();
+            let project = root.path().join("project");
+            std::fs::create_dir(&project).unwrap();
+            let home_config = root.path().join("home.toml");
+            std::fs::write(
+                project.join("foreman.toml"),
+                "[tools]\nrojo = { github = \"rojo-rbx/rojo\", version = \"7.3.0\" }\n",
+            )
+            .unwrap();
+            std::fs::write(
+                &home_config,
+                "[tools]\nrojo = { github = \"rojo-rbx/rojo\", version = \"6.0.0\" }\n",
+            )
+            .unwrap();
+
+            let (config, warnings) =
+                ConfigFile::aggregate_from(&project, &home_config, true).unwrap();
+
+            assert_eq!(
+                config.tools.get("rojo"),
+                Some(&new_github("rojo-rbx/rojo", version("7.3.0")))
+            );
+            assert_eq!(
+                warnings,
+                vec![ConfigWarning::ShadowedTool {
+                    name: "rojo".to_string(),
+                    path: project.join("foreman.toml"),
+                    shadowed_path: home_config,
+                }]
+            );
+        }
+
+        #[test]
+        fn identical_declarations_are_not_shadowed() {
+            let root = tempdir().unwrap();
+            let project = root.path().join("project");
+            std::fs::create_dir(&project).unwrap();
+            let home_config = root.path().join("home.toml");
+            let contents = "[tools]\nrojo = { github = \"rojo-rbx/rojo\", version = \"7.3.0\" }\n";
+            std::fs::write(project.join("foreman.toml"), contents).unwrap();
+            std::fs::write(&home_config, contents).unwrap();
+
+            let (_, warnings) = ConfigFile::aggregate_from(&project, &home_config, true).unwrap();
+
+            assert_eq!(warnings, Vec::new());
+        }
+
+        #[test]
+        fn reports_hosts_with_mismatched_protocol() {
+            let root = tempdir().unwrap();
+            let home_config = root.path().join("home.toml");
+            std::fs::write(
+                root.path().join("foreman.toml"),
+                "[hosts]\nwork = { source = \"https://gitlab.example.com\", protocol = \"github\" }\n[tools]\n",
+            )
+            .unwrap();
+
+            let (_, warnings) =
+                ConfigFile::aggregate_from(root.path(), &home_config, true).unwrap();
+
+            assert_eq!(
+                warnings,
+                vec![ConfigWarning::ProtocolMismatch {
+                    host: "work".to_string(),
+                    protocol: "github",
+                    suggested: "gitlab",
+                    path: root.path().join("foreman.toml"),
+                }]
+            );
+        }
+
+        #[test]
+        fn reports_unknown_keys() {
+            let root = tempdir().unwrap();
+            let home_config = root.path().join("home.toml");
+            std::fs::write(
+                root.path().join("foreman.toml"),
+                "tool = \"rojo\"\n[tools]\n",
+            )
+            .unwrap();
+
+            let (_, warnings) =
+                ConfigFile::aggregate_from(root.path(), &home_config, true).unwrap();
+
+            assert_eq!(
+                warnings,
+                vec![ConfigWarning::UnknownKey {
+                    key: "tool".to_string(),
+                    path: root.path().join("foreman.toml"),
+                }]
+            );
+        }
+    }
+
+    #[test]
+    fn aliases_starting_with_prefix() {
+        let mut config = ConfigFile::new_with_defaults();
+        for alias in ["rojo", "remodel", "stylua"] {
+            config
+                .tools
+                .insert(alias.to_string(), new_github("user/tool", version("1.0.0")));
+        }
+        config.dev_tools.insert(
+            "run-in-roblox".to_string(),
+            new_github("user/tool", version("1.0.0")),
+        );
+
+        assert_eq!(
+            config.aliases_starting_with("r"),
+            vec!["remodel", "rojo", "run-in-roblox"]
+        );
+        assert_eq!(config.aliases_starting_with("ro"), vec!["rojo"]);
+        assert_eq!(config.aliases_starting_with("x"), Vec::<&str>::new());
+        assert_eq!(config.aliases_starting_with("").len(), 4);
+    }
+
+    #[test]
+    fn config_file_with_platform_keywords() {
+        let value: Value = toml::from_str(
+            &[
+                r#"platform_keywords = ["freebsd-x86_64", "freebsd"]"#,
+                r#"[tools]"#,
+            ]
+            .join("\n"),
+        )
+        .unwrap();
+
+        let config = ConfigFile::from_value(value).unwrap();
+        assert_eq!(
+            config.platform_keywords,
+            Some(vec!["freebsd-x86_64".to_string(), "freebsd".to_string()])
+        );
+    }
+
+    #[test]
+    fn config_file_with_auth() {
+        let value: Value =
+            toml::from_str(&[r#"[auth]"#, r#"gitlab = "ci-token""#, r#"[tools]"#].join("\n"))
+                .unwrap();
+
+        let auth = ConfigFile::from_value(value).unwrap().auth.unwrap();
+        assert_eq!(auth.gitlab_token("group/project"), Some("ci-token"));
+        assert_eq!(auth.github_token("owner/repo"), None);
+    }
+
+    #[test]
+    fn config_file_with_passthrough() {
+        let value: Value = toml::from_str("passthrough = true\n[tools]").unwrap();
+        assert_eq!(
+            ConfigFile::from_value(value).unwrap().passthrough,
+            Some(true)
+        );
+
+        let value: Value = toml::from_str("passthrough = \"yes\"\n[tools]").unwrap();
+        assert_eq!(
+            ConfigFile::from_value(value),
+            Err(ConfigFileParseError::InvalidField {
+                field: "passthrough".to_string(),
+            })
+        );
+    }
+
+    #[test]
+    fn config_file_with_invalid_platform_keywords() {
+        let value: Value =
+            toml::from_str(&[r#"platform_keywords = "freebsd""#, r#"[tools]"#].join("\n")).unwrap();
+
+        assert_eq!(
+            ConfigFile::from_value(value),
+            Err(ConfigFileParseError::InvalidField {
+                field: "platform_keywords".to_string(),
+            })
+        );
+    }
+
+    #[test]
+    fn tool_with_bin_glob() {
+        let value: Value = toml::from_str(
+            r#"
+            github = "JohnnyMorganz/StyLua"
+            version = "0.14.3"
+            bin = "stylua*"
+            "#,
+        )
+        .unwrap();
+
+        let tool = ToolSpec::from_value(&value, &default_hosts()).unwrap();
+        assert_eq!(tool.bin(), Some("stylua*"));
+    }
+
+    #[test]
+    fn tool_with_invalid_bin_glob() {
+        let value: Value = toml::from_str(
+            r#"
+            github = "JohnnyMorganz/StyLua"
+            version = "0.14.3"
+            bin = "stylua[*"
+            "#,
+        )
+        .unwrap();
+
+        assert!(ToolSpec::from_value(&value, &default_hosts()).is_err());
+    }
+
+    #[test]
+    fn tool_with_asset_name() {
+        let value: Value = toml::from_str(
+            r#"
+            github = "rojo-rbx/rojo"
+            version = "7.3.0"
+            asset = "rojo-7.3.0-linux-x86_64.zip"
+            "#,
+        )
+        .unwrap();
+
+        let tool = ToolSpec::from_value(&value, &default_hosts()).unwrap();
+        assert_eq!(tool.asset(), Some("rojo-7.3.0-linux-x86_64.zip"));
+    }
+
+    #[test]
+    fn tool_with_source_fallback() {
+        let value: Value = toml::from_str(
+            r#"
+            github = "user/data-tool"
+            version = "1.0.0"
+            source_fallback = true
+            "#,
+        )
+        .unwrap();
+        assert!(ToolSpec::from_value(&value, &default_hosts())
+            .unwrap()
+            .source_fallback());
+
+        let value: Value = toml::from_str(
+            r#"
+            github = "user/data-tool"
+            version = "1.0.0"
+            source_fallback = "yes"
+            "#,
+        )
+        .unwrap();
+        assert!(ToolSpec::from_value(&value, &default_hosts()).is_err());
+    }
+
+    #[test]
+    fn tool_with_env() {
+        let value: Value = toml::from_str(
+            r#"
+            github = "rojo-rbx/rojo"
+            version = "7.3.0"
+            env = { RUST_LOG = "debug" }
+            "#,
+        )
+        .unwrap();
+
+        let tool = ToolSpec::from_value(&value, &default_hosts()).unwrap();
+        assert_eq!(
+            tool.env(),
+            &BTreeMap::from([("RUST_LOG".to_owned(), "debug".to_owned())])
+        );
+
+        let value: Value = toml::from_str(
+            r#"
+            github = "rojo-rbx/rojo"
+            version = "7.3.0"
+            env = { RUST_LOG = 1 }
+            "#,
+        )
+        .unwrap();
+        assert!(ToolSpec::from_value(&value, &default_hosts()).is_err());
+    }
+
+    #[test]
+    fn tool_path_expands_env_vars() {
+        env::set_var("FOREMAN_TEST_EXPAND_TEAM", "tools-team");
+        let value: Value = toml::from_str(
+            r#"
+            artifactory = "repo/${FOREMAN_TEST_EXPAND_TEAM}/tool/"
+            version = "1.0.0"
+            "#,
+        )
+        .unwrap();
+
+        let tool = ToolSpec::from_value(&value, &artifactory_host()).unwrap();
+        assert_eq!(tool.path, "repo/tools-team/tool/");
+    }
+
+    #[test]
+    fn tool_path_with_unset_env_var() {
+        env::remove_var("FOREMAN_TEST_UNSET_TEAM");
+        let value: Value = toml::from_str(
+            r#"
+            artifactory = "repo/${FOREMAN_TEST_UNSET_TEAM}/tool/"
+            version = "1.0.0"
+            "#,
+        )
+        .unwrap();
+
+        match ToolSpec::from_value(&value, &artifactory_host()) {
+            Err(ConfigFileParseError::UndefinedEnvVar { variable, .. }) => {
+                assert_eq!(variable, "FOREMAN_TEST_UNSET_TEAM")
+            }
+            other => panic!("expected an undefined variable error, got {:?}", other),
+        }
+
+        let value: Value = toml::from_str(
+            r#"
+            artifactory = "repo/${FOREMAN_TEST_UNSET_TEAM/tool/"
+            version = "1.0.0"
+            "#,
+        )
+        .unwrap();
+        assert!(matches!(
+            ToolSpec::from_value(&value, &artifactory_host()),
+            Err(ConfigFileParseError::Tool { .. })
+        ));
+    }
+
+    #[test]
+    fn tool_without_platforms_is_available_everywhere() {
+        let tool = new_github("user/repo", version("1.0.0"));
+        assert!(tool.is_available_on("windows", "x86_64"));
+        assert!(tool.is_available_on("linux", "aarch64"));
+    }
+
+    #[test]
+    fn tool_is_only_available_on_its_platforms() {
+        let mut tool = new_github("user/repo", version("1.0.0"));
+        tool.platforms = vec!["windows".to_string(), "macos-aarch64".to_string()];
+
+        assert!(tool.is_available_on("windows", "x86_64"));
+        assert!(tool.is_available_on("macos", "aarch64"));
+        assert!(!tool.is_available_on("macos", "x86_64"));
+        assert!(!tool.is_available_on("linux", "x86_64"));
     }
 
     #[test]
@@ -0,0 +1,201 @@
+//! Support for `foreman diff`, which compares the tools declared for the
+//! current directory with the tools installed, one line per difference.
+
+use std::{collections::HashSet, fmt::Write};
+
+use semver::Version;
+
+use crate::{config::ToolSpec, tool_cache::ToolCache};
+
+#[derive(Debug, PartialEq, Eq)]
+pub enum ToolDiff {
+    /// Versions of the tool are installed, but none satisfies its version
+    /// requirement anymore. `installed` is the newest of them.
+    Changed {
+        alias: String,
+        installed: Version,
+        requirement: String,
+    },
+    /// The tool is declared but no version of it is installed.
+    Added { alias: String, requirement: String },
+    /// The tool is installed but none of the declared tools uses it.
+    Stale {
+        tool: String,
+        versions: Vec<Version>,
+    },
+}
+
+impl ToolDiff {
+    fn marker(&self) -> char {
+        match self {
+            Self::Changed { .. } => '~',
+            Self::Added { .. } => '+',
+            Self::Stale { .. } => '-',
+        }
+    }
+}
+
+/// The differences between the `declared` tools, keyed by alias, and the
+/// tools installed in `cache`: changed tools first, then added ones, then
+/// stale ones. Tools declared for other platforms are left out.
+pub fn diff<'a, I>(declared: I, cache: &ToolCache) -> Vec<ToolDiff>
+where
+    I: IntoIterator<Item = (&'a String, &'a ToolSpec)>,
+{
+    let mut declared_keys = HashSet::new();
+    let mut changed = Vec::new();
+    let mut added = Vec::new();
+
+    for (alias, tool) in declared {
+        declared_keys.insert(tool.cache_key());
+        if !tool.is_available() {
+            continue;
+        }
+
+        let satisfied = cache
+            .installed_version(tool)
+            .is_some_and(|version| cache.installed_path(tool, version).is_some());
+        if satisfied {
+            continue;
+        }
+
+        let requirement = tool.version().to_string();
+        let newest_installed = cache
+            .versions_for(tool)
+            .iter()
+            .rev()
+            .find(|version| cache.installed_path(tool, version).is_some());
+        match newest_installed {
+            Some(installed) => changed.push(ToolDiff::Changed {
+                alias: alias.clone(),
+                installed: installed.clone(),
+                requirement,
+            }),
+            None => added.push(ToolDiff::Added {
+                alias: alias.clone(),
+                requirement,
+            }),
+        }
+    }
+
+    let mut stale: Vec<_> = cache
+        .installed_tools()
+        .filter(|(cache_key, _entry)| !declared_keys.contains(*cache_key))
+        .collect();
+    stale.sort_by(|a, b| a.0 .0.cmp(&b.0 .0));
+    let stale = stale.into_iter().map(|(cache_key, entry)| ToolDiff::Stale {
+        tool: cache_key.0.clone(),
+        versions: entry.versions.iter().cloned().collect(),
+    });
+
+    changed.into_iter().chain(added).chain(stale).collect()
+}
+
+/// Renders `diffs` a line each, marked with `~` for changed tools, `+` for
+/// added ones and `-` for stale ones, followed by a count of each.
+pub fn render(diffs: &[ToolDiff]) -> String {
+    let mut output = String::new();
+    let mut counts = [0; 3];
+
+    for tool_diff in diffs {
+        let (index, details) = match tool_diff {
+            ToolDiff::Changed {
+                alias,
+                installed,
+                requirement,
+            } => (0, format!("{} {} -> {}", alias, installed, requirement)),
+            ToolDiff::Added { alias, requirement } => (1, format!("{} {}", alias, requirement)),
+            ToolDiff::Stale { tool, versions } => {
+                let versions: Vec<String> = versions.iter().map(ToString::to_string).collect();
+                (2, format!("{} {}", tool, versions.join(", ")))
+            }
+        };
+        counts[index] += 1;
+        writeln!(output, "{} {}", tool_diff.marker(), details).unwrap();
+    }
+
+    writeln!(
+        output,
+        "{} changed, {} added, {} stale",
+        counts[0], counts[1], counts[2]
+    )
+    .unwrap();
+    output
+}
+
+#[cfg(test)]
+mod test {
+    use std::{collections::BTreeMap, env::consts::EXE_SUFFIX};
+
+    use tempfile::tempdir;
+
+    use super::*;
+    use crate::{config::ConfigFile, paths::ForemanPaths};
+
+    #[test]
+    fn changed_added_and_stale_tools() {
+        let foreman_root = tempdir().unwrap();
+        let tools_dir = foreman_root.path().join("tools");
+        std::fs::create_dir(&tools_dir).unwrap();
+        for binary in [
+            "rojo-rbx__rojo-7.3.0",
+            "Kampfkarren__selene-0.25.0",
+            "Roblox__old-tool-1.0.0",
+            "Roblox__old-tool-2.0.0",
+        ] {
+            std::fs::write(
+                tools_dir.join(format!("{}{}", binary, EXE_SUFFIX)),
+                "binary",
+            )
+            .unwrap();
+        }
+        std::fs::write(
+            foreman_root.path().join("tool-cache.json"),
+            r#"{ "tools": {
+                "rojo-rbx/rojo": { "versions": ["7.3.0"] },
+                "Kampfkarren/selene": { "versions": ["0.25.0"] },
+                "Roblox/old-tool": { "versions": ["1.0.0", "2.0.0"] }
+            } }"#,
+        )
+        .unwrap();
+        let cache = ToolCache::load(&ForemanPaths::new(foreman_root.path().to_owned())).unwrap();
+
+        let declared: BTreeMap<String, ToolSpec> = ConfigFile::from_value(
+            toml::from_str(
+                "[tools]\nrojo = { github = \"rojo-rbx/rojo\", version = \"^7.4.0\" }\n\
+                selene = { github = \"Kampfkarren/selene\", version = \"0.25\" }\n\
+                stylua = { github = \"JohnnyMorganz/StyLua\", version = \"0.20.0\" }\n",
+            )
+            .unwrap(),
+        )
+        .unwrap()
+        .tools;
+
+        let diffs = diff(&declared, &cache);
+        assert_eq!(
+            diffs,
+            vec![
+                ToolDiff::Changed {
+                    alias: "rojo".to_owned(),
+                    installed: Version::new(7, 3, 0),
+                    requirement: "^7.4.0".to_owned(),
+                },
+                ToolDiff::Added {
+                    alias: "stylua".to_owned(),
+                    requirement: "^0.20.0".to_owned(),
+                },
+                ToolDiff::Stale {
+                    tool: "Roblox/old-tool".to_owned(),
+                    versions: vec![Version::new(1, 0, 0), Version::new(2, 0, 0)],
+                },
+            ]
+        );
+        assert_eq!(
+            render(&diffs),
+            "~ rojo 7.3.0 -> ^7.4.0\n\
+            + stylua ^0.20.0\n\
+            - Roblox/old-tool 1.0.0, 2.0.0\n\
+            1 changed, 1 added, 1 stale\n"
+        );
+    }
+}
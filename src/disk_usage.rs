@@ -0,0 +1,81 @@
+//! Measures the disk space taken by Foreman's directories, for
+//! `foreman cache size`.
+
+use std::{io, path::Path};
+
+use crate::error::{ForemanError, ForemanResult};
+
+/// The total size, in bytes, of the files under `directory`, recursively.
+/// A missing directory takes no space.
+pub fn directory_size(directory: &Path) -> ForemanResult<u64> {
+    let entries = match std::fs::read_dir(directory) {
+        Ok(entries) => entries,
+        Err(err) if err.kind() == io::ErrorKind::NotFound => return Ok(0),
+        Err(err) => return Err(ForemanError::read_error(err, directory)),
+    };
+
+    let mut size = 0;
+    for entry in entries {
+        let entry = entry.map_err(|err| ForemanError::read_error(err, directory))?;
+        let metadata = entry
+            .metadata()
+            .map_err(|err| ForemanError::read_error(err, entry.path()))?;
+
+        if metadata.is_dir() {
+            size += directory_size(&entry.path())?;
+        } else {
+            size += metadata.len();
+        }
+    }
+
+    Ok(size)
+}
+
+/// Formats `bytes` with the largest binary unit that keeps the number at
+/// least 1, like `1.5 KiB`.
+pub fn human_size(bytes: u64) -> String {
+    const UNITS: [&str; 4] = ["KiB", "MiB", "GiB", "TiB"];
+
+    if bytes < 1024 {
+        return format!("{} B", bytes);
+    }
+
+    let mut size = bytes as f64 / 1024.0;
+    let mut unit = 0;
+    while size >= 1024.0 && unit < UNITS.len() - 1 {
+        size /= 1024.0;
+        unit += 1;
+    }
+
+    format!("{:.1} {}", size, UNITS[unit])
+}
+
+#[cfg(test)]
+mod test {
+    use tempfile::tempdir;
+
+    use super::*;
+
+    #[test]
+    fn sums_files_recursively() {
+        let directory = tempdir().unwrap();
+        std::fs::write(directory.path().join("rojo-7.3.0"), [0; 1000]).unwrap();
+        std::fs::create_dir(directory.path().join("nested")).unwrap();
+        std::fs::write(directory.path().join("nested").join("selene"), [0; 24]).unwrap();
+
+        assert_eq!(directory_size(directory.path()).unwrap(), 1024);
+        assert_eq!(
+            directory_size(&directory.path().join("missing")).unwrap(),
+            0
+        );
+    }
+
+    #[test]
+    fn formats_sizes_with_binary_units() {
+        assert_eq!(human_size(0), "0 B");
+        assert_eq!(human_size(1023), "1023 B");
+        assert_eq!(human_size(1536), "1.5 KiB");
+        assert_eq!(human_size(10 * 1024 * 1024), "10.0 MiB");
+        assert_eq!(human_size(3 * 1024 * 1024 * 1024), "3.0 GiB");
+    }
+}
@@ -1,10 +1,19 @@
-use std::{fmt, io, path::PathBuf};
+use std::{fmt, io, path::PathBuf, time::Duration};
 
 use semver::Version;
 
-use crate::config::{ConfigFile, ToolSpec};
+use crate::{
+    binary_format::BinaryFormat,
+    checksums::DigestAlgorithm,
+    config::{ConfigFile, ToolSpec},
+};
 use artiaa_auth::error::ArtifactoryAuthError;
 pub type ForemanResult<T> = Result<T, ForemanError>;
+
+/// Exit code used when Foreman is invoked through the alias of a tool that no
+/// configuration file declares, matching the shell's "command not found".
+pub const TOOL_NOT_INSTALLED_EXIT_CODE: i32 = 127;
+
 pub type ConfigFileParseResult<T> = Result<T, ConfigFileParseError>;
 #[derive(Debug)]
 pub enum ForemanError {
@@ -53,16 +62,72 @@ pub enum ForemanError {
         source: String,
         response_body: String,
         url: String,
+        status: Option<u16>,
     },
     NoCompatibleVersionFound {
         tool: ToolSpec,
         available_versions: Vec<Version>,
+        /// Whether the versions come from cached releases that could not be
+        /// listed again.
+        from_cached_releases: bool,
     },
     InvalidReleaseAsset {
         tool: ToolSpec,
         version: Version,
         message: String,
     },
+    UnsupportedArchive {
+        tool: ToolSpec,
+        version: Version,
+        asset: String,
+        /// Name of the archive's format, like `xz`.
+        format: &'static str,
+    },
+    PrereleaseNotAllowed {
+        tool: ToolSpec,
+        version: Version,
+    },
+    ChecksumMismatch {
+        tool: ToolSpec,
+        version: Version,
+        algorithm: DigestAlgorithm,
+        /// Where the expected checksum comes from, like
+        /// `foreman.checksums.toml` or a checksum asset of the release.
+        origin: String,
+        expected: String,
+        actual: String,
+    },
+    ChecksumMissing {
+        tool: ToolSpec,
+        version: Version,
+    },
+    VerificationFailed {
+        tool: ToolSpec,
+        version: Version,
+        command: String,
+        code: i32,
+        output: String,
+    },
+    WrongBinaryFormat {
+        tool: ToolSpec,
+        version: Version,
+        expected: BinaryFormat,
+        actual: BinaryFormat,
+    },
+    VersionNotInstalled {
+        tool: ToolSpec,
+        version: Version,
+    },
+    ReleasesWithoutAssets {
+        tool: ToolSpec,
+        versions: Vec<Version>,
+    },
+    ReleaseAssetNotFound {
+        tool: ToolSpec,
+        version: Version,
+        asset: String,
+        available_assets: Vec<String>,
+    },
     ToolNotInstalled {
         name: String,
         current_path: PathBuf,
@@ -71,23 +136,97 @@ pub enum ForemanError {
     ToolsNotDownloaded {
         tools: Vec<String>,
     },
+    ToolsNotResolved {
+        tools: Vec<String>,
+    },
+    ToolsNotDeclared {
+        tools: Vec<String>,
+    },
+    SelfCheckFailed {
+        failures: usize,
+    },
+    ToolsDiffer {
+        differences: usize,
+    },
+    ToolTimedOut {
+        tool: String,
+        timeout: Duration,
+    },
     EnvVarNotFound {
         env_var: String,
     },
+    TokenCommandFailed {
+        command: String,
+        message: String,
+    },
     ArtiAAError {
         error: ArtifactoryAuthError,
     },
+    InvalidToolShorthand {
+        shorthand: String,
+    },
+    ToolAlreadyDeclared {
+        name: String,
+        path: PathBuf,
+    },
+    InsufficientDiskSpace {
+        needed: u64,
+        available: u64,
+    },
+    AssetTooLarge {
+        url: String,
+        /// The size of the asset, when the server advertised it.
+        size: Option<u64>,
+        max_size: u64,
+    },
+    HomeDirectoryNotFound {
+        env_var: &'static str,
+    },
+    TimedOut {
+        url: String,
+        /// How long establishing the connection may take.
+        connect_timeout: Duration,
+        /// How long the whole request may take.
+        timeout: Duration,
+    },
+    RateLimited {
+        url: String,
+        /// How long until the quota resets, when GitHub told.
+        resets_in: Option<Duration>,
+    },
+    ToolNotAvailableOnPlatform {
+        name: String,
+        platforms: Vec<String>,
+    },
+    IncompleteAlias {
+        path: PathBuf,
+        expected_size: u64,
+        actual_size: u64,
+    },
 }
 
 #[derive(Debug, PartialEq)]
 pub enum ConfigFileParseError {
     MissingField { field: String },
+    InvalidField { field: String },
     Tool { tool: String },
     Host { host: String },
     InvalidProtocol { protocol: String },
+    // A protocol that cannot read from the host's URL, like `local` with an
+    // `https://` URL.
+    ProtocolMismatch { protocol: String, source: String },
+    UndefinedEnvVar { variable: String, tool: String },
 }
 
 impl ForemanError {
+    /// The exit code Foreman terminates with when it fails with this error.
+    pub fn exit_code(&self) -> i32 {
+        match self {
+            Self::ToolNotInstalled { .. } => TOOL_NOT_INSTALLED_EXIT_CODE,
+            _ => 1,
+        }
+    }
+
     pub fn io_error_with_context<S: Into<String>>(source: io::Error, message: S) -> Self {
         Self::IO {
             source,
@@ -170,6 +309,31 @@ impl ForemanError {
             source: source.into(),
             response_body: response_body.into(),
             url: url.into(),
+            status: None,
+        }
+    }
+
+    pub fn unexpected_response<S: Into<String>, S2: Into<String>, S3: Into<String>>(
+        status: reqwest::StatusCode,
+        source: S,
+        response_body: S2,
+        url: S3,
+    ) -> Self {
+        Self::UnexpectedResponseBody {
+            source: source.into(),
+            response_body: response_body.into(),
+            url: url.into(),
+            status: Some(status.as_u16()),
+        }
+    }
+
+    pub fn token_command_failed<S: Into<String>, S2: Into<String>>(
+        command: S,
+        message: S2,
+    ) -> Self {
+        Self::TokenCommandFailed {
+            command: command.into(),
+            message: message.into(),
         }
     }
 
@@ -177,6 +341,7 @@ impl ForemanError {
         Self::NoCompatibleVersionFound {
             tool: tool.clone(),
             available_versions,
+            from_cached_releases: false,
         }
     }
 
@@ -266,6 +431,17 @@ impl fmt::Display for ForemanError {
                 source,
                 response_body,
                 url,
+                status: Some(status),
+            } => write!(
+                f,
+                "unexpected response (status {}) from {}: {}\n\nReceived body:\n{}",
+                status, url, source, response_body
+            ),
+            Self::UnexpectedResponseBody {
+                source,
+                response_body,
+                url,
+                status: None,
             } => write!(
                 f,
                 "unexpected response body: {}\nRequest from `{}`\n\nReceived body:\n{}",
@@ -274,6 +450,7 @@ impl fmt::Display for ForemanError {
             Self::NoCompatibleVersionFound {
                 tool,
                 available_versions,
+                from_cached_releases,
             } => {
                 write!(
                     f,
@@ -292,7 +469,15 @@ impl fmt::Display for ForemanError {
                                 .join("\n* ")
                         )
                     }
-                )
+                )?;
+                if *from_cached_releases {
+                    write!(
+                        f,
+                        "\nIf a matching version was just released, run `foreman install --refresh` \
+                        to list the releases again"
+                    )?;
+                }
+                Ok(())
             }
             Self::InvalidReleaseAsset {
                 tool,
@@ -305,6 +490,129 @@ impl fmt::Display for ForemanError {
                 version,
                 message
             ),
+            Self::UnsupportedArchive {
+                tool,
+                version,
+                asset,
+                format,
+            } => write!(
+                f,
+                "release asset {} of {} ({}) is an archive in the {} format, which Foreman \
+                cannot extract. Only zip and tar.gz archives, or the tool's binary itself, are \
+                supported",
+                asset,
+                tool.source(),
+                version,
+                format
+            ),
+            Self::ReleasesWithoutAssets { tool, versions } => write!(
+                f,
+                "versions of {} matching version requirement {} exist, but their releases have \
+                no downloadable assets:\n* {}",
+                tool.source(),
+                tool.version(),
+                versions
+                    .iter()
+                    .map(|version| version.to_string())
+                    .collect::<Vec<_>>()
+                    .join("\n* ")
+            ),
+            Self::ReleaseAssetNotFound {
+                tool,
+                version,
+                asset,
+                available_assets,
+            } => {
+                write!(
+                    f,
+                    "release {} of {} has no asset named {}",
+                    version,
+                    tool.source(),
+                    asset
+                )?;
+                if available_assets.is_empty() {
+                    write!(f, ". The release has no assets")
+                } else {
+                    write!(
+                        f,
+                        ". Available assets:\n* {}",
+                        available_assets.join("\n* ")
+                    )
+                }
+            }
+            Self::ChecksumMismatch {
+                tool,
+                version,
+                algorithm,
+                origin,
+                expected,
+                actual,
+            } => write!(
+                f,
+                "checksum mismatch for {} ({}): {} expects {} {}, \
+                but the downloaded asset has {} {}",
+                tool.source(),
+                version,
+                origin,
+                algorithm,
+                expected,
+                algorithm,
+                actual
+            ),
+            Self::ChecksumMissing { tool, version } => write!(
+                f,
+                "no checksum for {} ({}) on this platform in foreman.checksums.toml \
+                or published with the release, which is required by --require-checksums",
+                tool.source(),
+                version
+            ),
+            Self::VerificationFailed {
+                tool,
+                version,
+                command,
+                code,
+                output,
+            } => {
+                write!(
+                    f,
+                    "{} ({}) failed its verification: `{}` exited with code {}",
+                    tool.source(),
+                    version,
+                    command,
+                    code
+                )?;
+                if !output.is_empty() {
+                    write!(f, ":\n{}", output)?;
+                }
+                Ok(())
+            }
+            Self::WrongBinaryFormat {
+                tool,
+                version,
+                expected,
+                actual,
+            } => write!(
+                f,
+                "the binary of {} ({}) is a {} executable, but this platform runs {} \
+                executables. The release asset picked is likely meant for another platform: \
+                set `asset` or `platform_keywords` to pick the right one",
+                tool.source(),
+                version,
+                actual,
+                expected
+            ),
+            Self::VersionNotInstalled { tool, version } => write!(
+                f,
+                "version {} of {} is not installed",
+                version,
+                tool.source()
+            ),
+            Self::PrereleaseNotAllowed { tool, version } => write!(
+                f,
+                "{} resolves to prerelease version {}, which is not allowed with --no-prerelease. \
+                Pin a stable version of the tool instead",
+                tool, version
+            ),
             Self::ToolNotInstalled {
                 name,
                 current_path,
@@ -312,7 +620,9 @@ impl fmt::Display for ForemanError {
             } => write!(
                 f,
                 "'{}' is not a known Foreman tool, but Foreman was invoked \
-                with its name.\n\nTo use this tool from {}, declare it in a \
+                with its name. This usually means an alias for it exists, but \
+                no configuration file declares it here, so no tool was run.\
+                \n\nTo use this tool from {}, declare it in a \
                 'foreman.toml' file in the current directory or a parent \
                 directory.\n\n{}",
                 name,
@@ -322,29 +632,176 @@ impl fmt::Display for ForemanError {
             Self::ToolsNotDownloaded { tools } => {
                 write!(f, "The following tools were not installed:\n{:#?}", tools)
             }
+            Self::ToolsNotDeclared { tools } => write!(
+                f,
+                "The following tools are not declared by any configuration file:\n{:#?}",
+                tools
+            ),
+            Self::ToolsNotResolved { tools } => write!(
+                f,
+                "The following tools do not resolve to an available version:\n{:#?}",
+                tools
+            ),
+            Self::ToolTimedOut { tool, timeout } => write!(
+                f,
+                "gave up on {} after downloading and extracting it took longer than {:?}",
+                tool, timeout
+            ),
+            Self::SelfCheckFailed { failures } => write!(
+                f,
+                "{} self-check(s) failed. Reinstalling Foreman and running `foreman install` should restore the missing or damaged files.",
+                failures
+            ),
+            Self::ToolsDiffer { differences } => write!(
+                f,
+                "{} difference(s) between the declared and the installed tools. Running `foreman install` installs the declared tools.",
+                differences
+            ),
             Self::EnvVarNotFound { env_var } => {
                 write!(f, "Environment Variable not found: {}", env_var)
             }
+            Self::TokenCommandFailed { command, message } => write!(
+                f,
+                "unable to get a token from the token command `{}` of the auth store: {}",
+                command, message
+            ),
             Self::ArtiAAError { error } => {
                 write!(f, "{}", error)
             }
+            Self::InvalidToolShorthand { shorthand } => write!(
+                f,
+                "invalid tool `{}`: expected `[host:]path@version`, like `rojo-rbx/rojo@7.3.0`",
+                shorthand
+            ),
+            Self::ToolAlreadyDeclared { name, path } => write!(
+                f,
+                "tool '{}' is already declared in {} (use --force to replace it)",
+                name,
+                path.display()
+            ),
+            Self::InsufficientDiskSpace { needed, available } => write!(
+                f,
+                "not enough disk space to extract the tool: {} bytes are needed but only {} bytes are available",
+                needed, available
+            ),
+            Self::AssetTooLarge {
+                url,
+                size: Some(size),
+                max_size,
+            } => write!(
+                f,
+                "release asset {} is {} bytes, which exceeds the maximum download size of {} bytes",
+                url, size, max_size
+            ),
+            Self::AssetTooLarge {
+                url,
+                size: None,
+                max_size,
+            } => write!(
+                f,
+                "release asset {} exceeds the maximum download size of {} bytes",
+                url, max_size
+            ),
+            Self::HomeDirectoryNotFound { env_var } => write!(
+                f,
+                "unable to find the home directory to store Foreman's data in. \
+                Set the {} environment variable to an existing directory that Foreman can use",
+                env_var
+            ),
+            Self::TimedOut {
+                url,
+                connect_timeout,
+                timeout,
+            } => write!(
+                f,
+                "request to {} timed out: no connection within {} seconds, or no complete \
+                response within {} seconds. Set the FOREMAN_HTTP_TIMEOUT environment variable \
+                to a number of seconds to wait longer",
+                url,
+                connect_timeout.as_secs(),
+                timeout.as_secs()
+            ),
+            Self::RateLimited { url, resets_in } => {
+                write!(f, "GitHub API rate limit exceeded while requesting {}", url)?;
+                if let Some(resets_in) = resets_in {
+                    write!(f, ". The limit resets in {}", format_wait(*resets_in))?;
+                }
+                write!(
+                    f,
+                    ".\n\nRequests without a token share a low limit per IP address. \
+                    Run `foreman github-auth` to store a GitHub personal access token \
+                    and get a much higher limit"
+                )
+            }
+            Self::ToolNotAvailableOnPlatform { name, platforms } => write!(
+                f,
+                "'{}' is not available on this platform ({}-{}). It is only declared for: {}",
+                name,
+                std::env::consts::OS,
+                std::env::consts::ARCH,
+                platforms.join(", ")
+            ),
+            Self::IncompleteAlias {
+                path,
+                expected_size,
+                actual_size,
+            } => write!(
+                f,
+                "the alias written to {} is incomplete ({} bytes instead of {}) and was removed. \
+                This can happen when the disk is full, or when an antivirus quarantines or \
+                truncates the file. Free some disk space or exclude the Foreman bin directory \
+                from antivirus scans, then run `foreman install` again",
+                path.display(),
+                actual_size,
+                expected_size
+            ),
         }
     }
 }
 
+/// Formats a wait rounded up to the minute, or in seconds when shorter than
+/// one, like `12 minutes`.
+fn format_wait(wait: Duration) -> String {
+    let seconds = wait.as_secs();
+    if seconds < 60 {
+        format!("{} second{}", seconds, if seconds == 1 { "" } else { "s" })
+    } else {
+        let minutes = seconds.div_ceil(60);
+        format!("{} minute{}", minutes, if minutes == 1 { "" } else { "s" })
+    }
+}
+
 impl fmt::Display for ConfigFileParseError {
     fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
         match self {
             Self::MissingField { field } => write!(f, "missing field `{}`", field),
+            Self::InvalidField { field } => {
+                write!(f, "field `{}` is not properly formatted", field)
+            }
             Self::Tool { tool } => {
                 write!(f, "data is not properly formatted for tool:\n\n{}", tool)
             }
             Self::Host { host } => {
                 write!(f, "data is not properly formatted for host:\n\n{}", host)
             }
+            Self::UndefinedEnvVar { variable, tool } => write!(
+                f,
+                "environment variable `{}` is not set, but is used by tool:\n\n{}",
+                variable, tool
+            ),
             Self::InvalidProtocol { protocol } => {
-                write!(f, "protocol `{}` is not valid. Foreman only supports `github`, `gitlab`, and `artifactory`\n\n", protocol)
+                write!(f, "protocol `{}` is not valid. Foreman only supports `github`, `gitlab`, `bitbucket`, `artifactory`, and `local`\n\n", protocol)
             }
+            Self::ProtocolMismatch { protocol, source } if protocol == "local" => write!(
+                f,
+                "host source `{}` cannot use protocol `local`, which only reads `file://` URLs",
+                source
+            ),
+            Self::ProtocolMismatch { protocol, source } => write!(
+                f,
+                "host source `{}` cannot use protocol `{}`, use protocol `local` to read from disk",
+                source, protocol
+            ),
         }
     }
 }
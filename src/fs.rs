@@ -6,14 +6,19 @@
 use std::{
     fs,
     io::{self, BufWriter, Read},
-    path::Path,
+    path::{Path, PathBuf},
 };
 
+/// Extension of the temporary files that files are written to before being
+/// renamed to their final name, so that an interrupted write never leaves a
+/// truncated file behind under that name.
+pub const TEMP_EXTENSION: &str = "tmp";
+
 /// A wrapper around std::fs::read that returns None if the file does not exist.
 pub fn try_read<P: AsRef<Path>>(path: P) -> ForemanResult<Option<Vec<u8>>> {
     let path = path.as_ref();
 
-    match fs::read(&path).map(Some) {
+    match fs::read(path).map(Some) {
         Ok(contents) => Ok(contents),
         Err(err) => {
             if err.kind() == io::ErrorKind::NotFound {
@@ -29,7 +34,7 @@ pub fn try_read<P: AsRef<Path>>(path: P) -> ForemanResult<Option<Vec<u8>>> {
 pub fn try_read_to_string<P: AsRef<Path>>(path: P) -> ForemanResult<Option<String>> {
     let path = path.as_ref();
 
-    match fs::read_to_string(&path).map(Some) {
+    match fs::read_to_string(path).map(Some) {
         Ok(contents) => Ok(contents),
         Err(err) => {
             if err.kind() == io::ErrorKind::NotFound {
@@ -41,6 +46,22 @@ pub fn try_read_to_string<P: AsRef<Path>>(path: P) -> ForemanResult<Option<Strin
     }
 }
 
+/// A wrapper around std::fs::remove_file that returns false if the file does not exist.
+pub fn try_remove_file<P: AsRef<Path>>(path: P) -> ForemanResult<bool> {
+    let path = path.as_ref();
+
+    match fs::remove_file(path) {
+        Ok(()) => Ok(true),
+        Err(err) => {
+            if err.kind() == io::ErrorKind::NotFound {
+                Ok(false)
+            } else {
+                Err(ForemanError::write_error(err, path))
+            }
+        }
+    }
+}
+
 /// A wrapper around std::fs::write that only writes if the file does not exist.
 pub fn write_if_not_found<P: AsRef<Path>, C: AsRef<[u8]>>(
     path: P,
@@ -48,9 +69,9 @@ pub fn write_if_not_found<P: AsRef<Path>, C: AsRef<[u8]>>(
 ) -> ForemanResult<()> {
     let path = path.as_ref();
 
-    if let Err(err) = std::fs::metadata(&path) {
+    if let Err(err) = std::fs::metadata(path) {
         if err.kind() == io::ErrorKind::NotFound {
-            write(&path, contents)
+            write(path, contents)
         } else {
             Err(ForemanError::write_error(err, path))
         }
@@ -81,11 +102,36 @@ pub fn copy_from_reader<R: Read + ?Sized, P: AsRef<Path>>(
     dest_path: P,
 ) -> ForemanResult<u64> {
     let dest_path = dest_path.as_ref();
-    let output_file = std::fs::File::create(&dest_path)
-        .map_err(|err| ForemanError::create_file_error(err, &dest_path))?;
+    let output_file = std::fs::File::create(dest_path)
+        .map_err(|err| ForemanError::create_file_error(err, dest_path))?;
     let mut output = BufWriter::new(output_file);
 
-    io::copy(reader, &mut output).map_err(|err| ForemanError::write_error(err, &dest_path))
+    io::copy(reader, &mut output).map_err(|err| ForemanError::write_error(err, dest_path))
+}
+
+/// The temporary file this process writes `path` to before renaming it:
+/// `path` with the process ID and `TEMP_EXTENSION` appended, like
+/// `rojo-rbx__rojo-7.3.0.4242.tmp`.
+pub fn temp_path<P: AsRef<Path>>(path: P) -> PathBuf {
+    let mut file_name = path.as_ref().file_name().unwrap_or_default().to_owned();
+    file_name.push(format!(".{}.{}", std::process::id(), TEMP_EXTENSION));
+    path.as_ref().with_file_name(file_name)
+}
+
+/// Whether `path` is a temporary file named by `temp_path`.
+pub fn is_temp_path<P: AsRef<Path>>(path: P) -> bool {
+    path.as_ref()
+        .extension()
+        .is_some_and(|extension| extension == TEMP_EXTENSION)
+}
+
+/// A wrapper around std::fs::rename.
+pub fn rename<P: AsRef<Path>, Q: AsRef<Path>>(source_path: P, dest_path: Q) -> ForemanResult<()> {
+    let source_path = source_path.as_ref();
+    let dest_path = dest_path.as_ref();
+
+    fs::rename(source_path, dest_path)
+        .map_err(|source| ForemanError::copy_error(source, source_path, dest_path))
 }
 
 /// A wrapper around std::fs::create_dir_all.
@@ -97,6 +143,32 @@ pub fn create_dir_all<P: AsRef<Path>>(path: P) -> ForemanResult<()> {
     fs::create_dir_all(path).map_err(|source| ForemanError::write_error(source, path))
 }
 
+/// Checks that the filesystem containing `path` has room for `needed` more
+/// bytes. If the available space cannot be determined, the check is skipped.
+pub fn ensure_available_space<P: AsRef<Path>>(path: P, needed: u64) -> ForemanResult<()> {
+    let path = path.as_ref();
+
+    match fs2::available_space(path) {
+        Ok(available) => check_available_space(needed, available),
+        Err(err) => {
+            log::debug!(
+                "unable to determine available disk space at {}: {}",
+                path.display(),
+                err
+            );
+            Ok(())
+        }
+    }
+}
+
+fn check_available_space(needed: u64, available: u64) -> ForemanResult<()> {
+    if needed > available {
+        Err(ForemanError::InsufficientDiskSpace { needed, available })
+    } else {
+        Ok(())
+    }
+}
+
 pub use fs::Permissions;
 
 use crate::error::{ForemanError, ForemanResult};
@@ -109,3 +181,32 @@ pub fn set_permissions<P: AsRef<Path>>(path: P, permissions: Permissions) -> For
     fs::set_permissions(path, permissions)
         .map_err(|source| ForemanError::set_permission_error(source, path))
 }
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn temp_paths_are_recognized() {
+        let temp = temp_path("/foreman/tools/rojo-rbx__rojo-7.3.0");
+
+        assert!(temp.starts_with("/foreman/tools"));
+        assert!(is_temp_path(&temp));
+        assert!(!is_temp_path("/foreman/tools/rojo-rbx__rojo-7.3.0"));
+    }
+
+    #[test]
+    fn enough_available_space() {
+        assert!(check_available_space(1024, 4096).is_ok());
+        assert!(check_available_space(4096, 4096).is_ok());
+    }
+
+    #[test]
+    fn insufficient_available_space() {
+        let err = check_available_space(4097, 4096).unwrap_err();
+        assert_eq!(
+            err.to_string(),
+            "not enough disk space to extract the tool: 4097 bytes are needed but only 4096 bytes are available"
+        );
+    }
+}
@@ -0,0 +1,122 @@
+//! Events sent by `foreman install --events-socket` as tools get installed,
+//! one JSON object per line, so that editors can show progress without
+//! parsing Foreman's output.
+
+use std::{io::Write, path::Path};
+
+use serde::Serialize;
+
+use crate::error::{ForemanError, ForemanResult};
+
+#[derive(Debug, Serialize)]
+#[serde(tag = "event", rename_all = "snake_case")]
+pub enum InstallEvent<'a> {
+    /// Installing the tool declared as `tool` begins.
+    Start {
+        tool: &'a str,
+        source: String,
+    },
+    /// The tool is installed. `cached` is set when a matching version was
+    /// already installed, so nothing was downloaded.
+    Done {
+        tool: &'a str,
+        version: String,
+        cached: bool,
+    },
+    Error {
+        tool: &'a str,
+        message: String,
+    },
+    /// `completed` of the `total` tools to install are done with, whether
+    /// they were installed or failed.
+    Progress {
+        completed: usize,
+        total: usize,
+    },
+}
+
+/// Where events are sent, if anywhere.
+#[derive(Default)]
+pub struct EventSink {
+    stream: Option<Box<dyn Write>>,
+}
+
+impl EventSink {
+    /// Connects to the Unix domain socket, or the Windows named pipe, at
+    /// `path`, which the receiving end must already listen on.
+    pub fn connect(path: &Path) -> ForemanResult<Self> {
+        let stream = connect(path).map_err(|err| {
+            ForemanError::io_error_with_context(
+                err,
+                format!("unable to connect to the events socket {}", path.display()),
+            )
+        })?;
+
+        Ok(Self {
+            stream: Some(stream),
+        })
+    }
+
+    /// Sends `event`. Events stop being sent once the receiving end goes
+    /// away, without failing the install.
+    pub fn send(&mut self, event: &InstallEvent) {
+        if let Some(stream) = &mut self.stream {
+            let mut line = serde_json::to_vec(event).expect("unable to serialize install event");
+            line.push(b'\n');
+
+            if let Err(err) = stream.write_all(&line).and_then(|_| stream.flush()) {
+                log::warn!("Unable to send install events anymore: {}", err);
+                self.stream = None;
+            }
+        }
+    }
+}
+
+#[cfg(unix)]
+fn connect(path: &Path) -> std::io::Result<Box<dyn Write>> {
+    Ok(Box::new(std::os::unix::net::UnixStream::connect(path)?))
+}
+
+#[cfg(windows)]
+fn connect(path: &Path) -> std::io::Result<Box<dyn Write>> {
+    // The client end of a named pipe is opened like a file.
+    Ok(Box::new(
+        std::fs::OpenOptions::new().write(true).open(path)?,
+    ))
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn events_are_tagged_json_objects() {
+        let events = [
+            InstallEvent::Start {
+                tool: "rojo",
+                source: "github.com/rojo-rbx/rojo".to_owned(),
+            },
+            InstallEvent::Error {
+                tool: "rojo",
+                message: "no release".to_owned(),
+            },
+            InstallEvent::Progress {
+                completed: 1,
+                total: 2,
+            },
+        ];
+        let serialized: Vec<String> = events
+            .iter()
+            .map(|event| serde_json::to_string(event).unwrap())
+            .collect();
+
+        assert_eq!(
+            serialized,
+            vec![
+                r#"{"event":"start","tool":"rojo","source":"github.com/rojo-rbx/rojo"}"#,
+                r#"{"event":"error","tool":"rojo","message":"no release"}"#,
+                r#"{"event":"progress","completed":1,"total":2}"#,
+            ]
+        );
+    }
+}
@@ -0,0 +1,143 @@
+//! The lines `foreman install` prints as each tool gets installed: one when
+//! a tool starts, and one with the version it resolved to and how long it
+//! took once it is done. On a terminal, the start line is replaced by the
+//! done line.
+
+use std::{
+    io::{self, IsTerminal, Write},
+    time::{Duration, Instant},
+};
+
+use semver::{Version, VersionReq};
+
+pub struct InstallStatus<W> {
+    out: W,
+    /// Whether the start line is left open to be overwritten.
+    in_place: bool,
+    started: Option<Instant>,
+}
+
+impl InstallStatus<io::Stderr> {
+    /// Prints to stderr, replacing lines in place when it is a terminal.
+    pub fn stderr() -> Self {
+        let in_place = io::stderr().is_terminal();
+        Self::new(io::stderr(), in_place)
+    }
+}
+
+impl<W: Write> InstallStatus<W> {
+    pub fn new(out: W, in_place: bool) -> Self {
+        Self {
+            out,
+            in_place,
+            started: None,
+        }
+    }
+
+    pub fn start(&mut self, alias: &str, version: &VersionReq) {
+        self.started = Some(Instant::now());
+
+        let line = format!("Installing {} ({})...", alias, version);
+        if self.in_place {
+            self.write(format_args!("{}", line));
+        } else {
+            self.write(format_args!("{}\n", line));
+        }
+    }
+
+    /// Reports the tool as installed at `version`. `cached` is set when that
+    /// version was already installed.
+    pub fn done(&mut self, alias: &str, version: &Version, cached: bool) {
+        let line = if cached {
+            format!("{} {} is already installed", alias, version)
+        } else {
+            format!(
+                "Installed {} {} in {}",
+                alias,
+                version,
+                format_elapsed(self.elapsed())
+            )
+        };
+        self.finish(&line);
+    }
+
+    /// Ends the start line of a tool that failed to install, whose error is
+    /// reported separately.
+    pub fn failed(&mut self) {
+        self.started = None;
+        if self.in_place {
+            self.write(format_args!("\n"));
+        }
+    }
+
+    fn finish(&mut self, line: &str) {
+        self.started = None;
+        if self.in_place {
+            // Return to the start of the line and clear it.
+            self.write(format_args!("\r\x1b[2K{}\n", line));
+        } else {
+            self.write(format_args!("{}\n", line));
+        }
+    }
+
+    fn elapsed(&self) -> Duration {
+        self.started
+            .map(|started| started.elapsed())
+            .unwrap_or_default()
+    }
+
+    /// Writes to the output, ignoring failures: status lines are not worth
+    /// failing an install over.
+    fn write(&mut self, text: std::fmt::Arguments) {
+        let _ = self.out.write_fmt(text).and_then(|_| self.out.flush());
+    }
+}
+
+/// Formats `elapsed` with a tenth of a second precision, like `1.3s`, or in
+/// minutes and seconds past a minute, like `2m05s`.
+fn format_elapsed(elapsed: Duration) -> String {
+    let seconds = elapsed.as_secs();
+    if seconds < 60 {
+        format!("{:.1}s", elapsed.as_secs_f64())
+    } else {
+        format!("{}m{:02}s", seconds / 60, seconds % 60)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn sequential_lines_without_terminal() {
+        let mut status = InstallStatus::new(Vec::new(), false);
+        status.start("rojo", &VersionReq::parse("^7.2").unwrap());
+        status.done("rojo", &Version::new(7, 2, 1), true);
+        status.start("selene", &VersionReq::parse("0.25").unwrap());
+        status.failed();
+
+        assert_eq!(
+            String::from_utf8(status.out).unwrap(),
+            "Installing rojo (^7.2)...\nrojo 7.2.1 is already installed\n\
+            Installing selene (^0.25)...\n"
+        );
+    }
+
+    #[test]
+    fn done_line_replaces_start_line_on_terminal() {
+        let mut status = InstallStatus::new(Vec::new(), true);
+        status.start("rojo", &VersionReq::parse("^7.2").unwrap());
+        status.done("rojo", &Version::new(7, 2, 1), true);
+
+        assert_eq!(
+            String::from_utf8(status.out).unwrap(),
+            "Installing rojo (^7.2)...\r\x1b[2Krojo 7.2.1 is already installed\n"
+        );
+    }
+
+    #[test]
+    fn formats_elapsed_time() {
+        assert_eq!(format_elapsed(Duration::from_millis(1340)), "1.3s");
+        assert_eq!(format_elapsed(Duration::from_secs(125)), "2m05s");
+    }
+}
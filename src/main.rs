@@ -1,47 +1,110 @@
+// `ForemanError` carries a full `ConfigFile` for some variants, so it is large
+// by design; boxing it everywhere would only add noise.
+#![allow(clippy::result_large_err)]
+
 mod aliaser;
 mod artifact_choosing;
 mod artifactory_auth_store;
 mod artifactory_path;
 mod auth_store;
+mod binary_format;
+mod checksums;
 mod ci_string;
 mod config;
+mod diff;
+mod disk_usage;
 mod error;
 mod fs;
+mod install_events;
+mod install_status;
 mod paths;
 mod process;
+mod resolution_report;
+mod selfcheck;
+mod status;
 mod tool_cache;
 mod tool_provider;
+mod versioning;
+mod watch;
 
 use std::{
+    collections::BTreeMap,
     env,
     ffi::OsStr,
-    io::{stdout, Write},
+    io::{stderr, stdout, Write},
+    path::PathBuf,
+    sync::Arc,
+    time::Duration,
 };
 
 use artifactory_auth_store::ArtifactoryAuthStore;
 use paths::ForemanPaths;
+use semver::Version;
 use structopt::StructOpt;
 
 use crate::{
-    aliaser::add_self_alias,
+    aliaser::{add_self_alias, find_alias, remove_self_alias, AliasStyle},
     auth_store::AuthStore,
-    config::ConfigFile,
+    checksums::Checksums,
+    config::{ConfigFile, ToolShorthand, ToolSpec},
     error::{ForemanError, ForemanResult},
-    tool_cache::ToolCache,
+    install_events::{EventSink, InstallEvent},
+    install_status::InstallStatus,
+    resolution_report::ResolutionReport,
+    status::{AliasState, Latest, ToolState, ToolStatus},
+    tool_cache::{ToolCache, UpdatePolicy},
     tool_provider::ToolProvider,
+    watch::{ConfigWatcher, ToolChange},
 };
 
 #[derive(Debug)]
 struct ToolInvocation {
     name: String,
     args: Vec<String>,
+    /// Where the tool's output goes when it is captured instead of streamed.
+    capture: Option<CaptureTarget>,
+    cache_options: CacheOptions,
+    /// Environment variables set for this run, on top of the ones declared
+    /// by the tool's configuration.
+    env: Vec<(String, String)>,
+    /// Working directory of the tool, instead of Foreman's own.
+    current_dir: Option<PathBuf>,
+    /// Print the command that would run the tool instead of running it.
+    print_command: bool,
+    /// Start the tool in the background and print its PID instead of
+    /// waiting for it.
+    detach: bool,
+    /// Whether the tool was invoked through its alias, in which case an
+    /// undeclared tool can fall back to an executable on `PATH`.
+    from_alias: bool,
+}
+
+/// Set to the name of the tool when running an executable found on `PATH`
+/// in place of an undeclared tool, so that finding another Foreman alias
+/// there does not loop forever.
+const PASSTHROUGH_ENV_VAR: &str = "FOREMAN_PASSTHROUGH";
+
+#[derive(Debug)]
+enum CaptureTarget {
+    /// Print the captured output once the tool terminates.
+    Print,
+    /// Write the captured stdout, followed by stderr, to a file.
+    File(PathBuf),
 }
 
 impl ToolInvocation {
     fn from_env() -> ForemanResult<Option<Self>> {
-        let app_path = env::current_exe().map_err(|err| {
-            ForemanError::io_error_with_context(err, "unable to obtain foreman executable location")
-        })?;
+        // The name Foreman was invoked as, which `current_exe` would lose for
+        // aliases that are symbolic links to it.
+        let app_path = match env::args_os().next() {
+            Some(arg0) => PathBuf::from(arg0),
+            None => env::current_exe().map_err(|err| {
+                ForemanError::io_error_with_context(
+                    err,
+                    "unable to obtain foreman executable location",
+                )
+            })?,
+        };
         let name = if let Some(name) = app_path
             .file_stem()
             .and_then(OsStr::to_str)
@@ -59,44 +122,174 @@ impl ToolInvocation {
 
         let args = env::args().skip(1).collect();
 
-        Ok(Some(Self { name, args }))
+        Ok(Some(Self {
+            name,
+            args,
+            capture: None,
+            cache_options: CacheOptions::default(),
+            env: Vec::new(),
+            current_dir: None,
+            print_command: false,
+            detach: false,
+            from_alias: true,
+        }))
     }
 
     fn run(self, paths: &ForemanPaths) -> ForemanResult<()> {
-        let config = ConfigFile::aggregate(paths)?;
+        let config = load_config(paths)?;
 
-        if let Some(tool_spec) = config.tools.get(&self.name) {
+        if let Some(tool_spec) = config.tool(&self.name) {
             log::debug!("Found tool spec {}", tool_spec);
 
-            let mut tool_cache = ToolCache::load(paths)?;
-            let providers = ToolProvider::new(paths);
+            if !tool_spec.is_available() {
+                return Err(ForemanError::ToolNotAvailableOnPlatform {
+                    name: self.name,
+                    platforms: tool_spec.platforms().to_vec(),
+                });
+            }
+
+            if let Some(current_dir) = &self.current_dir {
+                if !current_dir.is_dir() {
+                    return Err(ForemanError::io_error_with_context(
+                        std::io::ErrorKind::NotFound.into(),
+                        format!(
+                            "unable to run {} in {}: the directory does not exist",
+                            self.name,
+                            current_dir.display()
+                        ),
+                    ));
+                }
+            }
+
+            let mut tool_cache = install_cache(paths, &config, self.cache_options)?;
+            let providers = ToolProvider::new(paths, &config);
             let version = tool_cache.download_if_necessary(tool_spec, &providers)?;
 
-            let exit_code = tool_cache.run(tool_spec, &version, self.args)?;
+            let mut env = tool_spec.env().clone();
+            env.extend(self.env);
+
+            if self.print_command {
+                println!(
+                    "{}",
+                    tool_cache.command_line(
+                        tool_spec,
+                        &version,
+                        &self.args,
+                        &env,
+                        self.current_dir.as_deref(),
+                    )
+                );
+                return Ok(());
+            }
+
+            if self.detach {
+                let pid = tool_cache.spawn_detached(
+                    tool_spec,
+                    &version,
+                    self.args,
+                    &env,
+                    self.current_dir.as_deref(),
+                )?;
+                println!("{}", pid);
+                return Ok(());
+            }
+
+            let exit_code = match self.capture {
+                None => tool_cache.run(
+                    tool_spec,
+                    &version,
+                    self.args,
+                    &env,
+                    self.current_dir.as_deref(),
+                )?,
+                Some(target) => {
+                    let output = tool_cache.run_captured(
+                        tool_spec,
+                        &version,
+                        self.args,
+                        &env,
+                        self.current_dir.as_deref(),
+                    )?;
+
+                    match target {
+                        CaptureTarget::Print => {
+                            stdout().write_all(&output.stdout).ok();
+                            stderr().write_all(&output.stderr).ok();
+                        }
+                        CaptureTarget::File(path) => {
+                            fs::write(&path, [output.stdout, output.stderr].concat())?;
+                        }
+                    }
+
+                    output.code
+                }
+            };
 
             if exit_code != 0 {
                 std::process::exit(exit_code);
             }
 
             Ok(())
+        } else if self.from_alias && config.passthrough == Some(true) {
+            self.run_passthrough(paths, config)
         } else {
-            let current_dir = env::current_dir().map_err(|err| {
-                ForemanError::io_error_with_context(
-                    err,
-                    "unable to obtain the current working directory",
-                )
-            })?;
-            Err(ForemanError::ToolNotInstalled {
-                name: self.name,
-                current_path: current_dir,
-                config_file: config,
-            })
+            self.not_installed(config)
+        }
+    }
+
+    /// Runs the first executable on `PATH` named after the tool that is not
+    /// a Foreman alias, instead of the undeclared tool.
+    fn run_passthrough(self, paths: &ForemanPaths, config: ConfigFile) -> ForemanResult<()> {
+        if env::var_os(PASSTHROUGH_ENV_VAR).as_deref() == Some(OsStr::new(&self.name)) {
+            log::debug!("Not passing {} through again", self.name);
+            return self.not_installed(config);
+        }
+
+        let foreman_path = env::current_exe().map_err(|err| {
+            ForemanError::io_error_with_context(err, "unable to obtain foreman executable location")
+        })?;
+        let exe_path = match process::find_on_path(&self.name, &[paths.bin_dir()], &foreman_path) {
+            Some(exe_path) => exe_path,
+            None => return self.not_installed(config),
+        };
+        log::debug!("Passing {} through to {}", self.name, exe_path.display());
+
+        let env = BTreeMap::from([(PASSTHROUGH_ENV_VAR.to_owned(), self.name.clone())]);
+        let exit_code = process::run(&exe_path, self.args, &env, None).map_err(|err| {
+            ForemanError::io_error_with_context(
+                err.source,
+                format!("unable to run {}", exe_path.display()),
+            )
+        })?;
+
+        if exit_code != 0 {
+            std::process::exit(exit_code);
         }
+
+        Ok(())
+    }
+
+    fn not_installed(self, config: ConfigFile) -> ForemanResult<()> {
+        let current_dir = env::current_dir().map_err(|err| {
+            ForemanError::io_error_with_context(
+                err,
+                "unable to obtain the current working directory",
+            )
+        })?;
+        Err(ForemanError::ToolNotInstalled {
+            name: self.name,
+            current_path: current_dir,
+            config_file: config,
+        })
     }
 }
 
 fn main() {
-    let paths = ForemanPaths::from_env().unwrap_or_default();
+    let mut paths = match ForemanPaths::from_env() {
+        Some(paths) => paths,
+        None => ForemanPaths::from_home_dir().unwrap_or_else(|error| exit_with_error(error)),
+    };
+    paths.set_auth_file(ForemanPaths::auth_file_from_env());
 
     if let Err(error) = paths.create_all() {
         exit_with_error(error);
@@ -111,6 +304,7 @@ fn main() {
                 .format_indent(Some(8))
                 .init();
 
+            paths.check_auth_file()?;
             invocation.run(&paths)
         } else {
             actual_main(paths)
@@ -122,9 +316,212 @@ fn main() {
     }
 }
 
+/// Gathers the configuration files that apply to the current directory and
+/// reports any warnings found along the way.
+fn load_config(paths: &ForemanPaths) -> ForemanResult<ConfigFile> {
+    let (config, warnings) = ConfigFile::aggregate(paths)?;
+
+    for warning in warnings {
+        log::warn!("{}", warning);
+    }
+
+    Ok(config)
+}
+
+/// How tools are installed, from the global options or, for tools run
+/// through their alias, the defaults.
+#[derive(Debug, Default, Clone, Copy)]
+struct CacheOptions {
+    /// Download tools even when a matching version is installed.
+    ignore_cache: bool,
+    /// Whether to check for a newer matching release of tools first.
+    update_policy: UpdatePolicy,
+    /// Refuse to download tools without a checksum to verify them against.
+    require_checksums: bool,
+    /// Fail instead of warning when a tool's binary is for another platform.
+    strict: bool,
+}
+
+/// Loads the tool cache, applying the platform keywords and yanked versions
+/// of `config`.
+fn configured_cache(paths: &ForemanPaths, config: &ConfigFile) -> ForemanResult<ToolCache> {
+    let mut cache = ToolCache::load(paths)?;
+    cache.set_platform_keywords(config.platform_keywords.clone());
+    cache.set_yanked(config.yanked.clone());
+    Ok(cache)
+}
+
+/// Like `configured_cache`, for commands that install tools: the recorded
+/// checksums and `cache_options` are applied too.
+fn install_cache(
+    paths: &ForemanPaths,
+    config: &ConfigFile,
+    cache_options: CacheOptions,
+) -> ForemanResult<ToolCache> {
+    let mut cache = configured_cache(paths, config)?;
+    cache.set_checksums(Checksums::load(paths)?);
+    cache.set_ignore_cache(cache_options.ignore_cache);
+    cache.set_update_policy(cache_options.update_policy);
+    cache.set_require_checksums(cache_options.require_checksums);
+    cache.set_strict_binary_format(cache_options.strict);
+    Ok(cache)
+}
+
+/// Records the projects tools are being installed for, so that `foreman
+/// prune` keeps their tools. Failing to do so is not worth failing the
+/// install over.
+fn remember_projects(paths: &ForemanPaths, cache: &mut ToolCache) {
+    let user_config = paths.user_config();
+    let projects = match ConfigFile::candidate_paths(paths) {
+        Ok(projects) => projects
+            .into_iter()
+            .filter(|config_path| *config_path != user_config && config_path.is_file())
+            .map(|config_path| std::fs::canonicalize(&config_path).unwrap_or(config_path)),
+        Err(err) => {
+            log::warn!("Could not find the projects to remember: {}", err);
+            return;
+        }
+    };
+
+    if let Err(err) = cache.add_projects(projects) {
+        log::warn!(
+            "Could not remember the projects tools are installed for: {}",
+            err
+        );
+    }
+}
+
+/// Whether `alias` is declared for another tool than `tool_spec` in the
+/// current configuration, or by another project Foreman installed tools for,
+/// in which case its alias must be kept when `tool_spec` is uninstalled.
+fn alias_declared_elsewhere(
+    paths: &ForemanPaths,
+    cache: &ToolCache,
+    config: &ConfigFile,
+    alias: &str,
+    tool_spec: &ToolSpec,
+) -> ForemanResult<bool> {
+    let other_tool = |declared: Option<&ToolSpec>| {
+        declared.is_some_and(|declared| declared.cache_key() != tool_spec.cache_key())
+    };
+    if other_tool(config.tools.get(alias)) || other_tool(config.dev_tools.get(alias)) {
+        return Ok(true);
+    }
+
+    let current_projects: Vec<PathBuf> = ConfigFile::candidate_paths(paths)?
+        .into_iter()
+        .map(|config_path| std::fs::canonicalize(&config_path).unwrap_or(config_path))
+        .collect();
+    for config_path in cache.projects() {
+        if current_projects.contains(config_path) {
+            continue;
+        }
+        match ConfigFile::load_file(config_path) {
+            Ok(Some(project)) if project.tool(alias).is_some() => return Ok(true),
+            Ok(_) => {}
+            Err(err) => log::warn!("Skipping project {}: {}", config_path.display(), err),
+        }
+    }
+
+    Ok(false)
+}
+
+/// The tool declared as `name`, in `[tools]` or `[dev-tools]`.
+fn declared_tool<'a>(config: &'a ConfigFile, name: &str) -> ForemanResult<&'a ToolSpec> {
+    config
+        .tool(name)
+        .ok_or_else(|| ForemanError::ToolsNotDeclared {
+            tools: vec![name.to_owned()],
+        })
+}
+
+/// The tools to install from `config`, leaving out tools that are not
+/// available on this platform. When `only` names some tools, only those are
+/// returned, whether they are development tools or not. Otherwise,
+/// development tools are left out unless `dev` is set.
+fn declared_tools<'a>(
+    config: &'a ConfigFile,
+    dev: bool,
+    only: &[String],
+) -> ForemanResult<Vec<(&'a String, &'a ToolSpec)>> {
+    let selected: Vec<(&String, &ToolSpec)> = if only.is_empty() {
+        if !dev && !config.dev_tools.is_empty() {
+            log::info!(
+                "Skipping development tools ({}), use --dev to install them",
+                config
+                    .dev_tools
+                    .keys()
+                    .map(String::as_str)
+                    .collect::<Vec<_>>()
+                    .join(", ")
+            );
+        }
+        let dev_tools = config.dev_tools.iter().filter(|_| dev);
+
+        config.tools.iter().chain(dev_tools).collect()
+    } else {
+        let unknown_tools: Vec<String> = only
+            .iter()
+            .filter(|name| config.tool(name).is_none())
+            .cloned()
+            .collect();
+        if !unknown_tools.is_empty() {
+            return Err(ForemanError::ToolsNotDeclared {
+                tools: unknown_tools,
+            });
+        }
+
+        config
+            .tools
+            .iter()
+            .chain(&config.dev_tools)
+            .filter(|(tool_alias, _)| only.contains(tool_alias))
+            .collect()
+    };
+
+    Ok(selected
+        .into_iter()
+        .filter(|(tool_alias, tool_spec)| {
+            let available = tool_spec.is_available();
+            if !available {
+                log::info!(
+                    "Skipping {} because it is only available on: {}",
+                    tool_alias,
+                    tool_spec.platforms().join(", ")
+                );
+            }
+            available
+        })
+        .collect())
+}
+
+fn parse_env_var(env_var: &str) -> Result<(String, String), String> {
+    match env_var.split_once('=') {
+        Some((key, value)) if !key.is_empty() => Ok((key.to_owned(), value.to_owned())),
+        _ => Err(format!("expected KEY=VALUE, found `{}`", env_var)),
+    }
+}
+
+/// Creates the tool providers, applying the `[auth]` table of the
+/// configuration and the `--max-download-size` and `--empty-release-retries`
+/// options.
+fn tool_provider(
+    paths: &ForemanPaths,
+    config: &ConfigFile,
+    max_download_size: Option<u64>,
+    empty_release_retries: u32,
+) -> ToolProvider {
+    let mut providers = ToolProvider::new(paths, config);
+    if let Some(max_download_size) = max_download_size {
+        providers.set_max_download_size(max_download_size);
+    }
+    providers.set_empty_releases_retries(empty_release_retries);
+    providers
+}
+
 fn exit_with_error(error: ForemanError) -> ! {
     eprintln!("{}", error);
-    std::process::exit(1);
+    std::process::exit(error.exit_code());
 }
 
 #[derive(Debug, StructOpt)]
@@ -133,17 +530,174 @@ struct Options {
     #[structopt(short, parse(from_occurrences), global = true)]
     pub verbose: u8,
 
+    /// Abort downloading a release asset larger than this many bytes. Can
+    /// also be set with the FOREMAN_MAX_DOWNLOAD_SIZE environment variable.
+    #[structopt(long, global = true)]
+    max_download_size: Option<u64>,
+
+    /// List the releases of a tool installed before again up to this many
+    /// times when its host returns none, which hosts sometimes do for a
+    /// moment.
+    #[structopt(long, global = true, value_name = "n", default_value = "2")]
+    empty_release_retries: u32,
+
+    /// Resolve and download tools even when a matching version is installed,
+    /// without removing installed versions.
+    #[structopt(long, global = true)]
+    ignore_cache: bool,
+
+    /// When to check for newer releases of tools: `cached` uses any installed
+    /// version matching a tool's version requirement without touching the
+    /// network, `latest` always resolves the newest matching release and
+    /// downloads it unless it is installed.
+    #[structopt(
+        long,
+        global = true,
+        default_value = "cached",
+        possible_values = UpdatePolicy::VARIANTS
+    )]
+    update_policy: UpdatePolicy,
+
+    /// Only read the foreman.toml of this directory, along with the user's
+    /// configuration, instead of the ones of the current directory and its
+    /// parents.
+    #[structopt(long, global = true, parse(from_os_str))]
+    config_root: Option<PathBuf>,
+
+    /// Read tokens from this file instead of the auth.toml of Foreman's home
+    /// directory, and Artifactory tokens from artiaa-tokens.json next to it.
+    /// Can also be set with the FOREMAN_AUTH_FILE environment variable.
+    #[structopt(long, global = true, value_name = "path", parse(from_os_str))]
+    auth_file: Option<PathBuf>,
+
+    /// Refuse to download a release asset that has no checksum, either in a
+    /// foreman.checksums.toml file or published with the release as a
+    /// .sha256 or .sha512 asset, instead of downloading it unverified.
+    #[structopt(long, global = true, alias = "require-checksum")]
+    require_checksums: bool,
+
+    /// Fail instead of warning when a downloaded tool's binary is an
+    /// executable for another platform, like a Linux ELF binary on macOS.
+    #[structopt(long, global = true)]
+    strict: bool,
+
+    /// How the aliases of tools are written to the bin directory: a copy of
+    /// the Foreman executable, a symbolic link to it, or a small launcher
+    /// script running `foreman run <tool>`.
+    #[structopt(
+        long,
+        global = true,
+        default_value = "copy",
+        possible_values = AliasStyle::VARIANTS
+    )]
+    alias_style: AliasStyle,
+
     #[structopt(subcommand)]
     subcommand: Subcommand,
 }
 
+impl Options {
+    fn cache_options(&self) -> CacheOptions {
+        CacheOptions {
+            ignore_cache: self.ignore_cache,
+            update_policy: self.update_policy,
+            require_checksums: self.require_checksums,
+            strict: self.strict,
+        }
+    }
+}
+
 #[derive(Debug, StructOpt)]
 enum Subcommand {
     /// Install tools defined by foreman.toml.
-    Install,
+    Install(InstallCommand),
+
+    /// Update tools defined by foreman.toml to the newest versions they allow.
+    ///
+    /// Tools are never downgraded: a tool is only replaced when a newer
+    /// version than the installed one is found.
+    Update(UpdateCommand),
+
+    /// Remove an installed tool declared by foreman.toml.
+    ///
+    /// Deletes the tool's binaries from the tool cache. Its alias is removed
+    /// once no version of it is left installed.
+    Uninstall(UninstallCommand),
+
+    /// Remove the installed tool versions that no foreman.toml uses, among the
+    /// ones applying to the current directory and the ones Foreman installed
+    /// tools from before.
+    ///
+    /// Versions whose binary went missing and files of the tools directory
+    /// that no installed version refers to are removed as well.
+    Prune(PruneCommand),
+
+    /// Print the path of the binary Foreman runs for a tool declared by
+    /// foreman.toml.
+    ///
+    /// Fails without downloading anything when the version the tool resolves
+    /// to is not installed.
+    Which(WhichCommand),
 
     /// List installed tools.
-    List,
+    List(ListCommand),
+
+    /// Summarize whether the tools declared by foreman.toml are set up.
+    ///
+    /// Prints each tool with its version requirement, the installed version,
+    /// the newest release matching the requirement and whether its alias is
+    /// intact.
+    Status(StatusCommand),
+
+    /// Compare the tools declared by foreman.toml with the installed tools.
+    ///
+    /// Prints a line per difference: `~` for tools whose installed versions
+    /// no longer satisfy their version requirement, `+` for tools that are not
+    /// installed and `-` for installed tools that are no longer declared.
+    /// This never needs network access.
+    Diff(DiffCommand),
+
+    /// Run a tool declared by foreman.toml, installing it if needed.
+    Run(RunCommand),
+
+    /// Add a tool to the nearest foreman.toml.
+    ///
+    /// If no foreman.toml is found in the current directory or one of its
+    /// parents, one is created in the current directory.
+    Add(AddCommand),
+
+    /// Pin every tool of the nearest foreman.toml to its installed version.
+    ///
+    /// Version requirements like `^0.14` are rewritten to the exact version
+    /// installed, like `=0.14.3`. Tools that are not installed are left as is.
+    Freeze,
+
+    /// Install the tools declared by foreman.toml, then keep them installed
+    /// as the configuration files are edited, until interrupted.
+    ///
+    /// Prints a line for each tool installed or removed from the
+    /// configuration.
+    Watch(WatchCommand),
+
+    /// Print completions for editor integrations, one per line.
+    ///
+    /// With a single argument, prints the declared tool aliases starting with
+    /// it. With a tool alias and a second argument, prints the installed
+    /// versions of that tool starting with the second argument. This never
+    /// needs network access.
+    Complete(CompleteCommand),
+
+    /// Inspect the tool cache.
+    Cache(CacheCommand),
+
+    /// Print information useful to include in bug reports.
+    Debug(DebugCommand),
+
+    /// Verify that the Foreman executable and the tool aliases in its bin
+    /// directory are present and intact.
+    ///
+    /// Exits with a non-zero status if any check fails.
+    Selfcheck,
 
     /// Set the GitHub Personal Access Token that Foreman should use with the
     /// GitHub API.
@@ -171,12 +725,264 @@ enum Subcommand {
     GenerateArtifactoryPath(GenerateArtifactoryPathCommand),
 }
 
+#[derive(Debug, StructOpt)]
+struct InstallCommand {
+    /// Only install these tools, by alias. All tools are installed when none
+    /// are given.
+    tools: Vec<String>,
+
+    /// Also install the tools declared under `[dev-tools]`.
+    #[structopt(long)]
+    dev: bool,
+
+    /// Only check that every tool resolves to a version available from its
+    /// provider, without downloading anything.
+    #[structopt(long)]
+    check: bool,
+
+    /// Fail instead of installing a tool that resolves to a prerelease version.
+    #[structopt(long)]
+    no_prerelease: bool,
+
+    /// Give up on a tool when downloading and extracting it takes longer than
+    /// this many seconds, and move on to the next tool.
+    #[structopt(long, value_name = "secs")]
+    timeout_per_tool: Option<u64>,
+
+    /// Write a JSON report of what each tool resolved to and where it was
+    /// downloaded from to this file.
+    #[structopt(long, parse(from_os_str))]
+    resolution_report: Option<PathBuf>,
+
+    /// Also copy each tool's binary to this directory, named after its
+    /// alias, for bundling the tools elsewhere.
+    #[structopt(long, parse(from_os_str), conflicts_with = "check")]
+    output: Option<PathBuf>,
+
+    /// Send newline-delimited JSON events about each tool's installation to
+    /// the Unix domain socket, or Windows named pipe, listening at this
+    /// path.
+    #[structopt(long, parse(from_os_str), conflicts_with = "check")]
+    events_socket: Option<PathBuf>,
+
+    /// Before installing, merge tools installed under several spellings of
+    /// the same cache key, like host URLs written before they were
+    /// canonicalized, keeping a single copy of each version.
+    #[structopt(long, conflicts_with = "check")]
+    dedupe: bool,
+
+    /// List the releases of tools again instead of reusing the ones listed
+    /// during the last hour, or the duration set by the
+    /// FOREMAN_RELEASE_CACHE_TTL environment variable.
+    #[structopt(long)]
+    refresh: bool,
+}
+
+#[derive(Debug, StructOpt)]
+struct WatchCommand {
+    /// Also install the tools declared under `[dev-tools]`.
+    #[structopt(long)]
+    dev: bool,
+}
+
+#[derive(Debug, StructOpt)]
+struct UpdateCommand {
+    /// Only update these tools, by alias. All tools are updated when none are
+    /// given.
+    tools: Vec<String>,
+
+    /// Also update the tools declared under `[dev-tools]`.
+    #[structopt(long)]
+    dev: bool,
+
+    /// Fail instead of updating a tool to a prerelease version.
+    #[structopt(long)]
+    no_prerelease: bool,
+}
+
+#[derive(Debug, StructOpt)]
+struct UninstallCommand {
+    /// Name of the tool to remove, as declared in foreman.toml.
+    tool: String,
+
+    /// Only remove this version of the tool instead of every installed
+    /// version.
+    #[structopt(long)]
+    version: Option<Version>,
+}
+
+#[derive(Debug, StructOpt)]
+struct PruneCommand {
+    /// Also keep this many of the newest versions of each tool, whether a
+    /// foreman.toml uses them or not.
+    #[structopt(long, value_name = "N", default_value = "0")]
+    keep: usize,
+
+    /// Print what would be removed without removing anything.
+    #[structopt(long)]
+    dry_run: bool,
+}
+
+#[derive(Debug, StructOpt)]
+struct WhichCommand {
+    /// Name of the tool, as declared in foreman.toml.
+    tool: String,
+}
+
+#[derive(Debug, StructOpt)]
+struct StatusCommand {
+    /// Do not look up the newest release of each tool, which needs network
+    /// access.
+    #[structopt(long)]
+    offline: bool,
+}
+
+#[derive(Debug, StructOpt)]
+struct DiffCommand {
+    /// Exit with a non-zero status when there are differences.
+    #[structopt(long)]
+    exit_code: bool,
+}
+
+#[derive(Debug, StructOpt)]
+struct ListCommand {
+    /// List the tools declared by the foreman.toml files that apply to the
+    /// current directory instead of the installed tools. This does not need
+    /// network access nor the tool cache.
+    #[structopt(long)]
+    declared: bool,
+
+    /// List the installed tool versions that no foreman.toml applying to the
+    /// current directory uses, along with the disk space they take.
+    #[structopt(long, conflicts_with = "declared")]
+    stale: bool,
+
+    /// List the tools installed under several cache keys for the same path,
+    /// like a tool that moved from GitHub to GitLab, so that the leftover
+    /// versions can be cleaned up.
+    #[structopt(long, conflicts_with_all = &["declared", "stale"])]
+    duplicates: bool,
+}
+
+#[derive(Debug, StructOpt)]
+#[structopt(setting = structopt::clap::AppSettings::TrailingVarArg)]
+struct RunCommand {
+    /// Name of the tool to run.
+    tool: String,
+
+    /// Capture the tool's output and print it once the tool terminates,
+    /// instead of streaming it to the terminal.
+    #[structopt(long)]
+    capture: bool,
+
+    /// Write the captured output to this file instead of printing it.
+    #[structopt(long, parse(from_os_str), requires = "capture")]
+    output_file: Option<PathBuf>,
+
+    /// Set an environment variable for the tool, as `KEY=VALUE`. Can be
+    /// repeated, and takes precedence over the tool's configured `env`.
+    #[structopt(long, parse(try_from_str = parse_env_var), number_of_values = 1)]
+    env: Vec<(String, String)>,
+
+    /// Run the tool in this directory instead of the current one. Tools are
+    /// still looked up from the current directory's configuration.
+    #[structopt(long, parse(from_os_str))]
+    cwd: Option<PathBuf>,
+
+    /// Print the command line that would run the tool, with its resolved
+    /// path, arguments and environment variables, instead of running it.
+    /// The tool is still installed if needed.
+    #[structopt(long, conflicts_with = "capture")]
+    print_command: bool,
+
+    /// Start the tool in the background, detached from the terminal, print
+    /// its process ID and return right away. The tool keeps running after
+    /// Foreman exits, and its output is discarded.
+    #[structopt(long, conflicts_with_all = &["capture", "print-command"])]
+    detach: bool,
+
+    /// Arguments passed to the tool.
+    #[structopt(allow_hyphen_values = true)]
+    args: Vec<String>,
+}
+
+#[derive(Debug, StructOpt)]
+struct AddCommand {
+    /// Name of the tool, as it will be invoked.
+    alias: String,
+
+    /// Where to find the tool, written as `[host:]path@version`.
+    ///
+    /// For example: `rojo-rbx/rojo@7.3.0` or `gitlab:seaofvoices/darklua@0.7.0`.
+    source: String,
+
+    /// Install the tool once it is added.
+    #[structopt(long)]
+    install: bool,
+
+    /// Replace the tool if it is already declared.
+    #[structopt(long)]
+    force: bool,
+}
+
+#[derive(Debug, StructOpt)]
+struct CompleteCommand {
+    /// Beginning of a tool alias, or a full alias when completing versions.
+    partial: String,
+
+    /// Beginning of a version of the tool named by the first argument.
+    version: Option<String>,
+}
+
+#[derive(Debug, StructOpt)]
+enum CacheCommand {
+    /// Print the disk space taken by the installed tools.
+    Size(CacheSizeCommand),
+
+    /// Remove the temporary files that interrupted installs left in the
+    /// tools directory.
+    #[structopt(name = "clean-temp")]
+    CleanTemp(CacheCleanTempCommand),
+}
+
+#[derive(Debug, StructOpt)]
+struct CacheSizeCommand {
+    /// Also print the size of each installed version of each tool.
+    #[structopt(long)]
+    breakdown: bool,
+}
+
+#[derive(Debug, StructOpt)]
+struct CacheCleanTempCommand {
+    /// Only remove temporary files last modified more than this many seconds
+    /// ago, since newer ones may belong to an install that is still running.
+    #[structopt(long, value_name = "secs", default_value = "3600")]
+    older_than: u64,
+
+    /// Print what would be removed without removing anything.
+    #[structopt(long)]
+    dry_run: bool,
+}
+
+#[derive(Debug, StructOpt)]
+enum DebugCommand {
+    /// Print the paths Foreman uses and the configuration files it reads, in
+    /// precedence order.
+    Paths,
+}
+
 #[derive(Debug, StructOpt)]
 struct GitHubAuthCommand {
     /// GitHub personal access token that Foreman should use.
     ///
     /// If not specified, Foreman will prompt for it.
     token: Option<String>,
+
+    /// Only use the token for this owner (`owner`) or repository
+    /// (`owner/repo`). The most specific token is used for each repository,
+    /// falling back to the GitHub-wide token.
+    #[structopt(long)]
+    repo: Option<String>,
 }
 
 #[derive(Debug, StructOpt)]
@@ -185,6 +991,12 @@ struct GitLabAuthCommand {
     ///
     /// If not specified, Foreman will prompt for it.
     token: Option<String>,
+
+    /// Only use the token for this owner (`owner`) or repository
+    /// (`owner/repo`). The most specific token is used for each repository,
+    /// falling back to the GitLab-wide token.
+    #[structopt(long)]
+    repo: Option<String>,
 }
 
 #[derive(Debug, StructOpt)]
@@ -198,12 +1010,26 @@ struct GenerateArtifactoryPathCommand {
     repo: String,
     tool_name: String,
     version: String,
-    operating_system: String,
+    #[structopt(required_unless = "all-platforms")]
+    operating_system: Option<String>,
     architecture: Option<String>,
+
+    /// Print the path for every valid operating system and architecture
+    /// pair, one per line, instead of a single path.
+    #[structopt(long, conflicts_with_all = &["operating-system", "architecture"])]
+    all_platforms: bool,
+
+    /// Print the paths of --all-platforms as a JSON array.
+    #[structopt(long, requires = "all-platforms")]
+    json: bool,
 }
 
-fn actual_main(paths: ForemanPaths) -> ForemanResult<()> {
+fn actual_main(mut paths: ForemanPaths) -> ForemanResult<()> {
     let options = Options::from_args();
+    paths.set_config_root(options.config_root.clone());
+    if let Some(auth_file) = &options.auth_file {
+        paths.set_auth_file(Some(auth_file.clone()));
+    }
 
     {
         let log_filter = match options.verbose {
@@ -223,23 +1049,138 @@ fn actual_main(paths: ForemanPaths) -> ForemanResult<()> {
             .init();
     }
 
+    // Commands that save tokens create the auth file.
+    if !matches!(
+        options.subcommand,
+        Subcommand::GitHubAuth(_) | Subcommand::GitLabAuth(_) | Subcommand::ArtifactoryAuth(_)
+    ) {
+        paths.check_auth_file()?;
+    }
+
+    let alias_style = options.alias_style;
+    let cache_options = options.cache_options();
+
     match options.subcommand {
-        Subcommand::Install => {
-            let config = ConfigFile::aggregate(&paths)?;
+        Subcommand::Install(subcommand) => {
+            let config = load_config(&paths)?;
 
             log::trace!("Installing from gathered config: {:#?}", config);
 
-            let mut cache = ToolCache::load(&paths)?;
+            let mut cache = install_cache(&paths, &config, cache_options)?;
+            cache.set_reject_prereleases(subcommand.no_prerelease);
+            remember_projects(&paths, &mut cache);
 
-            let providers = ToolProvider::new(&paths);
+            if subcommand.dedupe {
+                for (cache_key, canonical) in cache.dedupe()? {
+                    log::info!("Merged {} into {}", cache_key, canonical);
+                }
+            }
 
-            let tools_not_downloaded: Vec<String> = config
-                .tools
-                .iter()
-                .filter_map(|(tool_alias, tool_spec)| {
-                    cache
-                        .download_if_necessary(tool_spec, &providers)
-                        .and_then(|_| add_self_alias(tool_alias, &paths.bin_dir()))
+            let mut providers = tool_provider(
+                &paths,
+                &config,
+                options.max_download_size,
+                options.empty_release_retries,
+            );
+            providers.set_refresh_releases(subcommand.refresh);
+            let providers = Arc::new(providers);
+
+            if subcommand.check {
+                let tools_not_resolved: Vec<String> = declared_tools(&config, subcommand.dev, &subcommand.tools)?
+                    .into_iter()
+                    .filter_map(|(tool_alias, tool_spec)| {
+                        match cache.resolve(tool_spec, &providers) {
+                            Ok(version) => {
+                                log::info!("{} resolves to version {}", tool_alias, version);
+                                None
+                            }
+                            Err(err) => {
+                                log::error!(
+                                    "The following error occurred while trying to resolve tool \"{}\":\n{}",
+                                    tool_alias,
+                                    err
+                                );
+                                Some(tool_alias.to_string())
+                            }
+                        }
+                    })
+                    .collect();
+
+                if !tools_not_resolved.is_empty() {
+                    return Err(ForemanError::ToolsNotResolved {
+                        tools: tools_not_resolved,
+                    });
+                }
+                return Ok(());
+            }
+
+            if let Some(output) = &subcommand.output {
+                fs::create_dir_all(output)?;
+            }
+
+            let mut events = match &subcommand.events_socket {
+                Some(path) => EventSink::connect(path)?,
+                None => EventSink::default(),
+            };
+
+            // Each tool gets its own status lines instead.
+            cache.set_quiet_downloads(true);
+            let mut status = InstallStatus::stderr();
+
+            let tools = declared_tools(&config, subcommand.dev, &subcommand.tools)?;
+            let total = tools.len();
+            let mut report = ResolutionReport::default();
+            let tools_not_downloaded: Vec<String> = tools
+                .into_iter()
+                .enumerate()
+                .filter_map(|(index, (tool_alias, tool_spec))| {
+                    events.send(&InstallEvent::Start {
+                        tool: tool_alias,
+                        source: tool_spec.source(),
+                    });
+                    status.start(tool_alias, tool_spec.version());
+                    let installed = match subcommand.timeout_per_tool {
+                        Some(timeout) => cache.download_if_necessary_within(
+                            tool_spec,
+                            &providers,
+                            Duration::from_secs(timeout),
+                        ),
+                        None => cache.install(tool_spec, &providers),
+                    };
+                    report.record(tool_alias, tool_spec, &installed);
+                    let result = installed.and_then(|installation| {
+                        add_self_alias(tool_alias, &paths.bin_dir(), alias_style)?;
+                        if let Some(output) = &subcommand.output {
+                            cache.export(tool_spec, &installation.version, output, tool_alias)?;
+                        }
+                        Ok(installation)
+                    });
+
+                    match &result {
+                        Ok(installation) => status.done(
+                            tool_alias,
+                            &installation.version,
+                            installation.download.is_none(),
+                        ),
+                        Err(_) => status.failed(),
+                    }
+                    events.send(&match &result {
+                        Ok(installation) => InstallEvent::Done {
+                            tool: tool_alias,
+                            version: installation.version.to_string(),
+                            cached: installation.download.is_none(),
+                        },
+                        Err(err) => InstallEvent::Error {
+                            tool: tool_alias,
+                            message: err.to_string(),
+                        },
+                    });
+                    events.send(&InstallEvent::Progress {
+                        completed: index + 1,
+                        total,
+                    });
+
+                    result
                         .err()
                         .map(|err| {
                             log::error!(
@@ -252,13 +1193,17 @@ fn actual_main(paths: ForemanPaths) -> ForemanResult<()> {
                 })
                 .collect();
 
+            if let Some(report_path) = &subcommand.resolution_report {
+                report.write(report_path)?;
+            }
+
             if !tools_not_downloaded.is_empty() {
                 return Err(ForemanError::ToolsNotDownloaded {
                     tools: tools_not_downloaded,
                 });
             }
 
-            if config.tools.is_empty() {
+            if config.tools.is_empty() && config.dev_tools.is_empty() {
                 log::info!(
                     concat!(
                         "foreman did not find any tools to install.\n\n",
@@ -269,12 +1214,292 @@ fn actual_main(paths: ForemanPaths) -> ForemanResult<()> {
                 );
             }
         }
-        Subcommand::List => {
+        Subcommand::Update(subcommand) => {
+            let config = load_config(&paths)?;
+
+            // `update` looks for newer releases whatever `--ignore-cache` and
+            // `--update-policy` say.
+            let mut cache = install_cache(
+                &paths,
+                &config,
+                CacheOptions {
+                    ignore_cache: false,
+                    update_policy: UpdatePolicy::default(),
+                    ..cache_options
+                },
+            )?;
+            cache.set_reject_prereleases(subcommand.no_prerelease);
+            remember_projects(&paths, &mut cache);
+
+            let mut providers = tool_provider(
+                &paths,
+                &config,
+                options.max_download_size,
+                options.empty_release_retries,
+            );
+            // Updating is about finding releases published since the last
+            // install, so cached listings would defeat it.
+            providers.set_refresh_releases(true);
+
+            let tools_not_updated: Vec<String> =
+                declared_tools(&config, subcommand.dev, &subcommand.tools)?
+                    .into_iter()
+                    .filter_map(|(tool_alias, tool_spec)| {
+                        let previous = cache.installed_version(tool_spec).cloned();
+                        cache
+                            .update(tool_spec, &providers)
+                            .and_then(|updated| {
+                                add_self_alias(tool_alias, &paths.bin_dir(), alias_style)?;
+                                match (previous, updated) {
+                                    (Some(previous), Some(updated)) => {
+                                        println!("{}: {} -> {}", tool_alias, previous, updated)
+                                    }
+                                    (None, Some(updated)) => {
+                                        println!("{}: installed {}", tool_alias, updated)
+                                    }
+                                    (Some(previous), None) => {
+                                        println!("{}: {} is up to date", tool_alias, previous)
+                                    }
+                                    (None, None) => {}
+                                }
+                                Ok(())
+                            })
+                            .err()
+                            .map(|err| {
+                                log::error!(
+                            "The following error occurred while trying to update tool \"{}\":\n{}",
+                            tool_alias,
+                            err
+                        );
+                                tool_alias.to_string()
+                            })
+                    })
+                    .collect();
+
+            if !tools_not_updated.is_empty() {
+                return Err(ForemanError::ToolsNotDownloaded {
+                    tools: tools_not_updated,
+                });
+            }
+        }
+        Subcommand::Uninstall(subcommand) => {
+            let config = load_config(&paths)?;
+            let tool_spec = declared_tool(&config, &subcommand.tool)?;
+
+            let mut cache = ToolCache::load(&paths)?;
+            let removed = cache.uninstall(tool_spec, subcommand.version.as_ref())?;
+
+            if cache.versions_for(tool_spec).is_empty()
+                && !alias_declared_elsewhere(&paths, &cache, &config, &subcommand.tool, tool_spec)?
+            {
+                remove_self_alias(&subcommand.tool, &paths.bin_dir())?;
+            }
+
+            if removed.is_empty() {
+                println!("{}: not installed", subcommand.tool);
+            }
+            for version in removed {
+                println!("{}: removed {}", subcommand.tool, version);
+            }
+        }
+        Subcommand::Run(subcommand) => {
+            let capture = match subcommand.output_file {
+                Some(path) => Some(CaptureTarget::File(path)),
+                None if subcommand.capture => Some(CaptureTarget::Print),
+                None => None,
+            };
+
+            ToolInvocation {
+                name: subcommand.tool,
+                args: subcommand.args,
+                capture,
+                cache_options,
+                env: subcommand.env,
+                current_dir: subcommand.cwd,
+                print_command: subcommand.print_command,
+                detach: subcommand.detach,
+                from_alias: false,
+            }
+            .run(&paths)?;
+        }
+        Subcommand::Which(subcommand) => {
+            let config = load_config(&paths)?;
+            let tool_spec = declared_tool(&config, &subcommand.tool)?;
+
+            let mut cache = configured_cache(&paths, &config)?;
+            cache.set_ignore_cache(cache_options.ignore_cache);
+            cache.set_update_policy(cache_options.update_policy);
+
+            let providers = tool_provider(
+                &paths,
+                &config,
+                options.max_download_size,
+                options.empty_release_retries,
+            );
+            let version = cache.version_to_run(tool_spec, &providers)?;
+            let tool_path = cache.installed_path(tool_spec, &version).ok_or_else(|| {
+                ForemanError::VersionNotInstalled {
+                    tool: tool_spec.clone(),
+                    version: version.clone(),
+                }
+            })?;
+
+            println!("{}", tool_path.display());
+        }
+        Subcommand::Prune(subcommand) => {
+            let mut cache = ToolCache::load(&paths)?;
+
+            // The tools of other projects Foreman was used in are kept too.
+            let mut configs = ConfigFile::load_each(&paths)?;
+            for config_path in cache.projects().clone() {
+                match ConfigFile::load_file(&config_path) {
+                    Ok(Some(config)) => configs.push(config),
+                    Ok(None) => {
+                        log::info!("Forgetting removed project {}", config_path.display());
+                        cache.forget_project(&config_path);
+                    }
+                    Err(err) => {
+                        log::warn!("Skipping project {}: {}", config_path.display(), err);
+                    }
+                }
+            }
+            let declared = configs
+                .iter()
+                .flat_map(|config| config.tools.values().chain(config.dev_tools.values()));
+
+            let plan = cache.plan_prune(declared, subcommand.keep)?;
+
+            if plan.is_empty() {
+                println!("Nothing to prune.");
+                return Ok(());
+            }
+
+            println!(
+                "{}:",
+                if subcommand.dry_run {
+                    "Would remove"
+                } else {
+                    "Removing"
+                }
+            );
+            for (tool, version) in &plan.versions {
+                println!("  {} {}", tool, version);
+            }
+            for orphan in &plan.orphans {
+                println!("  {}", orphan.display());
+            }
+            println!("Total: {} bytes", plan.size);
+
+            if !subcommand.dry_run {
+                cache.prune(&plan)?;
+            }
+        }
+        Subcommand::List(subcommand) if subcommand.declared => {
+            let config = load_config(&paths)?;
+
+            let mut tools_by_provider: BTreeMap<_, Vec<_>> = BTreeMap::new();
+            let tools = config.tools.iter().map(|tool| (tool, false));
+            let dev_tools = config.dev_tools.iter().map(|tool| (tool, true));
+            for ((tool_alias, tool_spec), dev) in tools.chain(dev_tools) {
+                tools_by_provider
+                    .entry(tool_spec.provider())
+                    .or_default()
+                    .push((tool_alias, tool_spec, dev));
+            }
+
+            println!("Declared tools:");
+
+            for (provider, tools) in tools_by_provider {
+                println!("  {}", provider);
+
+                for (tool_alias, tool_spec, dev) in tools {
+                    println!(
+                        "    {} => {} {}{}{}",
+                        tool_alias,
+                        tool_spec.path(),
+                        tool_spec.version(),
+                        if dev { " (dev)" } else { "" },
+                        if tool_spec.is_available() {
+                            ""
+                        } else {
+                            " (not available on this platform)"
+                        }
+                    );
+                }
+            }
+        }
+        Subcommand::List(subcommand) if subcommand.stale => {
+            let configs = ConfigFile::load_each(&paths)?;
+            let declared = configs
+                .iter()
+                .flat_map(|config| config.tools.values().chain(config.dev_tools.values()));
+
+            let cache = ToolCache::load(&paths)?;
+            let stale = cache.stale_versions(declared);
+
+            if stale.is_empty() {
+                println!("No stale tools.");
+                return Ok(());
+            }
+
+            println!("Stale tools:");
+
+            let mut current_tool = None;
+            for stale_version in &stale {
+                if current_tool != Some(stale_version.tool) {
+                    println!("  {}", stale_version.tool);
+                    current_tool = Some(stale_version.tool);
+                }
+                println!(
+                    "    - {} ({} bytes)",
+                    stale_version.version, stale_version.size
+                );
+            }
+
+            println!(
+                "Total: {} bytes in {} version{}",
+                stale
+                    .iter()
+                    .map(|stale_version| stale_version.size)
+                    .sum::<u64>(),
+                stale.len(),
+                if stale.len() == 1 { "" } else { "s" }
+            );
+        }
+        Subcommand::List(subcommand) if subcommand.duplicates => {
+            let cache = ToolCache::load(&paths)?;
+            let duplicates = cache.duplicate_tools();
+
+            if duplicates.is_empty() {
+                println!("No duplicate tools.");
+                return Ok(());
+            }
+
+            println!("Tools installed under several cache keys:");
+
+            for duplicate in &duplicates {
+                println!("  {}", duplicate.path);
+
+                for (cache_key, entry) in &duplicate.entries {
+                    println!(
+                        "    - {} ({})",
+                        cache_key,
+                        entry
+                            .versions
+                            .iter()
+                            .map(ToString::to_string)
+                            .collect::<Vec<_>>()
+                            .join(", ")
+                    );
+                }
+            }
+        }
+        Subcommand::List(_) => {
             println!("Installed tools:");
 
             let cache = ToolCache::load(&paths)?;
 
-            for (tool_source, tool) in &cache.tools {
+            for (tool_source, tool) in cache.installed_tools() {
                 println!("  {}", tool_source);
 
                 for version in &tool.versions {
@@ -282,6 +1507,366 @@ fn actual_main(paths: ForemanPaths) -> ForemanResult<()> {
                 }
             }
         }
+        Subcommand::Status(subcommand) => {
+            let config = load_config(&paths)?;
+
+            let cache = configured_cache(&paths, &config)?;
+
+            let providers = tool_provider(
+                &paths,
+                &config,
+                options.max_download_size,
+                options.empty_release_retries,
+            );
+            let foreman_path = env::current_exe().map_err(|err| {
+                ForemanError::io_error_with_context(
+                    err,
+                    "unable to obtain foreman executable location",
+                )
+            })?;
+
+            let tools = config.tools.iter().map(|tool| (tool, false));
+            let dev_tools = config.dev_tools.iter().map(|tool| (tool, true));
+            let statuses: Vec<ToolStatus> = tools
+                .chain(dev_tools)
+                .map(|((tool_alias, tool_spec), dev)| {
+                    let state = tool_spec.is_available().then(|| {
+                        let installed = cache
+                            .installed_version(tool_spec)
+                            .filter(|version| cache.installed_path(tool_spec, version).is_some())
+                            .cloned();
+
+                        let latest = if subcommand.offline {
+                            Latest::Unchecked
+                        } else {
+                            match cache.resolve(tool_spec, &providers) {
+                                Ok(version) => Latest::Version(version),
+                                Err(err) => {
+                                    log::debug!(
+                                        "unable to find the newest release of {}: {}",
+                                        tool_alias,
+                                        err
+                                    );
+                                    Latest::Unknown
+                                }
+                            }
+                        };
+
+                        let alias = match find_alias(tool_alias, &paths.bin_dir()) {
+                            None => AliasState::Missing,
+                            Some(alias_path) => {
+                                match selfcheck::check_alias(&alias_path, &foreman_path).problem {
+                                    None => AliasState::Ok,
+                                    Some(problem) => AliasState::Broken(problem),
+                                }
+                            }
+                        };
+
+                        ToolState {
+                            installed,
+                            latest,
+                            alias,
+                        }
+                    });
+
+                    ToolStatus {
+                        alias: tool_alias.clone(),
+                        dev,
+                        requirement: tool_spec.version().to_string(),
+                        state,
+                    }
+                })
+                .collect();
+
+            if statuses.is_empty() {
+                println!("No tools are declared for this directory.");
+                return Ok(());
+            }
+            print!("{}", status::render(&statuses));
+        }
+        Subcommand::Diff(subcommand) => {
+            let config = load_config(&paths)?;
+
+            let cache = configured_cache(&paths, &config)?;
+
+            let declared = config.tools.iter().chain(config.dev_tools.iter());
+            let diffs = diff::diff(declared, &cache);
+
+            if diffs.is_empty() {
+                println!("The installed tools match the declared tools.");
+                return Ok(());
+            }
+            print!("{}", diff::render(&diffs));
+
+            if subcommand.exit_code {
+                return Err(ForemanError::ToolsDiffer {
+                    differences: diffs.len(),
+                });
+            }
+        }
+        Subcommand::Complete(subcommand) => {
+            // Warnings would only get in the way of the completions.
+            let (config, _warnings) = ConfigFile::aggregate(&paths)?;
+
+            match subcommand.version {
+                None => {
+                    for alias in config.aliases_starting_with(&subcommand.partial) {
+                        println!("{}", alias);
+                    }
+                }
+                Some(version_prefix) => {
+                    if let Some(tool_spec) = config.tool(&subcommand.partial) {
+                        let cache = ToolCache::load(&paths)?;
+
+                        for version in cache.installed_versions(tool_spec) {
+                            let version = version.to_string();
+                            if version.starts_with(&version_prefix) {
+                                println!("{}", version);
+                            }
+                        }
+                    }
+                }
+            }
+        }
+        Subcommand::Add(subcommand) => {
+            let shorthand = ToolShorthand::parse(&subcommand.source)?;
+
+            let (base_dir, config_path) = ConfigFile::find_project(&paths)?;
+            let config_path = config_path.unwrap_or_else(|| base_dir.join("foreman.toml"));
+
+            // Resolve the tool against the known hosts before touching the
+            // file, so that a typo does not end up in the configuration.
+            let config = load_config(&paths)?;
+            let tool_spec = ToolSpec::from_value(&shorthand.to_value(), &config.hosts)
+                .map_err(|err| ForemanError::config_parsing(&config_path, err.to_string()))?;
+
+            config::add_tool(
+                &config_path,
+                &subcommand.alias,
+                &shorthand,
+                subcommand.force,
+            )?;
+            println!(
+                "Added {} ({}) to {}",
+                subcommand.alias,
+                tool_spec,
+                config_path.display()
+            );
+
+            if subcommand.install {
+                let mut cache = install_cache(&paths, &config, cache_options)?;
+                remember_projects(&paths, &mut cache);
+                let providers = tool_provider(
+                    &paths,
+                    &config,
+                    options.max_download_size,
+                    options.empty_release_retries,
+                );
+
+                cache.download_if_necessary(&tool_spec, &providers)?;
+                add_self_alias(&subcommand.alias, &paths.bin_dir(), alias_style)?;
+            }
+        }
+        Subcommand::Watch(subcommand) => {
+            let watcher = ConfigWatcher::new(ConfigFile::candidate_paths(&paths)?)?;
+            let mut declared: BTreeMap<String, ToolSpec> = BTreeMap::new();
+
+            loop {
+                // A configuration that does not parse, or a tool cache or
+                // checksums file that cannot be read, is reported, and kept
+                // watched until it is fixed.
+                let loaded = load_config(&paths).and_then(|config| {
+                    let new_declared: BTreeMap<String, ToolSpec> =
+                        declared_tools(&config, subcommand.dev, &[])?
+                            .into_iter()
+                            .map(|(alias, tool_spec)| (alias.clone(), tool_spec.clone()))
+                            .collect();
+                    let cache = install_cache(&paths, &config, cache_options)?;
+                    Ok((config, new_declared, cache))
+                });
+
+                match loaded {
+                    Ok((config, new_declared, mut cache)) => {
+                        remember_projects(&paths, &mut cache);
+                        let providers = tool_provider(
+                            &paths,
+                            &config,
+                            options.max_download_size,
+                            options.empty_release_retries,
+                        );
+
+                        for change in watch::tool_changes(&declared, &new_declared) {
+                            match change {
+                                ToolChange::Declared(alias) => {
+                                    let tool_spec = &new_declared[alias];
+                                    match cache.download_if_necessary(tool_spec, &providers).and_then(
+                                        |version| {
+                                            add_self_alias(alias, &paths.bin_dir(), alias_style)?;
+                                            Ok(version)
+                                        },
+                                    ) {
+                                        Ok(version) => {
+                                            println!("{}: {} {}", alias, tool_spec.source(), version)
+                                        }
+                                        Err(err) => log::error!(
+                                            "The following error occurred while trying to download tool \"{}\":\n{}",
+                                            alias,
+                                            err
+                                        ),
+                                    }
+                                }
+                                ToolChange::Removed(alias) => {
+                                    println!("{}: removed from the configuration", alias)
+                                }
+                            }
+                        }
+
+                        declared = new_declared;
+                    }
+                    Err(err) => log::error!("{}", err),
+                }
+
+                if let Some(changed) = watcher.next_change(None)? {
+                    for config_path in changed {
+                        log::info!("{} changed, reloading", config_path.display());
+                    }
+                }
+            }
+        }
+        Subcommand::Freeze => {
+            let (base_dir, config_path) = ConfigFile::find_project(&paths)?;
+            let config_path = config_path.ok_or_else(|| {
+                ForemanError::io_error_with_context(
+                    std::io::ErrorKind::NotFound.into(),
+                    if paths.config_root().is_some() {
+                        format!("unable to find a foreman.toml in {}", base_dir.display())
+                    } else {
+                        format!(
+                            "unable to find a foreman.toml in {} or its parents",
+                            base_dir.display()
+                        )
+                    },
+                )
+            })?;
+
+            let config = load_config(&paths)?;
+            let cache = ToolCache::load(&paths)?;
+
+            let versions: BTreeMap<String, Version> = config
+                .tools
+                .iter()
+                .chain(&config.dev_tools)
+                .filter_map(|(alias, tool_spec)| {
+                    let version = cache.installed_version(tool_spec)?;
+                    Some((alias.clone(), version.clone()))
+                })
+                .collect();
+
+            let (frozen, not_frozen) = config::freeze_tools(&config_path, &versions)?;
+
+            for alias in frozen {
+                println!("Froze {} to ={}", alias, versions[&alias]);
+            }
+            for alias in not_frozen {
+                log::warn!("{} is not installed and was not frozen", alias);
+            }
+        }
+        Subcommand::Cache(CacheCommand::Size(subcommand)) => {
+            if subcommand.breakdown {
+                let cache = ToolCache::load(&paths)?;
+
+                let mut current_tool = None;
+                for (tool, version, size) in cache.version_sizes() {
+                    if current_tool != Some(tool) {
+                        println!("{}", tool);
+                        current_tool = Some(tool);
+                    }
+                    println!("  {}: {}", version, disk_usage::human_size(size));
+                }
+            }
+
+            let size = disk_usage::directory_size(&paths.tools_dir())?;
+            println!(
+                "Total: {} ({} bytes) in {}",
+                disk_usage::human_size(size),
+                size,
+                paths.tools_dir().display()
+            );
+        }
+        Subcommand::Cache(CacheCommand::CleanTemp(subcommand)) => {
+            let cache = ToolCache::load(&paths)?;
+            let stale = cache.stale_temp_files(Duration::from_secs(subcommand.older_than))?;
+
+            if stale.is_empty() {
+                println!("No temporary files to remove.");
+                return Ok(());
+            }
+
+            println!(
+                "{}:",
+                if subcommand.dry_run {
+                    "Would remove"
+                } else {
+                    "Removing"
+                }
+            );
+            let mut total = 0;
+            for (path, size) in &stale {
+                println!("  {} ({})", path.display(), disk_usage::human_size(*size));
+                total += size;
+
+                if !subcommand.dry_run {
+                    fs::try_remove_file(path)?;
+                }
+            }
+            println!("Total: {}", disk_usage::human_size(total));
+        }
+        Subcommand::Debug(DebugCommand::Paths) => {
+            let existence = |path: &std::path::Path| {
+                if path.exists() {
+                    "found"
+                } else {
+                    "not found"
+                }
+            };
+
+            println!("Foreman paths:");
+            for (name, path) in [
+                ("root_dir", paths.root_dir()),
+                ("tools_dir", paths.tools_dir()),
+                ("bin_dir", paths.bin_dir()),
+                ("auth_store", paths.auth_store()),
+                ("user_config", paths.user_config()),
+                ("index_file", paths.index_file()),
+            ] {
+                println!("  {}: {} ({})", name, path.display(), existence(&path));
+            }
+
+            println!();
+            println!("Configuration files, from highest to lowest precedence:");
+            for config_path in ConfigFile::candidate_paths(&paths)? {
+                println!("  {} ({})", config_path.display(), existence(&config_path));
+            }
+        }
+        Subcommand::Selfcheck => {
+            let foreman_path = env::current_exe().map_err(|err| {
+                ForemanError::io_error_with_context(
+                    err,
+                    "unable to obtain foreman executable location",
+                )
+            })?;
+
+            let checks = selfcheck::check_installation(&foreman_path, &paths.bin_dir());
+            for check in &checks {
+                println!("{}", check);
+            }
+
+            let failures = checks.iter().filter(|check| !check.passed()).count();
+            if failures > 0 {
+                return Err(ForemanError::SelfCheckFailed { failures });
+            }
+            println!("All {} check(s) passed.", checks.len());
+        }
         Subcommand::GitHubAuth(subcommand) => {
             let token = prompt_auth_token(
                     subcommand.token,
@@ -289,7 +1874,12 @@ fn actual_main(paths: ForemanPaths) -> ForemanResult<()> {
                     "https://help.github.com/en/github/authenticating-to-github/creating-a-personal-access-token-for-the-command-line",
                 )?;
 
-            AuthStore::set_github_token(&paths.auth_store(), &token)?;
+            match &subcommand.repo {
+                Some(repo) => {
+                    AuthStore::set_github_repository_token(&paths.auth_store(), repo, &token)?
+                }
+                None => AuthStore::set_github_token(&paths.auth_store(), &token)?,
+            }
 
             println!("GitHub auth saved successfully.");
         }
@@ -300,16 +1890,41 @@ fn actual_main(paths: ForemanPaths) -> ForemanResult<()> {
                 "https://docs.gitlab.com/ee/user/profile/personal_access_tokens.html",
             )?;
 
-            AuthStore::set_gitlab_token(&paths.auth_store(), &token)?;
+            match &subcommand.repo {
+                Some(repo) => {
+                    AuthStore::set_gitlab_repository_token(&paths.auth_store(), repo, &token)?
+                }
+                None => AuthStore::set_gitlab_token(&paths.auth_store(), &token)?,
+            }
 
             println!("GitLab auth saved successfully.");
         }
+        Subcommand::GenerateArtifactoryPath(subcommand) if subcommand.all_platforms => {
+            let artifactory_paths = artifactory_path::generate_all_artifactory_paths(
+                &subcommand.repo,
+                &subcommand.tool_name,
+                &subcommand.version,
+            )?;
+            if subcommand.json {
+                println!(
+                    "{}",
+                    serde_json::to_string_pretty(&artifactory_paths)
+                        .expect("unable to serialize paths")
+                );
+            } else {
+                for artifactory_path in artifactory_paths {
+                    println!("{}", artifactory_path);
+                }
+            }
+        }
         Subcommand::GenerateArtifactoryPath(subcommand) => {
             let artifactory_path = artifactory_path::generate_artifactory_path(
                 subcommand.repo,
                 subcommand.tool_name,
                 subcommand.version,
-                subcommand.operating_system,
+                subcommand
+                    .operating_system
+                    .expect("operating system is required without --all-platforms"),
                 subcommand.architecture,
             )?;
             println!("{}", artifactory_path);
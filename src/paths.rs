@@ -1,12 +1,13 @@
 //! Contains all of the paths that Foreman needs to deal with.
 
 use std::{
-    env,
+    env, io,
     path::{Path, PathBuf},
 };
 
 use crate::{
     auth_store::DEFAULT_AUTH_CONFIG,
+    checksums::CHECKSUMS_FILE_NAME,
     error::{ForemanError, ForemanResult},
     fs,
 };
@@ -15,9 +16,22 @@ static DEFAULT_USER_CONFIG: &str = include_str!("../resources/default-foreman.to
 
 const FOREMAN_PATH_ENV_VARIABLE: &str = "FOREMAN_HOME";
 
+/// Environment variable pointing at the auth store to use instead of the one
+/// in Foreman's home directory. The `--auth-file` option takes precedence.
+const AUTH_FILE_ENV_VARIABLE: &str = "FOREMAN_AUTH_FILE";
+
+/// Name of the Artifactory token store written next to an overridden auth
+/// store.
+const ARTIAA_FILE_NAME: &str = "artiaa-tokens.json";
+
 #[derive(Debug, Clone, PartialEq, Eq)]
 pub struct ForemanPaths {
     root_dir: PathBuf,
+    /// The only project directory whose `foreman.toml` applies, instead of
+    /// the current directory and its parents.
+    config_root: Option<PathBuf>,
+    /// The auth store to use instead of the one in the root directory.
+    auth_file: Option<PathBuf>,
 }
 
 impl ForemanPaths {
@@ -27,7 +41,7 @@ impl ForemanPaths {
             .ok()
             .and_then(|path| {
                 if path.is_dir() {
-                    Some(Self { root_dir: path })
+                    Some(Self::new(path))
                 } else {
                     if path.exists() {
                         log::warn!(
@@ -48,37 +62,99 @@ impl ForemanPaths {
     }
 
     pub fn new(root_dir: PathBuf) -> Self {
-        Self { root_dir }
+        Self {
+            root_dir,
+            config_root: None,
+            auth_file: None,
+        }
+    }
+
+    /// Makes the `foreman.toml` of `config_root` the only project
+    /// configuration, as set by `--config-root`.
+    pub fn set_config_root(&mut self, config_root: Option<PathBuf>) {
+        self.config_root = config_root;
+    }
+
+    pub fn config_root(&self) -> Option<&Path> {
+        self.config_root.as_deref()
+    }
+
+    /// Reads tokens from `auth_file` instead of the auth store and the
+    /// Artifactory token store of the current user, as set by `--auth-file`
+    /// or `FOREMAN_AUTH_FILE`.
+    pub fn set_auth_file(&mut self, auth_file: Option<PathBuf>) {
+        self.auth_file = auth_file;
+    }
+
+    /// The auth store set with the `FOREMAN_AUTH_FILE` environment variable.
+    pub fn auth_file_from_env() -> Option<PathBuf> {
+        env::var_os(AUTH_FILE_ENV_VARIABLE)
+            .filter(|path| !path.is_empty())
+            .map(PathBuf::from)
+    }
+
+    /// Fails when the auth file set with `--auth-file` or `FOREMAN_AUTH_FILE`
+    /// does not exist, since reading no tokens from a mistyped path would
+    /// only show up later as failed downloads.
+    pub fn check_auth_file(&self) -> ForemanResult<()> {
+        match &self.auth_file {
+            Some(auth_file) if !auth_file.is_file() => Err(ForemanError::read_error(
+                io::Error::new(
+                    io::ErrorKind::NotFound,
+                    "the auth file set with --auth-file or FOREMAN_AUTH_FILE does not exist",
+                ),
+                auth_file,
+            )),
+            _ => Ok(()),
+        }
     }
 
     pub fn root_dir(&self) -> PathBuf {
         self.root_dir.clone()
     }
 
-    fn from_root<P: AsRef<Path>>(&self, path: P) -> PathBuf {
+    fn join_root<P: AsRef<Path>>(&self, path: P) -> PathBuf {
         let mut dir = self.root_dir();
         dir.push(path);
         dir
     }
 
     pub fn tools_dir(&self) -> PathBuf {
-        self.from_root("tools")
+        self.join_root("tools")
     }
 
     pub fn bin_dir(&self) -> PathBuf {
-        self.from_root("bin")
+        self.join_root("bin")
+    }
+
+    /// The releases listed for each tool, kept for a while.
+    pub fn release_cache_dir(&self) -> PathBuf {
+        self.join_root("release-cache")
+    }
+
+    /// The lock files of the slots tools run in when their number is
+    /// limited.
+    pub fn tool_slots_dir(&self) -> PathBuf {
+        self.join_root("tool-slots")
     }
 
     pub fn auth_store(&self) -> PathBuf {
-        self.from_root("auth.toml")
+        match &self.auth_file {
+            Some(auth_file) => auth_file.clone(),
+            None => self.join_root("auth.toml"),
+        }
     }
 
     pub fn user_config(&self) -> PathBuf {
-        self.from_root("foreman.toml")
+        self.join_root("foreman.toml")
+    }
+
+    pub fn user_checksums(&self) -> PathBuf {
+        self.join_root(CHECKSUMS_FILE_NAME)
     }
 
     pub fn index_file(&self) -> PathBuf {
-        self.from_root("tool-cache.json")
+        self.join_root("tool-cache.json")
     }
 
     pub fn create_all(&self) -> Result<(), ForemanError> {
@@ -89,13 +165,22 @@ impl ForemanPaths {
         let config = self.user_config();
         fs::write_if_not_found(&config, DEFAULT_USER_CONFIG)?;
 
-        let auth = self.auth_store();
+        // An overridden auth store is left alone: it is usually provisioned
+        // for a single job.
+        let auth = self.join_root("auth.toml");
         fs::write_if_not_found(&auth, DEFAULT_AUTH_CONFIG)?;
 
         Ok(())
     }
 
+    /// The Artifactory token store: the one of the current user, or the one
+    /// next to an overridden auth store, so that both kinds of tokens are
+    /// isolated together.
     pub fn artiaa_path(&self) -> ForemanResult<PathBuf> {
+        if let Some(auth_file) = &self.auth_file {
+            return Ok(auth_file.with_file_name(ARTIAA_FILE_NAME));
+        }
+
         get_artiaa_path_based_on_os()
     }
 }
@@ -125,32 +210,39 @@ fn get_artiaa_path_based_on_os() -> ForemanResult<PathBuf> {
 #[cfg(all(not(target_os = "macos"), target_family = "unix"))]
 fn get_artiaa_path_based_on_os() -> ForemanResult<PathBuf> {
     if let Ok(xdg_data_home) = env::var("XDG_DATA_HOME") {
-        return Ok(PathBuf::from(format!(
+        Ok(PathBuf::from(format!(
             "{}/artiaa-tokens.json",
             xdg_data_home
-        )));
+        )))
     } else if let Ok(home) = env::var("HOME") {
-        return Ok(PathBuf::from(format!(
+        Ok(PathBuf::from(format!(
             "{}/.local/share/artiaa-tokens.json",
             home
-        )));
+        )))
     } else {
-        return Err(ForemanError::EnvVarNotFound {
+        Err(ForemanError::EnvVarNotFound {
             env_var: "$HOME".to_string(),
-        });
+        })
     }
 }
 
-#[cfg(other)]
-fn get_artiaa_path_based_on_os() -> PathBuf {
+#[cfg(not(any(target_os = "windows", target_family = "unix")))]
+fn get_artiaa_path_based_on_os() -> ForemanResult<PathBuf> {
     unimplemented!("artiaa_path is only defined for windows or unix operating systems")
 }
 
-impl Default for ForemanPaths {
-    fn default() -> Self {
-        let mut root_dir = dirs::home_dir().expect("unable to get home directory");
+impl ForemanPaths {
+    /// Uses `~/.foreman` as the root directory.
+    pub fn from_home_dir() -> ForemanResult<Self> {
+        Self::from_home(dirs::home_dir())
+    }
+
+    fn from_home(home_dir: Option<PathBuf>) -> ForemanResult<Self> {
+        let mut root_dir = home_dir.ok_or(ForemanError::HomeDirectoryNotFound {
+            env_var: FOREMAN_PATH_ENV_VARIABLE,
+        })?;
         root_dir.push(".foreman");
-        Self::new(root_dir)
+        Ok(Self::new(root_dir))
     }
 }
 
@@ -166,6 +258,20 @@ mod test {
         assert_eq!(paths.root_dir(), root);
     }
 
+    #[test]
+    fn from_home() {
+        let paths = ForemanPaths::from_home(Some(PathBuf::from("/home/user"))).unwrap();
+
+        assert_eq!(paths.root_dir(), PathBuf::from("/home/user/.foreman"));
+    }
+
+    #[test]
+    fn from_home_without_home_directory() {
+        let err = ForemanPaths::from_home(None).unwrap_err();
+
+        assert!(err.to_string().contains("FOREMAN_HOME"));
+    }
+
     #[test]
     fn tools_dir() {
         let mut directory = PathBuf::from("/foreman");
@@ -193,6 +299,18 @@ mod test {
         assert_eq!(directory, paths.auth_store());
     }
 
+    #[test]
+    fn overridden_auth_file() {
+        let mut paths = ForemanPaths::new(PathBuf::from("/foreman"));
+        paths.set_auth_file(Some(PathBuf::from("/jobs/42/auth.toml")));
+
+        assert_eq!(paths.auth_store(), PathBuf::from("/jobs/42/auth.toml"));
+        assert_eq!(
+            paths.artiaa_path().unwrap(),
+            PathBuf::from("/jobs/42/artiaa-tokens.json")
+        );
+    }
+
     #[test]
     fn user_config() {
         let mut directory = PathBuf::from("/foreman");
@@ -1,13 +1,157 @@
 //Orignal source from https://github.com/LPGhatguy/aftman/blob/d3f8d1fac4c89d9163f8f3a0c97fa33b91294fea/src/process/mod.rs
 
+use std::{
+    collections::BTreeMap,
+    env::{self, consts::EXE_SUFFIX},
+    io,
+    path::{Path, PathBuf},
+};
+
+mod slots;
+
+pub use slots::ToolSlot;
+
 #[cfg(windows)]
 mod windows;
 
 #[cfg(windows)]
-pub use windows::run;
+pub use windows::{run, run_captured, spawn_detached};
 
 #[cfg(unix)]
 mod unix;
 
 #[cfg(unix)]
-pub use unix::run;
+pub use unix::{run, run_captured, spawn_detached};
+
+/// The step of running a tool that failed.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum RunPhase {
+    /// Creating the async runtime that waits for the tool and for signals at
+    /// the same time.
+    Runtime,
+    /// Registering the handlers that relay signals to the tool.
+    Signals,
+    /// Starting the tool's process.
+    Spawn,
+    /// Waiting for the tool to terminate.
+    Wait,
+}
+
+/// A failure to run a tool, with the step that failed.
+#[derive(Debug)]
+pub struct RunError {
+    pub phase: RunPhase,
+    pub source: io::Error,
+}
+
+impl RunError {
+    pub fn new(phase: RunPhase, source: io::Error) -> Self {
+        Self { phase, source }
+    }
+}
+
+/// The result of running a tool with its output captured instead of streamed
+/// to the terminal.
+#[derive(Debug)]
+pub struct CapturedOutput {
+    pub code: i32,
+    pub stdout: Vec<u8>,
+    pub stderr: Vec<u8>,
+}
+
+/// Describes how `exe_path` would be run with `args`, the extra `env`
+/// variables and the working directory `current_dir`, as a command line
+/// quoted for a POSIX shell.
+pub fn command_line(
+    exe_path: &Path,
+    args: &[String],
+    env: &BTreeMap<String, String>,
+    current_dir: Option<&Path>,
+) -> String {
+    let mut words = Vec::new();
+    if let Some(current_dir) = current_dir {
+        words.push(format!("cd {} &&", quote(&current_dir.to_string_lossy())));
+    }
+    for (key, value) in env {
+        words.push(format!("{}={}", key, quote(value)));
+    }
+    words.push(quote(&exe_path.to_string_lossy()));
+    words.extend(args.iter().map(|arg| quote(arg)));
+
+    words.join(" ")
+}
+
+/// Finds the executable named `name` in the directories of `PATH`, in order.
+/// Directories in `excluded_dirs` are skipped, and so is any file that is
+/// `excluded_exe` itself, like a symbolic link to it.
+pub fn find_on_path(name: &str, excluded_dirs: &[PathBuf], excluded_exe: &Path) -> Option<PathBuf> {
+    let excluded_dirs: Vec<PathBuf> = excluded_dirs
+        .iter()
+        .filter_map(|directory| directory.canonicalize().ok())
+        .collect();
+    let excluded_exe = excluded_exe.canonicalize().ok();
+    let file_name = format!("{}{}", name, EXE_SUFFIX);
+
+    env::split_paths(&env::var_os("PATH")?)
+        .filter(|directory| {
+            directory
+                .canonicalize()
+                .map(|directory| !excluded_dirs.contains(&directory))
+                .unwrap_or(false)
+        })
+        .map(|directory| directory.join(&file_name))
+        .find(|candidate| is_executable(candidate) && candidate.canonicalize().ok() != excluded_exe)
+}
+
+#[cfg(unix)]
+fn is_executable(path: &Path) -> bool {
+    use std::os::unix::fs::PermissionsExt;
+
+    path.metadata()
+        .map(|metadata| metadata.is_file() && metadata.permissions().mode() & 0o111 != 0)
+        .unwrap_or(false)
+}
+
+#[cfg(windows)]
+fn is_executable(path: &Path) -> bool {
+    path.is_file()
+}
+
+/// Quotes `word` with single quotes, unless it is only made of characters
+/// that a shell would not interpret.
+fn quote(word: &str) -> String {
+    let is_plain = |c: char| c.is_ascii_alphanumeric() || "-_./:=,+@%".contains(c);
+    if !word.is_empty() && word.chars().all(is_plain) {
+        word.to_owned()
+    } else {
+        format!("'{}'", word.replace('\'', "'\\''"))
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn quotes_words_shells_would_interpret() {
+        assert_eq!(quote("--port=8000"), "--port=8000");
+        assert_eq!(quote(""), "''");
+        assert_eq!(quote("two words"), "'two words'");
+        assert_eq!(quote("it's"), "'it'\\''s'");
+        assert_eq!(quote("$HOME"), "'$HOME'");
+    }
+
+    #[test]
+    fn command_line_lists_directory_env_and_args() {
+        let env = BTreeMap::from([("RUST_LOG".to_owned(), "debug".to_owned())]);
+        assert_eq!(
+            command_line(
+                Path::new("/tools/rojo-7.3.0"),
+                &["serve".to_owned(), "my project".to_owned()],
+                &env,
+                Some(Path::new("/work")),
+            ),
+            "cd /work && RUST_LOG=debug /tools/rojo-7.3.0 serve 'my project'"
+        );
+    }
+}
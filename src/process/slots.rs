@@ -0,0 +1,157 @@
+//! Caps how many tools Foreman runs at once across all of its processes, for
+//! scripts that invoke many tools in parallel.
+//!
+//! Each running tool holds an exclusive lock on one of
+//! `FOREMAN_MAX_CONCURRENT_TOOLS` slot files. Tools that find every slot taken
+//! wait for one to be released. Locks are released by the operating system
+//! when their process exits, so a crashed Foreman never holds a slot.
+
+use std::{
+    collections::BTreeMap,
+    env,
+    fs::{self, File, OpenOptions},
+    io,
+    path::Path,
+    thread,
+    time::Duration,
+};
+
+use fs2::FileExt;
+
+/// Environment variable enabling the limit, as the number of tools that may
+/// run at once.
+const MAX_CONCURRENT_TOOLS_ENV_VARIABLE: &str = "FOREMAN_MAX_CONCURRENT_TOOLS";
+
+/// Set for the tools that hold a slot, so that the tools they run through
+/// Foreman in turn do not wait for a slot their parent holds.
+const HOLDS_SLOT_ENV_VARIABLE: &str = "FOREMAN_HOLDS_TOOL_SLOT";
+
+/// How often a waiting tool checks for a released slot.
+const POLL_INTERVAL: Duration = Duration::from_millis(50);
+
+/// A slot to run a tool in, held until dropped.
+#[derive(Debug)]
+pub struct ToolSlot {
+    // Closing the file releases its lock.
+    _file: File,
+}
+
+impl ToolSlot {
+    /// Waits for a slot in `directory` when a limit is set and this process
+    /// was not started by a tool already holding a slot.
+    pub fn acquire_from_env(directory: &Path) -> io::Result<Option<Self>> {
+        if env::var_os(HOLDS_SLOT_ENV_VARIABLE).is_some() {
+            return Ok(None);
+        }
+
+        match max_concurrent_tools() {
+            Some(limit) => Self::acquire(directory, limit).map(Some),
+            None => Ok(None),
+        }
+    }
+
+    /// Waits until one of the `limit` slots in `directory` is free, then
+    /// takes it.
+    pub fn acquire(directory: &Path, limit: usize) -> io::Result<Self> {
+        fs::create_dir_all(directory)?;
+        let files = (0..limit.max(1))
+            .map(|index| {
+                OpenOptions::new()
+                    .create(true)
+                    .truncate(false)
+                    .write(true)
+                    .open(directory.join(format!("slot-{}.lock", index)))
+            })
+            .collect::<io::Result<Vec<_>>>()?;
+
+        let mut files = match Self::try_acquire(files)? {
+            Ok(slot) => return Ok(slot),
+            Err(files) => files,
+        };
+
+        log::info!(
+            "Waiting for one of the {} tools allowed by {} to finish",
+            files.len(),
+            MAX_CONCURRENT_TOOLS_ENV_VARIABLE
+        );
+        loop {
+            thread::sleep(POLL_INTERVAL);
+            files = match Self::try_acquire(files)? {
+                Ok(slot) => return Ok(slot),
+                Err(files) => files,
+            };
+        }
+    }
+
+    /// Locks the first free slot of `files`, or gives them back if they are
+    /// all taken.
+    fn try_acquire(mut files: Vec<File>) -> io::Result<Result<Self, Vec<File>>> {
+        for index in 0..files.len() {
+            match files[index].try_lock_exclusive() {
+                Ok(()) => {
+                    return Ok(Ok(Self {
+                        _file: files.swap_remove(index),
+                    }))
+                }
+                Err(err) if is_contended(&err) => {}
+                Err(err) => return Err(err),
+            }
+        }
+
+        Ok(Err(files))
+    }
+
+    /// The environment of the tool run in this slot: `env` with the marker
+    /// that the tool holds a slot.
+    pub fn tool_env(&self, env: &BTreeMap<String, String>) -> BTreeMap<String, String> {
+        let mut env = env.clone();
+        env.insert(HOLDS_SLOT_ENV_VARIABLE.to_owned(), "1".to_owned());
+        env
+    }
+}
+
+fn is_contended(err: &io::Error) -> bool {
+    err.raw_os_error() == fs2::lock_contended_error().raw_os_error()
+}
+
+/// The number of tools allowed to run at once, if limited.
+fn max_concurrent_tools() -> Option<usize> {
+    let value = env::var(MAX_CONCURRENT_TOOLS_ENV_VARIABLE).ok()?;
+    match value.trim().parse() {
+        Ok(0) | Err(_) => {
+            log::warn!(
+                "ignoring {}={}: expected a positive number",
+                MAX_CONCURRENT_TOOLS_ENV_VARIABLE,
+                value
+            );
+            None
+        }
+        Ok(limit) => Some(limit),
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use tempfile::tempdir;
+
+    use super::*;
+
+    #[test]
+    fn slots_are_released_when_dropped() {
+        let directory = tempdir().unwrap();
+        let first = ToolSlot::acquire(directory.path(), 2).unwrap();
+        let _second = ToolSlot::acquire(directory.path(), 2).unwrap();
+
+        let open = |index: usize| {
+            OpenOptions::new()
+                .write(true)
+                .open(directory.path().join(format!("slot-{}.lock", index)))
+                .unwrap()
+        };
+        let files = ToolSlot::try_acquire(vec![open(0), open(1)]).unwrap();
+        let files = files.expect_err("a third tool got a slot");
+
+        drop(first);
+        assert!(ToolSlot::try_acquire(files).unwrap().is_ok());
+    }
+}
@@ -3,8 +3,12 @@
 //! On Unix, we use tokio to spawn processes so that we can listen for signals
 //! and wait for process completion at the same time.
 
-use std::io::{Error, ErrorKind};
+use std::collections::BTreeMap;
+use std::future::Future;
+use std::io;
+use std::os::unix::process::{CommandExt, ExitStatusExt};
 use std::path::Path;
+use std::process::{ExitStatus, Stdio};
 use std::thread;
 
 use signal_hook::consts::signal::{SIGABRT, SIGINT, SIGQUIT, SIGTERM};
@@ -12,13 +16,134 @@ use signal_hook::iterator::Signals;
 use tokio::process::Command;
 use tokio::sync::oneshot;
 
-pub fn run(exe_path: &Path, args: Vec<String>) -> Result<i32, Error> {
+use super::{CapturedOutput, RunError, RunPhase};
+
+pub fn run(
+    exe_path: &Path,
+    args: Vec<String>,
+    env: &BTreeMap<String, String>,
+    current_dir: Option<&Path>,
+) -> Result<i32, RunError> {
+    run_until_signal(|| {
+        let mut child = command(exe_path, args, env, current_dir)
+            .kill_on_drop(true)
+            .spawn()
+            .map_err(|err| RunError::new(RunPhase::Spawn, err))?;
+
+        Ok(async move {
+            let status = child.wait().await;
+            status.ok().map(|status| exit_code(&status)).unwrap_or(1)
+        })
+    })
+}
+
+pub fn run_captured(
+    exe_path: &Path,
+    args: Vec<String>,
+    env: &BTreeMap<String, String>,
+    current_dir: Option<&Path>,
+) -> Result<CapturedOutput, RunError> {
+    run_until_signal(|| {
+        let child = command(exe_path, args, env, current_dir)
+            .stdin(Stdio::null())
+            .stdout(Stdio::piped())
+            .stderr(Stdio::piped())
+            .kill_on_drop(true)
+            .spawn()
+            .map_err(|err| RunError::new(RunPhase::Spawn, err))?;
+
+        Ok(async move {
+            let output = child
+                .wait_with_output()
+                .await
+                .map_err(|err| RunError::new(RunPhase::Wait, err))?;
+            Ok(CapturedOutput {
+                code: exit_code(&output.status),
+                stdout: output.stdout,
+                stderr: output.stderr,
+            })
+        })
+    })?
+}
+
+/// Starts a tool in its own session, with no terminal input or output, and
+/// returns its PID without waiting for it. Signals sent to Foreman's process
+/// group, like Ctrl-C in the terminal, do not reach the tool, and it is not
+/// killed when Foreman exits or its terminal hangs up.
+pub fn spawn_detached(
+    exe_path: &Path,
+    args: Vec<String>,
+    env: &BTreeMap<String, String>,
+    current_dir: Option<&Path>,
+) -> Result<u32, RunError> {
+    let mut command = std::process::Command::new(exe_path);
+    command
+        .args(args)
+        .envs(env)
+        .stdin(Stdio::null())
+        .stdout(Stdio::null())
+        .stderr(Stdio::null());
+    if let Some(current_dir) = current_dir {
+        command.current_dir(current_dir);
+    }
+    // SAFETY: `setsid` is async-signal-safe, and nothing else runs between
+    // forking and executing the tool.
+    unsafe {
+        command.pre_exec(|| {
+            if libc::setsid() == -1 {
+                return Err(io::Error::last_os_error());
+            }
+            Ok(())
+        });
+    }
+
+    // The child is never waited for: once Foreman exits, it is adopted by
+    // init, which reaps it.
+    let child = command
+        .spawn()
+        .map_err(|err| RunError::new(RunPhase::Spawn, err))?;
+    Ok(child.id())
+}
+
+fn command(
+    exe_path: &Path,
+    args: Vec<String>,
+    env: &BTreeMap<String, String>,
+    current_dir: Option<&Path>,
+) -> Command {
+    let mut command = Command::new(exe_path);
+    command.args(args).envs(env);
+    if let Some(current_dir) = current_dir {
+        command.current_dir(current_dir);
+    }
+    command
+}
+
+/// The exit code to report for a child that exited with `status`. Children
+/// terminated by a signal get the conventional `128 + signal` code used by
+/// shells, so that scripts can tell a crash apart from a failure.
+fn exit_code(status: &ExitStatus) -> i32 {
+    status
+        .code()
+        .or_else(|| status.signal().map(|signal| 128 + signal))
+        .unwrap_or(1)
+}
+
+/// Spawns a process with `spawn` and waits for the future it returns. If a
+/// signal is received in the meantime, the future is dropped, which kills the
+/// process since it is spawned with `kill_on_drop`, and Foreman exits with
+/// the matching error code.
+fn run_until_signal<F, T>(spawn: impl FnOnce() -> Result<F, RunError>) -> Result<T, RunError>
+where
+    F: Future<Output = T>,
+{
     let (kill_tx, kill_rx) = oneshot::channel();
 
     // Spawn a thread dedicated to listening for signals and relaying them to
     // our async runtime.
     let (signal_thread, signal_handle) = {
-        let mut signals = Signals::new(&[SIGABRT, SIGINT, SIGQUIT, SIGTERM]).unwrap();
+        let mut signals = Signals::new([SIGABRT, SIGINT, SIGQUIT, SIGTERM])
+            .map_err(|err| RunError::new(RunPhase::Signals, err))?;
         let signal_handle = signals.handle();
 
         let thread = thread::spawn(move || {
@@ -33,39 +158,144 @@ pub fn run(exe_path: &Path, args: Vec<String>) -> Result<i32, Error> {
     let runtime = tokio::runtime::Builder::new_current_thread()
         .enable_io()
         .build()
-        .map_err(|_| Error::new(ErrorKind::Other, "could not create tokio runtime"))?;
+        .map_err(|err| RunError::new(RunPhase::Runtime, err))?;
 
     let _guard = runtime.enter();
 
-    let mut child = Command::new(exe_path).args(args).spawn().map_err(|_| {
-        Error::new(
-            ErrorKind::Other,
-            format!("could not spawn {}", exe_path.display()),
-        )
-    })?;
+    let completion = spawn()?;
 
-    let code = runtime.block_on(async move {
+    let outcome = runtime.block_on(async move {
         tokio::select! {
-            // If the child exits cleanly, we can return its exit code directly.
+            // If the child exits cleanly, we can return its result directly.
             // I wish everything were this tidy.
-            status = child.wait() => {
-                let code = status.ok().and_then(|s| s.code()).unwrap_or(1);
-                signal_handle.close();
-                signal_thread.join().unwrap();
+            result = completion => Ok(result),
 
-                code
-            }
+            // If we received a signal while the process was running, stop
+            // waiting on it. Leaving this block drops the child, which kills it.
+            code = kill_rx => Err(code),
+        }
+    });
+
+    signal_handle.close();
+    signal_thread.join().unwrap();
+
+    match outcome {
+        Ok(result) => Ok(result),
+        // Exit immediately with the correct error code.
+        Err(code) => std::process::exit(128 + code.unwrap_or(0)),
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    fn shell(script: &str) -> Vec<String> {
+        vec!["-c".to_owned(), script.to_owned()]
+    }
+
+    #[test]
+    fn forwards_exit_code() {
+        assert_eq!(
+            run(
+                Path::new("/bin/sh"),
+                shell("exit 3"),
+                &BTreeMap::new(),
+                None
+            )
+            .unwrap(),
+            3
+        );
+    }
+
+    #[test]
+    fn child_killed_by_signal() {
+        // SIGKILL is 9 on every Unix.
+        assert_eq!(
+            run(
+                Path::new("/bin/sh"),
+                shell("kill -KILL $$"),
+                &BTreeMap::new(),
+                None
+            )
+            .unwrap(),
+            128 + 9
+        );
+        assert_eq!(
+            run_captured(
+                Path::new("/bin/sh"),
+                shell("kill -KILL $$"),
+                &BTreeMap::new(),
+                None
+            )
+            .unwrap()
+            .code,
+            128 + 9
+        );
+    }
 
-            // If we received a signal while the process was running, murder it
-            // and exit immediately with the correct error code.
-            code = kill_rx => {
-                child.kill().await.ok();
-                signal_handle.close();
-                signal_thread.join().unwrap();
-                std::process::exit(128 + code.unwrap_or(0));
+    #[test]
+    fn sets_environment_variables() {
+        let env = BTreeMap::from([("FOREMAN_TEST_VALUE".to_owned(), "from env".to_owned())]);
+        let output = run_captured(
+            Path::new("/bin/sh"),
+            shell("echo $FOREMAN_TEST_VALUE"),
+            &env,
+            None,
+        )
+        .unwrap();
+        assert_eq!(output.stdout, b"from env\n");
+    }
+
+    #[test]
+    fn detached_child_keeps_running() {
+        let directory = tempfile::tempdir().unwrap();
+        let marker = directory.path().join("marker");
+
+        let pid = spawn_detached(
+            Path::new("/bin/sh"),
+            shell("sleep 0.2; echo $$ > marker"),
+            &BTreeMap::new(),
+            Some(directory.path()),
+        )
+        .unwrap();
+
+        // The child leads its own session and process group.
+        let group = std::process::Command::new("ps")
+            .args(["-o", "pgid=,sid=", "-p", &pid.to_string()])
+            .output()
+            .unwrap();
+        if group.status.success() {
+            let ids = String::from_utf8(group.stdout).unwrap();
+            let pid = pid.to_string();
+            assert_eq!(ids.split_whitespace().collect::<Vec<_>>(), [&pid, &pid]);
+        }
+
+        for _ in 0..100 {
+            if let Ok(contents) = std::fs::read_to_string(&marker) {
+                if contents.ends_with('\n') {
+                    assert_eq!(contents.trim(), pid.to_string());
+                    return;
+                }
             }
+            thread::sleep(std::time::Duration::from_millis(50));
         }
-    });
+        panic!("the detached child never wrote its marker");
+    }
 
-    Ok(code)
+    #[test]
+    fn runs_in_current_directory() {
+        let directory = tempfile::tempdir().unwrap();
+        let output = run_captured(
+            Path::new("/bin/sh"),
+            shell("pwd -P"),
+            &BTreeMap::new(),
+            Some(directory.path()),
+        )
+        .unwrap();
+        assert_eq!(
+            Path::new(String::from_utf8(output.stdout).unwrap().trim_end()),
+            directory.path().canonicalize().unwrap()
+        );
+    }
 }
@@ -3,24 +3,86 @@
 //! On Windows, we use command_group to spawn processes in a job group that will
 //! be automatically cleaned up when this process exits.
 
-use std::io::{Error, ErrorKind};
+use std::collections::BTreeMap;
+use std::os::windows::process::CommandExt;
 use std::path::Path;
-use std::process::Command;
+use std::process::{Command, Stdio};
 
 use command_group::CommandGroup;
 
-pub fn run(exe_path: &Path, args: Vec<String>) -> Result<i32, Error> {
+use super::{CapturedOutput, RunError, RunPhase};
+
+pub fn run(
+    exe_path: &Path,
+    args: Vec<String>,
+    env: &BTreeMap<String, String>,
+    current_dir: Option<&Path>,
+) -> Result<i32, RunError> {
     // On Windows, using a job group here will cause the subprocess to terminate
     // automatically when Aftman is terminated.
-    let mut child = Command::new(exe_path)
-        .args(args)
+    let mut child = command(exe_path, args, env, current_dir)
         .group_spawn()
-        .map_err(|_| {
-            Error::new(
-                ErrorKind::Other,
-                format!("Could not spawn {}", exe_path.display()),
-            )
-        })?;
-    let status = child.wait()?;
+        .map_err(|err| RunError::new(RunPhase::Spawn, err))?;
+    let status = child
+        .wait()
+        .map_err(|err| RunError::new(RunPhase::Wait, err))?;
+    // Windows has no signals: a crashing tool exits with its exception code
+    // (like 0xC0000005 for an access violation), which is forwarded as is.
     Ok(status.code().unwrap_or(1))
 }
+
+pub fn run_captured(
+    exe_path: &Path,
+    args: Vec<String>,
+    env: &BTreeMap<String, String>,
+    current_dir: Option<&Path>,
+) -> Result<CapturedOutput, RunError> {
+    // The tool is expected to terminate on its own when its output is
+    // captured, so it does not need a job group.
+    let output = command(exe_path, args, env, current_dir)
+        .stdin(Stdio::null())
+        .output()
+        .map_err(|err| RunError::new(RunPhase::Spawn, err))?;
+    Ok(CapturedOutput {
+        code: output.status.code().unwrap_or(1),
+        stdout: output.stdout,
+        stderr: output.stderr,
+    })
+}
+
+/// The tool gets no console, and Ctrl-C in Foreman's console does not reach
+/// it.
+const DETACHED_PROCESS: u32 = 0x0000_0008;
+const CREATE_NEW_PROCESS_GROUP: u32 = 0x0000_0200;
+
+/// Starts a tool outside of any job group, with no console, and returns its
+/// PID without waiting for it, so that it keeps running once Foreman exits.
+pub fn spawn_detached(
+    exe_path: &Path,
+    args: Vec<String>,
+    env: &BTreeMap<String, String>,
+    current_dir: Option<&Path>,
+) -> Result<u32, RunError> {
+    let child = command(exe_path, args, env, current_dir)
+        .stdin(Stdio::null())
+        .stdout(Stdio::null())
+        .stderr(Stdio::null())
+        .creation_flags(DETACHED_PROCESS | CREATE_NEW_PROCESS_GROUP)
+        .spawn()
+        .map_err(|err| RunError::new(RunPhase::Spawn, err))?;
+    Ok(child.id())
+}
+
+fn command(
+    exe_path: &Path,
+    args: Vec<String>,
+    env: &BTreeMap<String, String>,
+    current_dir: Option<&Path>,
+) -> Command {
+    let mut command = Command::new(exe_path);
+    command.args(args).envs(env);
+    if let Some(current_dir) = current_dir {
+        command.current_dir(current_dir);
+    }
+    command
+}
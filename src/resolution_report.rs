@@ -0,0 +1,162 @@
+//! The report written by `foreman install --resolution-report`, recording
+//! what each tool resolved to and where it came from, for provenance.
+
+use std::path::Path;
+
+use serde::Serialize;
+
+use crate::{config::ToolSpec, error::ForemanResult, fs, tool_cache::Installation};
+
+#[derive(Debug, Default, Serialize)]
+pub struct ResolutionReport {
+    tools: Vec<ToolResolution>,
+}
+
+#[derive(Debug, Serialize)]
+struct ToolResolution {
+    alias: String,
+    source: String,
+    version_req: String,
+    version: Option<String>,
+    /// Name of the downloaded asset, absent for source tarballs and for tools
+    /// that were already installed.
+    asset: Option<String>,
+    url: Option<String>,
+    /// Digest of the downloaded asset, as `sha256:<hex>`.
+    checksum: Option<String>,
+    /// Whether a matching version was already installed, so nothing was
+    /// downloaded.
+    cache_hit: bool,
+    error: Option<String>,
+}
+
+impl ResolutionReport {
+    /// Records how the tool declared as `alias` was installed, or why it was
+    /// not.
+    pub fn record(&mut self, alias: &str, tool: &ToolSpec, result: &ForemanResult<Installation>) {
+        let mut resolution = ToolResolution {
+            alias: alias.to_owned(),
+            source: tool.source(),
+            version_req: tool.version().to_string(),
+            version: None,
+            asset: None,
+            url: None,
+            checksum: None,
+            cache_hit: false,
+            error: None,
+        };
+
+        match result {
+            Ok(installation) => {
+                resolution.version = Some(installation.version.to_string());
+                match &installation.download {
+                    Some(download) => {
+                        resolution.asset = download.asset.clone();
+                        resolution.url = Some(download.url.clone());
+                        resolution.checksum = Some(format!("sha256:{}", download.sha256));
+                    }
+                    None => resolution.cache_hit = true,
+                }
+            }
+            Err(err) => resolution.error = Some(err.to_string()),
+        }
+
+        self.tools.push(resolution);
+    }
+
+    pub fn write(&self, path: &Path) -> ForemanResult<()> {
+        let serialized =
+            serde_json::to_string_pretty(self).expect("unable to serialize resolution report");
+        fs::write(path, serialized)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use semver::Version;
+
+    use super::*;
+    use crate::{error::ForemanError, tool_cache::Download};
+
+    fn tool() -> ToolSpec {
+        ToolSpec::from_value(
+            &toml::from_str("github = \"user/tool\"\nversion = \"^1.0\"").unwrap(),
+            &crate::config::ConfigFile::new_with_defaults().hosts,
+        )
+        .unwrap()
+    }
+
+    #[test]
+    fn records_downloads_cache_hits_and_failures() {
+        let mut report = ResolutionReport::default();
+        report.record(
+            "downloaded",
+            &tool(),
+            &Ok(Installation {
+                version: Version::new(1, 2, 0),
+                download: Some(Download {
+                    asset: Some("tool-linux.zip".to_owned()),
+                    url: "https://example.com/tool-linux.zip".to_owned(),
+                    sha256: "abc123".to_owned(),
+                }),
+            }),
+        );
+        report.record(
+            "cached",
+            &tool(),
+            &Ok(Installation {
+                version: Version::new(1, 1, 0),
+                download: None,
+            }),
+        );
+        report.record(
+            "failed",
+            &tool(),
+            &Err(ForemanError::EnvVarNotFound {
+                env_var: "TOKEN".to_owned(),
+            }),
+        );
+
+        let value = serde_json::to_value(&report).unwrap();
+        assert_eq!(
+            value,
+            serde_json::json!({
+                "tools": [
+                    {
+                        "alias": "downloaded",
+                        "source": "github.com/user/tool",
+                        "version_req": "^1.0",
+                        "version": "1.2.0",
+                        "asset": "tool-linux.zip",
+                        "url": "https://example.com/tool-linux.zip",
+                        "checksum": "sha256:abc123",
+                        "cache_hit": false,
+                        "error": null,
+                    },
+                    {
+                        "alias": "cached",
+                        "source": "github.com/user/tool",
+                        "version_req": "^1.0",
+                        "version": "1.1.0",
+                        "asset": null,
+                        "url": null,
+                        "checksum": null,
+                        "cache_hit": true,
+                        "error": null,
+                    },
+                    {
+                        "alias": "failed",
+                        "source": "github.com/user/tool",
+                        "version_req": "^1.0",
+                        "version": null,
+                        "asset": null,
+                        "url": null,
+                        "checksum": null,
+                        "cache_hit": false,
+                        "error": "Environment Variable not found: TOKEN",
+                    },
+                ]
+            })
+        );
+    }
+}
@@ -0,0 +1,198 @@
+//! Checks that the running Foreman binary and the aliases in its `bin`
+//! directory are intact, since antivirus software or interrupted upgrades can
+//! leave them missing or truncated.
+
+use std::{
+    fmt,
+    fs::File,
+    path::{Path, PathBuf},
+};
+
+use crate::aliaser;
+
+/// The outcome of a single check.
+#[derive(Debug, PartialEq)]
+pub struct Check {
+    pub subject: String,
+    pub path: PathBuf,
+    /// What is wrong, or `None` when the check passed.
+    pub problem: Option<String>,
+}
+
+impl Check {
+    pub fn passed(&self) -> bool {
+        self.problem.is_none()
+    }
+}
+
+impl fmt::Display for Check {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match &self.problem {
+            None => write!(f, "[ok] {} {}", self.subject, self.path.display()),
+            Some(problem) => write!(
+                f,
+                "[FAILED] {} {}: {}",
+                self.subject,
+                self.path.display(),
+                problem
+            ),
+        }
+    }
+}
+
+/// Checks the Foreman executable at `foreman_path`, then every alias in
+/// `bin_dir`, which should all be copies of it.
+pub fn check_installation(foreman_path: &Path, bin_dir: &Path) -> Vec<Check> {
+    let (executable_size, problem) = match readable_size(foreman_path) {
+        Ok(0) => (None, Some("the file is empty".to_owned())),
+        Ok(size) => (Some(size), None),
+        Err(problem) => (None, Some(problem)),
+    };
+
+    let mut checks = vec![Check {
+        subject: "executable".to_owned(),
+        path: foreman_path.to_owned(),
+        problem,
+    }];
+
+    let mut aliases: Vec<PathBuf> = match std::fs::read_dir(bin_dir) {
+        Ok(entries) => entries
+            .filter_map(|entry| entry.ok())
+            .map(|entry| entry.path())
+            .filter(|path| path.is_file())
+            .collect(),
+        Err(err) => {
+            log::debug!("unable to list aliases in {}: {}", bin_dir.display(), err);
+            Vec::new()
+        }
+    };
+    aliases.sort();
+
+    checks.extend(
+        aliases
+            .into_iter()
+            .map(|alias_path| alias_check(alias_path, executable_size)),
+    );
+    checks
+}
+
+/// Checks the alias at `alias_path` against the Foreman executable at
+/// `foreman_path`.
+pub fn check_alias(alias_path: &Path, foreman_path: &Path) -> Check {
+    let executable_size = readable_size(foreman_path).ok().filter(|size| *size > 0);
+    alias_check(alias_path.to_owned(), executable_size)
+}
+
+fn alias_check(alias_path: PathBuf, executable_size: Option<u64>) -> Check {
+    // Launcher scripts run Foreman instead of being copies of it.
+    if aliaser::is_launcher(&alias_path) {
+        return Check {
+            subject: "launcher".to_owned(),
+            path: alias_path,
+            problem: None,
+        };
+    }
+
+    let problem = match (readable_size(&alias_path), executable_size) {
+        (Err(problem), _) => Some(problem),
+        (Ok(_), None) => Some("unable to compare with the Foreman executable".to_owned()),
+        (Ok(size), Some(expected)) if size != expected => Some(format!(
+            "the alias is {} bytes but the Foreman executable is {} bytes",
+            size, expected
+        )),
+        (Ok(_), Some(_)) => None,
+    };
+
+    Check {
+        subject: "alias".to_owned(),
+        path: alias_path,
+        problem,
+    }
+}
+
+fn readable_size(path: &Path) -> Result<u64, String> {
+    let metadata =
+        std::fs::metadata(path).map_err(|err| format!("the file is missing ({})", err))?;
+    File::open(path).map_err(|err| format!("the file is not readable ({})", err))?;
+    Ok(metadata.len())
+}
+
+#[cfg(test)]
+mod test {
+    use tempfile::tempdir;
+
+    use super::*;
+
+    #[test]
+    fn intact_installation_passes() {
+        let directory = tempdir().unwrap();
+        let foreman_path = directory.path().join("foreman");
+        let bin_dir = directory.path().join("bin");
+        std::fs::create_dir(&bin_dir).unwrap();
+        std::fs::write(&foreman_path, b"foreman binary").unwrap();
+        std::fs::write(bin_dir.join("rojo"), b"foreman binary").unwrap();
+
+        let checks = check_installation(&foreman_path, &bin_dir);
+        assert_eq!(checks.len(), 2);
+        assert!(checks.iter().all(Check::passed));
+    }
+
+    #[test]
+    fn missing_executable_fails() {
+        let directory = tempdir().unwrap();
+        let foreman_path = directory.path().join("foreman");
+        let bin_dir = directory.path().join("bin");
+        std::fs::create_dir(&bin_dir).unwrap();
+        std::fs::write(bin_dir.join("rojo"), b"foreman binary").unwrap();
+
+        let checks = check_installation(&foreman_path, &bin_dir);
+        assert!(checks[0]
+            .problem
+            .as_deref()
+            .unwrap()
+            .starts_with("the file is missing"));
+        assert_eq!(
+            checks[1].problem.as_deref(),
+            Some("unable to compare with the Foreman executable")
+        );
+    }
+
+    #[test]
+    fn empty_executable_and_truncated_alias_fail() {
+        let directory = tempdir().unwrap();
+        let bin_dir = directory.path().join("bin");
+        std::fs::create_dir(&bin_dir).unwrap();
+
+        let empty_path = directory.path().join("empty");
+        std::fs::write(&empty_path, b"").unwrap();
+        let checks = check_installation(&empty_path, &bin_dir);
+        assert_eq!(checks[0].problem.as_deref(), Some("the file is empty"));
+
+        let foreman_path = directory.path().join("foreman");
+        std::fs::write(&foreman_path, b"foreman binary").unwrap();
+        std::fs::write(bin_dir.join("rojo"), b"foreman").unwrap();
+        let checks = check_installation(&foreman_path, &bin_dir);
+        assert!(checks[0].passed());
+        assert_eq!(
+            checks[1].to_string(),
+            format!(
+                "[FAILED] alias {}: the alias is 7 bytes but the Foreman executable is 14 bytes",
+                bin_dir.join("rojo").display()
+            )
+        );
+    }
+
+    #[test]
+    fn launcher_scripts_pass() {
+        let directory = tempdir().unwrap();
+        let foreman_path = directory.path().join("foreman");
+        let bin_dir = directory.path().join("bin");
+        std::fs::create_dir(&bin_dir).unwrap();
+        std::fs::write(&foreman_path, b"foreman binary").unwrap();
+        aliaser::write_alias(&foreman_path, "rojo", &bin_dir, aliaser::AliasStyle::Script).unwrap();
+
+        let checks = check_installation(&foreman_path, &bin_dir);
+        assert_eq!(checks[1].subject, "launcher");
+        assert!(checks[1].passed());
+    }
+}
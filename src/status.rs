@@ -0,0 +1,244 @@
+//! Support for `foreman status`, which sums up whether the tools declared
+//! for the current directory are set up: installed, up to date and reachable
+//! through their alias.
+
+use std::fmt::Write;
+
+use semver::Version;
+
+const HEADERS: [&str; 5] = ["TOOL", "REQUIREMENT", "INSTALLED", "LATEST", "ALIAS"];
+
+#[derive(Debug)]
+pub struct ToolStatus {
+    pub alias: String,
+    pub dev: bool,
+    pub requirement: String,
+    /// `None` when the tool is not declared for this platform, so none of it
+    /// applies.
+    pub state: Option<ToolState>,
+}
+
+#[derive(Debug)]
+pub struct ToolState {
+    /// The installed version Foreman would run, if any.
+    pub installed: Option<Version>,
+    pub latest: Latest,
+    pub alias: AliasState,
+}
+
+/// The newest release matching the tool's version requirement.
+#[derive(Debug)]
+pub enum Latest {
+    /// Releases were not listed, because `--offline` was passed.
+    Unchecked,
+    /// Listing releases failed, for example without network access.
+    Unknown,
+    Version(Version),
+}
+
+#[derive(Debug)]
+pub enum AliasState {
+    Ok,
+    Missing,
+    /// The alias exists but would not run Foreman, for the given reason.
+    Broken(String),
+}
+
+impl ToolStatus {
+    /// Whether `foreman install` has something to fix for this tool.
+    pub fn needs_install(&self) -> bool {
+        match &self.state {
+            Some(state) => state.installed.is_none() || !matches!(state.alias, AliasState::Ok),
+            None => false,
+        }
+    }
+
+    fn cells(&self) -> [String; 5] {
+        let tool = if self.dev {
+            format!("{} (dev)", self.alias)
+        } else {
+            self.alias.clone()
+        };
+
+        let state = match &self.state {
+            Some(state) => state,
+            None => {
+                return [
+                    tool,
+                    self.requirement.clone(),
+                    "not for this platform".to_owned(),
+                    "-".to_owned(),
+                    "-".to_owned(),
+                ]
+            }
+        };
+
+        let installed = match &state.installed {
+            Some(version) => version.to_string(),
+            None => "no".to_owned(),
+        };
+        let latest = match &state.latest {
+            Latest::Unchecked => "-".to_owned(),
+            Latest::Unknown => "unknown".to_owned(),
+            Latest::Version(latest) if state.installed.as_ref() == Some(latest) => {
+                "up to date".to_owned()
+            }
+            Latest::Version(latest) => latest.to_string(),
+        };
+        let alias = match &state.alias {
+            AliasState::Ok => "ok",
+            AliasState::Missing => "missing",
+            AliasState::Broken(_) => "broken",
+        };
+
+        [
+            tool,
+            self.requirement.clone(),
+            installed,
+            latest,
+            alias.to_owned(),
+        ]
+    }
+}
+
+/// Renders `statuses` as a table with a row per tool, followed by the
+/// problems of broken aliases and what to do about missing pieces.
+pub fn render(statuses: &[ToolStatus]) -> String {
+    let rows: Vec<[String; 5]> = statuses.iter().map(ToolStatus::cells).collect();
+
+    let mut widths = HEADERS.map(str::len);
+    for row in &rows {
+        for (width, cell) in widths.iter_mut().zip(row) {
+            *width = (*width).max(cell.len());
+        }
+    }
+
+    let mut output = String::new();
+    let headers = HEADERS.map(str::to_owned);
+    for row in std::iter::once(&headers).chain(&rows) {
+        let mut line = String::new();
+        for (cell, width) in row.iter().zip(widths) {
+            write!(line, "{:width$}  ", cell, width = width).unwrap();
+        }
+        output.push_str(line.trim_end());
+        output.push('\n');
+    }
+
+    for status in statuses {
+        if let Some(ToolState {
+            alias: AliasState::Broken(problem),
+            ..
+        }) = &status.state
+        {
+            writeln!(
+                output,
+                "\nThe alias of {} is broken: {}",
+                status.alias, problem
+            )
+            .unwrap();
+        }
+    }
+
+    let needs_install: Vec<&ToolStatus> = statuses
+        .iter()
+        .filter(|status| status.needs_install())
+        .collect();
+    if needs_install.is_empty() {
+        output.push_str("\nEverything is set up.\n");
+    } else {
+        let dev = needs_install.iter().any(|status| status.dev);
+        writeln!(
+            output,
+            "\nRun `foreman install{}` to install the missing tools and aliases.",
+            if dev { " --dev" } else { "" }
+        )
+        .unwrap();
+    }
+
+    output
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    fn status(alias: &str, requirement: &str, state: Option<ToolState>) -> ToolStatus {
+        ToolStatus {
+            alias: alias.to_owned(),
+            dev: false,
+            requirement: requirement.to_owned(),
+            state,
+        }
+    }
+
+    #[test]
+    fn renders_an_aligned_table() {
+        let statuses = vec![
+            status(
+                "rojo",
+                "^7.3.0",
+                Some(ToolState {
+                    installed: Some(Version::new(7, 3, 0)),
+                    latest: Latest::Version(Version::new(7, 4, 1)),
+                    alias: AliasState::Ok,
+                }),
+            ),
+            status(
+                "selene",
+                "=0.25.0",
+                Some(ToolState {
+                    installed: Some(Version::new(0, 25, 0)),
+                    latest: Latest::Version(Version::new(0, 25, 0)),
+                    alias: AliasState::Broken("the file is empty".to_owned()),
+                }),
+            ),
+            ToolStatus {
+                dev: true,
+                ..status(
+                    "stylua",
+                    "^0.11",
+                    Some(ToolState {
+                        installed: None,
+                        latest: Latest::Unknown,
+                        alias: AliasState::Missing,
+                    }),
+                )
+            },
+            status("wally", "^0.3", None),
+        ];
+
+        assert_eq!(
+            render(&statuses),
+            "TOOL          REQUIREMENT  INSTALLED              LATEST      ALIAS\n\
+            rojo          ^7.3.0       7.3.0                  7.4.1       ok\n\
+            selene        =0.25.0      0.25.0                 up to date  broken\n\
+            stylua (dev)  ^0.11        no                     unknown     missing\n\
+            wally         ^0.3         not for this platform  -           -\n\
+            \n\
+            The alias of selene is broken: the file is empty\n\
+            \n\
+            Run `foreman install --dev` to install the missing tools and aliases.\n"
+        );
+    }
+
+    #[test]
+    fn healthy_tools_need_nothing() {
+        let statuses = vec![status(
+            "rojo",
+            "^7.3.0",
+            Some(ToolState {
+                installed: Some(Version::new(7, 3, 0)),
+                latest: Latest::Unchecked,
+                alias: AliasState::Ok,
+            }),
+        )];
+
+        assert_eq!(
+            render(&statuses),
+            "TOOL  REQUIREMENT  INSTALLED  LATEST  ALIAS\n\
+            rojo  ^7.3.0       7.3.0      -       ok\n\
+            \n\
+            Everything is set up.\n"
+        );
+    }
+}
@@ -1,36 +1,112 @@
 use std::{
-    collections::{BTreeSet, HashMap},
+    collections::{BTreeMap, BTreeSet, HashMap, HashSet},
     env::consts::EXE_SUFFIX,
-    io::Cursor,
-    path::PathBuf,
+    fmt,
+    io::{Cursor, Read, Seek},
+    path::{Path, PathBuf},
+    str::FromStr,
+    sync::{
+        mpsc::{self, RecvTimeoutError},
+        Arc,
+    },
+    thread,
+    time::Duration,
 };
 
-use semver::Version;
+use flate2::read::GzDecoder;
+use semver::{BuildMetadata, Comparator, Op, Version, VersionReq};
 use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
 use zip::ZipArchive;
 
 use crate::{
     artifact_choosing::platform_keywords,
+    binary_format::BinaryFormat,
+    checksums::{Checksums, DigestAlgorithm, CHECKSUMS_FILE_NAME},
     ci_string::CiString,
-    config::ToolSpec,
+    config::{canonical_cache_key, ToolSpec},
     error::{ForemanError, ForemanResult},
     fs,
     paths::ForemanPaths,
-    process,
-    tool_provider::{Release, ToolProvider},
+    process::{self, CapturedOutput, RunError, RunPhase, ToolSlot},
+    tool_provider::{Release, ReleaseAsset, ToolProvider},
+    versioning::Versioning,
 };
 
+/// The first bytes of a gzip stream, used to tell tarballs apart from zip
+/// archives.
+const GZIP_MAGIC: [u8; 2] = [0x1f, 0x8b];
+
+/// The first bytes of zip archives, including empty ones.
+const ZIP_MAGIC: [u8; 2] = *b"PK";
+
+/// The first bytes and extensions of archive formats Foreman cannot extract,
+/// so that their assets are not installed as binaries.
+const UNSUPPORTED_ARCHIVES: &[(&str, &[u8], &[&str])] = &[
+    ("xz", b"\xfd7zXZ", &[".xz", ".txz"]),
+    ("bzip2", b"BZh", &[".bz2", ".tbz", ".tbz2"]),
+    ("7z", b"7z\xbc\xaf", &[".7z"]),
+    ("rar", b"Rar!", &[".rar"]),
+];
+
+/// Names of each architecture found in asset names. `x86_64` comes before
+/// `x86`, whose token it contains.
+const ARCHITECTURES: &[&[&str]] = &[
+    &["x86_64", "amd64", "x64", "win64"],
+    &["arm64", "aarch64"],
+    &["i686", "i386", "x86", "win32"],
+];
+
+/// The index in `ARCHITECTURES` of the architecture `name` mentions, if any.
+fn architecture(name: &str) -> Option<usize> {
+    ARCHITECTURES
+        .iter()
+        .position(|names| names.iter().any(|arch| contains_tokens(name, arch)))
+}
+
 fn choose_asset(release: &Release, platform_keywords: &[&str]) -> Option<usize> {
     log::trace!(
         "Checking for name with compatible os/arch pair from platform-derived list: {:?}",
         platform_keywords
     );
-    let asset_index = platform_keywords.iter().find_map(|keyword| {
+
+    // Architectures the platform keywords name, like `arm64` on Apple
+    // Silicon.
+    let own_architectures: HashSet<usize> = platform_keywords
+        .iter()
+        .filter_map(|keyword| architecture(keyword))
+        .collect();
+    let names_other_architecture =
+        |name: &str| architecture(name).is_some_and(|arch| !own_architectures.contains(&arch));
+
+    // Every asset is scored against every keyword: the earliest matching
+    // keyword, which is the most specific one, wins. Among the assets
+    // matching the same keyword, like `tool-macos.zip` and
+    // `tool-macos-x86_64.zip` for `macos`, the ones that do not name another
+    // architecture are preferred.
+    //
+    // Keywords are matched against whole tokens of asset names first, so that
+    // a tool or repository name containing a platform word, like
+    // `darwinian-tool-linux.zip`, does not pass for that platform. Substring
+    // matching remains as a fallback for names like `tool-linux64.zip`.
+    let find_asset = |matches: fn(&str, &str) -> bool| {
         release
             .assets
             .iter()
-            .position(|asset| asset.name.contains(keyword))
-    })?;
+            .enumerate()
+            // Checksums published next to assets name the same platforms.
+            .filter(|(_, asset)| DigestAlgorithm::of_checksum_file(&asset.name).is_none())
+            .filter_map(|(index, asset)| {
+                let keyword_rank = platform_keywords
+                    .iter()
+                    .position(|keyword| matches(&asset.name, keyword))?;
+                Some((keyword_rank, names_other_architecture(&asset.name), index))
+            })
+            .min()
+            .map(|(_, _, index)| index)
+    };
+    let asset_index = find_asset(contains_tokens)
+        .or_else(|| find_asset(|name, keyword| name.contains(keyword)))?;
 
     log::debug!(
         "Found matching artifact: {}",
@@ -39,38 +115,504 @@ fn choose_asset(release: &Release, platform_keywords: &[&str]) -> Option<usize>
     Some(asset_index)
 }
 
+/// Whether the tokens of `keyword` appear consecutively among the tokens of
+/// `name`, where tokens are separated by `-`, `_` or `.`.
+fn contains_tokens(name: &str, keyword: &str) -> bool {
+    let tokenize = |text: &str| -> Vec<String> {
+        text.split(['-', '_', '.'])
+            .filter(|token| !token.is_empty())
+            .map(str::to_owned)
+            .collect()
+    };
+    let name_tokens = tokenize(name);
+    let keyword_tokens = tokenize(keyword);
+
+    !keyword_tokens.is_empty()
+        && name_tokens
+            .windows(keyword_tokens.len())
+            .any(|window| window == keyword_tokens.as_slice())
+}
+
+/// For tools pinned to an exact semantic version, fetches the release tagged
+/// with it alone, with or without a leading `v`, so that every release only
+/// needs to be listed when neither exists.
+fn pinned_release(tool: &ToolSpec, providers: &ToolProvider) -> ForemanResult<Option<Release>> {
+    let pinned = exact_version(tool.version()).filter(|_| tool.versioning() == Versioning::Semver);
+
+    if let Some(version) = pinned {
+        for tag_name in [format!("v{}", version), version.to_string()] {
+            if let Some(release) = providers.get_release_by_tag(tool, &tag_name)? {
+                log::debug!("Fetched release {} of {} by its tag", tag_name, tool);
+                return Ok(Some(release));
+            }
+        }
+    }
+
+    Ok(None)
+}
+
+/// The only version `version_req` allows, when it is an exact version like
+/// `=7.2.1`.
+fn exact_version(version_req: &VersionReq) -> Option<Version> {
+    match version_req.comparators.as_slice() {
+        [Comparator {
+            op: Op::Exact,
+            major,
+            minor: Some(minor),
+            patch: Some(patch),
+            pre,
+        }] => Some(Version {
+            major: *major,
+            minor: *minor,
+            patch: *patch,
+            pre: pre.clone(),
+            build: BuildMetadata::EMPTY,
+        }),
+        _ => None,
+    }
+}
+
+/// For providers that list the assets of releases on demand, lists the
+/// assets of the releases matching the tool's version requirement, from the
+/// newest, until one has an asset to install on this platform. Yanked
+/// versions are passed over. Returns the tag names of the releases whose
+/// assets were left unlisted.
+fn list_assets_on_demand(
+    tool: &ToolSpec,
+    providers: &ToolProvider,
+    releases: &mut [Release],
+    platform_keywords: &[&str],
+    is_yanked: impl Fn(&Version) -> bool,
+) -> ForemanResult<HashSet<String>> {
+    if !providers.lists_assets_on_demand(tool) {
+        return Ok(HashSet::new());
+    }
+
+    let mut candidates: Vec<(Version, usize)> = releases
+        .iter()
+        .enumerate()
+        .filter_map(|(index, release)| {
+            Some((tool.versioning().tag_version(&release.tag_name)?, index))
+        })
+        .filter(|(version, _index)| tool.version().matches(version) && !is_yanked(version))
+        .collect();
+    candidates.sort_by(|a, b| b.0.cmp(&a.0));
+
+    let mut unlisted: HashSet<String> = releases
+        .iter()
+        .map(|release| release.tag_name.clone())
+        .collect();
+    for (_version, index) in candidates {
+        let release = &mut releases[index];
+        release.assets = providers.get_release_assets(tool, &release.tag_name)?;
+        unlisted.remove(&release.tag_name);
+
+        let selected = match tool.asset() {
+            Some(asset) => find_named_asset(release, asset).is_some(),
+            None => {
+                choose_asset(release, platform_keywords).is_some()
+                    || (tool.source_fallback() && release.source_tarball.is_some())
+            }
+        };
+        if selected {
+            break;
+        }
+    }
+
+    Ok(unlisted)
+}
+
+/// Finds the asset named `name`, ignoring case, for tools that name the asset
+/// to download instead of relying on platform keywords.
+fn find_named_asset(release: &Release, name: &str) -> Option<usize> {
+    release
+        .assets
+        .iter()
+        .position(|asset| asset.name.eq_ignore_ascii_case(name))
+}
+
+/// When installing a tool checks its provider for a newer matching release.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub enum UpdatePolicy {
+    /// Use any installed version matching the tool's version requirement
+    /// without touching the network, even if a newer one was released.
+    #[default]
+    Cached,
+    /// Always resolve the newest matching release, downloading it unless it
+    /// is already installed.
+    Latest,
+}
+
+impl UpdatePolicy {
+    pub const VARIANTS: &'static [&'static str] = &["cached", "latest"];
+}
+
+impl FromStr for UpdatePolicy {
+    type Err = String;
+
+    fn from_str(value: &str) -> Result<Self, Self::Err> {
+        match value {
+            "cached" => Ok(Self::Cached),
+            "latest" => Ok(Self::Latest),
+            _ => Err(format!(
+                "unknown update policy `{}`, expected one of: {}",
+                value,
+                Self::VARIANTS.join(", ")
+            )),
+        }
+    }
+}
+
+/// How a tool was installed.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Installation {
+    pub version: Version,
+    /// What was downloaded, or `None` when a matching version was already
+    /// installed.
+    pub download: Option<Download>,
+}
+
+/// The release asset downloaded to install a tool.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Download {
+    /// Name of the asset, or `None` for a release's source tarball.
+    pub asset: Option<String>,
+    pub url: String,
+    /// SHA-256 digest of the asset, in hexadecimal.
+    pub sha256: String,
+}
+
+/// What a downloaded release is checked against.
+#[derive(Debug, Clone)]
+struct ReleaseChecks {
+    /// SHA-256 digest the asset must have, in hexadecimal.
+    sha256: Option<String>,
+    /// Executable format the tool's binary is expected in.
+    binary_format: Option<BinaryFormat>,
+    /// Whether a binary in another executable format is an error rather
+    /// than a warning.
+    strict_binary_format: bool,
+}
+
+/// The release asset a tool resolves to.
+#[derive(Debug, Clone)]
+struct ResolvedAsset {
+    /// Name of the asset, or `None` for a release's source tarball.
+    name: Option<String>,
+    url: String,
+    /// The asset holding the checksum of this one, like
+    /// `tool-linux.zip.sha256`, when the release publishes one.
+    checksum: Option<PublishedChecksum>,
+}
+
+#[derive(Debug, Clone)]
+struct PublishedChecksum {
+    algorithm: DigestAlgorithm,
+    asset: ReleaseAsset,
+}
+
+/// Whether installing a tool uses an installed version or downloads a
+/// release.
+enum InstallPlan {
+    Installed(Version),
+    Download(Version, ResolvedAsset),
+}
+
+impl ResolvedAsset {
+    /// The asset at `index` in the release, along with the asset holding its
+    /// checksum, if any. SHA-256 checksums are preferred over SHA-512 ones.
+    fn in_release(release: &Release, index: usize) -> Self {
+        let asset = &release.assets[index];
+        let checksum = DigestAlgorithm::ALL.iter().copied().find_map(|algorithm| {
+            let name = algorithm.checksum_file_name(&asset.name);
+            release
+                .assets
+                .iter()
+                .find(|candidate| candidate.name.eq_ignore_ascii_case(&name))
+                .map(|checksum_asset| PublishedChecksum {
+                    algorithm,
+                    asset: checksum_asset.clone(),
+                })
+        });
+
+        Self {
+            name: Some(asset.name.clone()),
+            url: asset.url.clone(),
+            checksum,
+        }
+    }
+}
+
 /// Contains the current state of all of the tools that Foreman manages.
-#[derive(Debug, PartialEq, Serialize, Deserialize)]
+#[derive(Debug, Clone, PartialEq, Serialize)]
 pub struct ToolCache {
-    pub tools: HashMap<CiString, ToolEntry>,
+    tools: HashMap<CiString, ToolEntry>,
+    /// The project configuration files Foreman loaded tools from, whose
+    /// tools `foreman prune` keeps wherever it runs.
+    #[serde(skip_serializing_if = "BTreeSet::is_empty")]
+    projects: BTreeSet<PathBuf>,
     #[serde(skip)]
     paths: ForemanPaths,
+    #[serde(skip)]
+    platform_keywords: Option<Vec<String>>,
+    #[serde(skip)]
+    yanked: HashMap<CiString, Vec<Version>>,
+    #[serde(skip)]
+    reject_prereleases: bool,
+    #[serde(skip)]
+    ignore_cache: bool,
+    #[serde(skip)]
+    update_policy: UpdatePolicy,
+    #[serde(skip)]
+    checksums: Checksums,
+    #[serde(skip)]
+    require_checksums: bool,
+    #[serde(skip)]
+    binary_format: Option<BinaryFormat>,
+    #[serde(skip)]
+    strict_binary_format: bool,
+    #[serde(skip)]
+    quiet_downloads: bool,
+}
+
+/// The part of the tool cache stored in its index file.
+#[derive(Deserialize)]
+struct ToolCacheIndex {
+    tools: HashMap<CiString, ToolEntry>,
+    #[serde(default)]
+    projects: BTreeSet<PathBuf>,
 }
 
 impl ToolCache {
     pub fn new(paths: &ForemanPaths) -> Self {
         Self {
             tools: Default::default(),
+            projects: BTreeSet::new(),
             paths: paths.clone(),
+            platform_keywords: None,
+            yanked: HashMap::new(),
+            reject_prereleases: false,
+            ignore_cache: false,
+            update_policy: UpdatePolicy::Cached,
+            checksums: Checksums::default(),
+            require_checksums: false,
+            binary_format: BinaryFormat::host(),
+            strict_binary_format: false,
+            quiet_downloads: false,
+        }
+    }
+
+    /// Makes resolving a tool fail when the version it resolves to is a
+    /// prerelease, instead of installing it.
+    pub fn set_reject_prereleases(&mut self, reject_prereleases: bool) {
+        self.reject_prereleases = reject_prereleases;
+    }
+
+    /// Makes `download_if_necessary` resolve and download tools even when a
+    /// matching version is installed. Installed versions are kept.
+    pub fn set_ignore_cache(&mut self, ignore_cache: bool) {
+        self.ignore_cache = ignore_cache;
+    }
+
+    /// Sets whether `download_if_necessary` uses any matching installed
+    /// version, or checks for a newer matching release first.
+    pub fn set_update_policy(&mut self, update_policy: UpdatePolicy) {
+        self.update_policy = update_policy;
+    }
+
+    /// Sets the checksums that downloaded release assets are verified
+    /// against.
+    pub fn set_checksums(&mut self, checksums: Checksums) {
+        self.checksums = checksums;
+    }
+
+    /// Makes downloading a release asset fail when the checksums have no
+    /// entry for it, instead of downloading it unverified.
+    pub fn set_require_checksums(&mut self, require_checksums: bool) {
+        self.require_checksums = require_checksums;
+    }
+
+    /// Makes installing a tool fail, instead of warning, when its binary is
+    /// an executable for another platform.
+    pub fn set_strict_binary_format(&mut self, strict_binary_format: bool) {
+        self.strict_binary_format = strict_binary_format;
+    }
+
+    /// Only logs that a tool gets downloaded at the debug level, for callers
+    /// that report installs themselves.
+    pub fn set_quiet_downloads(&mut self, quiet_downloads: bool) {
+        self.quiet_downloads = quiet_downloads;
+    }
+
+    fn log_download(&self, message: fmt::Arguments) {
+        if self.quiet_downloads {
+            log::debug!("{}", message);
+        } else {
+            log::info!("{}", message);
+        }
+    }
+
+    /// Replaces the platform keywords used to pick release assets, as set by
+    /// the `platform_keywords` configuration key.
+    pub fn set_platform_keywords(&mut self, platform_keywords: Option<Vec<String>>) {
+        self.platform_keywords = platform_keywords;
+    }
+
+    /// Sets the versions never to install, keyed by tool source, as set by
+    /// the `[yanked]` table. Versions yanked by the tools themselves are
+    /// always honored.
+    pub fn set_yanked(&mut self, yanked: HashMap<CiString, Vec<Version>>) {
+        self.yanked = yanked;
+    }
+
+    fn is_yanked(&self, tool: &ToolSpec, version: &Version) -> bool {
+        tool.yanked().contains(version)
+            || self
+                .yanked
+                .get(&CiString(tool.source()))
+                .is_some_and(|versions| versions.contains(version))
+    }
+
+    fn platform_keywords(&self) -> Vec<&str> {
+        match &self.platform_keywords {
+            Some(keywords) => keywords.iter().map(String::as_str).collect(),
+            None => platform_keywords().to_vec(),
+        }
+    }
+
+    /// What the release `asset` of `version` of the tool is checked against
+    /// once downloaded. A missing checksum is reported before anything is
+    /// downloaded when one is required, whether declared or published with
+    /// the release.
+    fn release_checks(
+        &self,
+        tool: &ToolSpec,
+        version: &Version,
+        asset: &ResolvedAsset,
+    ) -> ForemanResult<ReleaseChecks> {
+        let sha256 = self
+            .checksums
+            .get(tool, version, &self.platform_keywords())
+            .map(str::to_owned);
+
+        if sha256.is_none() && asset.checksum.is_none() && self.require_checksums {
+            return Err(ForemanError::ChecksumMissing {
+                tool: tool.clone(),
+                version: version.clone(),
+            });
         }
+
+        Ok(ReleaseChecks {
+            sha256,
+            binary_format: self.binary_format,
+            strict_binary_format: self.strict_binary_format,
+        })
     }
 
-    pub fn run(&self, tool: &ToolSpec, version: &Version, args: Vec<String>) -> ForemanResult<i32> {
+    pub fn run(
+        &self,
+        tool: &ToolSpec,
+        version: &Version,
+        args: Vec<String>,
+        env: &BTreeMap<String, String>,
+        current_dir: Option<&Path>,
+    ) -> ForemanResult<i32> {
         let tool_path = self.get_tool_exe_path(tool, version);
 
+        let slot = self.acquire_tool_slot()?;
+        let env = tool_env(slot.as_ref(), env);
+
         log::debug!("Running tool {} ({})", tool, tool_path.display());
 
-        let code = process::run(&tool_path, args).map_err(|err| {
+        process::run(&tool_path, args, &env, current_dir)
+            .map_err(|err| run_error(err, tool, &tool_path))
+    }
+
+    /// Runs a tool until it terminates, capturing its output instead of
+    /// streaming it to the terminal.
+    pub fn run_captured(
+        &self,
+        tool: &ToolSpec,
+        version: &Version,
+        args: Vec<String>,
+        env: &BTreeMap<String, String>,
+        current_dir: Option<&Path>,
+    ) -> ForemanResult<CapturedOutput> {
+        let tool_path = self.get_tool_exe_path(tool, version);
+        let slot = self.acquire_tool_slot()?;
+        let env = tool_env(slot.as_ref(), env);
+
+        log::debug!(
+            "Running tool {} ({}) and capturing its output",
+            tool,
+            tool_path.display()
+        );
+
+        process::run_captured(&tool_path, args, &env, current_dir)
+            .map_err(|err| run_error(err, tool, &tool_path))
+    }
+
+    /// Waits for one of the slots tools run in, when their number is limited
+    /// with `FOREMAN_MAX_CONCURRENT_TOOLS`. Detached tools outlive Foreman, so
+    /// they do not take slots.
+    fn acquire_tool_slot(&self) -> ForemanResult<Option<ToolSlot>> {
+        let directory = self.paths.tool_slots_dir();
+        ToolSlot::acquire_from_env(&directory).map_err(|err| {
             ForemanError::io_error_with_context(
                 err,
-                format!(
-                    "an error happened trying to run `{}` at `{}` (this is an error in Foreman)",
-                    tool,
-                    tool_path.display()
-                ),
+                format!("unable to reserve a tool slot in {}", directory.display()),
             )
-        })?;
-        Ok(code)
+        })
+    }
+
+    /// Starts a tool in the background, detached from Foreman and its
+    /// terminal, and returns its PID without waiting for it.
+    pub fn spawn_detached(
+        &self,
+        tool: &ToolSpec,
+        version: &Version,
+        args: Vec<String>,
+        env: &BTreeMap<String, String>,
+        current_dir: Option<&Path>,
+    ) -> ForemanResult<u32> {
+        let tool_path = self.get_tool_exe_path(tool, version);
+
+        log::debug!(
+            "Running tool {} ({}) in the background",
+            tool,
+            tool_path.display()
+        );
+
+        process::spawn_detached(&tool_path, args, env, current_dir)
+            .map_err(|err| run_error(err, tool, &tool_path))
+    }
+
+    /// Copies the installed binary of `version` of the tool to `directory`,
+    /// named after `alias` with the platform's executable extension.
+    pub fn export(
+        &self,
+        tool: &ToolSpec,
+        version: &Version,
+        directory: &Path,
+        alias: &str,
+    ) -> ForemanResult<()> {
+        let dest_path = directory.join(format!("{}{}", alias, EXE_SUFFIX));
+        log::debug!("Copying {} to {}", tool, dest_path.display());
+        fs::copy(self.get_tool_exe_path(tool, version), dest_path)?;
+        Ok(())
+    }
+
+    /// The command line `run` would spawn for the tool, for display.
+    pub fn command_line(
+        &self,
+        tool: &ToolSpec,
+        version: &Version,
+        args: &[String],
+        env: &BTreeMap<String, String>,
+        current_dir: Option<&Path>,
+    ) -> String {
+        let tool_path = self.get_tool_exe_path(tool, version);
+        process::command_line(&tool_path, args, env, current_dir)
     }
 
     pub fn download_if_necessary(
@@ -78,285 +620,3271 @@ impl ToolCache {
         tool: &ToolSpec,
         providers: &ToolProvider,
     ) -> ForemanResult<Version> {
-        if let Some(tool_entry) = self.tools.get(&tool.cache_key()) {
-            log::debug!("Tool has some versions installed");
-
-            let matching_version = tool_entry
-                .versions
-                .iter()
-                .rev()
-                .find(|version| tool.version().matches(version));
+        self.install(tool, providers)
+            .map(|installation| installation.version)
+    }
 
-            if let Some(version) = matching_version {
-                return Ok(version.clone());
+    /// Like `download_if_necessary`, but also describes what was downloaded.
+    pub fn install(
+        &mut self,
+        tool: &ToolSpec,
+        providers: &ToolProvider,
+    ) -> ForemanResult<Installation> {
+        match self.plan_install(tool, providers)? {
+            InstallPlan::Installed(version) => Ok(Installation {
+                version,
+                download: None,
+            }),
+            InstallPlan::Download(version, asset) => {
+                self.download_release(tool, providers, version, asset)
             }
         }
-
-        self.download(tool, providers)
     }
 
-    pub fn download(
+    fn download_release(
         &mut self,
         tool: &ToolSpec,
         providers: &ToolProvider,
-    ) -> ForemanResult<Version> {
-        log::info!("Downloading {}", tool);
+        version: Version,
+        asset: ResolvedAsset,
+    ) -> ForemanResult<Installation> {
+        let sha256 = self.install_release(tool, providers, &version, &asset)?;
 
-        let provider = providers.get(&tool.provider());
-        let releases = provider.get_releases(tool.path(), tool.host())?;
+        Ok(Installation {
+            version,
+            download: Some(Download {
+                asset: asset.name,
+                url: asset.url,
+                sha256,
+            }),
+        })
+    }
 
-        // Filter down our set of releases to those that are valid versions and
-        // have release assets for our current platform.
-        let mut semver_releases: Vec<_> = releases
-            .into_iter()
-            .filter_map(|release| {
-                log::trace!("Evaluating tag {}", release.tag_name);
+    /// Decides between an installed version of the tool and a release to
+    /// download, following the update policy. Only the `latest` policy, or
+    /// the lack of a matching installed version, touches the network.
+    fn plan_install(
+        &self,
+        tool: &ToolSpec,
+        providers: &ToolProvider,
+    ) -> ForemanResult<InstallPlan> {
+        match self.update_policy {
+            UpdatePolicy::Cached => {
+                if let Some(version) = self.cached_version(tool) {
+                    return Ok(InstallPlan::Installed(version));
+                }
+                self.log_download(format_args!("Downloading {}", tool));
+            }
+            UpdatePolicy::Latest => {
+                self.log_download(format_args!("Checking for the newest release of {}", tool))
+            }
+        }
 
-                let version = Version::parse(&release.tag_name).ok().or_else(|| {
-                    if !release.tag_name.starts_with('v') {
-                        log::debug!(
-                            "Release tag name did not start with 'v'! {}",
-                            release.tag_name
-                        );
-                        return None;
-                    }
+        let (version, asset) = self.resolve_release(tool, providers)?;
+        if self.update_policy == UpdatePolicy::Latest {
+            if !self.ignore_cache
+                && self.contains(tool, &version)
+                && self.has_binary(tool, &version)
+            {
+                log::debug!("Newest matching release {} is already installed", version);
+                return Ok(InstallPlan::Installed(version));
+            }
+            self.log_download(format_args!("Downloading {} {}", tool, version));
+        }
 
-                    Version::parse(&release.tag_name[1..]).ok()
-                })?;
+        Ok(InstallPlan::Download(version, asset))
+    }
 
-                let asset_index = choose_asset(&release, platform_keywords())?;
+    /// The version of the tool `download_if_necessary` would run, following
+    /// the update policy, without downloading anything.
+    pub fn version_to_run(
+        &self,
+        tool: &ToolSpec,
+        providers: &ToolProvider,
+    ) -> ForemanResult<Version> {
+        if self.update_policy == UpdatePolicy::Cached {
+            if let Some(version) = self.cached_version(tool) {
+                return Ok(version);
+            }
+        }
 
-                Some((version, asset_index, release))
-            })
-            .collect();
+        self.resolve(tool, providers)
+    }
 
-        // Releases should come back chronological, but we want strictly
-        // descending version numbers.
-        semver_releases.sort_by(|a, b| b.0.cmp(&a.0));
+    /// The path of the binary of `version` of the tool, or `None` when that
+    /// version is not installed.
+    pub fn installed_path(&self, tool: &ToolSpec, version: &Version) -> Option<PathBuf> {
+        (self.contains(tool, version) && self.has_binary(tool, version))
+            .then(|| self.get_tool_exe_path(tool, version))
+    }
 
-        let version_req = tool.version();
-        let matching_release = semver_releases
-            .iter()
-            .find(|(version, _asset_index, _release)| version_req.matches(version));
+    /// The installed version of the tool to use instead of downloading it,
+    /// unless installed versions are ignored.
+    fn cached_version(&self, tool: &ToolSpec) -> Option<Version> {
+        if self.ignore_cache {
+            log::debug!("Ignoring installed versions of the tool");
+            return None;
+        }
 
-        if let Some((version, asset_index, release)) = matching_release {
-            log::trace!("Picked version {}", version);
+        let version = self.installed_version(tool)?;
+        if !self.has_binary(tool, version) {
+            log::warn!(
+                "The binary of {} {} is missing or empty, downloading it again",
+                tool,
+                version
+            );
+            return None;
+        }
 
-            let url = &release.assets[*asset_index].url;
-            let buffer = provider.download_asset(url)?;
+        log::debug!("Tool has a matching version installed");
+        Some(version.clone())
+    }
 
-            log::trace!("Extracting downloaded artifact");
-            let mut archive = ZipArchive::new(Cursor::new(&buffer)).map_err(|err| {
-                ForemanError::invalid_release_asset(
-                    tool,
-                    version,
-                    format!("unable to open zip archive ({})", err),
-                )
-            })?;
-            let mut file = archive.by_index(0).map_err(|err| {
-                ForemanError::invalid_release_asset(
+    /// Whether the binary of `version` of the tool is in the tools directory
+    /// and not empty. The index can list versions whose binary was removed
+    /// from under Foreman, for example by an antivirus.
+    fn has_binary(&self, tool: &ToolSpec, version: &Version) -> bool {
+        std::fs::metadata(self.get_tool_exe_path(tool, version))
+            .map(|metadata| metadata.is_file() && metadata.len() > 0)
+            .unwrap_or(false)
+    }
+
+    /// Installs the newest release matching the tool's version requirement,
+    /// unless it is not newer than the newest installed version of the tool.
+    /// Returns the version that was installed, if any.
+    ///
+    /// This never downgrades a tool, even when its version requirement allows
+    /// it, for example after the newest release was deleted.
+    pub fn update(
+        &mut self,
+        tool: &ToolSpec,
+        providers: &ToolProvider,
+    ) -> ForemanResult<Option<Version>> {
+        let (version, asset) = self.resolve_release(tool, providers)?;
+
+        if let Some(installed) = self.installed_version(tool) {
+            if version <= *installed {
+                log::debug!(
+                    "Skipping {}: resolved version {} is not newer than installed version {}",
                     tool,
                     version,
-                    format!("unable to obtain file from zip archive ({})", err),
-                )
-            })?;
+                    installed
+                );
+                return Ok(None);
+            }
+        }
 
-            let tool_path = self.get_tool_exe_path(tool, version);
+        log::debug!("Updating {} to {}", tool, version);
+        self.install_release(tool, providers, &version, &asset)?;
 
-            fs::copy_from_reader(&mut file, &tool_path)?;
+        Ok(Some(version))
+    }
 
-            // On Unix systems, mark the tool as executable.
-            #[cfg(unix)]
-            {
-                use std::os::unix::fs::PermissionsExt;
+    /// Removes `version` of the tool, or every installed version when `None`,
+    /// deleting their binaries from the tools directory. Returns the removed
+    /// versions, from oldest to newest.
+    pub fn uninstall(
+        &mut self,
+        tool: &ToolSpec,
+        version: Option<&Version>,
+    ) -> ForemanResult<Vec<Version>> {
+        let cache_key = tool.cache_key();
+        let installed = self
+            .tools
+            .get(&cache_key)
+            .map(|entry| entry.versions.clone())
+            .unwrap_or_default();
 
-                fs::set_permissions(&tool_path, fs::Permissions::from_mode(0o777))?;
+        let removed: Vec<Version> = match version {
+            Some(version) if installed.contains(version) => vec![version.clone()],
+            Some(version) => {
+                return Err(ForemanError::VersionNotInstalled {
+                    tool: tool.clone(),
+                    version: version.clone(),
+                })
             }
+            None => installed.into_iter().collect(),
+        };
 
-            log::trace!("Updating tool cache");
-            let tool_entry = self.tools.entry(tool.cache_key()).or_default();
-            tool_entry.versions.insert(version.clone());
-            self.save()?;
+        for version in &removed {
+            let tool_path = self.get_tool_exe_path(tool, version);
+            log::debug!("Removing {}", tool_path.display());
+            fs::try_remove_file(&tool_path)?;
+        }
 
-            Ok(version.clone())
-        } else {
-            Err(ForemanError::no_compatible_version_found(
-                tool,
-                semver_releases
-                    .into_iter()
-                    .map(|(version, _asset_index, _release)| version)
-                    .collect(),
-            ))
+        if let Some(entry) = self.tools.get_mut(&cache_key) {
+            for version in &removed {
+                entry.versions.remove(version);
+            }
+            if entry.versions.is_empty() {
+                self.tools.remove(&cache_key);
+            }
         }
+
+        self.save()?;
+        Ok(removed)
     }
 
-    pub fn load(paths: &ForemanPaths) -> ForemanResult<Self> {
-        let path = paths.index_file();
-        log::debug!("load tool cache from {}", path.display());
+    /// Every tool with installed versions, keyed by the tool's cache key, in
+    /// no particular order.
+    pub fn installed_tools(&self) -> impl Iterator<Item = (&CiString, &ToolEntry)> {
+        self.tools.iter()
+    }
 
-        let mut tool_cache = fs::try_read(&path)?
-            .map(|contents| {
-                serde_json::from_slice(&contents)
-                    .map_err(|err| ForemanError::tool_cache_parsing(&path, err.to_string()))
-            })
-            .unwrap_or_else(|| Ok(Self::new(paths)))?;
+    /// The installed versions of the tool, from oldest to newest.
+    pub fn versions_for(&self, tool: &ToolSpec) -> &BTreeSet<Version> {
+        static NO_VERSIONS: BTreeSet<Version> = BTreeSet::new();
 
-        tool_cache.paths = paths.clone();
-        Ok(tool_cache)
+        self.tools
+            .get(&tool.cache_key())
+            .map(|entry| &entry.versions)
+            .unwrap_or(&NO_VERSIONS)
     }
 
-    fn save(&self) -> ForemanResult<()> {
-        let serialized =
-            serde_json::to_string_pretty(self).expect("unable to serialize tool cache");
-        fs::write(self.paths.index_file(), serialized)
+    /// Whether `version` of the tool is recorded as installed.
+    pub fn contains(&self, tool: &ToolSpec, version: &Version) -> bool {
+        self.versions_for(tool).contains(version)
     }
 
-    fn get_tool_exe_path(&self, tool: &ToolSpec, version: &Version) -> PathBuf {
-        let mut tool_path = self.paths.tools_dir();
-        let exe_name = tool_identifier_to_exe_name(tool, version);
-        tool_path.push(exe_name);
-        tool_path
-    }
-}
+    /// The installed versions that none of the `declared` tools would run,
+    /// sorted by tool and version.
+    pub fn stale_versions<'a, I>(&self, declared: I) -> Vec<StaleVersion<'_>>
+    where
+        I: IntoIterator<Item = &'a ToolSpec>,
+    {
+        let used: HashSet<(CiString, &Version)> = declared
+            .into_iter()
+            .filter_map(|tool| Some((tool.cache_key(), self.installed_version(tool)?)))
+            .collect();
 
-#[derive(Debug, Default, PartialEq, Serialize, Deserialize)]
-pub struct ToolEntry {
-    pub versions: BTreeSet<Version>,
-}
+        let mut stale: Vec<StaleVersion> = self
+            .tools
+            .iter()
+            .flat_map(|(tool, entry)| entry.versions.iter().map(move |version| (tool, version)))
+            .filter(|(tool, version)| !used.contains(&((*tool).clone(), *version)))
+            .map(|(tool, version)| StaleVersion {
+                tool,
+                version,
+                size: self.binary_size(tool, version),
+            })
+            .collect();
 
-fn tool_identifier_to_exe_name(tool: &ToolSpec, version: &Version) -> String {
-    let mut name = format!("{}-{}{}", tool.cache_key().0, version, EXE_SUFFIX);
-    name = name.replace('/', "__");
-    name.replace('\\', "__")
-}
+        stale.sort_by(|a, b| (&a.tool.0, a.version).cmp(&(&b.tool.0, b.version)));
+        stale
+    }
 
-#[cfg(test)]
-mod test {
-    use tempfile::tempdir;
+    /// Every installed version along with the size of its binary, in bytes,
+    /// sorted by tool and version.
+    pub fn version_sizes(&self) -> Vec<(&CiString, &Version, u64)> {
+        let mut sizes: Vec<(&CiString, &Version, u64)> = self
+            .tools
+            .iter()
+            .flat_map(|(tool, entry)| {
+                entry
+                    .versions
+                    .iter()
+                    .map(move |version| (tool, version, self.binary_size(tool, version)))
+            })
+            .collect();
 
-    use crate::tool_provider::ReleaseAsset;
+        sizes.sort_by(|a, b| (&a.0 .0, a.1).cmp(&(&b.0 .0, b.1)));
+        sizes
+    }
 
-    use super::*;
+    /// The size of the binary of `version` of the tool with `cache_key`, in
+    /// bytes, or 0 when it is missing.
+    fn binary_size(&self, cache_key: &CiString, version: &Version) -> u64 {
+        std::fs::metadata(self.paths.tools_dir().join(exe_name(cache_key, version)))
+            .map(|metadata| metadata.len())
+            .unwrap_or(0)
+    }
 
-    // Regression test for LUAFDN-1041, based on the release that surfaced it
-    #[test]
-    fn select_correct_asset() {
-        let release = Release {
-            prerelease: false,
-            tag_name: "v0.5.2".to_string(),
-            assets: vec![
-                ReleaseAsset {
-                    name: "tool-linux.zip".to_string(),
-                    url: "https://example.com/some/repo/releases/assets/1".to_string(),
-                },
-                ReleaseAsset {
-                    name: "tool-macos-arm64.zip".to_string(),
-                    url: "https://example.com/some/repo/releases/assets/2".to_string(),
-                },
-                ReleaseAsset {
-                    name: "tool-macos-x86_64.zip".to_string(),
-                    url: "https://example.com/some/repo/releases/assets/3".to_string(),
-                },
-                ReleaseAsset {
-                    name: "tool-win64.zip".to_string(),
-                    url: "https://example.com/some/repo/releases/assets/4".to_string(),
-                },
-            ],
+    /// Finds the installed versions that none of the `declared` tools would
+    /// run, except for the `keep` newest versions of each tool, along with
+    /// the versions whose binary went missing and the files of the tools
+    /// directory that no installed version refers to.
+    pub fn plan_prune<'a, I>(&self, declared: I, keep: usize) -> ForemanResult<PrunePlan>
+    where
+        I: IntoIterator<Item = &'a ToolSpec>,
+    {
+        let tools_dir = self.paths.tools_dir();
+        let is_present = |cache_key: &CiString, version: &Version| {
+            tools_dir.join(exe_name(cache_key, version)).is_file()
         };
-        assert_eq!(
-            choose_asset(&release, &["win32", "win64", "windows"]),
-            Some(3)
-        );
-        assert_eq!(
-            choose_asset(
-                &release,
-                &["macos-x86_64", "darwin-x86_64", "macos", "darwin"]
-            ),
-            Some(2)
-        );
-        assert_eq!(
-            choose_asset(
-                &release,
-                &[
-                    "macos-arm64",
-                    "darwin-arm64",
-                    "macos-x86_64",
-                    "darwin-x86_64",
-                    "macos",
-                    "darwin",
-                ]
-            ),
-            Some(1)
-        );
-        assert_eq!(choose_asset(&release, &["linux"]), Some(0));
+
+        // Versions whose binary is missing get removed, so a tool runs the
+        // next best version once they are.
+        let used: HashSet<(CiString, &Version)> = declared
+            .into_iter()
+            .filter_map(|tool| {
+                let cache_key = tool.cache_key();
+                let version = self.versions_for(tool).iter().rev().find(|version| {
+                    tool.version().matches(version)
+                        && !self.is_yanked(tool, version)
+                        && is_present(&cache_key, version)
+                })?;
+                Some((cache_key, version))
+            })
+            .collect();
+        let mut plan = PrunePlan::default();
+
+        for (cache_key, entry) in &self.tools {
+            for (index, version) in entry.versions.iter().rev().enumerate() {
+                let size = std::fs::metadata(tools_dir.join(exe_name(cache_key, version)))
+                    .ok()
+                    .map(|metadata| metadata.len());
+                let kept = index < keep || used.contains(&(cache_key.clone(), version));
+
+                if size.is_none() || !kept {
+                    plan.versions.push((cache_key.clone(), version.clone()));
+                    plan.size += size.unwrap_or(0);
+                }
+            }
+        }
+
+        let referenced: HashSet<String> = self
+            .tools
+            .iter()
+            .flat_map(|(cache_key, entry)| {
+                entry
+                    .versions
+                    .iter()
+                    .map(move |version| exe_name(cache_key, version))
+            })
+            .collect();
+        let files = match std::fs::read_dir(&tools_dir) {
+            Ok(files) => files,
+            Err(err) if err.kind() == std::io::ErrorKind::NotFound => return Ok(plan),
+            Err(err) => return Err(ForemanError::read_error(err, tools_dir)),
+        };
+        for file in files {
+            let file = file.map_err(|err| ForemanError::read_error(err, &tools_dir))?;
+            let metadata = match file.metadata() {
+                Ok(metadata) if metadata.is_file() => metadata,
+                _ => continue,
+            };
+            // Temporary files may belong to an install that is still running,
+            // `foreman cache clean-temp` removes the stale ones.
+            if fs::is_temp_path(file.path()) {
+                continue;
+            }
+            if !referenced.contains(file.file_name().to_string_lossy().as_ref()) {
+                plan.orphans.push(file.path());
+                plan.size += metadata.len();
+            }
+        }
+
+        plan.versions
+            .sort_by(|a, b| (&a.0 .0, &a.1).cmp(&(&b.0 .0, &b.1)));
+        plan.orphans.sort();
+        Ok(plan)
     }
 
-    #[test]
-    fn select_correct_asset_macos() {
-        let release = Release {
-            prerelease: false,
-            tag_name: "v0.5.2".to_string(),
-            assets: vec![
-                ReleaseAsset {
-                    name: "tool-linux.zip".to_string(),
-                    url: "https://example.com/some/repo/releases/assets/1".to_string(),
-                },
-                ReleaseAsset {
-                    name: "tool-macos-x86_64.zip".to_string(),
-                    url: "https://example.com/some/repo/releases/assets/2".to_string(),
-                },
-                ReleaseAsset {
-                    name: "tool-macos-aarch64.zip".to_string(),
-                    url: "https://example.com/some/repo/releases/assets/3".to_string(),
-                },
-            ],
+    /// The temporary files that interrupted installs left in the tools
+    /// directory, along with their size, sorted by path. Only files last
+    /// modified more than `older_than` ago are stale: newer ones may belong
+    /// to an install that is still running.
+    pub fn stale_temp_files(&self, older_than: Duration) -> ForemanResult<Vec<(PathBuf, u64)>> {
+        let tools_dir = self.paths.tools_dir();
+        let files = match std::fs::read_dir(&tools_dir) {
+            Ok(files) => files,
+            Err(err) if err.kind() == std::io::ErrorKind::NotFound => return Ok(Vec::new()),
+            Err(err) => return Err(ForemanError::read_error(err, tools_dir)),
         };
-        assert_eq!(
-            choose_asset(
-                &release,
-                &[
-                    "macos-arm64",
-                    "darwin-arm64",
-                    "macos-aarch64",
-                    "darwin-aarch64",
-                    "macos",
-                    "darwin",
-                ]
-            ),
-            Some(2)
-        );
+
+        let mut stale = Vec::new();
+        for file in files {
+            let file = file.map_err(|err| ForemanError::read_error(err, &tools_dir))?;
+            let metadata = match file.metadata() {
+                Ok(metadata) if metadata.is_file() => metadata,
+                _ => continue,
+            };
+            // Modification times in the future count as fresh.
+            let age = metadata
+                .modified()
+                .ok()
+                .and_then(|modified| modified.elapsed().ok());
+
+            if fs::is_temp_path(file.path()) && age.is_some_and(|age| age >= older_than) {
+                stale.push((file.path(), metadata.len()));
+            }
+        }
+
+        stale.sort();
+        Ok(stale)
     }
 
-    #[test]
-    fn select_correct_asset_linux() {
-        let release = Release {
-            prerelease: false,
-            tag_name: "v0.5.2".to_string(),
-            assets: vec![
-                ReleaseAsset {
-                    name: "tool-linux-aarch64.zip".to_string(),
-                    url: "https://example.com/some/repo/releases/assets/1".to_string(),
-                },
-                ReleaseAsset {
-                    name: "tool-linux-x86_64.zip".to_string(),
-                    url: "https://example.com/some/repo/releases/assets/2".to_string(),
-                },
-                ReleaseAsset {
-                    name: "tool-macos-x86_64.zip".to_string(),
-                    url: "https://example.com/some/repo/releases/assets/3".to_string(),
-                },
-                ReleaseAsset {
-                    name: "tool-win64.zip".to_string(),
-                    url: "https://example.com/some/repo/releases/assets/4".to_string(),
+    /// Remembers `config_paths` as project configuration files Foreman
+    /// loaded tools from, saving the index when any is new.
+    pub fn add_projects<I>(&mut self, config_paths: I) -> ForemanResult<()>
+    where
+        I: IntoIterator<Item = PathBuf>,
+    {
+        let mut added = false;
+        for config_path in config_paths {
+            added |= self.projects.insert(config_path);
+        }
+
+        if added {
+            self.save()?;
+        }
+        Ok(())
+    }
+
+    /// The project configuration files Foreman loaded tools from.
+    pub fn projects(&self) -> &BTreeSet<PathBuf> {
+        &self.projects
+    }
+
+    /// Forgets a project configuration file that no longer exists. The index
+    /// is saved along with the next change.
+    pub fn forget_project(&mut self, config_path: &Path) {
+        self.projects.remove(config_path);
+    }
+
+    /// Removes the versions and files found by `plan_prune`.
+    pub fn prune(&mut self, plan: &PrunePlan) -> ForemanResult<()> {
+        let tools_dir = self.paths.tools_dir();
+
+        for (cache_key, version) in &plan.versions {
+            fs::try_remove_file(tools_dir.join(exe_name(cache_key, version)))?;
+
+            if let Some(entry) = self.tools.get_mut(cache_key) {
+                entry.versions.remove(version);
+                if entry.versions.is_empty() {
+                    self.tools.remove(cache_key);
+                }
+            }
+        }
+
+        for orphan in &plan.orphans {
+            fs::try_remove_file(orphan)?;
+        }
+
+        self.save()
+    }
+
+    /// Tools installed under several cache keys whose paths are the same,
+    /// like a tool that moved from GitHub to GitLab, sorted by path.
+    pub fn duplicate_tools(&self) -> Vec<DuplicateTool<'_>> {
+        let mut by_path: HashMap<CiString, Vec<(&CiString, &ToolEntry)>> = HashMap::new();
+        for (cache_key, entry) in &self.tools {
+            if let Some(path) = cache_key_path(cache_key) {
+                by_path
+                    .entry(CiString::from(path))
+                    .or_default()
+                    .push((cache_key, entry));
+            }
+        }
+
+        let mut duplicates: Vec<DuplicateTool> = by_path
+            .into_iter()
+            .filter(|(_path, entries)| entries.len() > 1)
+            .map(|(path, mut entries)| {
+                entries.sort_by(|a, b| a.0 .0.cmp(&b.0 .0));
+                DuplicateTool { path, entries }
+            })
+            .collect();
+
+        duplicates.sort_by(|a, b| a.path.0.cmp(&b.path.0));
+        duplicates
+    }
+
+    /// Merges tools installed under cache keys that spell the same tool
+    /// differently, like host URLs written before they were canonicalized,
+    /// into the key the tool has today. Binaries are renamed after that key,
+    /// or removed when it already has their version. Tools that moved to
+    /// another host are different tools and are left alone.
+    ///
+    /// Returns each merged key along with the key it was merged into, sorted.
+    pub fn dedupe(&mut self) -> ForemanResult<Vec<(CiString, CiString)>> {
+        let tools_dir = self.paths.tools_dir();
+
+        let mut merged: Vec<(CiString, CiString)> = self
+            .tools
+            .keys()
+            .filter_map(|cache_key| {
+                let canonical = canonical_cache_key(cache_key);
+                // Keys only differing in case are the same key.
+                (canonical != *cache_key).then(|| (cache_key.clone(), canonical))
+            })
+            .collect();
+        merged.sort_by(|a, b| a.0 .0.cmp(&b.0 .0));
+
+        for (cache_key, canonical) in &merged {
+            let entry = self.tools.remove(cache_key).unwrap_or_default();
+            let kept = self.tools.entry(canonical.clone()).or_default();
+
+            for version in entry.versions {
+                let binary = tools_dir.join(exe_name(cache_key, &version));
+
+                if kept.versions.contains(&version) {
+                    fs::try_remove_file(&binary)?;
+                } else if binary.exists() {
+                    fs::rename(&binary, tools_dir.join(exe_name(canonical, &version)))?;
+                    kept.versions.insert(version);
+                }
+            }
+            // Every binary of the merged key may have been missing.
+            if kept.versions.is_empty() {
+                self.tools.remove(canonical);
+            }
+        }
+
+        self.save()?;
+        Ok(merged)
+    }
+
+    /// Every installed version of the tool, from newest to oldest.
+    pub fn installed_versions(&self, tool: &ToolSpec) -> Vec<&Version> {
+        self.versions_for(tool).iter().rev().collect()
+    }
+
+    /// Finds the version of the tool that would be installed, without
+    /// downloading it.
+    pub fn resolve(&self, tool: &ToolSpec, providers: &ToolProvider) -> ForemanResult<Version> {
+        let (version, _asset) = self.resolve_release(tool, providers)?;
+        Ok(version)
+    }
+
+    /// The newest installed version of the tool that satisfies its version
+    /// requirement and is not yanked.
+    pub fn installed_version(&self, tool: &ToolSpec) -> Option<&Version> {
+        self.versions_for(tool)
+            .iter()
+            .rev()
+            .find(|version| tool.version().matches(version) && !self.is_yanked(tool, version))
+    }
+
+    /// Finds the newest release matching the tool's version requirement that
+    /// has an asset for the current platform, and returns its version along
+    /// with the URL of that asset. Tools declared with a `url` resolve to
+    /// that URL without listing any release.
+    fn resolve_release(
+        &self,
+        tool: &ToolSpec,
+        providers: &ToolProvider,
+    ) -> ForemanResult<(Version, ResolvedAsset)> {
+        if let Some(direct) = tool.direct() {
+            log::debug!("Using the asset at {}", direct.url);
+            let name = direct
+                .url
+                .path_segments()
+                .and_then(|mut segments| segments.next_back())
+                .filter(|name| !name.is_empty())
+                .map(str::to_owned);
+            return Ok((
+                direct.version.clone(),
+                ResolvedAsset {
+                    name,
+                    url: direct.url.to_string(),
+                    checksum: None,
                 },
-            ],
+            ));
+        }
+
+        if let Some(release) = pinned_release(tool, providers)? {
+            return self.pick_release(tool, providers, vec![release]);
+        }
+
+        // Releases cached before a matching one was published, or before its
+        // assets were uploaded, are listed again once.
+        let mut cached_error = None;
+        if let Some(releases) = providers.cached_releases(tool) {
+            match self.pick_release(tool, providers, releases) {
+                Err(
+                    error @ (ForemanError::NoCompatibleVersionFound { .. }
+                    | ForemanError::ReleasesWithoutAssets { .. }
+                    | ForemanError::ReleaseAssetNotFound { .. }),
+                ) => {
+                    log::debug!(
+                        "None of the cached releases of {} can be installed, listing them again",
+                        tool.source()
+                    );
+                    cached_error = Some(error);
+                }
+                result => return result,
+            }
+        }
+
+        // A tool installed before has releases, even when its host lists
+        // none for a moment.
+        let expect_releases = !self.versions_for(tool).is_empty();
+        let releases = match (providers.list_releases(tool, expect_releases), cached_error) {
+            (Ok(releases), _) => releases,
+            (
+                Err(error),
+                Some(ForemanError::NoCompatibleVersionFound {
+                    tool: cached_tool,
+                    available_versions,
+                    ..
+                }),
+            ) => {
+                log::warn!(
+                    "Unable to list the releases of {} again: {}",
+                    tool.source(),
+                    error
+                );
+                return Err(ForemanError::NoCompatibleVersionFound {
+                    tool: cached_tool,
+                    available_versions,
+                    from_cached_releases: true,
+                });
+            }
+            (Err(error), _) => return Err(error),
         };
-        assert_eq!(choose_asset(&release, &["linux"]), Some(0));
-        assert_eq!(choose_asset(&release, &["linux-x86_64", "linux"]), Some(1));
-        assert_eq!(
-            choose_asset(&release, &["linux-arm64", "linux-aarch64", "linux"]),
-            Some(0)
-        );
+        self.pick_release(tool, providers, releases)
+    }
+
+    /// Picks the newest of `releases` matching the tool's version requirement
+    /// that has an asset for the current platform.
+    fn pick_release(
+        &self,
+        tool: &ToolSpec,
+        providers: &ToolProvider,
+        mut releases: Vec<Release>,
+    ) -> ForemanResult<(Version, ResolvedAsset)> {
+        let platform_keywords = self.platform_keywords();
+        let unlisted = list_assets_on_demand(
+            tool,
+            providers,
+            &mut releases,
+            &platform_keywords,
+            |version| self.is_yanked(tool, version),
+        )?;
+        let version_req = tool.version();
+
+        // Versions that would have matched if their release had any assets,
+        // to explain why no version was found.
+        let mut versions_without_assets = Vec::new();
+
+        // Filter down our set of releases to those that are valid versions and
+        // have release assets for our current platform.
+        let mut semver_releases: Vec<_> = releases
+            .into_iter()
+            .filter_map(|release| {
+                log::trace!("Evaluating tag {}", release.tag_name);
+
+                let version = tool.versioning().tag_version(&release.tag_name)?;
+
+                if self.is_yanked(tool, &version) {
+                    if version_req.matches(&version) {
+                        log::info!("Skipping {} {}: it is yanked", tool.source(), version);
+                    }
+                    return None;
+                }
+
+                let source_tarball = release
+                    .source_tarball
+                    .as_ref()
+                    .filter(|_| tool.source_fallback());
+
+                // Releases whose assets were never listed cannot be picked,
+                // but still count as available versions.
+                let listed = !unlisted.contains(&release.tag_name);
+
+                if listed
+                    && release.assets.is_empty()
+                    && tool.asset().is_none()
+                    && source_tarball.is_none()
+                {
+                    log::debug!("Release {} has no assets", release.tag_name);
+                    if version_req.matches(&version) {
+                        versions_without_assets.push(version);
+                    }
+                    return None;
+                }
+
+                // A named asset is looked up once a release is picked, so
+                // that a missing asset is reported instead of skipping the
+                // release.
+                let asset = match tool.asset() {
+                    Some(asset) => find_named_asset(&release, asset)
+                        .map(|index| ResolvedAsset::in_release(&release, index)),
+                    None => match choose_asset(&release, &platform_keywords) {
+                        Some(index) => Some(ResolvedAsset::in_release(&release, index)),
+                        None => {
+                            let source_tarball = source_tarball?;
+                            log::debug!(
+                                "No asset of release {} matches, using its source tarball",
+                                release.tag_name
+                            );
+                            Some(ResolvedAsset {
+                                name: None,
+                                url: source_tarball.clone(),
+                                checksum: None,
+                            })
+                        }
+                    },
+                };
+
+                Some((version, asset, release))
+            })
+            .collect();
+
+        // Releases should come back chronological, but we want strictly
+        // descending version numbers.
+        semver_releases.sort_by(|a, b| b.0.cmp(&a.0));
+
+        let matching_release = semver_releases
+            .iter()
+            .position(|(version, _asset, _release)| version_req.matches(version));
+
+        if let Some(index) = matching_release {
+            let (version, asset, release) = semver_releases.swap_remove(index);
+            log::trace!("Picked version {}", version);
+
+            if self.reject_prereleases && (release.prerelease || !version.pre.is_empty()) {
+                return Err(ForemanError::PrereleaseNotAllowed {
+                    tool: tool.clone(),
+                    version,
+                });
+            }
+
+            let asset = asset.ok_or_else(|| ForemanError::ReleaseAssetNotFound {
+                tool: tool.clone(),
+                version: version.clone(),
+                asset: tool.asset().unwrap_or_default().to_owned(),
+                available_assets: release
+                    .assets
+                    .iter()
+                    .map(|asset| asset.name.clone())
+                    .collect(),
+            })?;
+
+            Ok((version, asset))
+        } else if !versions_without_assets.is_empty() {
+            versions_without_assets.sort_by(|a, b| b.cmp(a));
+            Err(ForemanError::ReleasesWithoutAssets {
+                tool: tool.clone(),
+                versions: versions_without_assets,
+            })
+        } else {
+            Err(ForemanError::no_compatible_version_found(
+                tool,
+                semver_releases
+                    .into_iter()
+                    .map(|(version, _asset, _release)| version)
+                    .collect(),
+            ))
+        }
+    }
+
+    fn install_release(
+        &mut self,
+        tool: &ToolSpec,
+        providers: &ToolProvider,
+        version: &Version,
+        asset: &ResolvedAsset,
+    ) -> ForemanResult<String> {
+        let checks = self.release_checks(tool, version, asset)?;
+        let sha256 = write_release(&self.paths, tool, providers, version, asset, &checks)?;
+        self.verify_release(tool, version)?;
+        self.record_release(tool, version)?;
+        Ok(sha256)
+    }
+
+    /// Runs the freshly written binary of `version` with the tool's `verify`
+    /// arguments, if any. When it does not exit successfully, the binary is
+    /// removed so that the version is downloaded again next time.
+    fn verify_release(&self, tool: &ToolSpec, version: &Version) -> ForemanResult<()> {
+        let args = match tool.verify() {
+            Some(args) => args.to_vec(),
+            None => return Ok(()),
+        };
+        let tool_path = self.get_tool_exe_path(tool, version);
+        let command = process::command_line(&tool_path, &args, tool.env(), None);
+
+        log::debug!("Verifying {} {} with `{}`", tool, version, command);
+        let error = match process::run_captured(&tool_path, args, tool.env(), None) {
+            Ok(output) if output.code == 0 => return Ok(()),
+            Ok(output) => {
+                let printed = if output.stderr.is_empty() {
+                    output.stdout
+                } else {
+                    output.stderr
+                };
+                ForemanError::VerificationFailed {
+                    tool: tool.clone(),
+                    version: version.clone(),
+                    command,
+                    code: output.code,
+                    output: String::from_utf8_lossy(&printed).trim().to_owned(),
+                }
+            }
+            Err(err) => run_error(err, tool, &tool_path),
+        };
+
+        fs::try_remove_file(&tool_path)?;
+        Err(error)
+    }
+
+    /// Like `download_if_necessary`, but gives up on the tool when resolving,
+    /// downloading, extracting and verifying its release takes longer than
+    /// `timeout`.
+    ///
+    /// The abandoned work keeps running in the background until the process
+    /// exits, but the release it writes is never recorded in the index and
+    /// it no longer holds up requests to the same host.
+    pub fn download_if_necessary_within(
+        &mut self,
+        tool: &ToolSpec,
+        providers: &Arc<ToolProvider>,
+        timeout: Duration,
+    ) -> ForemanResult<Installation> {
+        let cache = self.clone();
+        let worker_providers = Arc::clone(providers);
+        let worker_tool = tool.clone();
+        let installation = with_timeout(tool, providers, timeout, move || {
+            let (version, asset) = match cache.plan_install(&worker_tool, &worker_providers)? {
+                InstallPlan::Installed(version) => {
+                    return Ok(Installation {
+                        version,
+                        download: None,
+                    })
+                }
+                InstallPlan::Download(version, asset) => (version, asset),
+            };
+
+            let checks = cache.release_checks(&worker_tool, &version, &asset)?;
+            let sha256 = write_release(
+                &cache.paths,
+                &worker_tool,
+                &worker_providers,
+                &version,
+                &asset,
+                &checks,
+            )?;
+            cache.verify_release(&worker_tool, &version)?;
+            Ok(Installation {
+                version,
+                download: Some(Download {
+                    asset: asset.name,
+                    url: asset.url,
+                    sha256,
+                }),
+            })
+        })?;
+
+        if installation.download.is_some() {
+            self.record_release(tool, &installation.version)?;
+        }
+        Ok(installation)
+    }
+
+    fn record_release(&mut self, tool: &ToolSpec, version: &Version) -> ForemanResult<()> {
+        log::trace!("Updating tool cache");
+        let tool_entry = self.tools.entry(tool.cache_key()).or_default();
+        tool_entry.versions.insert(version.clone());
+        self.save()
+    }
+
+    pub fn load(paths: &ForemanPaths) -> ForemanResult<Self> {
+        let path = paths.index_file();
+        log::debug!("load tool cache from {}", path.display());
+
+        let index = fs::try_read(&path)?
+            .map(|contents| {
+                serde_json::from_slice::<ToolCacheIndex>(&contents)
+                    .map_err(|err| ForemanError::tool_cache_parsing(&path, err.to_string()))
+            })
+            .transpose()?;
+
+        Ok(match index {
+            Some(index) => Self {
+                tools: index.tools,
+                projects: index.projects,
+                ..Self::new(paths)
+            },
+            None => Self::new(paths),
+        })
+    }
+
+    fn save(&self) -> ForemanResult<()> {
+        let serialized =
+            serde_json::to_string_pretty(self).expect("unable to serialize tool cache");
+        fs::write(self.paths.index_file(), serialized)
+    }
+
+    fn get_tool_exe_path(&self, tool: &ToolSpec, version: &Version) -> PathBuf {
+        tool_exe_path(&self.paths, tool, version)
+    }
+}
+
+fn tool_exe_path(paths: &ForemanPaths, tool: &ToolSpec, version: &Version) -> PathBuf {
+    let mut tool_path = paths.tools_dir();
+    let exe_name = tool_identifier_to_exe_name(tool, version);
+    tool_path.push(exe_name);
+    tool_path
+}
+
+/// Downloads the release asset and writes the tool's binary from it to the
+/// tools directory, without recording it in the index. Returns the SHA-256
+/// digest of the downloaded asset, in hexadecimal.
+///
+/// An asset that does not match the checksum declared for it, or else the
+/// one published with the release, is rejected before anything is written.
+fn write_release(
+    paths: &ForemanPaths,
+    tool: &ToolSpec,
+    providers: &ToolProvider,
+    version: &Version,
+    asset: &ResolvedAsset,
+    checks: &ReleaseChecks,
+) -> ForemanResult<String> {
+    let buffer = providers.download_asset(tool, &asset.url)?;
+    let sha256 = format!("{:x}", Sha256::digest(&buffer));
+
+    if let Some(expected) = &checks.sha256 {
+        if !expected.eq_ignore_ascii_case(&sha256) {
+            return Err(ForemanError::ChecksumMismatch {
+                tool: tool.clone(),
+                version: version.clone(),
+                algorithm: DigestAlgorithm::Sha256,
+                origin: CHECKSUMS_FILE_NAME.to_owned(),
+                expected: expected.clone(),
+                actual: sha256,
+            });
+        }
+        log::debug!("Downloaded asset matches its declared checksum");
+    } else if let Some(checksum) = &asset.checksum {
+        verify_published_checksum(tool, providers, version, &buffer, checksum)?;
+    }
+    let tool_path = tool_exe_path(paths, tool, version);
+    let check = |binary_path: &Path| check_binary_format(tool, version, binary_path, checks);
+
+    log::trace!("Extracting downloaded artifact");
+    match ArchiveKind::of(asset.name.as_deref(), &buffer) {
+        ArchiveKind::Unsupported(format) => {
+            return Err(ForemanError::UnsupportedArchive {
+                tool: tool.clone(),
+                version: version.clone(),
+                asset: asset.name.clone().unwrap_or_else(|| asset.url.clone()),
+                format,
+            });
+        }
+        ArchiveKind::Raw => {
+            log::debug!("Release asset is not an archive, installing it as the binary");
+            fs::ensure_available_space(paths.tools_dir(), buffer.len() as u64)?;
+            install_binary(&mut buffer.as_slice(), &tool_path, check)?;
+        }
+        ArchiveKind::TarGz => {
+            // Release assets default to their first file like zip archives,
+            // but source tarballs hold a whole repository.
+            let first_file_without_bin = asset.name.is_some();
+            let binary = read_tarball_binary(&buffer, tool.bin(), first_file_without_bin)
+                .map_err(|message| ForemanError::invalid_release_asset(tool, version, message))?;
+
+            fs::ensure_available_space(paths.tools_dir(), binary.len() as u64)?;
+            install_binary(&mut binary.as_slice(), &tool_path, check)?;
+        }
+        ArchiveKind::Zip => {
+            let mut archive = ZipArchive::new(Cursor::new(&buffer)).map_err(|err| {
+                ForemanError::invalid_release_asset(
+                    tool,
+                    version,
+                    format!("unable to open zip archive ({})", err),
+                )
+            })?;
+            let index = find_binary(&mut archive, tool.bin())
+                .map_err(|message| ForemanError::invalid_release_asset(tool, version, message))?;
+            let mut file = archive.by_index(index).map_err(|err| {
+                ForemanError::invalid_release_asset(
+                    tool,
+                    version,
+                    format!("unable to obtain file from zip archive ({})", err),
+                )
+            })?;
+
+            // Fail before writing anything rather than leaving a truncated
+            // binary behind when the disk fills up.
+            fs::ensure_available_space(paths.tools_dir(), file.size())?;
+            install_binary(&mut file, &tool_path, check)?;
+        }
+    }
+
+    Ok(sha256)
+}
+
+/// Checks the downloaded `buffer` against the checksum the release publishes
+/// for it.
+fn verify_published_checksum(
+    tool: &ToolSpec,
+    providers: &ToolProvider,
+    version: &Version,
+    buffer: &[u8],
+    checksum: &PublishedChecksum,
+) -> ForemanResult<()> {
+    let contents = providers.download_asset(tool, &checksum.asset.url)?;
+    let expected = checksum
+        .algorithm
+        .parse_checksum_file(&contents)
+        .ok_or_else(|| {
+            ForemanError::invalid_release_asset(
+                tool,
+                version,
+                format!(
+                    "{} does not contain a {} checksum",
+                    checksum.asset.name, checksum.algorithm
+                ),
+            )
+        })?;
+
+    let actual = checksum.algorithm.hex_digest(buffer);
+    if expected != actual {
+        return Err(ForemanError::ChecksumMismatch {
+            tool: tool.clone(),
+            version: version.clone(),
+            algorithm: checksum.algorithm,
+            origin: checksum.asset.name.clone(),
+            expected,
+            actual,
+        });
+    }
+
+    log::debug!(
+        "Downloaded asset matches its published checksum {}",
+        checksum.asset.name
+    );
+    Ok(())
+}
+
+/// Writes the binary read from `reader` to a temporary file next to
+/// `tool_path`, marks it as executable and passes it to `check`, then renames
+/// it to `tool_path`, so that an interrupted install never leaves a truncated
+/// binary behind and a rejected one never replaces an installed binary. The
+/// temporary file is removed when writing or checking fails, and otherwise
+/// left for `foreman cache clean-temp`.
+fn install_binary<R, F>(reader: &mut R, tool_path: &Path, check: F) -> ForemanResult<()>
+where
+    R: Read + ?Sized,
+    F: FnOnce(&Path) -> ForemanResult<()>,
+{
+    let temp_path = fs::temp_path(tool_path);
+
+    let written = fs::copy_from_reader(reader, &temp_path).and_then(|_| {
+        // On Unix systems, mark the tool as executable.
+        #[cfg(unix)]
+        {
+            use std::os::unix::fs::PermissionsExt;
+
+            fs::set_permissions(&temp_path, fs::Permissions::from_mode(0o777))?;
+        }
+
+        check(&temp_path)?;
+        fs::rename(&temp_path, tool_path)
+    });
+
+    if written.is_err() {
+        let _ = std::fs::remove_file(&temp_path);
+    }
+    written
+}
+
+/// Archive formats of release assets.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum ArchiveKind {
+    Zip,
+    TarGz,
+    /// Not an archive: the asset is the tool's binary itself.
+    Raw,
+    /// An archive in a format that cannot be extracted, by name.
+    Unsupported(&'static str),
+}
+
+impl ArchiveKind {
+    /// The format of the asset named `name`, from its first bytes, since
+    /// asset names do not always match their contents. The extension is only
+    /// used for contents in no format recognized, which are otherwise
+    /// installed as they are, like scripts.
+    fn of(name: Option<&str>, buffer: &[u8]) -> Self {
+        let from_contents = if buffer.starts_with(&GZIP_MAGIC) {
+            Some(Self::TarGz)
+        } else if buffer.starts_with(&ZIP_MAGIC) {
+            Some(Self::Zip)
+        } else if BinaryFormat::detect(buffer).is_some() {
+            Some(Self::Raw)
+        } else {
+            UNSUPPORTED_ARCHIVES
+                .iter()
+                .find(|(_, magic, _)| buffer.starts_with(magic))
+                .map(|(format, _, _)| Self::Unsupported(format))
+        };
+
+        let lowercase_name = name.unwrap_or_default().to_ascii_lowercase();
+        let from_name = if lowercase_name.ends_with(".tar.gz") || lowercase_name.ends_with(".tgz") {
+            Some(Self::TarGz)
+        } else if lowercase_name.ends_with(".zip") {
+            Some(Self::Zip)
+        } else {
+            UNSUPPORTED_ARCHIVES
+                .iter()
+                .find(|(_, _, extensions)| {
+                    extensions
+                        .iter()
+                        .any(|extension| lowercase_name.ends_with(extension))
+                })
+                .map(|(format, _, _)| Self::Unsupported(format))
+        };
+
+        match (from_contents, from_name) {
+            (Some(kind), Some(named)) if kind != named => {
+                log::debug!(
+                    "Asset {} is {:?} despite its name, extracting it as such",
+                    name.unwrap_or_default(),
+                    kind
+                );
+                kind
+            }
+            (Some(kind), _) | (None, Some(kind)) => kind,
+            (None, None) => Self::Raw,
+        }
+    }
+}
+
+/// Warns, or fails when the check is strict, when the tool's binary at
+/// `binary_path` is an executable for another platform than the expected
+/// one. Binaries in no known executable format, like scripts, are accepted.
+fn check_binary_format(
+    tool: &ToolSpec,
+    version: &Version,
+    binary_path: &Path,
+    checks: &ReleaseChecks,
+) -> ForemanResult<()> {
+    let expected = match checks.binary_format {
+        Some(expected) => expected,
+        None => return Ok(()),
+    };
+
+    let mut header = Vec::new();
+    std::fs::File::open(binary_path)
+        .and_then(|file| file.take(4).read_to_end(&mut header))
+        .map_err(|err| ForemanError::read_error(err, binary_path))?;
+
+    match BinaryFormat::detect(&header) {
+        Some(actual) if actual != expected => {
+            let err = ForemanError::WrongBinaryFormat {
+                tool: tool.clone(),
+                version: version.clone(),
+                expected,
+                actual,
+            };
+            if checks.strict_binary_format {
+                return Err(err);
+            }
+            log::warn!("{}", err);
+            Ok(())
+        }
+        _ => Ok(()),
+    }
+}
+
+/// Runs `work` on a worker thread, failing with `ToolTimedOut` if it does not
+/// finish within `timeout`. The worker is abandoned rather than stopped, and
+/// the request slots it holds in `providers` are freed.
+fn with_timeout<T, F>(
+    tool: &ToolSpec,
+    providers: &ToolProvider,
+    timeout: Duration,
+    work: F,
+) -> ForemanResult<T>
+where
+    T: Send + 'static,
+    F: FnOnce() -> ForemanResult<T> + Send + 'static,
+{
+    let (sender, receiver) = mpsc::channel();
+    let worker = thread::spawn(move || {
+        // The receiver is gone when the work timed out.
+        let _ = sender.send(work());
+    });
+
+    match receiver.recv_timeout(timeout) {
+        Ok(result) => result,
+        Err(RecvTimeoutError::Timeout) => {
+            providers.abandon(worker.thread().id());
+            Err(ForemanError::ToolTimedOut {
+                tool: tool.to_string(),
+                timeout,
+            })
+        }
+        Err(RecvTimeoutError::Disconnected) => {
+            panic!("the worker installing {} stopped unexpectedly", tool)
+        }
+    }
+}
+
+/// Finds the index of the tool's binary in `archive`: the single file whose
+/// name matches the `bin` glob, or the first entry when there is no glob.
+fn find_binary<R: Read + Seek>(
+    archive: &mut ZipArchive<R>,
+    bin: Option<&str>,
+) -> Result<usize, String> {
+    let bin = match bin {
+        Some(bin) => bin,
+        None => return Ok(0),
+    };
+
+    let mut files = Vec::new();
+    for index in 0..archive.len() {
+        let file = archive
+            .by_index(index)
+            .map_err(|err| format!("unable to read zip archive ({})", err))?;
+        if !file.is_dir() {
+            files.push((index, file.name().to_owned()));
+        }
+    }
+
+    match_binary(bin, &files)
+}
+
+/// Reads the tool's binary from a gzipped tarball, like `.tar.gz` release
+/// assets or the source tarballs of GitHub releases: the single file whose
+/// name matches the `bin` glob. Without a glob, it is the first regular file
+/// when `first_file_without_bin` is set, and otherwise the only file of the
+/// tarball.
+fn read_tarball_binary(
+    buffer: &[u8],
+    bin: Option<&str>,
+    first_file_without_bin: bool,
+) -> Result<Vec<u8>, String> {
+    let mut archive = tar::Archive::new(GzDecoder::new(buffer));
+    let entries = archive
+        .entries()
+        .map_err(|err| format!("unable to read tar.gz archive ({})", err))?;
+
+    let mut files = Vec::new();
+    let mut contents = Vec::new();
+    for entry in entries {
+        let mut entry = entry.map_err(|err| format!("unable to read tar.gz archive ({})", err))?;
+        if !entry.header().entry_type().is_file() {
+            continue;
+        }
+
+        let name = entry
+            .path()
+            .map_err(|err| format!("unable to read tar.gz archive ({})", err))?
+            .to_string_lossy()
+            .into_owned();
+        let mut file_contents = Vec::new();
+        entry
+            .read_to_end(&mut file_contents)
+            .map_err(|err| format!("unable to read {} from tar.gz archive ({})", name, err))?;
+        files.push((contents.len(), name));
+        contents.push(file_contents);
+
+        if bin.is_none() && first_file_without_bin {
+            break;
+        }
+    }
+
+    if files.is_empty() {
+        return Err("the tar.gz archive contains no files".to_owned());
+    }
+
+    let index =
+        match bin {
+            Some(bin) => match_binary(bin, &files)?,
+            None if files.len() == 1 => 0,
+            None => return Err(
+                "the tarball contains several files, set `bin` to the name of the tool's binary"
+                    .to_owned(),
+            ),
+        };
+
+    Ok(contents.swap_remove(index))
+}
+
+/// The index of the single file among the archive's `files`, as pairs of
+/// index and path, whose path or file name matches the `bin` glob.
+fn match_binary(bin: &str, files: &[(usize, String)]) -> Result<usize, String> {
+    let pattern = glob::Pattern::new(bin)
+        .map_err(|err| format!("invalid `bin` pattern `{}` ({})", bin, err))?;
+
+    let matches: Vec<&(usize, String)> = files
+        .iter()
+        .filter(|(_, name)| {
+            let file_name = name.rsplit('/').next().unwrap_or(name);
+            pattern.matches(name) || pattern.matches(file_name)
+        })
+        .collect();
+
+    match matches.as_slice() {
+        [(index, name)] => {
+            log::debug!("Found binary {} matching `{}`", name, bin);
+            Ok(*index)
+        }
+        [] => Err(format!(
+            "no file in the archive matches `bin = \"{}\"`",
+            bin
+        )),
+        _ => Err(format!(
+            "`bin = \"{}\"` is ambiguous, it matches: {}",
+            bin,
+            matches
+                .iter()
+                .map(|(_, name)| name.as_str())
+                .collect::<Vec<_>>()
+                .join(", ")
+        )),
+    }
+}
+
+/// Explains which step of running the tool failed, and what to do about the
+/// failures that come from the environment rather than from Foreman.
+fn run_error(err: RunError, tool: &ToolSpec, tool_path: &Path) -> ForemanError {
+    let message = match err.phase {
+        RunPhase::Runtime => format!(
+            "unable to create the async runtime Foreman needs to run `{}` at `{}`. \
+            This usually means a sandbox or seccomp policy forbids the system calls it \
+            relies on, like epoll or eventfd: allow them, or run Foreman outside of the sandbox",
+            tool,
+            tool_path.display()
+        ),
+        RunPhase::Signals => format!(
+            "unable to register the signal handlers Foreman needs to run `{}` at `{}`. \
+            This usually means a sandbox forbids installing signal handlers: allow it, \
+            or run Foreman outside of the sandbox",
+            tool,
+            tool_path.display()
+        ),
+        RunPhase::Spawn => format!("unable to start `{}` at `{}`", tool, tool_path.display()),
+        RunPhase::Wait => format!(
+            "an error happened waiting for `{}` at `{}` to terminate (this is an error in Foreman)",
+            tool,
+            tool_path.display()
+        ),
+    };
+
+    ForemanError::io_error_with_context(err.source, message)
+}
+
+/// The environment of a tool run in `slot`, if any.
+fn tool_env(slot: Option<&ToolSlot>, env: &BTreeMap<String, String>) -> BTreeMap<String, String> {
+    match slot {
+        Some(slot) => slot.tool_env(env),
+        None => env.clone(),
+    }
+}
+
+/// An installed version of a tool that no configuration file uses.
+#[derive(Debug, PartialEq)]
+pub struct StaleVersion<'a> {
+    pub tool: &'a CiString,
+    pub version: &'a Version,
+    /// Size of the tool's binary, in bytes.
+    pub size: u64,
+}
+
+/// What `ToolCache::prune` removes.
+#[derive(Debug, Default, PartialEq)]
+pub struct PrunePlan {
+    /// Versions to remove from the index, by cache key, sorted.
+    pub versions: Vec<(CiString, Version)>,
+    /// Files of the tools directory that no installed version refers to,
+    /// sorted.
+    pub orphans: Vec<PathBuf>,
+    /// Size of the binaries and files to remove, in bytes.
+    pub size: u64,
+}
+
+impl PrunePlan {
+    pub fn is_empty(&self) -> bool {
+        self.versions.is_empty() && self.orphans.is_empty()
+    }
+}
+
+/// A tool path installed under several cache keys.
+#[derive(Debug)]
+pub struct DuplicateTool<'a> {
+    pub path: CiString,
+    /// The cache keys the tool is installed under, sorted.
+    pub entries: Vec<(&'a CiString, &'a ToolEntry)>,
+}
+
+/// The path of the tool a cache key refers to, without its host. Tools
+/// installed from a direct URL have no path to compare.
+fn cache_key_path(cache_key: &CiString) -> Option<&str> {
+    if cache_key.0.starts_with("url@") {
+        return None;
+    }
+
+    match cache_key.0.rsplit_once('@') {
+        Some((_host, path)) => Some(path),
+        None => Some(&cache_key.0),
+    }
+}
+
+#[derive(Debug, Default, Clone, PartialEq, Serialize, Deserialize)]
+pub struct ToolEntry {
+    pub versions: BTreeSet<Version>,
+}
+
+fn tool_identifier_to_exe_name(tool: &ToolSpec, version: &Version) -> String {
+    exe_name(&tool.cache_key(), version)
+}
+
+fn exe_name(cache_key: &CiString, version: &Version) -> String {
+    let mut name = format!("{}-{}{}", cache_key.0, version, EXE_SUFFIX);
+    name = name.replace('/', "__");
+    name.replace('\\', "__")
+}
+
+#[cfg(test)]
+mod test {
+    use flate2::{write::GzEncoder, Compression};
+    use tempfile::tempdir;
+
+    use crate::tool_provider::{fixed_releases::FixedReleases, ReleaseCache};
+
+    use super::*;
+
+    // Regression test for LUAFDN-1041, based on the release that surfaced it
+    #[test]
+    fn select_correct_asset() {
+        let release = Release {
+            prerelease: false,
+            source_tarball: None,
+            tag_name: "v0.5.2".to_string(),
+            assets: vec![
+                ReleaseAsset {
+                    name: "tool-linux.zip".to_string(),
+                    url: "https://example.com/some/repo/releases/assets/1".to_string(),
+                },
+                ReleaseAsset {
+                    name: "tool-macos-arm64.zip".to_string(),
+                    url: "https://example.com/some/repo/releases/assets/2".to_string(),
+                },
+                ReleaseAsset {
+                    name: "tool-macos-x86_64.zip".to_string(),
+                    url: "https://example.com/some/repo/releases/assets/3".to_string(),
+                },
+                ReleaseAsset {
+                    name: "tool-win64.zip".to_string(),
+                    url: "https://example.com/some/repo/releases/assets/4".to_string(),
+                },
+            ],
+        };
+        assert_eq!(
+            choose_asset(&release, &["win32", "win64", "windows"]),
+            Some(3)
+        );
+        assert_eq!(
+            choose_asset(
+                &release,
+                &["macos-x86_64", "darwin-x86_64", "macos", "darwin"]
+            ),
+            Some(2)
+        );
+        assert_eq!(
+            choose_asset(
+                &release,
+                &[
+                    "macos-arm64",
+                    "darwin-arm64",
+                    "macos-x86_64",
+                    "darwin-x86_64",
+                    "macos",
+                    "darwin",
+                ]
+            ),
+            Some(1)
+        );
+        assert_eq!(choose_asset(&release, &["linux"]), Some(0));
+    }
+
+    #[test]
+    fn select_correct_asset_macos() {
+        let release = Release {
+            prerelease: false,
+            source_tarball: None,
+            tag_name: "v0.5.2".to_string(),
+            assets: vec![
+                ReleaseAsset {
+                    name: "tool-linux.zip".to_string(),
+                    url: "https://example.com/some/repo/releases/assets/1".to_string(),
+                },
+                ReleaseAsset {
+                    name: "tool-macos-x86_64.zip".to_string(),
+                    url: "https://example.com/some/repo/releases/assets/2".to_string(),
+                },
+                ReleaseAsset {
+                    name: "tool-macos-aarch64.zip".to_string(),
+                    url: "https://example.com/some/repo/releases/assets/3".to_string(),
+                },
+            ],
+        };
+        assert_eq!(
+            choose_asset(
+                &release,
+                &[
+                    "macos-arm64",
+                    "darwin-arm64",
+                    "macos-aarch64",
+                    "darwin-aarch64",
+                    "macos",
+                    "darwin",
+                ]
+            ),
+            Some(2)
+        );
+    }
+
+    fn release_with_assets(names: &[&str]) -> Release {
+        Release {
+            prerelease: false,
+            source_tarball: None,
+            tag_name: "v1.0.0".to_string(),
+            assets: names
+                .iter()
+                .enumerate()
+                .map(|(index, name)| ReleaseAsset {
+                    name: name.to_string(),
+                    url: format!("https://example.com/some/repo/releases/assets/{}", index),
+                })
+                .collect(),
+        }
+    }
+
+    const MACOS_ARM64: &[&str] = &[
+        "macos-arm64",
+        "darwin-arm64",
+        "macos-aarch64",
+        "darwin-aarch64",
+        "macos-universal2",
+        "macos-universal",
+        "darwin-universal",
+        "macos",
+        "darwin",
+    ];
+
+    #[test]
+    fn select_arch_specific_asset_over_generic_one() {
+        let release = release_with_assets(&[
+            "tool-macos.zip",
+            "tool-macos-x86_64.zip",
+            "tool-macos-arm64.zip",
+        ]);
+        assert_eq!(choose_asset(&release, MACOS_ARM64), Some(2));
+        assert_eq!(
+            choose_asset(
+                &release,
+                &["macos-x86_64", "darwin-x86_64", "macos", "darwin"]
+            ),
+            Some(1)
+        );
+    }
+
+    #[test]
+    fn select_generic_asset_over_other_arch() {
+        // Without an arm64 build, the universal `macos` build must win over
+        // the x86_64 one, whatever their order.
+        let release = release_with_assets(&["tool-macos-x86_64.zip", "tool-macos.zip"]);
+        assert_eq!(choose_asset(&release, MACOS_ARM64), Some(1));
+
+        let release = release_with_assets(&["tool-macos-amd64.zip", "tool-macos.zip"]);
+        assert_eq!(choose_asset(&release, MACOS_ARM64), Some(1));
+
+        // Another name for the host's own architecture is not another one.
+        let release = release_with_assets(&["tool-linux-amd64.zip", "tool-linux.zip"]);
+        assert_eq!(choose_asset(&release, &["linux-x86_64", "linux"]), Some(0));
+    }
+
+    #[test]
+    fn select_universal_asset_on_arm64() {
+        let release = release_with_assets(&[
+            "tool-macos.zip",
+            "tool-macos-x86_64.zip",
+            "tool-macos-universal.zip",
+        ]);
+        assert_eq!(choose_asset(&release, MACOS_ARM64), Some(2));
+
+        let release = release_with_assets(&["tool-macos-x86_64.zip", "tool-macos-universal2.zip"]);
+        assert_eq!(choose_asset(&release, MACOS_ARM64), Some(1));
+
+        // A native arm64 build still wins.
+        let release = release_with_assets(&["tool-macos-universal.zip", "tool-macos-arm64.zip"]);
+        assert_eq!(choose_asset(&release, MACOS_ARM64), Some(1));
+    }
+
+    #[test]
+    fn select_correct_asset_linux() {
+        let release = Release {
+            prerelease: false,
+            source_tarball: None,
+            tag_name: "v0.5.2".to_string(),
+            assets: vec![
+                ReleaseAsset {
+                    name: "tool-linux-aarch64.zip".to_string(),
+                    url: "https://example.com/some/repo/releases/assets/1".to_string(),
+                },
+                ReleaseAsset {
+                    name: "tool-linux-x86_64.zip".to_string(),
+                    url: "https://example.com/some/repo/releases/assets/2".to_string(),
+                },
+                ReleaseAsset {
+                    name: "tool-macos-x86_64.zip".to_string(),
+                    url: "https://example.com/some/repo/releases/assets/3".to_string(),
+                },
+                ReleaseAsset {
+                    name: "tool-win64.zip".to_string(),
+                    url: "https://example.com/some/repo/releases/assets/4".to_string(),
+                },
+            ],
+        };
+        assert_eq!(choose_asset(&release, &["linux"]), Some(0));
+        assert_eq!(choose_asset(&release, &["linux-x86_64", "linux"]), Some(1));
+        assert_eq!(
+            choose_asset(&release, &["linux-arm64", "linux-aarch64", "linux"]),
+            Some(0)
+        );
+    }
+
+    #[test]
+    fn select_correct_asset_with_platform_word_in_tool_name() {
+        let release = Release {
+            prerelease: false,
+            source_tarball: None,
+            tag_name: "v1.0.0".to_string(),
+            assets: vec![
+                ReleaseAsset {
+                    name: "darwin-only-tool-macos.zip".to_string(),
+                    url: "https://example.com/some/repo/releases/assets/1".to_string(),
+                },
+                ReleaseAsset {
+                    name: "darwin-only-tool-linux.zip".to_string(),
+                    url: "https://example.com/some/repo/releases/assets/2".to_string(),
+                },
+                ReleaseAsset {
+                    name: "windowsish-tool-linux.zip".to_string(),
+                    url: "https://example.com/some/repo/releases/assets/3".to_string(),
+                },
+                ReleaseAsset {
+                    name: "windowsish-tool-windows.zip".to_string(),
+                    url: "https://example.com/some/repo/releases/assets/4".to_string(),
+                },
+            ],
+        };
+        assert_eq!(choose_asset(&release, &["linux-x86_64", "linux"]), Some(1));
+        assert_eq!(
+            choose_asset(
+                &release,
+                &["macos-x86_64", "darwin-x86_64", "macos", "darwin"]
+            ),
+            Some(0)
+        );
+        assert_eq!(choose_asset(&release, &["win64", "windows"]), Some(3));
+    }
+
+    #[test]
+    fn substring_match_is_a_fallback() {
+        let release = Release {
+            prerelease: false,
+            source_tarball: None,
+            tag_name: "v1.0.0".to_string(),
+            assets: vec![ReleaseAsset {
+                name: "tool-linux64.zip".to_string(),
+                url: "https://example.com/some/repo/releases/assets/1".to_string(),
+            }],
+        };
+        assert_eq!(choose_asset(&release, &["linux-x86_64", "linux"]), Some(0));
+    }
+
+    #[test]
+    fn configured_platform_keywords_drive_asset_selection() {
+        let release = Release {
+            prerelease: false,
+            source_tarball: None,
+            tag_name: "v1.0.0".to_string(),
+            assets: vec![
+                ReleaseAsset {
+                    name: "tool-linux-x86_64.zip".to_string(),
+                    url: "https://example.com/some/repo/releases/assets/1".to_string(),
+                },
+                ReleaseAsset {
+                    name: "tool-freebsd-x86_64.zip".to_string(),
+                    url: "https://example.com/some/repo/releases/assets/2".to_string(),
+                },
+            ],
+        };
+
+        let foreman_root = tempdir().expect("unable to create temporary directory");
+        let mut cache = ToolCache::new(&ForemanPaths::new(foreman_root.into_path()));
+        cache.set_platform_keywords(Some(vec![
+            "freebsd-x86_64".to_string(),
+            "freebsd".to_string(),
+        ]));
+
+        assert_eq!(cache.platform_keywords(), vec!["freebsd-x86_64", "freebsd"]);
+        assert_eq!(choose_asset(&release, &cache.platform_keywords()), Some(1));
+    }
+
+    /// Providers listing releases tagged `tags`, each with a `tool-linux.zip`
+    /// asset that must never be downloaded.
+    fn fixed_releases(tags: Vec<&'static str>) -> ToolProvider {
+        FixedReleases::new(tags).providers()
+    }
+
+    /// Builds a gzipped tarball in memory. Like `tar czf`, each directory of
+    /// the files gets its own entry first. Names ending with `/` are
+    /// directories.
+    fn tarball(files: &[(&str, &str)]) -> Vec<u8> {
+        let mut builder = tar::Builder::new(GzEncoder::new(Vec::new(), Compression::default()));
+        let mut directories = HashSet::new();
+
+        for (name, contents) in files {
+            for (index, _) in name.match_indices('/') {
+                let directory = &name[..=index];
+                if directories.insert(directory) {
+                    let mut header = tar::Header::new_gnu();
+                    header.set_entry_type(tar::EntryType::Directory);
+                    header.set_size(0);
+                    header.set_mode(0o755);
+                    header.set_cksum();
+                    builder
+                        .append_data(&mut header, directory, std::io::empty())
+                        .unwrap();
+                }
+            }
+            if name.ends_with('/') {
+                continue;
+            }
+
+            let mut header = tar::Header::new_gnu();
+            header.set_size(contents.len() as u64);
+            header.set_mode(0o755);
+            header.set_cksum();
+            builder
+                .append_data(&mut header, name, contents.as_bytes())
+                .unwrap();
+        }
+        builder.into_inner().unwrap().finish().unwrap()
+    }
+
+    fn github_tool(path: &str, version_req: &str) -> ToolSpec {
+        ToolSpec::from_value(
+            &toml::from_str(&format!(
+                "github = \"{}\"\nversion = \"{}\"",
+                path, version_req
+            ))
+            .unwrap(),
+            &crate::config::ConfigFile::new_with_defaults().hosts,
+        )
+        .unwrap()
+    }
+
+    /// A tool cache in a temporary directory that picks `linux` assets
+    /// whatever the current platform is.
+    fn linux_cache(foreman_root: &tempfile::TempDir) -> ToolCache {
+        let mut cache = ToolCache::new(&ForemanPaths::new(foreman_root.path().to_owned()));
+        cache.set_platform_keywords(Some(vec!["linux".to_string()]));
+        cache
+    }
+
+    mod installed {
+        use super::*;
+
+        fn cache_with(
+            foreman_root: &tempfile::TempDir,
+            versions: &[&str],
+        ) -> (ToolCache, ToolSpec) {
+            let mut cache = linux_cache(foreman_root);
+            let tool = github_tool("user/tool", "^1.0.0");
+            cache.tools.entry(tool.cache_key()).or_default().versions = versions
+                .iter()
+                .map(|version| Version::parse(version).unwrap())
+                .collect();
+            (cache, tool)
+        }
+
+        #[test]
+        fn enumerates_installed_tools() {
+            let foreman_root = tempdir().expect("unable to create temporary directory");
+            let (cache, tool) = cache_with(&foreman_root, &["1.0.0", "1.1.0"]);
+
+            let installed: Vec<(&CiString, Vec<String>)> = cache
+                .installed_tools()
+                .map(|(key, entry)| {
+                    (
+                        key,
+                        entry.versions.iter().map(ToString::to_string).collect(),
+                    )
+                })
+                .collect();
+            assert_eq!(
+                installed,
+                vec![(
+                    &tool.cache_key(),
+                    vec!["1.0.0".to_owned(), "1.1.0".to_owned()]
+                )]
+            );
+        }
+
+        #[test]
+        fn versions_of_tool() {
+            let foreman_root = tempdir().expect("unable to create temporary directory");
+            let (cache, tool) = cache_with(&foreman_root, &["1.0.0", "1.1.0"]);
+
+            assert_eq!(cache.versions_for(&tool).len(), 2);
+            assert!(cache.contains(&tool, &Version::parse("1.1.0").unwrap()));
+            assert!(!cache.contains(&tool, &Version::parse("1.2.0").unwrap()));
+
+            let other = github_tool("user/other", "^1.0.0");
+            assert!(cache.versions_for(&other).is_empty());
+            assert!(!cache.contains(&other, &Version::parse("1.0.0").unwrap()));
+        }
+
+        #[test]
+        fn uninstall_removes_versions_and_binaries() {
+            let foreman_root = tempdir().expect("unable to create temporary directory");
+            let (mut cache, tool) = cache_with(&foreman_root, &["1.0.0", "1.1.0"]);
+            let tools_dir = cache.paths.tools_dir();
+            std::fs::create_dir_all(&tools_dir).unwrap();
+            let old = Version::parse("1.0.0").unwrap();
+            let new = Version::parse("1.1.0").unwrap();
+            for version in [&old, &new] {
+                std::fs::write(cache.get_tool_exe_path(&tool, version), b"binary").unwrap();
+            }
+
+            assert!(matches!(
+                cache.uninstall(&tool, Some(&Version::parse("1.2.0").unwrap())),
+                Err(ForemanError::VersionNotInstalled { .. })
+            ));
+
+            assert_eq!(
+                cache.uninstall(&tool, Some(&old)).unwrap(),
+                vec![old.clone()]
+            );
+            assert!(!cache.get_tool_exe_path(&tool, &old).exists());
+            assert!(cache.get_tool_exe_path(&tool, &new).exists());
+            assert_eq!(cache.installed_versions(&tool), vec![&new]);
+
+            assert_eq!(cache.uninstall(&tool, None).unwrap(), vec![new.clone()]);
+            assert!(!cache.get_tool_exe_path(&tool, &new).exists());
+            assert_eq!(cache.installed_tools().count(), 0);
+
+            let saved = ToolCache::load(&cache.paths).unwrap();
+            assert_eq!(saved.installed_tools().count(), 0);
+        }
+
+        #[test]
+        fn same_path_under_several_hosts_is_duplicate() {
+            let foreman_root = tempdir().expect("unable to create temporary directory");
+            let (mut cache, tool) = cache_with(&foreman_root, &["1.0.0"]);
+            let gitlab = ToolSpec::from_value(
+                &toml::from_str("gitlab = \"User/Tool\"\nversion = \"^1.0.0\"").unwrap(),
+                &crate::config::ConfigFile::new_with_defaults().hosts,
+            )
+            .unwrap();
+            cache.tools.entry(gitlab.cache_key()).or_default().versions =
+                BTreeSet::from([Version::parse("1.1.0").unwrap()]);
+            let other = github_tool("user/other", "^1.0.0");
+            cache.tools.entry(other.cache_key()).or_default().versions =
+                BTreeSet::from([Version::parse("1.0.0").unwrap()]);
+
+            let duplicates = cache.duplicate_tools();
+            assert_eq!(duplicates.len(), 1);
+            assert_eq!(duplicates[0].path, CiString::from("user/tool"));
+            let cache_keys: Vec<&CiString> =
+                duplicates[0].entries.iter().map(|(key, _)| *key).collect();
+            assert_eq!(cache_keys, vec![&gitlab.cache_key(), &tool.cache_key()]);
+        }
+
+        #[test]
+        fn dedupe_merges_equivalent_cache_keys() {
+            let foreman_root = tempdir().expect("unable to create temporary directory");
+            let mut cache = linux_cache(&foreman_root);
+            cache.paths.create_all().unwrap();
+            let tools_dir = cache.paths.tools_dir();
+
+            // The same Artifactory tool, installed before and after host URLs
+            // got a canonical spelling.
+            let old_key = CiString::from("https://ART.example.com:443/tools@team/tool");
+            let new_key = CiString::from("https://art.example.com/tools/@team/tool");
+            let (v1, v2) = (Version::new(1, 0, 0), Version::new(2, 0, 0));
+            cache.tools.entry(old_key.clone()).or_default().versions =
+                BTreeSet::from([v1.clone(), v2.clone()]);
+            cache.tools.entry(new_key.clone()).or_default().versions = BTreeSet::from([v2.clone()]);
+            for (cache_key, version) in [(&old_key, &v1), (&old_key, &v2), (&new_key, &v2)] {
+                std::fs::write(tools_dir.join(exe_name(cache_key, version)), "tool").unwrap();
+            }
+
+            assert_eq!(
+                cache.dedupe().unwrap(),
+                vec![(old_key.clone(), new_key.clone())]
+            );
+
+            let saved = ToolCache::load(&cache.paths).unwrap();
+            assert_eq!(saved.tools.len(), 1);
+            assert_eq!(
+                saved.tools[&new_key].versions,
+                BTreeSet::from([v1.clone(), v2.clone()])
+            );
+
+            let mut binaries: Vec<String> = std::fs::read_dir(&tools_dir)
+                .unwrap()
+                .map(|entry| entry.unwrap().file_name().into_string().unwrap())
+                .collect();
+            binaries.sort();
+            assert_eq!(
+                binaries,
+                vec![exe_name(&new_key, &v1), exe_name(&new_key, &v2)]
+            );
+
+            assert!(cache.dedupe().unwrap().is_empty());
+        }
+
+        #[test]
+        fn prune_removes_unused_versions_missing_binaries_and_orphans() {
+            let foreman_root = tempdir().expect("unable to create temporary directory");
+            let (mut cache, tool) =
+                cache_with(&foreman_root, &["1.0.0", "1.1.0", "1.2.0", "2.0.0"]);
+            let tools_dir = cache.paths.tools_dir();
+            std::fs::create_dir_all(&tools_dir).unwrap();
+            // 1.2.0 is in the index, but its binary is gone.
+            for version in ["1.0.0", "1.1.0", "2.0.0"] {
+                let version = Version::parse(version).unwrap();
+                std::fs::write(cache.get_tool_exe_path(&tool, &version), b"binary").unwrap();
+            }
+            let orphan = tools_dir.join("user__leftover-1.0.0");
+            std::fs::write(&orphan, b"orphan").unwrap();
+
+            let pruned = |plan: &PrunePlan| -> Vec<String> {
+                plan.versions
+                    .iter()
+                    .map(|(_, version)| version.to_string())
+                    .collect()
+            };
+
+            // 1.1.0 is the version the declared tool runs.
+            let plan = cache.plan_prune([&tool], 0).unwrap();
+            assert_eq!(pruned(&plan), vec!["1.0.0", "1.2.0", "2.0.0"]);
+            assert_eq!(plan.orphans, vec![orphan.clone()]);
+            assert_eq!(plan.size, 18);
+
+            let plan = cache.plan_prune([&tool], 1).unwrap();
+            assert_eq!(pruned(&plan), vec!["1.0.0", "1.2.0"]);
+
+            cache.prune(&plan).unwrap();
+            assert_eq!(cache.installed_versions(&tool).len(), 2);
+            assert!(!cache
+                .get_tool_exe_path(&tool, &Version::parse("1.0.0").unwrap())
+                .exists());
+            assert!(!orphan.exists());
+            assert!(cache.plan_prune([&tool], 1).unwrap().is_empty());
+        }
+
+        #[test]
+        fn only_old_temp_files_are_stale() {
+            let foreman_root = tempdir().expect("unable to create temporary directory");
+            let (cache, tool) = cache_with(&foreman_root, &["1.0.0"]);
+            let tools_dir = cache.paths.tools_dir();
+            std::fs::create_dir_all(&tools_dir).unwrap();
+
+            let binary = cache.get_tool_exe_path(&tool, &Version::new(1, 0, 0));
+            let interrupted = tools_dir.join("user__tool-1.1.0.4242.tmp");
+            let in_progress = tools_dir.join("user__tool-1.2.0.4243.tmp");
+            for path in [&binary, &interrupted, &in_progress] {
+                std::fs::write(path, b"binary").unwrap();
+            }
+            let two_hours_ago = std::time::SystemTime::now() - Duration::from_secs(2 * 3600);
+            for path in [&binary, &interrupted] {
+                std::fs::File::options()
+                    .write(true)
+                    .open(path)
+                    .unwrap()
+                    .set_modified(two_hours_ago)
+                    .unwrap();
+            }
+
+            assert_eq!(
+                cache.stale_temp_files(Duration::from_secs(3600)).unwrap(),
+                vec![(interrupted, 6)]
+            );
+            // Temporary files are left to `stale_temp_files`.
+            assert!(cache.plan_prune([&tool], 0).unwrap().is_empty());
+        }
+
+        #[test]
+        fn versions_not_run_by_any_declared_tool_are_stale() {
+            let foreman_root = tempdir().expect("unable to create temporary directory");
+            let (mut cache, tool) = cache_with(&foreman_root, &["1.0.0", "1.1.0", "2.0.0"]);
+            let unused = github_tool("user/unused", "^1.0.0");
+            cache.tools.entry(unused.cache_key()).or_default().versions =
+                BTreeSet::from([Version::parse("1.0.0").unwrap()]);
+
+            let stale: Vec<(String, String)> = cache
+                .stale_versions([&tool])
+                .into_iter()
+                .map(|stale| (stale.tool.to_string(), stale.version.to_string()))
+                .collect();
+            assert_eq!(
+                stale,
+                vec![
+                    ("user/tool".to_owned(), "1.0.0".to_owned()),
+                    ("user/tool".to_owned(), "2.0.0".to_owned()),
+                    ("user/unused".to_owned(), "1.0.0".to_owned()),
+                ]
+            );
+        }
+    }
+
+    mod update {
+        use super::*;
+
+        fn update_with_releases(installed: &str, releases: Vec<&'static str>) {
+            let foreman_root = tempdir().expect("unable to create temporary directory");
+            let mut cache = linux_cache(&foreman_root);
+            let tool = github_tool("user/tool", "^1.0.0");
+
+            cache.tools.entry(tool.cache_key()).or_default().versions =
+                BTreeSet::from([Version::parse(installed).unwrap()]);
+
+            let providers = fixed_releases(releases);
+
+            assert_eq!(cache.update(&tool, &providers).unwrap(), None);
+            assert_eq!(
+                cache.tools[&tool.cache_key()].versions,
+                BTreeSet::from([Version::parse(installed).unwrap()])
+            );
+        }
+
+        #[test]
+        fn never_downgrades_installed_tool() {
+            update_with_releases("1.2.0", vec!["v1.0.0", "v1.1.0"]);
+        }
+
+        #[test]
+        fn skips_tool_already_at_resolved_version() {
+            update_with_releases("1.1.0", vec!["v1.0.0", "v1.1.0"]);
+        }
+    }
+
+    mod resolve {
+        use super::*;
+
+        #[test]
+        fn resolves_newest_matching_version_without_downloading() {
+            let foreman_root = tempdir().expect("unable to create temporary directory");
+            let cache = linux_cache(&foreman_root);
+            let providers = fixed_releases(vec!["v1.0.0", "v1.1.0", "v2.0.0"]);
+
+            assert_eq!(
+                cache
+                    .resolve(&github_tool("user/tool", "^1.0.0"), &providers)
+                    .unwrap(),
+                Version::parse("1.1.0").unwrap()
+            );
+            assert!(cache.tools.is_empty());
+        }
+
+        #[test]
+        fn exact_versions_fetch_their_release_by_tag() {
+            let foreman_root = tempdir().expect("unable to create temporary directory");
+            let cache = linux_cache(&foreman_root);
+
+            let (providers, requests) =
+                FixedReleases::new(vec!["v1.0.0", "1.1.0", "v2.0.0"]).logged_providers();
+            assert_eq!(
+                cache
+                    .resolve(&github_tool("user/tool", "=1.1.0"), &providers)
+                    .unwrap(),
+                Version::parse("1.1.0").unwrap()
+            );
+            assert_eq!(requests.get(), vec!["tag v1.1.0", "tag 1.1.0"]);
+
+            let (providers, requests) =
+                FixedReleases::new(vec!["v1.0.0", "v2.0.0"]).logged_providers();
+            assert_eq!(
+                cache
+                    .resolve(&github_tool("user/tool", "=2.0.0"), &providers)
+                    .unwrap(),
+                Version::parse("2.0.0").unwrap()
+            );
+            assert_eq!(requests.get(), vec!["tag v2.0.0"]);
+        }
+
+        #[test]
+        fn ranges_and_missing_tags_list_every_release() {
+            let foreman_root = tempdir().expect("unable to create temporary directory");
+            let cache = linux_cache(&foreman_root);
+
+            let (providers, requests) =
+                FixedReleases::new(vec!["v1.0.0", "v1.1.0"]).logged_providers();
+            assert_eq!(
+                cache
+                    .resolve(&github_tool("user/tool", "~1.0"), &providers)
+                    .unwrap(),
+                Version::parse("1.0.0").unwrap()
+            );
+            assert_eq!(requests.get(), vec!["list"]);
+
+            let (providers, requests) =
+                FixedReleases::new(vec!["v1.0.0", "v1.1.0"]).logged_providers();
+            let error = cache
+                .resolve(&github_tool("user/tool", "=1.2.0"), &providers)
+                .unwrap_err();
+            assert!(
+                matches!(error, ForemanError::NoCompatibleVersionFound { .. }),
+                "{:?}",
+                error
+            );
+            assert_eq!(requests.get(), vec!["tag v1.2.0", "tag 1.2.0", "list"]);
+        }
+
+        #[test]
+        fn only_exact_requirements_pin_a_version() {
+            let exact = |requirement: &str| exact_version(&VersionReq::parse(requirement).unwrap());
+
+            assert_eq!(exact("=7.2.1"), Some(Version::parse("7.2.1").unwrap()));
+            assert_eq!(
+                exact("=1.0.0-beta.2"),
+                Some(Version::parse("1.0.0-beta.2").unwrap())
+            );
+            assert_eq!(exact("7.2.1"), None);
+            assert_eq!(exact("=7.2"), None);
+            assert_eq!(exact(">=7.2.1, <8"), None);
+        }
+
+        #[test]
+        fn resolves_calver_tags_by_date() {
+            let foreman_root = tempdir().expect("unable to create temporary directory");
+            let cache = linux_cache(&foreman_root);
+            let providers = fixed_releases(vec![
+                "2023.12.31",
+                "2024.03.01",
+                "v2024.05.30",
+                "2024.06.01",
+            ]);
+            let tool = ToolSpec::from_value(
+                &toml::from_str(
+                    "github = \"user/tool\"\nversion = \">=2024.01.01, <2024.06\"\n\
+                    versioning = \"calver\"\nyanked = [\"2024.05.30\"]",
+                )
+                .unwrap(),
+                &crate::config::ConfigFile::new_with_defaults().hosts,
+            )
+            .unwrap();
+
+            assert_eq!(
+                cache.resolve(&tool, &providers).unwrap(),
+                Version::new(2024, 3, 1)
+            );
+        }
+
+        #[test]
+        fn prerelease_is_rejected_when_requested() {
+            let foreman_root = tempdir().expect("unable to create temporary directory");
+            let mut cache = linux_cache(&foreman_root);
+            let providers = fixed_releases(vec!["v2.0.0-rc.1"]);
+            let tool = github_tool("user/tool", "2.0.0-rc.1");
+
+            assert_eq!(
+                cache.resolve(&tool, &providers).unwrap(),
+                Version::parse("2.0.0-rc.1").unwrap()
+            );
+
+            cache.set_reject_prereleases(true);
+            assert!(matches!(
+                cache.resolve(&tool, &providers),
+                Err(ForemanError::PrereleaseNotAllowed { .. })
+            ));
+        }
+
+        #[test]
+        fn yanked_version_is_skipped_for_next_best_match() {
+            let foreman_root = tempdir().expect("unable to create temporary directory");
+            let mut cache = linux_cache(&foreman_root);
+            let providers = fixed_releases(vec!["v0.14.1", "v0.14.2", "v0.15.0"]);
+            let tool = ToolSpec::from_value(
+                &toml::from_str(
+                    "github = \"user/tool\"\nversion = \"^0.14.0\"\nyanked = [\"0.14.2\"]",
+                )
+                .unwrap(),
+                &crate::config::ConfigFile::new_with_defaults().hosts,
+            )
+            .unwrap();
+
+            assert_eq!(
+                cache.resolve(&tool, &providers).unwrap(),
+                Version::parse("0.14.1").unwrap()
+            );
+
+            // An installed yanked version is not used either.
+            cache.tools.entry(tool.cache_key()).or_default().versions =
+                BTreeSet::from([Version::parse("0.14.2").unwrap()]);
+            assert_eq!(cache.installed_version(&tool), None);
+        }
+
+        #[test]
+        fn globally_yanked_version_is_skipped() {
+            let foreman_root = tempdir().expect("unable to create temporary directory");
+            let mut cache = linux_cache(&foreman_root);
+            let providers = fixed_releases(vec!["v1.0.0", "v1.1.0"]);
+            let tool = github_tool("User/Tool", "^1.0.0");
+            cache.set_yanked(HashMap::from([(
+                CiString::from("github.com/user/tool"),
+                vec![Version::parse("1.1.0").unwrap()],
+            )]));
+
+            assert_eq!(
+                cache.resolve(&tool, &providers).unwrap(),
+                Version::parse("1.0.0").unwrap()
+            );
+        }
+
+        fn tool_with_asset(asset: &str) -> ToolSpec {
+            ToolSpec::from_value(
+                &toml::from_str(&format!(
+                    "github = \"user/tool\"\nversion = \"^1.0.0\"\nasset = \"{}\"",
+                    asset
+                ))
+                .unwrap(),
+                &crate::config::ConfigFile::new_with_defaults().hosts,
+            )
+            .unwrap()
+        }
+
+        #[test]
+        fn named_asset_bypasses_platform_keywords() {
+            let foreman_root = tempdir().expect("unable to create temporary directory");
+            let mut cache = linux_cache(&foreman_root);
+            cache.set_platform_keywords(Some(vec!["windows".to_owned()]));
+            let providers = fixed_releases(vec!["v1.0.0", "v1.1.0"]);
+
+            let (version, asset) = cache
+                .resolve_release(&tool_with_asset("Tool-Linux.zip"), &providers)
+                .unwrap();
+            assert_eq!(version, Version::parse("1.1.0").unwrap());
+            assert_eq!(asset.name.as_deref(), Some("tool-linux.zip"));
+            assert_eq!(asset.url, "https://example.com/v1.1.0/tool-linux.zip");
+        }
+
+        #[test]
+        fn missing_named_asset_lists_available_assets() {
+            let foreman_root = tempdir().expect("unable to create temporary directory");
+            let cache = linux_cache(&foreman_root);
+            let providers = fixed_releases(vec!["v1.0.0"]);
+
+            let error = cache
+                .resolve_release(&tool_with_asset("tool-macos.zip"), &providers)
+                .unwrap_err();
+            assert!(matches!(
+                &error,
+                ForemanError::ReleaseAssetNotFound { available_assets, .. }
+                    if available_assets == &vec!["tool-linux.zip".to_owned()]
+            ));
+            assert_eq!(
+                error.to_string(),
+                "release 1.0.0 of github.com/user/tool has no asset named tool-macos.zip. \
+                Available assets:\n* tool-linux.zip"
+            );
+        }
+
+        #[test]
+        fn empty_release_list_is_listed_again() {
+            let foreman_root = tempdir().expect("unable to create temporary directory");
+            let mut cache = linux_cache(&foreman_root);
+            let tool = github_tool("user/tool", "^1.0.0");
+            cache
+                .tools
+                .entry(tool.cache_key())
+                .or_default()
+                .versions
+                .insert(Version::new(0, 9, 0));
+            let lagging = |empty_listings: usize, retries: u32| {
+                let (mut providers, requests) = FixedReleases::new(vec!["v1.0.0"])
+                    .empty_listings(empty_listings)
+                    .logged_providers();
+                providers.set_empty_releases_retries(retries);
+                (providers, requests)
+            };
+
+            let (providers, requests) = lagging(1, 2);
+            assert_eq!(
+                cache.resolve(&tool, &providers).unwrap(),
+                Version::new(1, 0, 0)
+            );
+            assert_eq!(requests.listings(), 2);
+
+            let (providers, requests) = lagging(3, 2);
+            assert!(matches!(
+                cache.resolve(&tool, &providers),
+                Err(ForemanError::NoCompatibleVersionFound { .. })
+            ));
+            assert_eq!(requests.listings(), 3);
+        }
+
+        #[test]
+        fn cached_releases_without_a_match_are_listed_again() {
+            let foreman_root = tempdir().expect("unable to create temporary directory");
+            let cache = linux_cache(&foreman_root);
+            let tool = github_tool("user/tool", "^1.1.0");
+            let release_cache_dir = foreman_root.path().join("release-cache");
+
+            // Listed before 1.1.0 was published.
+            ReleaseCache::new(release_cache_dir.clone(), Duration::from_secs(60)).put(
+                &tool,
+                &FixedReleases::new(vec!["v1.0.0"]).releases(true),
+                std::time::SystemTime::now(),
+            );
+            let (mut providers, requests) =
+                FixedReleases::new(vec!["v1.0.0", "v1.1.0"]).logged_providers();
+            providers.set_release_cache(ReleaseCache::new(
+                release_cache_dir,
+                Duration::from_secs(60),
+            ));
+
+            assert_eq!(
+                cache.resolve(&tool, &providers).unwrap(),
+                Version::new(1, 1, 0)
+            );
+            assert_eq!(requests.listings(), 1);
+
+            // The new listing was cached.
+            assert_eq!(
+                cache.resolve(&tool, &providers).unwrap(),
+                Version::new(1, 1, 0)
+            );
+            assert_eq!(requests.listings(), 1);
+        }
+
+        #[test]
+        fn refresh_is_suggested_when_cached_releases_cannot_be_listed_again() {
+            let foreman_root = tempdir().expect("unable to create temporary directory");
+            let cache = linux_cache(&foreman_root);
+            let tool = github_tool("user/tool", "^1.1.0");
+            let release_cache_dir = foreman_root.path().join("release-cache");
+
+            ReleaseCache::new(release_cache_dir.clone(), Duration::from_secs(60)).put(
+                &tool,
+                &FixedReleases::new(vec!["v1.0.0"]).releases(true),
+                std::time::SystemTime::now(),
+            );
+            let mut providers = FixedReleases::new(vec!["v1.0.0"])
+                .failing_listings()
+                .providers();
+            providers.set_release_cache(ReleaseCache::new(
+                release_cache_dir,
+                Duration::from_secs(60),
+            ));
+
+            let message = cache.resolve(&tool, &providers).unwrap_err().to_string();
+            assert!(message.contains("foreman install --refresh"), "{}", message);
+
+            // Releases that were listed just now need no refresh.
+            let message = cache
+                .resolve(&tool, &FixedReleases::new(vec!["v1.0.0"]).providers())
+                .unwrap_err()
+                .to_string();
+            assert!(!message.contains("--refresh"), "{}", message);
+        }
+
+        #[test]
+        fn empty_release_list_of_new_tool_is_not_listed_again() {
+            let foreman_root = tempdir().expect("unable to create temporary directory");
+            let cache = linux_cache(&foreman_root);
+            let (mut providers, requests) = FixedReleases::new(vec!["v1.0.0"])
+                .empty_listings(1)
+                .logged_providers();
+            providers.set_empty_releases_retries(2);
+
+            assert!(matches!(
+                cache.resolve(&github_tool("user/tool", "^1.0.0"), &providers),
+                Err(ForemanError::NoCompatibleVersionFound { .. })
+            ));
+            assert_eq!(requests.listings(), 1);
+        }
+
+        #[test]
+        fn matching_releases_without_assets_are_reported() {
+            let foreman_root = tempdir().expect("unable to create temporary directory");
+            let cache = linux_cache(&foreman_root);
+            let providers = FixedReleases::new(vec!["v1.0.0", "v1.1.0", "v2.0.0"])
+                .assets(&[])
+                .providers();
+
+            let error = cache
+                .resolve(&github_tool("user/tool", "^1.0.0"), &providers)
+                .unwrap_err();
+            assert_eq!(
+                error.to_string(),
+                "versions of github.com/user/tool matching version requirement ^1.0.0 exist, \
+                but their releases have no downloadable assets:\n* 1.1.0\n* 1.0.0"
+            );
+        }
+
+        #[test]
+        fn assets_listed_on_demand_for_newest_matching_releases_only() {
+            let foreman_root = tempdir().expect("unable to create temporary directory");
+            let cache = linux_cache(&foreman_root);
+            // Version folders, like Artifactory's, with an empty one.
+            let (providers, requests) =
+                FixedReleases::new(vec!["v1.0.0", "v1.1.0", "v1.2.0", "v2.0.0"])
+                    .assets_on_demand()
+                    .release_assets("v1.2.0", &[])
+                    .logged_providers();
+
+            let (version, asset) = cache
+                .resolve_release(&github_tool("user/tool", "^1.0.0"), &providers)
+                .unwrap();
+            assert_eq!(version, Version::parse("1.1.0").unwrap());
+            assert_eq!(asset.url, "https://example.com/v1.1.0/tool-linux.zip");
+            assert_eq!(
+                requests.get(),
+                vec!["list", "assets v1.2.0", "assets v1.1.0"]
+            );
+        }
+
+        #[test]
+        fn assets_listed_on_demand_until_one_matches_the_platform() {
+            let foreman_root = tempdir().expect("unable to create temporary directory");
+            let cache = linux_cache(&foreman_root);
+            let (providers, requests) = FixedReleases::new(vec!["v1.0.0", "v1.1.0"])
+                .assets_on_demand()
+                .release_assets("v1.1.0", &["tool-windows.zip"])
+                .logged_providers();
+
+            let (version, asset) = cache
+                .resolve_release(&github_tool("user/tool", "^1.0.0"), &providers)
+                .unwrap();
+            assert_eq!(version, Version::new(1, 0, 0));
+            assert_eq!(asset.url, "https://example.com/v1.0.0/tool-linux.zip");
+            assert_eq!(
+                requests.get(),
+                vec!["list", "assets v1.1.0", "assets v1.0.0"]
+            );
+        }
+
+        #[test]
+        fn only_unsatisfiable_tool_fails() {
+            let foreman_root = tempdir().expect("unable to create temporary directory");
+            let cache = linux_cache(&foreman_root);
+            let providers = fixed_releases(vec!["v1.0.0", "v1.1.0"]);
+
+            assert!(cache
+                .resolve(&github_tool("user/tool", "^1.0.0"), &providers)
+                .is_ok());
+            assert!(matches!(
+                cache.resolve(&github_tool("user/other-tool", "^3.0.0"), &providers),
+                Err(ForemanError::NoCompatibleVersionFound { .. })
+            ));
+        }
+    }
+
+    mod timeout {
+        use super::*;
+
+        #[test]
+        fn slow_download_times_out_without_being_recorded() {
+            let foreman_root = tempdir().expect("unable to create temporary directory");
+            let mut cache = linux_cache(&foreman_root);
+            let providers = Arc::new(
+                FixedReleases::new(vec!["v1.0.0"])
+                    .payload("tool-linux.zip", Vec::new())
+                    .download_delay(Duration::from_secs(5))
+                    .providers(),
+            );
+            let tool = github_tool("user/tool", "1.0.0");
+
+            match cache.download_if_necessary_within(&tool, &providers, Duration::from_millis(50)) {
+                Err(ForemanError::ToolTimedOut {
+                    tool: name,
+                    timeout,
+                }) => {
+                    assert_eq!(name, tool.to_string());
+                    assert_eq!(timeout, Duration::from_millis(50));
+                }
+                other => panic!("expected the tool to time out, got {:?}", other),
+            }
+            assert!(cache.versions_for(&tool).is_empty());
+        }
+
+        #[test]
+        fn timed_out_download_does_not_hold_up_its_host() {
+            let foreman_root = tempdir().expect("unable to create temporary directory");
+            let mut cache = linux_cache(&foreman_root);
+            cache.paths.create_all().unwrap();
+            // Both tools come from github.com, which only allows a single
+            // request at a time in tests.
+            let providers = Arc::new(
+                FixedReleases::new(vec!["v1.0.0", "v2.0.0"])
+                    .release_assets("v2.0.0", &["other-linux.zip"])
+                    .payload("tool-linux.zip", Vec::new())
+                    .payload("other-linux.zip", direct_url::zipped_tool())
+                    .release_delay("v1.0.0", Duration::from_secs(30))
+                    .providers(),
+            );
+            let slow = github_tool("user/slow", "1.0.0");
+            let fast = github_tool("user/fast", "2.0.0");
+
+            assert!(matches!(
+                cache.download_if_necessary_within(&slow, &providers, Duration::from_millis(50)),
+                Err(ForemanError::ToolTimedOut { .. })
+            ));
+            let installation = cache
+                .download_if_necessary_within(&fast, &providers, Duration::from_secs(5))
+                .unwrap();
+            assert_eq!(installation.version, Version::new(2, 0, 0));
+        }
+
+        #[test]
+        fn work_finishing_in_time_returns_its_result() {
+            let tool = github_tool("user/tool", "1.0.0");
+            let providers = FixedReleases::new(Vec::new()).providers();
+            let result = with_timeout(&tool, &providers, Duration::from_secs(5), || Ok(42));
+            assert_eq!(result.unwrap(), 42);
+        }
+    }
+
+    mod source_fallback {
+        use super::*;
+
+        /// A single macOS-only release whose source tarball, laid out like
+        /// GitHub's, holds the tool.
+        fn source_only() -> ToolProvider {
+            FixedReleases::new(vec!["v1.0.0"])
+                .assets(&["tool-macos.zip"])
+                .source_tarballs()
+                .payload(
+                    "source.tar.gz",
+                    tarball(&[
+                        ("user-tool-abc123/README.md", "readme"),
+                        ("user-tool-abc123/bin/tool", "#!/bin/sh\necho tool\n"),
+                    ]),
+                )
+                .providers()
+        }
+
+        fn tool(source_fallback: bool) -> ToolSpec {
+            ToolSpec::from_value(
+                &toml::from_str(&format!(
+                    "github = \"user/tool\"\nversion = \"1.0.0\"\nbin = \"tool\"\nsource_fallback = {}",
+                    source_fallback
+                ))
+                .unwrap(),
+                &crate::config::ConfigFile::new_with_defaults().hosts,
+            )
+            .unwrap()
+        }
+
+        #[test]
+        fn downloads_source_tarball_when_no_asset_matches() {
+            let foreman_root = tempdir().expect("unable to create temporary directory");
+            let mut cache = linux_cache(&foreman_root);
+            cache.paths.create_all().unwrap();
+            let tool = tool(true);
+
+            let version = cache.install(&tool, &source_only()).unwrap().version;
+            assert_eq!(version, Version::parse("1.0.0").unwrap());
+            assert_eq!(
+                std::fs::read_to_string(cache.get_tool_exe_path(&tool, &version)).unwrap(),
+                "#!/bin/sh\necho tool\n"
+            );
+        }
+
+        #[test]
+        fn source_tarball_is_ignored_without_fallback() {
+            let foreman_root = tempdir().expect("unable to create temporary directory");
+            let cache = linux_cache(&foreman_root);
+
+            assert!(cache.resolve(&tool(false), &source_only()).is_err());
+        }
+
+        #[test]
+        fn tarball_without_bin_needs_a_single_file() {
+            let buffer = tarball(&[("tool", "binary")]);
+            assert_eq!(
+                read_tarball_binary(&buffer, None, false).unwrap(),
+                b"binary"
+            );
+
+            let buffer = tarball(&[("README.md", "readme"), ("tool", "binary")]);
+            assert!(read_tarball_binary(&buffer, None, false).is_err());
+        }
+    }
+
+    mod direct_url {
+        use std::io::Write;
+
+        use zip::{write::FileOptions, DateTime, ZipWriter};
+
+        use super::*;
+        use crate::{
+            config::ConfigFile,
+            tool_provider::test_server::{self, MockResponse},
+        };
+
+        /// A zip archive of a tool, the same bytes every time so that tests
+        /// can compare checksums of separately built archives.
+        pub(super) fn zipped_tool() -> Vec<u8> {
+            let mut writer = ZipWriter::new(Cursor::new(Vec::new()));
+            let options = FileOptions::default().last_modified_time(DateTime::default());
+            writer.start_file("tool", options).unwrap();
+            writer.write_all(b"#!/bin/sh\necho tool\n").unwrap();
+            writer.finish().unwrap().into_inner()
+        }
+
+        #[test]
+        fn installs_from_direct_url_without_listing_releases() {
+            let server =
+                test_server::serve(vec![MockResponse::with_bytes("200 OK", zipped_tool())]);
+            let url = format!("{}/tool-linux.zip", server);
+            let tool = ToolSpec::from_value(
+                &toml::from_str(&format!("url = \"{}\"\nversion = \"1.2.0\"", url)).unwrap(),
+                &ConfigFile::new_with_defaults().hosts,
+            )
+            .unwrap();
+
+            let foreman_root = tempdir().expect("unable to create temporary directory");
+            let mut cache = linux_cache(&foreman_root);
+            cache.paths.create_all().unwrap();
+            let providers =
+                ToolProvider::new(&cache.paths.clone(), &ConfigFile::new_with_defaults());
+
+            let installation = cache.install(&tool, &providers).unwrap();
+            assert_eq!(installation.version, Version::new(1, 2, 0));
+            let download = installation.download.unwrap();
+            assert_eq!(download.asset.as_deref(), Some("tool-linux.zip"));
+            assert_eq!(download.url, url);
+            assert_eq!(
+                std::fs::read_to_string(cache.get_tool_exe_path(&tool, &installation.version))
+                    .unwrap(),
+                "#!/bin/sh\necho tool\n"
+            );
+
+            // The server answered its only response: the installed version is
+            // used from now on.
+            assert_eq!(
+                cache.download_if_necessary(&tool, &providers).unwrap(),
+                Version::new(1, 2, 0)
+            );
+        }
+
+        #[test]
+        fn missing_or_empty_binary_is_downloaded_again() {
+            let server = test_server::serve(vec![
+                MockResponse::with_bytes("200 OK", zipped_tool()),
+                MockResponse::with_bytes("200 OK", zipped_tool()),
+            ]);
+            let tool = ToolSpec::from_value(
+                &toml::from_str(&format!(
+                    "url = \"{}/tool-linux.zip\"\nversion = \"1.2.0\"",
+                    server
+                ))
+                .unwrap(),
+                &ConfigFile::new_with_defaults().hosts,
+            )
+            .unwrap();
+
+            let foreman_root = tempdir().expect("unable to create temporary directory");
+            let mut cache = linux_cache(&foreman_root);
+            cache.paths.create_all().unwrap();
+            let providers =
+                ToolProvider::new(&cache.paths.clone(), &ConfigFile::new_with_defaults());
+            let version = Version::new(1, 2, 0);
+            let tool_path = cache.get_tool_exe_path(&tool, &version);
+
+            // The index lists the version, but its binary is gone.
+            cache.tools.entry(tool.cache_key()).or_default().versions =
+                BTreeSet::from([version.clone()]);
+            let installation = cache.install(&tool, &providers).unwrap();
+            assert!(installation.download.is_some());
+            assert!(tool_path.is_file());
+
+            std::fs::write(&tool_path, b"").unwrap();
+            let installation = cache.install(&tool, &providers).unwrap();
+            assert!(installation.download.is_some());
+            assert_eq!(
+                std::fs::read_to_string(&tool_path).unwrap(),
+                "#!/bin/sh\necho tool\n"
+            );
+
+            let installation = cache.install(&tool, &providers).unwrap();
+            assert_eq!(installation.download, None);
+        }
+    }
+
+    mod checksums {
+        use super::*;
+        use crate::{
+            config::ConfigFile,
+            tool_provider::test_server::{self, MockResponse},
+        };
+
+        /// A tool served by a mock server, and a cache in `foreman_root`
+        /// verifying downloads against `checksums`.
+        fn setup(
+            foreman_root: &tempfile::TempDir,
+            checksums: &str,
+        ) -> (ToolSpec, ToolCache, ToolProvider) {
+            let server = test_server::serve(vec![MockResponse::with_bytes(
+                "200 OK",
+                direct_url::zipped_tool(),
+            )]);
+            let tool = ToolSpec::from_value(
+                &toml::from_str(&format!(
+                    "url = \"{}/tool-linux.zip\"\nversion = \"1.2.0\"",
+                    server
+                ))
+                .unwrap(),
+                &ConfigFile::new_with_defaults().hosts,
+            )
+            .unwrap();
+
+            let checksums_path = foreman_root
+                .path()
+                .join(crate::checksums::CHECKSUMS_FILE_NAME);
+            std::fs::write(
+                &checksums_path,
+                format!("[\"{}\".\"1.2.0\"]\n{}", tool.source(), checksums),
+            )
+            .unwrap();
+
+            let mut cache = linux_cache(foreman_root);
+            cache.paths.create_all().unwrap();
+            cache.set_checksums(Checksums::load_from(&[checksums_path]).unwrap());
+            let providers =
+                ToolProvider::new(&cache.paths.clone(), &ConfigFile::new_with_defaults());
+
+            (tool, cache, providers)
+        }
+
+        fn tool_sha256() -> String {
+            format!("{:x}", Sha256::digest(direct_url::zipped_tool()))
+        }
+
+        #[test]
+        fn matching_checksum_installs() {
+            let foreman_root = tempdir().expect("unable to create temporary directory");
+            let (tool, mut cache, providers) =
+                setup(&foreman_root, &format!("linux = \"{}\"", tool_sha256()));
+            cache.set_require_checksums(true);
+
+            let installation = cache.install(&tool, &providers).unwrap();
+            assert_eq!(installation.download.unwrap().sha256, tool_sha256());
+            assert!(cache.contains(&tool, &Version::new(1, 2, 0)));
+        }
+
+        #[test]
+        fn mismatching_checksum_is_rejected() {
+            let foreman_root = tempdir().expect("unable to create temporary directory");
+            let (tool, mut cache, providers) = setup(&foreman_root, "linux = \"0123abcd\"");
+
+            match cache.install(&tool, &providers) {
+                Err(ForemanError::ChecksumMismatch {
+                    expected, actual, ..
+                }) => {
+                    assert_eq!(expected, "0123abcd");
+                    assert_eq!(actual, tool_sha256());
+                }
+                other => panic!("expected a checksum mismatch, got {:?}", other),
+            }
+            assert!(!cache.contains(&tool, &Version::new(1, 2, 0)));
+            assert!(!cache
+                .get_tool_exe_path(&tool, &Version::new(1, 2, 0))
+                .exists());
+        }
+
+        #[test]
+        fn missing_checksum_is_only_rejected_when_required() {
+            let foreman_root = tempdir().expect("unable to create temporary directory");
+            let (tool, mut cache, providers) = setup(&foreman_root, "windows = \"0123abcd\"");
+
+            cache.set_require_checksums(true);
+            assert!(matches!(
+                cache.install(&tool, &providers),
+                Err(ForemanError::ChecksumMissing { .. })
+            ));
+
+            // Nothing was downloaded, so the server still has its response.
+            cache.set_require_checksums(false);
+            assert!(cache.install(&tool, &providers).is_ok());
+        }
+
+        fn install_with_published_checksum(
+            foreman_root: &tempfile::TempDir,
+            checksum: &str,
+        ) -> (ForemanResult<Installation>, ToolCache) {
+            let mut cache = linux_cache(foreman_root);
+            cache.paths.create_all().unwrap();
+            cache.set_require_checksums(true);
+            // The release publishes `tool-linux.zip.sha256` next to
+            // `tool-linux.zip`, with `checksum` as its contents.
+            let providers = FixedReleases::new(vec!["v1.2.0"])
+                .assets(&["tool-linux.zip.sha256", "tool-linux.zip"])
+                .payload("tool-linux.zip.sha256", checksum.as_bytes().to_vec())
+                .payload("tool-linux.zip", direct_url::zipped_tool())
+                .providers();
+
+            let installation = cache.install(&github_tool("user/tool", "1.2.0"), &providers);
+            (installation, cache)
+        }
+
+        #[test]
+        fn published_checksum_is_verified() {
+            let foreman_root = tempdir().expect("unable to create temporary directory");
+            let (installation, cache) = install_with_published_checksum(
+                &foreman_root,
+                &format!("{}  tool-linux.zip\n", tool_sha256()),
+            );
+
+            // The checksum asset itself is never picked as the tool's asset.
+            let download = installation.unwrap().download.unwrap();
+            assert_eq!(download.asset.as_deref(), Some("tool-linux.zip"));
+            assert_eq!(download.sha256, tool_sha256());
+            assert!(cache.contains(&github_tool("user/tool", "1.2.0"), &Version::new(1, 2, 0)));
+        }
+
+        #[test]
+        fn mismatching_published_checksum_is_rejected() {
+            let foreman_root = tempdir().expect("unable to create temporary directory");
+            let published = format!("{:x}", Sha256::digest(b"another asset"));
+            let (installation, cache) = install_with_published_checksum(&foreman_root, &published);
+
+            match installation {
+                Err(ForemanError::ChecksumMismatch {
+                    origin,
+                    expected,
+                    actual,
+                    ..
+                }) => {
+                    assert_eq!(origin, "tool-linux.zip.sha256");
+                    assert_eq!(expected, published);
+                    assert_eq!(actual, tool_sha256());
+                }
+                other => panic!("expected a checksum mismatch, got {:?}", other),
+            }
+            assert!(!cache.contains(&github_tool("user/tool", "1.2.0"), &Version::new(1, 2, 0)));
+        }
+    }
+
+    mod binary_format {
+        use std::io::Write;
+
+        use zip::{write::FileOptions, ZipWriter};
+
+        use super::*;
+        use crate::{
+            config::ConfigFile,
+            tool_provider::test_server::{self, MockResponse},
+        };
+
+        /// Installs a zipped ELF binary with a cache in `foreman_root`
+        /// expecting Mach-O binaries, like on macOS, over the binary
+        /// `installed` when given.
+        fn install_elf_on_macos(
+            foreman_root: &tempfile::TempDir,
+            strict: bool,
+            installed: Option<&[u8]>,
+        ) -> (ForemanResult<Installation>, PathBuf) {
+            let mut writer = ZipWriter::new(Cursor::new(Vec::new()));
+            writer.start_file("tool", FileOptions::default()).unwrap();
+            writer.write_all(b"\x7fELF\x02\x01\x01\x00").unwrap();
+            let asset = writer.finish().unwrap().into_inner();
+
+            let server = test_server::serve(vec![MockResponse::with_bytes("200 OK", asset)]);
+            let tool = ToolSpec::from_value(
+                &toml::from_str(&format!(
+                    "url = \"{}/tool-linux.zip\"\nversion = \"1.2.0\"",
+                    server
+                ))
+                .unwrap(),
+                &ConfigFile::new_with_defaults().hosts,
+            )
+            .unwrap();
+
+            let mut cache = linux_cache(foreman_root);
+            cache.paths.create_all().unwrap();
+            cache.binary_format = Some(BinaryFormat::MachO);
+            cache.set_strict_binary_format(strict);
+            let providers =
+                ToolProvider::new(&cache.paths.clone(), &ConfigFile::new_with_defaults());
+            let tool_path = cache.get_tool_exe_path(&tool, &Version::new(1, 2, 0));
+            if let Some(installed) = installed {
+                std::fs::write(&tool_path, installed).unwrap();
+            }
+
+            let result = cache.install(&tool, &providers);
+            (result, tool_path)
+        }
+
+        #[test]
+        fn wrong_platform_binary_is_only_a_warning_by_default() {
+            let foreman_root = tempdir().expect("unable to create temporary directory");
+            let (result, tool_path) = install_elf_on_macos(&foreman_root, false, None);
+            assert!(result.is_ok());
+            assert!(tool_path.exists());
+        }
+
+        #[test]
+        fn wrong_platform_binary_fails_when_strict() {
+            let foreman_root = tempdir().expect("unable to create temporary directory");
+            let (result, tool_path) = install_elf_on_macos(&foreman_root, true, None);
+            match result {
+                Err(ForemanError::WrongBinaryFormat {
+                    expected, actual, ..
+                }) => {
+                    assert_eq!(expected, BinaryFormat::MachO);
+                    assert_eq!(actual, BinaryFormat::Elf);
+                }
+                other => panic!("expected a wrong binary format, got {:?}", other),
+            }
+            assert!(!tool_path.exists());
+        }
+
+        #[test]
+        fn rejected_binary_keeps_the_installed_one() {
+            let foreman_root = tempdir().expect("unable to create temporary directory");
+            let (result, tool_path) =
+                install_elf_on_macos(&foreman_root, true, Some(b"#!/bin/sh\n"));
+            assert!(matches!(
+                result,
+                Err(ForemanError::WrongBinaryFormat { .. })
+            ));
+            assert_eq!(std::fs::read(&tool_path).unwrap(), b"#!/bin/sh\n");
+        }
+    }
+
+    mod run_error {
+        use std::io;
+
+        use super::*;
+
+        fn message(phase: RunPhase) -> String {
+            let err = RunError::new(phase, io::Error::from_raw_os_error(1));
+            run_error(
+                err,
+                &github_tool("JohnnyMorganz/StyLua", "=0.14.3"),
+                Path::new("/foreman/tools/stylua"),
+            )
+            .to_string()
+        }
+
+        #[test]
+        fn runtime_failure_points_at_the_sandbox() {
+            let message = message(RunPhase::Runtime);
+            assert!(message.starts_with(
+                "unable to create the async runtime Foreman needs to run \
+                `github.com/JohnnyMorganz/StyLua@=0.14.3` at `/foreman/tools/stylua`"
+            ));
+            assert!(message.contains("sandbox"));
+            assert!(message.ends_with(&io::Error::from_raw_os_error(1).to_string()));
+            assert!(!message.contains("this is an error in Foreman"));
+        }
+
+        #[test]
+        fn only_wait_failures_blame_foreman() {
+            assert!(!message(RunPhase::Signals).contains("this is an error in Foreman"));
+            assert!(!message(RunPhase::Spawn).contains("this is an error in Foreman"));
+            assert!(message(RunPhase::Wait).contains("this is an error in Foreman"));
+        }
+    }
+
+    mod find_binary {
+        use std::io::Write;
+
+        use zip::{write::FileOptions, ZipWriter};
+
+        use super::*;
+
+        fn archive(names: &[&str]) -> ZipArchive<Cursor<Vec<u8>>> {
+            let mut writer = ZipWriter::new(Cursor::new(Vec::new()));
+            for name in names {
+                writer.start_file(*name, FileOptions::default()).unwrap();
+                writer.write_all(name.as_bytes()).unwrap();
+            }
+            ZipArchive::new(writer.finish().unwrap()).unwrap()
+        }
+
+        #[test]
+        fn defaults_to_first_entry() {
+            let mut archive = archive(&["README.md", "stylua"]);
+            assert_eq!(find_binary(&mut archive, None), Ok(0));
+        }
+
+        #[test]
+        fn matches_version_suffixed_binary() {
+            let mut archive = archive(&["README.md", "stylua-0.14.3"]);
+            assert_eq!(find_binary(&mut archive, Some("stylua*")), Ok(1));
+        }
+
+        #[test]
+        fn matches_file_name_in_directory() {
+            let mut archive = archive(&["stylua-0.14.3/LICENSE", "stylua-0.14.3/stylua"]);
+            assert_eq!(find_binary(&mut archive, Some("stylua")), Ok(1));
+        }
+
+        #[test]
+        fn ambiguous_glob_is_an_error() {
+            let mut archive = archive(&["stylua-0.14.3", "stylua-0.14.3.sig"]);
+            assert_eq!(
+                find_binary(&mut archive, Some("stylua*")),
+                Err(
+                    "`bin = \"stylua*\"` is ambiguous, it matches: stylua-0.14.3, stylua-0.14.3.sig"
+                        .to_owned()
+                )
+            );
+        }
+
+        #[test]
+        fn no_match_is_an_error() {
+            let mut archive = archive(&["README.md"]);
+            assert!(find_binary(&mut archive, Some("stylua*")).is_err());
+        }
+    }
+
+    mod tarball_assets {
+        use super::*;
+
+        #[test]
+        fn extracts_first_regular_file_without_bin() {
+            let buffer = tarball(&[
+                ("stylua-0.14.3/stylua", "binary"),
+                ("stylua-0.14.3/LICENSE", "license"),
+            ]);
+
+            assert_eq!(
+                read_tarball_binary(&buffer, None, true),
+                Ok(b"binary".to_vec())
+            );
+            assert_eq!(
+                read_tarball_binary(&buffer, Some("LICENSE"), true),
+                Ok(b"license".to_vec())
+            );
+        }
+
+        #[test]
+        fn invalid_tarball_mentions_the_archive_type() {
+            let err = read_tarball_binary(b"not a tarball", None, true).unwrap_err();
+            assert!(err.contains("tar.gz archive"), "{}", err);
+
+            let err =
+                read_tarball_binary(&tarball(&[("stylua-0.14.3/", "")]), None, true).unwrap_err();
+            assert_eq!(err, "the tar.gz archive contains no files");
+        }
+
+        #[test]
+        fn archive_kind_follows_contents_over_name() {
+            let gzip = tarball(&[("stylua", "binary")]);
+
+            assert_eq!(
+                ArchiveKind::of(Some("stylua-linux.tar.gz"), b"PK"),
+                ArchiveKind::Zip
+            );
+            assert_eq!(
+                ArchiveKind::of(Some("stylua-linux.zip"), &gzip),
+                ArchiveKind::TarGz
+            );
+            assert_eq!(
+                ArchiveKind::of(Some("stylua-linux.zip"), b"\x7fELF\x02\x01\x01"),
+                ArchiveKind::Raw
+            );
+            assert_eq!(ArchiveKind::of(None, &gzip), ArchiveKind::TarGz);
+            assert_eq!(
+                ArchiveKind::of(Some("stylua-linux"), b"PK"),
+                ArchiveKind::Zip
+            );
+        }
+
+        #[test]
+        fn unrecognized_contents_follow_asset_name() {
+            assert_eq!(
+                ArchiveKind::of(Some("stylua-linux.TGZ"), b"truncated"),
+                ArchiveKind::TarGz
+            );
+            assert_eq!(
+                ArchiveKind::of(Some("stylua-linux.zip"), b"truncated"),
+                ArchiveKind::Zip
+            );
+            assert_eq!(
+                ArchiveKind::of(Some("stylua-linux"), b"truncated"),
+                ArchiveKind::Raw
+            );
+        }
+
+        #[test]
+        fn other_archive_formats_are_unsupported() {
+            for (name, contents, format) in [
+                ("tool-linux.tar.xz", &b"\xfd7zXZ\x00\x00"[..], "xz"),
+                ("tool-linux.tar.bz2", b"BZh91AY&SY", "bzip2"),
+                ("tool-linux.7z", b"7z\xbc\xaf\x27\x1c", "7z"),
+                ("tool-linux.rar", b"Rar!\x1a\x07\x00", "rar"),
+                // Named like a supported format, but not one.
+                ("tool-linux.zip", b"Rar!\x1a\x07\x00", "rar"),
+                // Recognized from the name alone.
+                ("tool-linux.tar.xz", b"truncated", "xz"),
+                ("tool-linux.TBZ2", b"truncated", "bzip2"),
+            ] {
+                assert_eq!(
+                    ArchiveKind::of(Some(name), contents),
+                    ArchiveKind::Unsupported(format),
+                    "{}",
+                    name
+                );
+            }
+        }
+
+        #[test]
+        fn gzip_named_zip_is_extracted_as_tarball() {
+            let foreman_root = tempdir().expect("unable to create temporary directory");
+            let mut cache = linux_cache(&foreman_root);
+            cache.paths.create_all().unwrap();
+            let tool = github_tool("johnnymorganz/stylua", "0.14.3");
+            // A gzipped tarball named like a zip archive.
+            let providers = FixedReleases::new(vec!["v0.14.3"])
+                .assets(&["stylua-linux.zip"])
+                .payload(
+                    "stylua-linux.zip",
+                    tarball(&[("stylua-0.14.3/stylua", "binary")]),
+                )
+                .providers();
+
+            let version = cache.download_if_necessary(&tool, &providers).unwrap();
+
+            let tool_path = cache.get_tool_exe_path(&tool, &version);
+            assert_eq!(std::fs::read(tool_path).unwrap(), b"binary");
+        }
+    }
+
+    mod raw_assets {
+        use super::*;
+
+        const SCRIPT: &[u8] = b"#!/bin/sh\necho tool\n";
+
+        #[test]
+        fn bare_binary_is_installed_as_is() {
+            let foreman_root = tempdir().expect("unable to create temporary directory");
+            let mut cache = linux_cache(&foreman_root);
+            cache.paths.create_all().unwrap();
+            let tool = github_tool("user/tool", "1.0.0");
+            // The only release asset is the tool's binary itself.
+            let providers = FixedReleases::new(vec!["v1.0.0"])
+                .assets(&["tool-linux-x86_64"])
+                .payload("tool-linux-x86_64", SCRIPT.to_vec())
+                .providers();
+
+            let version = cache.download_if_necessary(&tool, &providers).unwrap();
+
+            let tool_path = cache.get_tool_exe_path(&tool, &version);
+            assert_eq!(std::fs::read(&tool_path).unwrap(), SCRIPT);
+            // The binary is written to a temporary file that is renamed.
+            assert_eq!(
+                std::fs::read_dir(cache.paths.tools_dir()).unwrap().count(),
+                1
+            );
+
+            #[cfg(unix)]
+            {
+                use std::os::unix::fs::PermissionsExt;
+
+                let mode = std::fs::metadata(&tool_path).unwrap().permissions().mode();
+                assert_ne!(mode & 0o111, 0);
+            }
+        }
+
+        #[test]
+        fn archives_are_recognized_by_their_first_bytes() {
+            assert_eq!(
+                ArchiveKind::of(Some("tool-linux"), SCRIPT),
+                ArchiveKind::Raw
+            );
+            assert_eq!(
+                ArchiveKind::of(Some("tool-windows.exe"), b"MZ\x90\x00"),
+                ArchiveKind::Raw
+            );
+            assert_eq!(
+                ArchiveKind::of(Some("tool-linux"), b"PK\x03\x04"),
+                ArchiveKind::Zip
+            );
+        }
+    }
+
+    #[cfg(unix)]
+    mod verify {
+        use super::*;
+
+        /// Succeeds with `--version` only.
+        const SCRIPT: &[u8] = b"#!/bin/sh
+if [ \"$1\" = \"--version\" ]; then echo \"tool 1.0.0\"; exit 0; fi
+if [ \"$1\" = \"--hang\" ]; then sleep 5; exit 0; fi
+echo \"unknown flag $1\" >&2
+exit 3
+";
+
+        fn verified_tool(verify: &str) -> ToolSpec {
+            ToolSpec::from_value(
+                &toml::from_str(&format!(
+                    "github = \"user/tool\"\nversion = \"1.0.0\"\nverify = \"{}\"",
+                    verify
+                ))
+                .unwrap(),
+                &crate::config::ConfigFile::new_with_defaults().hosts,
+            )
+            .unwrap()
+        }
+
+        fn script_releases() -> ToolProvider {
+            FixedReleases::new(vec!["v1.0.0"])
+                .assets(&["tool-linux"])
+                .payload("tool-linux", SCRIPT.to_vec())
+                .providers()
+        }
+
+        fn install_verified_by(
+            verify: &str,
+        ) -> (
+            tempfile::TempDir,
+            ToolCache,
+            ToolSpec,
+            ForemanResult<Version>,
+        ) {
+            let foreman_root = tempdir().expect("unable to create temporary directory");
+            let mut cache = linux_cache(&foreman_root);
+            cache.paths.create_all().unwrap();
+            let tool = verified_tool(verify);
+
+            let result = cache.download_if_necessary(&tool, &script_releases());
+            (foreman_root, cache, tool, result)
+        }
+
+        #[test]
+        fn verified_release_is_installed() {
+            let (_foreman_root, cache, tool, result) = install_verified_by("--version");
+
+            let version = result.unwrap();
+            assert!(cache.installed_path(&tool, &version).is_some());
+        }
+
+        #[test]
+        fn failed_verification_fails_the_tool() {
+            let (_foreman_root, cache, tool, result) = install_verified_by("--broken");
+
+            match result.unwrap_err() {
+                ForemanError::VerificationFailed { code, output, .. } => {
+                    assert_eq!(code, 3);
+                    assert_eq!(output, "unknown flag --broken");
+                }
+                err => panic!("expected a verification failure, got {:?}", err),
+            }
+            let version = Version::new(1, 0, 0);
+            assert!(cache.versions_for(&tool).is_empty());
+            assert!(!cache.get_tool_exe_path(&tool, &version).exists());
+        }
+
+        #[test]
+        fn slow_verification_counts_towards_the_timeout() {
+            let foreman_root = tempdir().expect("unable to create temporary directory");
+            let mut cache = linux_cache(&foreman_root);
+            cache.paths.create_all().unwrap();
+            let tool = verified_tool("--hang");
+            let providers = Arc::new(script_releases());
+
+            assert!(matches!(
+                cache.download_if_necessary_within(&tool, &providers, Duration::from_millis(500)),
+                Err(ForemanError::ToolTimedOut { .. })
+            ));
+            assert!(cache.versions_for(&tool).is_empty());
+        }
     }
 
     mod load {
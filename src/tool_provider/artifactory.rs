@@ -1,176 +1,269 @@
 //! Slice of Artifactory's API that Foreman consumes.
 
-use super::{Release, ReleaseAsset, ToolProviderImpl};
+use super::{http, DownloadOptions, Release, ReleaseAsset, ToolProviderImpl};
 use crate::{
+    config::AuthPlacement,
     error::{ForemanError, ForemanResult},
     paths::ForemanPaths,
 };
-use artiaa_auth;
-use reqwest::{
-    blocking::Client,
-    header::{AUTHORIZATION, USER_AGENT},
-};
-use serde::{Deserialize, Serialize};
+use reqwest::{blocking::RequestBuilder, header::AUTHORIZATION};
+use serde::Deserialize;
 use std::collections::HashMap;
 use url::Url;
 
 #[derive(Debug)]
 pub struct ArtifactoryProvider {
     paths: ForemanPaths,
+    /// Where requests to each host carry its token, keyed by the host's URL.
+    /// Hosts missing from the map use a bearer token.
+    auth_placements: HashMap<Url, AuthPlacement>,
 }
 
 impl ArtifactoryProvider {
-    pub fn new(paths: ForemanPaths) -> Self {
-        Self { paths }
+    pub fn new(paths: ForemanPaths, auth_placements: HashMap<Url, AuthPlacement>) -> Self {
+        Self {
+            paths,
+            auth_placements,
+        }
+    }
+
+    /// Adds the token for `url`, if there is one, to a request to `url`, where
+    /// the host serving `url` expects it.
+    fn authenticate(&self, builder: RequestBuilder, url: &Url) -> ForemanResult<RequestBuilder> {
+        let tokens = artiaa_auth::Tokens::load(&self.paths.artiaa_path()?)
+            .map_err(|error| ForemanError::ArtiAAError { error })?;
+
+        let credentials = match tokens.get_credentials(url) {
+            Some(credentials) => credentials,
+            None => return Ok(builder),
+        };
+
+        let placement = self
+            .auth_placements
+            .iter()
+            .find(|(host, _placement)| url.as_str().starts_with(host.as_str()))
+            .map(|(_host, placement)| placement)
+            .unwrap_or(&AuthPlacement::Bearer);
+
+        Ok(place_token(builder, placement, &credentials.token))
     }
 }
 
-impl ToolProviderImpl for ArtifactoryProvider {
-    fn get_releases(&self, repo: &str, host: &Url) -> ForemanResult<Vec<Release>> {
-        let client = Client::new();
+impl ArtifactoryProvider {
+    /// Lists the direct children of `path` in `repo` with the folder info
+    /// API, which unlike a deep file listing only goes one level down, so
+    /// that large repositories do not produce huge responses.
+    fn list_folder(&self, repo: &str, host: &Url, path: &str) -> ForemanResult<Vec<FolderChild>> {
+        let client = http::client();
 
-        let url = format!("{}artifactory/api/storage/{}", host, repo);
-        let params = vec![("list", ""), ("deep", "1")];
-        let mut builder = client
-            .get(&url)
-            .header(USER_AGENT, "Roblox/foreman")
-            .query(&params);
+        let url = format!("{}artifactory/api/storage/{}{}", host, repo, path);
+        let builder = self.authenticate(client.get(&url), host)?;
+        let response: FolderInfo = http::read_json(http::send(client, builder)?, &url)?;
 
-        let tokens = artiaa_auth::Tokens::load(&self.paths.artiaa_path()?)
-            .map_err(|error| ForemanError::ArtiAAError { error })?;
+        Ok(response.children)
+    }
+}
 
-        if let Some(credentials) = tokens.get_credentials(host) {
-            builder = builder.header(AUTHORIZATION, format!("Bearer {}", credentials.token));
-        }
+impl ToolProviderImpl for ArtifactoryProvider {
+    /// Lists the version folders of `repo`. Their assets are listed on
+    /// demand, only for the versions considered while resolving a tool.
+    fn get_releases(&self, repo: &str, host: &Url) -> ForemanResult<Vec<Release>> {
         log::debug!("Downloading artifactory releases for {}", repo);
-        let response_body = builder
-            .send()
-            .map_err(ForemanError::request_failed)?
-            .text()
-            .map_err(ForemanError::request_failed)?;
-
-        let response: ArtifactoryResponse =
-            serde_json::from_str(&response_body).map_err(|err| {
-                ForemanError::unexpected_response_body(err.to_string(), response_body, url)
-            })?;
-
-        let mut release_map: HashMap<&str, Vec<ArtifactoryAsset>> = HashMap::new();
-        for file in &response.files {
-            let uri = file.uri.split("/");
-            // file.uri should look something like /<version>/<artifact-name>, so uri will be ["", <version>, <artifact-name]
-            // we should skip files that do not follow the expected path
-            let Some((version, asset_name)) = get_version_and_asset_name(uri) else {
-                log::debug!(
-                    "Skipping '{}', does not match expected file path <version>/<asset_name>",
-                    file.uri
-                );
-                continue;
-            };
-
-            let asset_url = format!("{}artifactory/{}/{}/{}", host, repo, version, asset_name);
-
-            let asset = ArtifactoryAsset {
-                url: asset_url,
-                name: asset_name.to_string(),
-            };
-
-            release_map.entry(version).or_insert(Vec::new()).push(asset);
-        }
+        let children = self.list_folder(repo, host, "")?;
 
-        let releases: Vec<ArtifactoryRelease> = release_map
-            .into_iter()
-            .map(|(version, assets)| ArtifactoryRelease {
-                tag_name: version.to_string(),
-                assets,
+        Ok(child_names(children, true)
+            .map(|version| Release {
+                tag_name: version,
+                prerelease: false,
+                assets: Vec::new(),
+                source_tarball: None,
             })
-            .collect();
+            .collect())
+    }
 
-        Ok(releases.into_iter().map(Into::into).collect())
+    fn lists_assets_on_demand(&self) -> bool {
+        true
     }
 
-    fn download_asset(&self, url: &str) -> ForemanResult<Vec<u8>> {
-        let client = Client::new();
-        let artifactory_url = Url::parse(url).unwrap();
+    fn get_release_assets(
+        &self,
+        repo: &str,
+        host: &Url,
+        tag_name: &str,
+    ) -> ForemanResult<Vec<ReleaseAsset>> {
+        log::debug!("Downloading artifactory assets of {} {}", repo, tag_name);
+        let children = self.list_folder(repo, host, &format!("/{}", tag_name))?;
 
-        let mut builder = client.get(url).header(USER_AGENT, "Roblox/foreman");
+        Ok(child_names(children, false)
+            .map(|name| ReleaseAsset {
+                url: format!("{}artifactory/{}/{}/{}", host, repo, tag_name, name),
+                name,
+            })
+            .collect())
+    }
 
-        let tokens = artiaa_auth::Tokens::load(&self.paths.artiaa_path()?).unwrap();
-        if let Some(credentials) = tokens.get_credentials(&artifactory_url) {
-            builder = builder.header(AUTHORIZATION, format!("Bearer {}", credentials.token));
-        }
+    fn download_asset(&self, url: &str, options: &DownloadOptions) -> ForemanResult<Vec<u8>> {
+        let client = http::client();
+        let artifactory_url = Url::parse(url).unwrap();
+
+        let builder = self.authenticate(client.get(url), &artifactory_url)?;
 
         log::debug!("Downloading release asset {}", url);
-        let mut response = builder.send().map_err(ForemanError::request_failed)?;
+        let response = http::send(client, builder)?;
 
-        let mut output = Vec::new();
-        response
-            .copy_to(&mut output)
-            .map_err(ForemanError::request_failed)?;
-        Ok(output)
+        http::read_asset(response, url, options)
     }
 }
 
-fn get_version_and_asset_name<'a, I>(mut uri: I) -> Option<(&'a str, &'a str)>
-where
-    I: Iterator<Item = &'a str>,
-{
-    let Some(empty_string) = uri.next() else {
-        return None;
-    };
+/// Adds `token` to a request where `placement` says.
+fn place_token(builder: RequestBuilder, placement: &AuthPlacement, token: &str) -> RequestBuilder {
+    match placement {
+        AuthPlacement::Bearer => builder.header(AUTHORIZATION, format!("Bearer {}", token)),
+        AuthPlacement::Header(name) => builder.header(name.as_str(), token),
+        AuthPlacement::Query(name) => builder.query(&[(name.as_str(), token)]),
+    }
+}
+
+/// The names of the folders, or of the files, among `children`.
+fn child_names(children: Vec<FolderChild>, folders: bool) -> impl Iterator<Item = String> {
+    children
+        .into_iter()
+        .filter(move |child| child.folder == folders)
+        .filter_map(|child| {
+            // Children URIs look like `/<name>`.
+            let name = child.uri.strip_prefix('/').unwrap_or(&child.uri);
+            if name.is_empty() || name.contains('/') {
+                log::debug!("Skipping '{}', expected a direct child", child.uri);
+                return None;
+            }
+            Some(name.to_owned())
+        })
+}
+
+#[derive(Debug, Deserialize)]
+struct FolderInfo {
+    #[serde(default)]
+    children: Vec<FolderChild>,
+}
+
+#[derive(Debug, Deserialize)]
+struct FolderChild {
+    uri: String,
+    folder: bool,
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::tool_provider::http::test_server::{self, MockResponse};
 
-    if empty_string != "" {
-        return None;
+    /// The root of the mock server, standing in for an Artifactory host.
+    fn serve(responses: Vec<MockResponse>) -> Url {
+        Url::parse(&test_server::serve(responses))
+            .unwrap()
+            .join("/")
+            .unwrap()
     }
 
-    let Some(version) = uri.next() else {
-        return None;
-    };
+    fn provider() -> ArtifactoryProvider {
+        ArtifactoryProvider::new(ForemanPaths::new(std::env::temp_dir()), HashMap::new())
+    }
 
-    let Some(asset_name) = uri.next() else {
-        return None;
-    };
+    #[test]
+    fn lists_version_folders_then_assets_of_one_version() {
+        let host = serve(vec![
+            MockResponse::new(
+                "200 OK",
+                r#"{
+                    "repo": "tools",
+                    "path": "/",
+                    "children": [
+                        { "uri": "/1.0.0", "folder": true },
+                        { "uri": "/1.1.0", "folder": true },
+                        { "uri": "/README.md", "folder": false }
+                    ]
+                }"#,
+            ),
+            MockResponse::new(
+                "200 OK",
+                r#"{
+                    "repo": "tools",
+                    "path": "/1.1.0",
+                    "children": [
+                        { "uri": "/tool-linux.zip", "folder": false },
+                        { "uri": "/tool-windows.zip", "folder": false },
+                        { "uri": "/symbols", "folder": true }
+                    ]
+                }"#,
+            ),
+        ]);
+        let provider = provider();
 
-    if uri.next().is_some() {
-        return None;
+        let releases = provider.get_releases("tools", &host).unwrap();
+        let tag_names: Vec<&str> = releases
+            .iter()
+            .map(|release| release.tag_name.as_str())
+            .collect();
+        assert_eq!(tag_names, vec!["1.0.0", "1.1.0"]);
+        assert!(releases.iter().all(|release| release.assets.is_empty()));
+        assert!(provider.lists_assets_on_demand());
+
+        let assets = provider
+            .get_release_assets("tools", &host, "1.1.0")
+            .unwrap();
+        assert_eq!(
+            assets,
+            vec![
+                ReleaseAsset {
+                    url: format!("{}artifactory/tools/1.1.0/tool-linux.zip", host),
+                    name: "tool-linux.zip".to_owned(),
+                },
+                ReleaseAsset {
+                    url: format!("{}artifactory/tools/1.1.0/tool-windows.zip", host),
+                    name: "tool-windows.zip".to_owned(),
+                },
+            ]
+        );
     }
 
-    Some((version, asset_name))
-}
+    #[test]
+    fn empty_folder_has_no_children() {
+        let host = serve(vec![MockResponse::new(
+            "200 OK",
+            r#"{ "repo": "tools", "path": "/2.0.0" }"#,
+        )]);
 
-#[derive(Debug, Serialize, Deserialize)]
-struct ArtifactoryResponse {
-    files: Vec<ArtifactoryResponseFiles>,
-}
-#[derive(Debug, Serialize, Deserialize)]
-struct ArtifactoryResponseFiles {
-    uri: String,
-}
-#[derive(Debug)]
-struct ArtifactoryRelease {
-    tag_name: String,
-    assets: Vec<ArtifactoryAsset>,
-}
-#[derive(Debug)]
-struct ArtifactoryAsset {
-    url: String,
-    name: String,
-}
+        let assets = provider()
+            .get_release_assets("tools", &host, "2.0.0")
+            .unwrap();
+        assert!(assets.is_empty());
+    }
 
-impl From<ArtifactoryRelease> for Release {
-    fn from(release: ArtifactoryRelease) -> Self {
-        Release {
-            tag_name: release.tag_name,
-            prerelease: false,
-            assets: release.assets.into_iter().map(Into::into).collect(),
-        }
+    fn request_with_token(placement: AuthPlacement) -> reqwest::blocking::Request {
+        let builder =
+            http::client().get("https://artifactory.example.com/artifactory/tools/tool.zip");
+        place_token(builder, &placement, "secret").build().unwrap()
     }
-}
 
-impl From<ArtifactoryAsset> for ReleaseAsset {
-    fn from(asset: ArtifactoryAsset) -> Self {
-        ReleaseAsset {
-            url: asset.url,
-            name: asset.name,
-        }
+    #[test]
+    fn places_token_in_bearer_header() {
+        let request = request_with_token(AuthPlacement::Bearer);
+        assert_eq!(request.headers()[AUTHORIZATION], "Bearer secret");
+        assert_eq!(request.url().query(), None);
+    }
+
+    #[test]
+    fn places_token_in_named_header() {
+        let request = request_with_token(AuthPlacement::Header("X-JFrog-Art-Api".to_owned()));
+        assert_eq!(request.headers()["x-jfrog-art-api"], "secret");
+        assert!(request.headers().get(AUTHORIZATION).is_none());
+        assert_eq!(request.url().query(), None);
+    }
+
+    #[test]
+    fn places_token_in_query_parameter() {
+        let request = request_with_token(AuthPlacement::Query("token".to_owned()));
+        assert_eq!(request.url().query(), Some("token=secret"));
+        assert!(request.headers().get(AUTHORIZATION).is_none());
     }
 }
@@ -0,0 +1,327 @@
+//! Slice of Bitbucket Cloud's API that Foreman consumes.
+//!
+//! Bitbucket has no releases: files are uploaded to a repository's downloads
+//! without being tied to a tag. Each tag is turned into a release whose
+//! assets are the downloads named after the tag's version.
+
+use reqwest::blocking::RequestBuilder;
+use serde::{de::DeserializeOwned, Deserialize, Serialize};
+
+use super::{http, DownloadOptions, Release, ReleaseAsset, ToolProviderImpl};
+use crate::{auth_store::AuthStore, error::ForemanResult, paths::ForemanPaths};
+use url::Url;
+
+const API_URL: &str = "https://api.bitbucket.org/2.0/repositories";
+
+/// The largest page size Bitbucket allows, to list releases in fewer requests.
+const PAGE_LENGTH: u32 = 100;
+
+#[derive(Debug)]
+pub struct BitbucketProvider {
+    paths: ForemanPaths,
+    /// Tokens from the `[auth]` table of the configuration.
+    config_auth: AuthStore,
+}
+
+impl BitbucketProvider {
+    pub fn new(paths: ForemanPaths, config_auth: AuthStore) -> Self {
+        Self { paths, config_auth }
+    }
+
+    /// The stored token, or the one from the configuration.
+    fn token(&self) -> ForemanResult<Option<String>> {
+        let mut auth_store = AuthStore::load(&self.paths.auth_store())?;
+        auth_store.fill_from(&self.config_auth);
+        Ok(auth_store.bitbucket)
+    }
+}
+
+impl ToolProviderImpl for BitbucketProvider {
+    fn get_releases(&self, repo: &str, _host: &Url) -> ForemanResult<Vec<Release>> {
+        let token = self.token()?;
+
+        log::debug!("Downloading bitbucket tags and downloads for {}", repo);
+        let tags: Vec<BitbucketTag> = get_pages(
+            &format!("{}/{}/refs/tags?pagelen={}", API_URL, repo, PAGE_LENGTH),
+            token.as_deref(),
+        )?;
+        let downloads: Vec<BitbucketDownload> = get_pages(
+            &format!("{}/{}/downloads?pagelen={}", API_URL, repo, PAGE_LENGTH),
+            token.as_deref(),
+        )?;
+
+        Ok(releases_from_tags(repo, tags, &downloads))
+    }
+
+    fn download_asset(&self, url: &str, options: &DownloadOptions) -> ForemanResult<Vec<u8>> {
+        let client = http::client();
+        let token = self.token()?;
+        let builder = authenticate(client.get(url), token.as_deref());
+
+        log::debug!("Downloading release asset {}", url);
+        let response = http::send(client, builder)?;
+
+        http::read_asset(response, url, options)
+    }
+}
+
+/// A page of a paginated listing. `next` is the URL of the following page,
+/// missing on the last one.
+#[derive(Debug, Serialize, Deserialize)]
+struct Page<T> {
+    values: Vec<T>,
+    next: Option<String>,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+struct BitbucketTag {
+    name: String,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+struct BitbucketDownload {
+    name: String,
+    links: DownloadLinks,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+struct DownloadLinks {
+    #[serde(rename = "self")]
+    download: Link,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+struct Link {
+    href: String,
+}
+
+/// Reads every page of the listing starting at `url`.
+fn get_pages<T: DeserializeOwned>(url: &str, token: Option<&str>) -> ForemanResult<Vec<T>> {
+    let client = http::client();
+
+    let mut values = Vec::new();
+    let mut next = Some(url.to_owned());
+    while let Some(url) = next {
+        let builder = authenticate(client.get(&url), token);
+        let page: Page<T> = http::read_json(http::send(client, builder)?, &url)?;
+
+        values.extend(page.values);
+        next = page.next;
+    }
+
+    Ok(values)
+}
+
+/// A release per tag, with the downloads whose name contains the tag's
+/// version as assets.
+fn releases_from_tags(
+    repo: &str,
+    tags: Vec<BitbucketTag>,
+    downloads: &[BitbucketDownload],
+) -> Vec<Release> {
+    tags.into_iter()
+        .map(|tag| {
+            let version = tag.name.trim_start_matches('v');
+            let assets = downloads
+                .iter()
+                .filter(|download| names_version(&download.name, version))
+                .map(|download| ReleaseAsset {
+                    url: download.links.download.href.clone(),
+                    name: download.name.clone(),
+                })
+                .collect();
+
+            Release {
+                source_tarball: Some(format!(
+                    "https://bitbucket.org/{}/get/{}.tar.gz",
+                    repo, tag.name
+                )),
+                tag_name: tag.name,
+                prerelease: false,
+                assets,
+            }
+        })
+        .collect()
+}
+
+/// Whether `name` contains `version` as a whole, so that `tool-1.2.0.zip` is
+/// named after `1.2.0` but `tool-1.2.01.zip` and `tool-11.2.0.zip` are not.
+fn names_version(name: &str, version: &str) -> bool {
+    !version.is_empty()
+        && name.match_indices(version).any(|(start, _)| {
+            let before = &name[..start];
+            let after = &name[start + version.len()..];
+
+            let continues_before = before.ends_with(|c: char| c.is_ascii_digit() || c == '.');
+            let continues_after = after.starts_with(|c: char| c.is_ascii_digit())
+                || (after.starts_with('.') && after[1..].starts_with(|c: char| c.is_ascii_digit()));
+
+            !continues_before && !continues_after
+        })
+}
+
+/// Adds the credential for `token`: an app password, written as
+/// `username:app_password`, is sent with basic authentication, and access
+/// tokens as bearer tokens.
+fn authenticate(builder: RequestBuilder, token: Option<&str>) -> RequestBuilder {
+    match token {
+        Some(token) => match token.split_once(':') {
+            Some((username, password)) => builder.basic_auth(username, Some(password)),
+            None => builder.bearer_auth(token),
+        },
+        None => builder,
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    /// Captured from `/2.0/repositories/{repo}/refs/tags`, trimmed.
+    const TAGS: &str = r#"{
+        "pagelen": 10,
+        "values": [
+            {
+                "name": "v1.2.0",
+                "type": "tag",
+                "target": {"hash": "6d5fa4e0c7d1b3a1", "type": "commit"},
+                "links": {
+                    "self": {"href": "https://api.bitbucket.org/2.0/repositories/owner/tool/refs/tags/v1.2.0"}
+                }
+            },
+            {
+                "name": "v1.1.0",
+                "type": "tag",
+                "target": {"hash": "1f0c9b8a2e4d7c3b", "type": "commit"},
+                "links": {
+                    "self": {"href": "https://api.bitbucket.org/2.0/repositories/owner/tool/refs/tags/v1.1.0"}
+                }
+            }
+        ],
+        "page": 1,
+        "next": "https://api.bitbucket.org/2.0/repositories/owner/tool/refs/tags?page=2"
+    }"#;
+
+    /// Captured from `/2.0/repositories/{repo}/downloads`, trimmed.
+    const DOWNLOADS: &str = r#"{
+        "pagelen": 10,
+        "values": [
+            {
+                "name": "tool-1.2.0-linux.zip",
+                "links": {
+                    "self": {"href": "https://api.bitbucket.org/2.0/repositories/owner/tool/downloads/tool-1.2.0-linux.zip"}
+                },
+                "downloads": 12,
+                "created_on": "2024-03-01T10:00:00.000000+00:00",
+                "type": "download",
+                "size": 4096
+            },
+            {
+                "name": "tool-1.2.0-windows.zip",
+                "links": {
+                    "self": {"href": "https://api.bitbucket.org/2.0/repositories/owner/tool/downloads/tool-1.2.0-windows.zip"}
+                },
+                "downloads": 3,
+                "created_on": "2024-03-01T10:00:00.000000+00:00",
+                "type": "download",
+                "size": 4096
+            },
+            {
+                "name": "tool-1.1.0-linux.zip",
+                "links": {
+                    "self": {"href": "https://api.bitbucket.org/2.0/repositories/owner/tool/downloads/tool-1.1.0-linux.zip"}
+                },
+                "downloads": 40,
+                "created_on": "2024-01-15T10:00:00.000000+00:00",
+                "type": "download",
+                "size": 4000
+            }
+        ],
+        "page": 1,
+        "size": 3
+    }"#;
+
+    #[test]
+    fn maps_tags_and_downloads_to_releases() {
+        let tags: Page<BitbucketTag> = serde_json::from_str(TAGS).unwrap();
+        let downloads: Page<BitbucketDownload> = serde_json::from_str(DOWNLOADS).unwrap();
+        assert_eq!(
+            tags.next.as_deref(),
+            Some("https://api.bitbucket.org/2.0/repositories/owner/tool/refs/tags?page=2")
+        );
+        assert_eq!(downloads.next, None);
+
+        let releases = releases_from_tags("owner/tool", tags.values, &downloads.values);
+        assert_eq!(
+            releases,
+            vec![
+                Release {
+                    tag_name: "v1.2.0".to_owned(),
+                    prerelease: false,
+                    assets: vec![
+                        ReleaseAsset {
+                            url: "https://api.bitbucket.org/2.0/repositories/owner/tool/downloads/tool-1.2.0-linux.zip".to_owned(),
+                            name: "tool-1.2.0-linux.zip".to_owned(),
+                        },
+                        ReleaseAsset {
+                            url: "https://api.bitbucket.org/2.0/repositories/owner/tool/downloads/tool-1.2.0-windows.zip".to_owned(),
+                            name: "tool-1.2.0-windows.zip".to_owned(),
+                        },
+                    ],
+                    source_tarball: Some(
+                        "https://bitbucket.org/owner/tool/get/v1.2.0.tar.gz".to_owned()
+                    ),
+                },
+                Release {
+                    tag_name: "v1.1.0".to_owned(),
+                    prerelease: false,
+                    assets: vec![ReleaseAsset {
+                        url: "https://api.bitbucket.org/2.0/repositories/owner/tool/downloads/tool-1.1.0-linux.zip".to_owned(),
+                        name: "tool-1.1.0-linux.zip".to_owned(),
+                    }],
+                    source_tarball: Some(
+                        "https://bitbucket.org/owner/tool/get/v1.1.0.tar.gz".to_owned()
+                    ),
+                },
+            ]
+        );
+    }
+
+    #[test]
+    fn downloads_match_whole_versions() {
+        assert!(names_version("tool-1.2.0-linux.zip", "1.2.0"));
+        assert!(names_version("tool-1.2.0.zip", "1.2.0"));
+        assert!(names_version("tool_v1.2.0_macos.zip", "1.2.0"));
+        assert!(!names_version("tool-1.2.01-linux.zip", "1.2.0"));
+        assert!(!names_version("tool-11.2.0-linux.zip", "1.2.0"));
+        assert!(!names_version("tool-1.2.0.1-linux.zip", "1.2.0"));
+    }
+
+    fn authorization(token: Option<&str>) -> Option<String> {
+        let builder = http::client().get(API_URL);
+        let request = authenticate(builder, token).build().unwrap();
+
+        request
+            .headers()
+            .get("authorization")
+            .map(|value| value.to_str().unwrap().to_owned())
+    }
+
+    #[test]
+    fn access_tokens_are_bearer_tokens() {
+        assert_eq!(
+            authorization(Some("access-token")),
+            Some("Bearer access-token".to_owned())
+        );
+    }
+
+    #[test]
+    fn app_passwords_use_basic_authentication() {
+        // base64 of `user:app-password`.
+        assert_eq!(
+            authorization(Some("user:app-password")),
+            Some("Basic dXNlcjphcHAtcGFzc3dvcmQ=".to_owned())
+        );
+        assert_eq!(authorization(None), None);
+    }
+}
@@ -0,0 +1,29 @@
+//! Tool provider for tools declared with a `url` outside of any configured
+//! host. Such tools have no releases to list: their asset is downloaded from
+//! that URL as is, without authentication.
+
+use url::Url;
+
+use super::{http, DownloadOptions, Release, ToolProviderImpl};
+use crate::error::{ForemanError, ForemanResult};
+
+#[derive(Debug, Default)]
+pub struct DirectProvider;
+
+impl ToolProviderImpl for DirectProvider {
+    fn get_releases(&self, _repo: &str, _host: &Url) -> ForemanResult<Vec<Release>> {
+        // Tools with a direct URL are resolved from their configuration.
+        Ok(Vec::new())
+    }
+
+    fn download_asset(&self, url: &str, options: &DownloadOptions) -> ForemanResult<Vec<u8>> {
+        let client = http::client();
+
+        log::debug!("Downloading asset {}", url);
+        let response = http::send(client, client.get(url))?
+            .error_for_status()
+            .map_err(ForemanError::request_failed)?;
+
+        http::read_asset(response, url, options)
+    }
+}
@@ -0,0 +1,242 @@
+//! A provider serving a fixed set of releases, for tests that need releases
+//! without a host.
+
+use std::{
+    collections::HashMap,
+    sync::{Arc, Mutex},
+    thread,
+    time::Duration,
+};
+
+use url::Url;
+
+use super::{DownloadOptions, Provider, Release, ReleaseAsset, ToolProvider, ToolProviderImpl};
+use crate::error::{ForemanError, ForemanResult};
+
+/// Serves a release per tag, each with the same assets unless given others,
+/// and records every request made to it. Downloading an asset without a
+/// payload panics, so tests that must not download anything fail loudly.
+#[derive(Debug)]
+pub struct FixedReleases {
+    tags: Vec<&'static str>,
+    assets: Vec<&'static str>,
+    /// Releases whose assets differ from the others, by tag.
+    release_assets: HashMap<&'static str, Vec<&'static str>>,
+    source_tarballs: bool,
+    assets_on_demand: bool,
+    /// How many listings come back empty before the releases show up.
+    empty_listings: usize,
+    failing_listings: bool,
+    /// What downloading each asset returns, by asset name.
+    payloads: HashMap<&'static str, Vec<u8>>,
+    download_delay: Duration,
+    /// How long downloading from each release takes instead, by tag.
+    release_delays: HashMap<&'static str, Duration>,
+    requests: RequestLog,
+}
+
+/// The requests made to a `FixedReleases`, in order: `list`, `tag <tag>`,
+/// `assets <tag>` and `download <asset>`.
+#[derive(Debug, Clone, Default)]
+pub struct RequestLog(Arc<Mutex<Vec<String>>>);
+
+impl RequestLog {
+    fn push(&self, request: String) {
+        self.0.lock().unwrap().push(request);
+    }
+
+    pub fn get(&self) -> Vec<String> {
+        self.0.lock().unwrap().clone()
+    }
+
+    /// How many times the releases were listed.
+    pub fn listings(&self) -> usize {
+        self.get()
+            .iter()
+            .filter(|request| *request == "list")
+            .count()
+    }
+}
+
+impl FixedReleases {
+    /// Releases tagged `tags`, each with a `tool-linux.zip` asset.
+    pub fn new(tags: Vec<&'static str>) -> Self {
+        Self {
+            tags,
+            assets: vec!["tool-linux.zip"],
+            release_assets: HashMap::new(),
+            source_tarballs: false,
+            assets_on_demand: false,
+            empty_listings: 0,
+            failing_listings: false,
+            payloads: HashMap::new(),
+            download_delay: Duration::ZERO,
+            release_delays: HashMap::new(),
+            requests: RequestLog::default(),
+        }
+    }
+
+    /// Gives every release the assets named `names` instead.
+    pub fn assets(mut self, names: &[&'static str]) -> Self {
+        self.assets = names.to_vec();
+        self
+    }
+
+    /// Gives the release tagged `tag` the assets named `names` instead.
+    pub fn release_assets(mut self, tag: &'static str, names: &[&'static str]) -> Self {
+        self.release_assets.insert(tag, names.to_vec());
+        self
+    }
+
+    /// Gives every release a source tarball, whose asset name is
+    /// `source.tar.gz`.
+    pub fn source_tarballs(mut self) -> Self {
+        self.source_tarballs = true;
+        self
+    }
+
+    /// Leaves assets out of listed releases, listing them on demand instead.
+    pub fn assets_on_demand(mut self) -> Self {
+        self.assets_on_demand = true;
+        self
+    }
+
+    /// Lists no releases the first `count` times.
+    pub fn empty_listings(mut self, count: usize) -> Self {
+        self.empty_listings = count;
+        self
+    }
+
+    /// Fails every listing of the releases, like an unreachable host.
+    pub fn failing_listings(mut self) -> Self {
+        self.failing_listings = true;
+        self
+    }
+
+    /// Makes downloading the asset named `name` return `contents`.
+    pub fn payload(mut self, name: &'static str, contents: Vec<u8>) -> Self {
+        self.payloads.insert(name, contents);
+        self
+    }
+
+    /// Makes every download take `delay`.
+    pub fn download_delay(mut self, delay: Duration) -> Self {
+        self.download_delay = delay;
+        self
+    }
+
+    /// Makes downloads from the release tagged `tag` take `delay`.
+    pub fn release_delay(mut self, tag: &'static str, delay: Duration) -> Self {
+        self.release_delays.insert(tag, delay);
+        self
+    }
+
+    /// The log of the requests made to these releases.
+    pub fn requests(&self) -> RequestLog {
+        self.requests.clone()
+    }
+
+    /// Providers serving these releases as GitHub.
+    pub fn providers(self) -> ToolProvider {
+        ToolProvider::with_provider(Provider::Github, Box::new(self))
+    }
+
+    /// Like `providers`, along with the log of their requests.
+    pub fn logged_providers(self) -> (ToolProvider, RequestLog) {
+        let requests = self.requests();
+        (self.providers(), requests)
+    }
+
+    /// The releases as listed, with or without their assets.
+    pub fn releases(&self, with_assets: bool) -> Vec<Release> {
+        self.tags
+            .iter()
+            .map(|tag| Release {
+                tag_name: tag.to_string(),
+                prerelease: false,
+                assets: if with_assets {
+                    self.assets_of(tag)
+                } else {
+                    Vec::new()
+                },
+                source_tarball: self
+                    .source_tarballs
+                    .then(|| format!("https://example.com/{}/source.tar.gz", tag)),
+            })
+            .collect()
+    }
+
+    fn assets_of(&self, tag: &str) -> Vec<ReleaseAsset> {
+        self.release_assets
+            .get(tag)
+            .unwrap_or(&self.assets)
+            .iter()
+            .map(|name| ReleaseAsset {
+                url: format!("https://example.com/{}/{}", tag, name),
+                name: name.to_string(),
+            })
+            .collect()
+    }
+}
+
+impl ToolProviderImpl for FixedReleases {
+    fn get_releases(&self, _repo: &str, _host: &Url) -> ForemanResult<Vec<Release>> {
+        self.requests.push("list".to_owned());
+        if self.failing_listings {
+            return Err(ForemanError::unexpected_response_body(
+                "unable to list releases",
+                "",
+                "https://example.com",
+            ));
+        }
+        if self.requests.listings() <= self.empty_listings {
+            return Ok(Vec::new());
+        }
+        Ok(self.releases(!self.assets_on_demand))
+    }
+
+    fn lists_assets_on_demand(&self) -> bool {
+        self.assets_on_demand
+    }
+
+    fn get_release_assets(
+        &self,
+        _repo: &str,
+        _host: &Url,
+        tag_name: &str,
+    ) -> ForemanResult<Vec<ReleaseAsset>> {
+        self.requests.push(format!("assets {}", tag_name));
+        Ok(self.assets_of(tag_name))
+    }
+
+    fn get_release_by_tag(
+        &self,
+        _repo: &str,
+        _host: &Url,
+        tag_name: &str,
+    ) -> ForemanResult<Option<Release>> {
+        self.requests.push(format!("tag {}", tag_name));
+        Ok(self
+            .releases(!self.assets_on_demand)
+            .into_iter()
+            .find(|release| release.tag_name == tag_name))
+    }
+
+    fn download_asset(&self, url: &str, _options: &DownloadOptions) -> ForemanResult<Vec<u8>> {
+        let mut parts = url.rsplit('/');
+        let name = parts.next().unwrap_or_default();
+        let tag = parts.next().unwrap_or_default();
+        self.requests.push(format!("download {}", name));
+        thread::sleep(
+            self.release_delays
+                .get(tag)
+                .copied()
+                .unwrap_or(self.download_delay),
+        );
+
+        match self.payloads.get(name) {
+            Some(contents) => Ok(contents.clone()),
+            None => panic!("unexpected download of {}", url),
+        }
+    }
+}
@@ -1,80 +1,314 @@
 //! Slice of GitHub's API that Foreman consumes.
 
 use reqwest::{
-    blocking::Client,
-    header::{ACCEPT, AUTHORIZATION, USER_AGENT},
+    blocking::{Client, RequestBuilder, Response},
+    header::{ACCEPT, AUTHORIZATION, LINK, RETRY_AFTER},
+    StatusCode,
 };
 use serde::{Deserialize, Serialize};
 
-use super::{Release, ReleaseAsset, ToolProviderImpl};
+use super::{http, DownloadOptions, Release, ReleaseAsset, ToolProviderImpl};
 use crate::{
     auth_store::AuthStore,
     error::{ForemanError, ForemanResult},
     paths::ForemanPaths,
 };
+use std::{
+    env, thread,
+    time::{Duration, SystemTime, UNIX_EPOCH},
+};
 use url::Url;
 
+/// When set to `1` or `true`, release assets are downloaded from their
+/// `browser_download_url` instead of the API asset URL. Some proxies and GitHub
+/// Enterprise versions serve assets more reliably this way.
+const BROWSER_DOWNLOAD_ENV_VARIABLE: &str = "FOREMAN_GITHUB_BROWSER_DOWNLOAD";
+
+/// How many times a request is retried after hitting GitHub's secondary rate
+/// limits.
+const MAX_SECONDARY_RATE_LIMIT_RETRIES: u32 = 3;
+
+/// The longest `Retry-After` Foreman waits for. The request holds a request
+/// slot for its host while waiting, so longer waits fail with the rate limit
+/// error instead.
+const MAX_SECONDARY_RATE_LIMIT_WAIT: Duration = Duration::from_secs(60);
+
+/// The largest page size GitHub allows, to list releases in fewer requests.
+const RELEASES_PER_PAGE: u32 = 100;
+
+/// How many pages of releases are listed at most. Each page costs a request
+/// from the rate limit quota, and tools rarely pin versions older than their
+/// last thousand releases.
+const MAX_RELEASE_PAGES: usize = 10;
+
 #[derive(Debug)]
 pub struct GithubProvider {
     paths: ForemanPaths,
+    /// Tokens from the `[auth]` table of the configuration.
+    config_auth: AuthStore,
+    prefer_browser_download_url: bool,
 }
 
 impl GithubProvider {
-    pub fn new(paths: ForemanPaths) -> Self {
-        Self { paths }
+    pub fn new(paths: ForemanPaths, config_auth: AuthStore) -> Self {
+        let prefer_browser_download_url = env::var(BROWSER_DOWNLOAD_ENV_VARIABLE)
+            .map(|value| value == "1" || value.eq_ignore_ascii_case("true"))
+            .unwrap_or(false);
+
+        Self {
+            paths,
+            config_auth,
+            prefer_browser_download_url,
+        }
+    }
+
+    /// The stored tokens, completed with the ones from the configuration.
+    fn auth_store(&self) -> ForemanResult<AuthStore> {
+        let mut auth_store = AuthStore::load(&self.paths.auth_store())?;
+        auth_store.run_github_token_command()?;
+        auth_store.fill_from(&self.config_auth);
+        Ok(auth_store)
     }
 }
 
 impl ToolProviderImpl for GithubProvider {
     fn get_releases(&self, repo: &str, _host: &Url) -> ForemanResult<Vec<Release>> {
-        let client = Client::new();
+        let url = format!(
+            "https://api.github.com/repos/{}/releases?per_page={}",
+            repo, RELEASES_PER_PAGE
+        );
+        let auth_store = self.auth_store()?;
 
-        let url = format!("https://api.github.com/repos/{}/releases", repo);
-        let mut builder = client.get(&url).header(USER_AGENT, "Roblox/foreman");
+        log::debug!("Downloading github releases for {}", repo);
+        let mut releases = list_releases(&url, auth_store.github_token(repo))?;
 
-        let auth_store = AuthStore::load(&self.paths.auth_store())?;
-        if let Some(token) = &auth_store.github {
-            builder = builder.header(AUTHORIZATION, format!("token {}", token));
+        if self.prefer_browser_download_url {
+            use_browser_download_urls(&mut releases);
         }
 
-        log::debug!("Downloading github releases for {}", repo);
-        let response_body = builder
-            .send()
-            .map_err(ForemanError::request_failed)?
-            .text()
-            .map_err(ForemanError::request_failed)?;
+        Ok(releases.into_iter().map(Into::into).collect())
+    }
 
-        let releases: Vec<GithubRelease> = serde_json::from_str(&response_body).map_err(|err| {
-            ForemanError::unexpected_response_body(err.to_string(), response_body, url)
-        })?;
+    fn get_release_by_tag(
+        &self,
+        repo: &str,
+        _host: &Url,
+        tag_name: &str,
+    ) -> ForemanResult<Option<Release>> {
+        let url = format!(
+            "https://api.github.com/repos/{}/releases/tags/{}",
+            repo,
+            urlencoding::encode(tag_name)
+        );
+        let auth_store = self.auth_store()?;
 
-        Ok(releases.into_iter().map(Into::into).collect())
+        log::debug!("Downloading github release {} of {}", tag_name, repo);
+        let release = get_release(&url, auth_store.github_token(repo))?;
+
+        Ok(release.map(|mut release| {
+            if self.prefer_browser_download_url {
+                use_browser_download_urls(std::slice::from_mut(&mut release));
+            }
+            release.into()
+        }))
     }
 
-    fn download_asset(&self, url: &str) -> ForemanResult<Vec<u8>> {
-        let client = Client::new();
+    fn download_asset(&self, url: &str, options: &DownloadOptions) -> ForemanResult<Vec<u8>> {
+        let client = http::client();
 
         let mut builder = client
             .get(url)
-            .header(USER_AGENT, "Roblox/foreman")
             // Setting `Accept` is required to make the GitHub API return the actual
             // release asset instead of JSON metadata about the release.
             .header(ACCEPT, "application/octet-stream");
 
-        let auth_store = AuthStore::load(&self.paths.auth_store())?;
-        if let Some(token) = &auth_store.github {
+        let auth_store = self.auth_store()?;
+        let token = match repository_from_asset_url(url) {
+            Some(repo) => auth_store.github_token(&repo),
+            None => auth_store.github.as_deref(),
+        };
+        if let Some(token) = token {
             builder = builder.header(AUTHORIZATION, format!("token {}", token));
         }
 
         log::debug!("Downloading release asset {}", url);
-        let mut response = builder.send().map_err(ForemanError::request_failed)?;
+        let response = check_rate_limit(send_with_backoff(client, builder)?, url)?;
+
+        http::read_asset(response, url, options)
+    }
+}
+
+/// Lists the releases at `url`, following the `Link` header of each page to
+/// the next one, up to `MAX_RELEASE_PAGES` pages.
+fn list_releases(url: &str, token: Option<&str>) -> ForemanResult<Vec<GithubRelease>> {
+    let client = http::client();
+
+    let mut releases = Vec::new();
+    let mut next = Some(url.to_owned());
+    let mut pages = 0;
+    while let Some(url) = next {
+        if pages == MAX_RELEASE_PAGES {
+            log::warn!(
+                "Only the first {} releases listed at {} are considered",
+                releases.len(),
+                url
+            );
+            break;
+        }
+
+        let mut builder = client.get(&url);
+        if let Some(token) = token {
+            builder = builder.header(AUTHORIZATION, format!("token {}", token));
+        }
+
+        let response = check_rate_limit(send_with_backoff(client, builder)?, &url)?;
+        next = response
+            .headers()
+            .get(LINK)
+            .and_then(|link| link.to_str().ok())
+            .and_then(next_page_url);
+        let page: Vec<GithubRelease> = http::read_json(response, &url)?;
+
+        releases.extend(page);
+        pages += 1;
+    }
+
+    Ok(releases)
+}
+
+/// Gets the single release at `url`, or `None` when GitHub has no such
+/// release.
+fn get_release(url: &str, token: Option<&str>) -> ForemanResult<Option<GithubRelease>> {
+    let client = http::client();
+
+    let mut builder = client.get(url);
+    if let Some(token) = token {
+        builder = builder.header(AUTHORIZATION, format!("token {}", token));
+    }
+
+    let response = check_rate_limit(send_with_backoff(client, builder)?, url)?;
+    if response.status() == StatusCode::NOT_FOUND {
+        return Ok(None);
+    }
+
+    http::read_json(response, url).map(Some)
+}
+
+/// The URL of the next page from a `Link` header, like
+/// `<https://api.github.com/repositories/1/releases?page=2>; rel="next"`.
+fn next_page_url(link: &str) -> Option<String> {
+    link.split(',').find_map(|link| {
+        let mut parts = link.split(';');
+        let url = parts.next()?.trim();
+        let is_next = parts.any(|parameter| {
+            matches!(
+                parameter.trim().split_once('='),
+                Some(("rel", "\"next\"")) | Some(("rel", "next"))
+            )
+        });
+
+        if !is_next {
+            return None;
+        }
+        url.strip_prefix('<')?.strip_suffix('>').map(str::to_owned)
+    })
+}
+
+/// Sends a request, retrying it when GitHub's secondary rate limits reject it.
+///
+/// These limits guard against abuse rather than quotas: GitHub answers with a
+/// 403 or 429 and a `Retry-After` header telling how long to wait, while the
+/// quota of the token is not exhausted.
+fn send_with_backoff(client: &Client, mut builder: RequestBuilder) -> ForemanResult<Response> {
+    let mut retries = 0;
+
+    loop {
+        let retry = builder.try_clone();
+        let response = http::send(client, builder)?;
+
+        let wait = match (secondary_rate_limit_wait(&response), retry) {
+            (Some(wait), _) if wait > MAX_SECONDARY_RATE_LIMIT_WAIT => {
+                return Err(ForemanError::RateLimited {
+                    url: response.url().to_string(),
+                    resets_in: Some(wait),
+                });
+            }
+            (Some(wait), Some(retry)) if retries < MAX_SECONDARY_RATE_LIMIT_RETRIES => {
+                builder = retry;
+                wait
+            }
+            _ => return Ok(response),
+        };
 
-        let mut output = Vec::new();
+        retries += 1;
+        log::warn!(
+            "GitHub secondary rate limit hit, backing off for {} seconds (retry {} of {})",
+            wait.as_secs(),
+            retries,
+            MAX_SECONDARY_RATE_LIMIT_RETRIES
+        );
+        thread::sleep(wait);
+    }
+}
+
+/// How long to wait before retrying a response rejected by GitHub's secondary
+/// rate limits, if it was.
+fn secondary_rate_limit_wait(response: &Response) -> Option<Duration> {
+    if !matches!(
+        response.status(),
+        StatusCode::FORBIDDEN | StatusCode::TOO_MANY_REQUESTS
+    ) {
+        return None;
+    }
+
+    let header = |name: &str| {
         response
-            .copy_to(&mut output)
-            .map_err(ForemanError::request_failed)?;
-        Ok(output)
+            .headers()
+            .get(name)
+            .and_then(|value| value.to_str().ok())
+    };
+
+    // An exhausted quota only resets at `x-ratelimit-reset`, which is usually
+    // too far away to wait for.
+    if header("x-ratelimit-remaining") == Some("0") {
+        return None;
     }
+
+    let seconds = header(RETRY_AFTER.as_str())?.trim().parse().ok()?;
+    Some(Duration::from_secs(seconds))
+}
+
+/// Fails with [`ForemanError::RateLimited`] when the response to a request to
+/// `url` was rejected because the rate limit quota is exhausted, instead of
+/// trying to read GitHub's error message as the expected body.
+fn check_rate_limit(response: Response, url: &str) -> ForemanResult<Response> {
+    let header = |name: &str| {
+        response
+            .headers()
+            .get(name)
+            .and_then(|value| value.to_str().ok())
+    };
+
+    let exhausted = matches!(
+        response.status(),
+        StatusCode::FORBIDDEN | StatusCode::TOO_MANY_REQUESTS
+    ) && header("x-ratelimit-remaining") == Some("0");
+    if !exhausted {
+        return Ok(response);
+    }
+
+    Err(ForemanError::RateLimited {
+        url: url.to_owned(),
+        resets_in: header("x-ratelimit-reset")
+            .and_then(|reset| time_until_reset(reset, SystemTime::now())),
+    })
+}
+
+/// How long from `now` until the `x-ratelimit-reset` time `reset`, given in
+/// seconds since the Unix epoch. A reset in the past is due right away.
+fn time_until_reset(reset: &str, now: SystemTime) -> Option<Duration> {
+    let reset = UNIX_EPOCH + Duration::from_secs(reset.trim().parse().ok()?);
+    Some(reset.duration_since(now).unwrap_or_default())
 }
 
 #[derive(Debug, Serialize, Deserialize)]
@@ -82,12 +316,24 @@ struct GithubRelease {
     pub tag_name: String,
     pub prerelease: bool,
     pub assets: Vec<GithubAsset>,
+    #[serde(default)]
+    pub tarball_url: Option<String>,
 }
 
 #[derive(Debug, Serialize, Deserialize)]
 struct GithubAsset {
     pub url: String,
     pub name: String,
+    #[serde(default)]
+    pub browser_download_url: Option<String>,
+}
+
+fn use_browser_download_urls(releases: &mut [GithubRelease]) {
+    for asset in releases.iter_mut().flat_map(|release| &mut release.assets) {
+        if let Some(browser_download_url) = asset.browser_download_url.take() {
+            asset.url = browser_download_url;
+        }
+    }
 }
 
 impl From<GithubRelease> for Release {
@@ -96,6 +342,7 @@ impl From<GithubRelease> for Release {
             tag_name: release.tag_name,
             prerelease: release.prerelease,
             assets: release.assets.into_iter().map(Into::into).collect(),
+            source_tarball: release.tarball_url,
         }
     }
 }
@@ -108,3 +355,284 @@ impl From<GithubAsset> for ReleaseAsset {
         }
     }
 }
+
+/// The `owner/repo` a release asset belongs to, from either its API URL
+/// (`/repos/owner/repo/releases/assets/1`) or its browser download URL
+/// (`/owner/repo/releases/download/v1.0.0/tool.zip`).
+fn repository_from_asset_url(url: &str) -> Option<String> {
+    let url = Url::parse(url).ok()?;
+    let mut segments = url.path_segments()?;
+
+    let mut owner = segments.next()?;
+    if owner == "repos" {
+        owner = segments.next()?;
+    }
+    let repo = segments.next()?;
+
+    Some(format!("{}/{}", owner, repo))
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::tool_provider::http::test_server::{self, MockResponse};
+
+    const RELEASES: &str = r#"[
+        {
+            "tag_name": "v0.1.0",
+            "prerelease": false,
+            "assets": [
+                {
+                    "url": "https://api.github.com/repos/user/tool/releases/assets/1",
+                    "name": "tool-linux.zip",
+                    "browser_download_url": "https://github.com/user/tool/releases/download/v0.1.0/tool-linux.zip"
+                }
+            ]
+        }
+    ]"#;
+
+    fn parse_releases() -> Vec<GithubRelease> {
+        serde_json::from_str(RELEASES).unwrap()
+    }
+
+    #[test]
+    fn uses_api_url_by_default() {
+        let release: Release = parse_releases().remove(0).into();
+        assert_eq!(
+            release.assets[0].url,
+            "https://api.github.com/repos/user/tool/releases/assets/1"
+        );
+    }
+
+    #[test]
+    fn uses_browser_download_url_when_preferred() {
+        let mut releases = parse_releases();
+        use_browser_download_urls(&mut releases);
+
+        let release: Release = releases.remove(0).into();
+        assert_eq!(
+            release.assets[0].url,
+            "https://github.com/user/tool/releases/download/v0.1.0/tool-linux.zip"
+        );
+    }
+
+    #[test]
+    fn repository_from_api_and_browser_asset_urls() {
+        assert_eq!(
+            repository_from_asset_url("https://api.github.com/repos/owner/repo/releases/assets/1"),
+            Some("owner/repo".to_string())
+        );
+        assert_eq!(
+            repository_from_asset_url(
+                "https://github.com/owner/repo/releases/download/v0.1.0/tool-linux.zip"
+            ),
+            Some("owner/repo".to_string())
+        );
+        assert_eq!(repository_from_asset_url("not a url"), None);
+    }
+
+    #[test]
+    fn gets_release_by_tag() {
+        let release = RELEASES
+            .trim()
+            .trim_start_matches('[')
+            .trim_end_matches(']');
+        let url = test_server::serve(vec![
+            MockResponse::new("200 OK", release),
+            MockResponse::new("404 Not Found", r#"{"message":"Not Found"}"#),
+        ]);
+
+        let release: Release = get_release(&url, None).unwrap().unwrap().into();
+        assert_eq!(release.tag_name, "v0.1.0");
+        assert!(get_release(&url, None).unwrap().is_none());
+    }
+
+    #[test]
+    fn retries_after_secondary_rate_limit() {
+        let url = test_server::serve(vec![
+            MockResponse::new("403 Forbidden", r#"{"message":"secondary rate limit"}"#)
+                .header("Retry-After", "0")
+                .header("x-ratelimit-remaining", "4000"),
+            MockResponse::new("200 OK", "[]"),
+        ]);
+
+        let response = send_with_backoff(http::client(), http::client().get(&url)).unwrap();
+        assert_eq!(response.status(), StatusCode::OK);
+    }
+
+    #[test]
+    fn long_secondary_rate_limit_wait_is_a_rate_limit_error() {
+        let url = test_server::serve(vec![MockResponse::new(
+            "403 Forbidden",
+            r#"{"message":"secondary rate limit"}"#,
+        )
+        .header("Retry-After", "3600")
+        .header("x-ratelimit-remaining", "4000")]);
+
+        match send_with_backoff(http::client(), http::client().get(&url)) {
+            Err(ForemanError::RateLimited { resets_in, .. }) => {
+                assert_eq!(resets_in, Some(Duration::from_secs(3600)));
+            }
+            other => panic!("expected a rate limit error, got {:?}", other),
+        }
+    }
+
+    #[test]
+    fn does_not_retry_exhausted_quota() {
+        let url = test_server::serve(vec![MockResponse::new(
+            "403 Forbidden",
+            r#"{"message":"API rate limit exceeded"}"#,
+        )
+        .header("Retry-After", "0")
+        .header("x-ratelimit-remaining", "0")]);
+
+        let response = send_with_backoff(http::client(), http::client().get(&url)).unwrap();
+        assert_eq!(response.status(), StatusCode::FORBIDDEN);
+    }
+
+    #[test]
+    fn exhausted_quota_is_a_rate_limit_error() {
+        let url = test_server::serve(vec![MockResponse::new(
+            "403 Forbidden",
+            r#"{"message":"API rate limit exceeded for 127.0.0.1.","documentation_url":"https://docs.github.com/rest/overview/resources-in-the-rest-api#rate-limiting"}"#,
+        )
+        .header("x-ratelimit-limit", "60")
+        .header("x-ratelimit-remaining", "0")
+        .header("x-ratelimit-reset", "1700000000")]);
+
+        let response = http::send(http::client(), http::client().get(&url)).unwrap();
+        let err = check_rate_limit(response, &url).unwrap_err();
+        assert!(
+            matches!(
+                err,
+                ForemanError::RateLimited {
+                    resets_in: Some(_),
+                    ..
+                }
+            ),
+            "expected a rate limit error, got {:?}",
+            err
+        );
+        assert!(err.to_string().contains("foreman github-auth"), "{}", err);
+    }
+
+    #[test]
+    fn rate_limit_reset_is_relative_to_now() {
+        let now = UNIX_EPOCH + Duration::from_secs(1_700_000_000);
+        assert_eq!(
+            time_until_reset("1700000600", now),
+            Some(Duration::from_secs(600))
+        );
+        assert_eq!(time_until_reset("1699999000", now), Some(Duration::ZERO));
+        assert_eq!(time_until_reset("soon", now), None);
+
+        let err = ForemanError::RateLimited {
+            url: "https://api.github.com/repos/rojo-rbx/rojo/releases".to_owned(),
+            resets_in: time_until_reset("1700000570", now),
+        };
+        assert!(err.to_string().contains("resets in 10 minutes"), "{}", err);
+    }
+
+    #[test]
+    fn other_forbidden_responses_are_not_rate_limits() {
+        let url = test_server::serve(vec![MockResponse::new(
+            "403 Forbidden",
+            r#"{"message":"Resource not accessible by integration"}"#,
+        )
+        .header("x-ratelimit-remaining", "4999")]);
+
+        let response = http::send(http::client(), http::client().get(&url)).unwrap();
+        let response = check_rate_limit(response, &url).unwrap();
+        assert_eq!(response.status(), StatusCode::FORBIDDEN);
+    }
+
+    #[test]
+    fn gives_up_after_max_retries() {
+        let limited = || MockResponse::new("429 Too Many Requests", "").header("Retry-After", "0");
+        let url = test_server::serve(
+            (0..=MAX_SECONDARY_RATE_LIMIT_RETRIES)
+                .map(|_| limited())
+                .collect(),
+        );
+
+        let response = send_with_backoff(http::client(), http::client().get(&url)).unwrap();
+        assert_eq!(response.status(), StatusCode::TOO_MANY_REQUESTS);
+    }
+
+    #[test]
+    fn finds_next_page_in_link_header() {
+        assert_eq!(
+            next_page_url(
+                "<https://api.github.com/repositories/1/releases?per_page=100&page=2>; rel=\"next\", \
+                <https://api.github.com/repositories/1/releases?per_page=100&page=5>; rel=\"last\""
+            ),
+            Some("https://api.github.com/repositories/1/releases?per_page=100&page=2".to_owned())
+        );
+        assert_eq!(
+            next_page_url(
+                "<https://api.github.com/repositories/1/releases?page=4>; rel=\"prev\", \
+                <https://api.github.com/repositories/1/releases?page=1>; rel=\"first\""
+            ),
+            None
+        );
+    }
+
+    #[test]
+    fn merges_every_page_of_releases() {
+        let url = test_server::serve_with(|url| {
+            vec![
+                MockResponse::new("200 OK", RELEASES).header(
+                    "Link",
+                    format!(
+                        "<{}?page=2>; rel=\"next\", <{}?page=2>; rel=\"last\"",
+                        url, url
+                    ),
+                ),
+                MockResponse::new(
+                    "200 OK",
+                    r#"[{"tag_name": "v0.0.9", "prerelease": false, "assets": []}]"#,
+                )
+                .header("Link", format!("<{}?page=1>; rel=\"prev\"", url)),
+            ]
+        });
+
+        let releases = list_releases(&url, None).unwrap();
+        let tags: Vec<&str> = releases
+            .iter()
+            .map(|release| release.tag_name.as_str())
+            .collect();
+        assert_eq!(tags, vec!["v0.1.0", "v0.0.9"]);
+    }
+
+    #[test]
+    fn stops_listing_releases_after_max_pages() {
+        let url = test_server::serve_with(|url| {
+            (0..=MAX_RELEASE_PAGES)
+                .map(|_| {
+                    MockResponse::new("200 OK", RELEASES)
+                        .header("Link", format!("<{}>; rel=\"next\"", url))
+                })
+                .collect()
+        });
+
+        let releases = list_releases(&url, None).unwrap();
+        assert_eq!(releases.len(), MAX_RELEASE_PAGES);
+    }
+
+    #[test]
+    fn reads_tokens_from_overridden_auth_file() {
+        let directory = tempfile::tempdir().unwrap();
+        let mut paths = ForemanPaths::new(directory.path().to_owned());
+        std::fs::write(paths.auth_store(), "github = \"home\"").unwrap();
+
+        let auth_file = directory.path().join("job-auth.toml");
+        std::fs::write(&auth_file, "github = \"job\"").unwrap();
+        paths.set_auth_file(Some(auth_file));
+
+        let provider = GithubProvider::new(paths, AuthStore::default());
+        assert_eq!(
+            provider.auth_store().unwrap().github_token("rojo-rbx/rojo"),
+            Some("job")
+        );
+    }
+}
@@ -1,82 +1,125 @@
 //! Slice of Gitlab's API that Foreman consumes.
 
-use reqwest::{
-    blocking::Client,
-    header::{ACCEPT, USER_AGENT},
-};
+use reqwest::{blocking::RequestBuilder, header::ACCEPT, StatusCode};
 use serde::{Deserialize, Serialize};
 
-use super::{Release, ReleaseAsset, ToolProviderImpl};
-use crate::{
-    auth_store::AuthStore,
-    error::{ForemanError, ForemanResult},
-    paths::ForemanPaths,
-};
+use super::{http, DownloadOptions, Release, ReleaseAsset, ToolProviderImpl};
+use crate::{auth_store::AuthStore, error::ForemanResult, paths::ForemanPaths};
+use std::env;
 use url::Url;
 
-#[derive(Debug, Default)]
+/// Set by GitLab CI to a token that is valid for the duration of a job. It is
+/// used when no personal access token applies to a project.
+const JOB_TOKEN_ENV_VARIABLE: &str = "CI_JOB_TOKEN";
+
+#[derive(Debug)]
 pub struct GitlabProvider {
     paths: ForemanPaths,
+    /// Tokens from the `[auth]` table of the configuration.
+    config_auth: AuthStore,
+    job_token: Option<String>,
 }
 
 impl GitlabProvider {
-    pub fn new(paths: ForemanPaths) -> Self {
-        Self { paths }
+    pub fn new(paths: ForemanPaths, config_auth: AuthStore) -> Self {
+        let job_token = env::var(JOB_TOKEN_ENV_VARIABLE)
+            .ok()
+            .filter(|token| !token.is_empty());
+
+        Self {
+            paths,
+            config_auth,
+            job_token,
+        }
+    }
+
+    /// The stored tokens, completed with the ones from the configuration
+    /// unless a CI job token is set, which takes precedence over them.
+    fn auth_store(&self) -> ForemanResult<AuthStore> {
+        let mut auth_store = AuthStore::load(&self.paths.auth_store())?;
+        auth_store.run_gitlab_token_command()?;
+        if self.job_token.is_none() {
+            auth_store.fill_from(&self.config_auth);
+        }
+        Ok(auth_store)
     }
 }
 
 impl ToolProviderImpl for GitlabProvider {
     fn get_releases(&self, repo: &str, _host: &Url) -> ForemanResult<Vec<Release>> {
-        let client = Client::new();
+        let client = http::client();
 
         let url = format!(
             "https://gitlab.com/api/v4/projects/{}/releases",
             urlencoding::encode(repo)
         );
-        let mut builder = client.get(&url).header(USER_AGENT, "Roblox/foreman");
+        let mut builder = client.get(&url);
 
-        let auth_store = AuthStore::load(&self.paths.auth_store())?;
-        if let Some(token) = &auth_store.gitlab {
-            builder = builder.header("PRIVATE-TOKEN", token);
-        }
+        let auth_store = self.auth_store()?;
+        builder = authenticate(
+            builder,
+            auth_store.gitlab_token(repo),
+            self.job_token.as_deref(),
+        );
 
         log::debug!("Downloading gitlab releases for {}", repo);
-        let response_body = builder
-            .send()
-            .map_err(ForemanError::request_failed)?
-            .text()
-            .map_err(ForemanError::request_failed)?;
-
-        let releases: Vec<GitlabRelease> = serde_json::from_str(&response_body).map_err(|err| {
-            ForemanError::unexpected_response_body(err.to_string(), response_body, url)
-        })?;
+        let releases: Vec<GitlabRelease> = http::read_json(http::send(client, builder)?, &url)?;
 
         Ok(releases.into_iter().map(Into::into).collect())
     }
 
-    fn download_asset(&self, url: &str) -> ForemanResult<Vec<u8>> {
-        let client = Client::new();
+    fn get_release_by_tag(
+        &self,
+        repo: &str,
+        _host: &Url,
+        tag_name: &str,
+    ) -> ForemanResult<Option<Release>> {
+        let client = http::client();
+
+        let url = format!(
+            "https://gitlab.com/api/v4/projects/{}/releases/{}",
+            urlencoding::encode(repo),
+            urlencoding::encode(tag_name)
+        );
+        let mut builder = client.get(&url);
+
+        let auth_store = self.auth_store()?;
+        builder = authenticate(
+            builder,
+            auth_store.gitlab_token(repo),
+            self.job_token.as_deref(),
+        );
+
+        log::debug!("Downloading gitlab release {} of {}", tag_name, repo);
+        let response = http::send(client, builder)?;
+        if response.status() == StatusCode::NOT_FOUND {
+            return Ok(None);
+        }
+
+        let release: GitlabRelease = http::read_json(response, &url)?;
+        Ok(Some(release.into()))
+    }
+
+    fn download_asset(&self, url: &str, options: &DownloadOptions) -> ForemanResult<Vec<u8>> {
+        let client = http::client();
 
         let mut builder = client
             .get(url)
-            .header(USER_AGENT, "Roblox/foreman")
             // Setting `Accept` is required to make the GitLab API return the actual
             // release asset instead of JSON metadata about the release.
             .header(ACCEPT, "application/octet-stream");
 
-        let auth_store = AuthStore::load(&self.paths.auth_store())?;
-        if let Some(token) = &auth_store.gitlab {
-            builder = builder.header("PRIVATE-TOKEN", token);
-        }
+        let auth_store = self.auth_store()?;
+        let token = match project_from_asset_url(url) {
+            Some(project) => auth_store.gitlab_token(&project),
+            None => auth_store.gitlab.as_deref(),
+        };
+        builder = authenticate(builder, token, self.job_token.as_deref());
 
         log::debug!("Downloading release asset {}", url);
-        let mut response = builder.send().map_err(ForemanError::request_failed)?;
+        let response = http::send(client, builder)?;
 
-        let mut output = Vec::new();
-        response
-            .copy_to(&mut output)
-            .map_err(ForemanError::request_failed)?;
-        Ok(output)
+        http::read_asset(response, url, options)
     }
 }
 
@@ -105,6 +148,7 @@ impl From<GitlabRelease> for Release {
             tag_name: release.tag_name,
             prerelease: release.upcoming_release,
             assets: release.assets.links.into_iter().map(Into::into).collect(),
+            source_tarball: None,
         }
     }
 }
@@ -117,3 +161,118 @@ impl From<GitlabAsset> for ReleaseAsset {
         }
     }
 }
+
+/// Adds the header for the credential to use: a personal access token when one
+/// applies, otherwise a CI job token, which GitLab expects in a different header.
+fn authenticate(
+    builder: RequestBuilder,
+    personal_token: Option<&str>,
+    job_token: Option<&str>,
+) -> RequestBuilder {
+    match (personal_token, job_token) {
+        (Some(token), _) => builder.header("PRIVATE-TOKEN", token),
+        (None, Some(token)) => builder.header("JOB-TOKEN", token),
+        (None, None) => builder,
+    }
+}
+
+/// The path of the project a release asset belongs to, from either an API URL
+/// (`/api/v4/projects/group%2Fproject/...`) or a project URL
+/// (`/group/project/-/...`).
+fn project_from_asset_url(url: &str) -> Option<String> {
+    let url = Url::parse(url).ok()?;
+    let segments: Vec<&str> = url.path_segments()?.collect();
+
+    if let ["api", "v4", "projects", project, ..] = segments.as_slice() {
+        return urlencoding::decode(project)
+            .ok()
+            .map(|project| project.into_owned());
+    }
+
+    let end = segments.iter().position(|segment| *segment == "-")?;
+    Some(segments[..end].join("/"))
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn job_token_takes_precedence_over_configured_tokens() {
+        let directory = tempfile::tempdir().unwrap();
+        let paths = ForemanPaths::new(directory.path().to_owned());
+        let config_auth = AuthStore {
+            gitlab: Some("config".to_owned()),
+            ..AuthStore::default()
+        };
+        let provider = |job_token: Option<&str>| GitlabProvider {
+            paths: paths.clone(),
+            config_auth: config_auth.clone(),
+            job_token: job_token.map(str::to_owned),
+        };
+
+        let without_job_token = provider(None).auth_store().unwrap();
+        assert_eq!(
+            without_job_token.gitlab_token("group/project"),
+            Some("config")
+        );
+
+        let with_job_token = provider(Some("job")).auth_store().unwrap();
+        assert_eq!(with_job_token.gitlab_token("group/project"), None);
+
+        std::fs::write(paths.auth_store(), "gitlab = \"stored\"").unwrap();
+        let stored = provider(Some("job")).auth_store().unwrap();
+        assert_eq!(stored.gitlab_token("group/project"), Some("stored"));
+    }
+
+    #[test]
+    fn project_from_api_and_project_asset_urls() {
+        assert_eq!(
+            project_from_asset_url(
+                "https://gitlab.com/api/v4/projects/group%2Fproject/packages/generic/tool/1.0.0/tool.zip"
+            ),
+            Some("group/project".to_string())
+        );
+        assert_eq!(
+            project_from_asset_url(
+                "https://gitlab.com/group/subgroup/project/-/releases/v1.0.0/downloads/tool.zip"
+            ),
+            Some("group/subgroup/project".to_string())
+        );
+        assert_eq!(project_from_asset_url("https://example.com/tool.zip"), None);
+    }
+
+    fn headers(personal_token: Option<&str>, job_token: Option<&str>) -> Vec<(String, String)> {
+        let builder = http::client().get("https://gitlab.com/api/v4/projects");
+        let request = authenticate(builder, personal_token, job_token)
+            .build()
+            .unwrap();
+
+        request
+            .headers()
+            .iter()
+            .map(|(name, value)| (name.to_string(), value.to_str().unwrap().to_owned()))
+            .collect()
+    }
+
+    #[test]
+    fn personal_token_takes_precedence_over_job_token() {
+        assert_eq!(
+            headers(Some("personal"), Some("job")),
+            vec![("private-token".to_owned(), "personal".to_owned())]
+        );
+    }
+
+    #[test]
+    fn job_token_is_used_without_personal_token() {
+        assert_eq!(
+            headers(None, Some("job")),
+            vec![("job-token".to_owned(), "job".to_owned())]
+        );
+    }
+
+    #[test]
+    fn unauthenticated_without_tokens() {
+        assert!(headers(None, None).is_empty());
+    }
+}
@@ -0,0 +1,193 @@
+//! Caps how many requests Foreman makes to the same host at once, so that
+//! installing tools in parallel does not trip the rate limits of self-hosted
+//! servers while requests to distinct hosts still run concurrently.
+
+use std::{
+    collections::{HashMap, HashSet},
+    env,
+    sync::{Condvar, Mutex},
+    thread::{self, ThreadId},
+};
+
+use url::Url;
+
+/// Environment variable overriding the number of concurrent requests allowed
+/// per host.
+const MAX_REQUESTS_PER_HOST_ENV_VARIABLE: &str = "FOREMAN_MAX_REQUESTS_PER_HOST";
+const DEFAULT_MAX_REQUESTS_PER_HOST: usize = 2;
+
+#[derive(Debug)]
+pub struct HostLimiter {
+    limit: usize,
+    state: Mutex<State>,
+    released: Condvar,
+}
+
+#[derive(Debug, Default)]
+struct State {
+    /// The threads holding a request slot, by host.
+    active: HashMap<String, Vec<ThreadId>>,
+    /// Threads whose work was given up on, which no longer take up slots.
+    abandoned: HashSet<ThreadId>,
+}
+
+impl HostLimiter {
+    pub fn new(limit: usize) -> Self {
+        Self {
+            limit: limit.max(1),
+            state: Mutex::default(),
+            released: Condvar::new(),
+        }
+    }
+
+    pub fn from_env() -> Self {
+        let limit = match env::var(MAX_REQUESTS_PER_HOST_ENV_VARIABLE) {
+            Ok(value) => value.parse().unwrap_or_else(|_| {
+                log::warn!(
+                    "ignoring {}={}: expected a positive number",
+                    MAX_REQUESTS_PER_HOST_ENV_VARIABLE,
+                    value
+                );
+                DEFAULT_MAX_REQUESTS_PER_HOST
+            }),
+            Err(_) => DEFAULT_MAX_REQUESTS_PER_HOST,
+        };
+
+        Self::new(limit)
+    }
+
+    /// Waits until a request to `host` is allowed. The request slot is held
+    /// until the returned permit is dropped.
+    pub fn acquire(&self, host: &Url) -> HostPermit<'_> {
+        let key = host.origin().ascii_serialization();
+        let thread = thread::current().id();
+
+        let mut state = self.state.lock().unwrap();
+        if !state.abandoned.contains(&thread) {
+            while state.active.get(&key).map_or(0, Vec::len) >= self.limit {
+                log::trace!("Waiting for a request slot for {}", key);
+                state = self.released.wait(state).unwrap();
+            }
+            state.active.entry(key.clone()).or_default().push(thread);
+        }
+
+        HostPermit {
+            limiter: self,
+            key,
+            thread,
+        }
+    }
+
+    /// Frees the request slots held by `thread`, whose work was given up on,
+    /// and lets it make further requests without taking up any.
+    pub fn abandon(&self, thread: ThreadId) {
+        let mut state = self.state.lock().unwrap();
+        state.abandoned.insert(thread);
+        for holders in state.active.values_mut() {
+            holders.retain(|holder| *holder != thread);
+        }
+        state.active.retain(|_, holders| !holders.is_empty());
+        self.released.notify_all();
+    }
+}
+
+pub struct HostPermit<'a> {
+    limiter: &'a HostLimiter,
+    key: String,
+    thread: ThreadId,
+}
+
+impl Drop for HostPermit<'_> {
+    fn drop(&mut self) {
+        let mut state = self.limiter.state.lock().unwrap();
+        if let Some(holders) = state.active.get_mut(&self.key) {
+            if let Some(index) = holders.iter().position(|holder| *holder == self.thread) {
+                holders.remove(index);
+            }
+            if holders.is_empty() {
+                state.active.remove(&self.key);
+            }
+        }
+        self.limiter.released.notify_all();
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use std::{
+        sync::{
+            atomic::{AtomicUsize, Ordering},
+            Barrier,
+        },
+        thread,
+        time::Duration,
+    };
+
+    use super::*;
+
+    #[test]
+    fn caps_concurrent_requests_to_the_same_host() {
+        let limiter = HostLimiter::new(2);
+        let host = Url::parse("https://artifactory.example.com").unwrap();
+        let current = AtomicUsize::new(0);
+        let highest = AtomicUsize::new(0);
+
+        thread::scope(|scope| {
+            for _ in 0..8 {
+                scope.spawn(|| {
+                    let _permit = limiter.acquire(&host);
+                    let now = current.fetch_add(1, Ordering::SeqCst) + 1;
+                    highest.fetch_max(now, Ordering::SeqCst);
+                    thread::sleep(Duration::from_millis(20));
+                    current.fetch_sub(1, Ordering::SeqCst);
+                });
+            }
+        });
+
+        assert!(highest.load(Ordering::SeqCst) <= 2);
+        assert_eq!(current.load(Ordering::SeqCst), 0);
+    }
+
+    #[test]
+    fn distinct_hosts_do_not_wait_on_each_other() {
+        let limiter = HostLimiter::new(1);
+        // Both threads must hold their permit at the same time to get past
+        // the barrier, which would deadlock if the hosts shared a slot.
+        let barrier = Barrier::new(2);
+
+        thread::scope(|scope| {
+            for host in ["https://gitlab.example.com", "https://github.com"] {
+                let limiter = &limiter;
+                let barrier = &barrier;
+                scope.spawn(move || {
+                    let _permit = limiter.acquire(&Url::parse(host).unwrap());
+                    barrier.wait();
+                });
+            }
+        });
+    }
+
+    #[test]
+    fn abandoned_threads_free_their_slots() {
+        let limiter = HostLimiter::new(1);
+        let host = Url::parse("https://github.com").unwrap();
+
+        thread::scope(|scope| {
+            let (acquired, wait_for_acquired) = std::sync::mpsc::channel();
+            let (finish, wait_for_finish) = std::sync::mpsc::channel::<()>();
+            let limiter = &limiter;
+            let host = &host;
+            let worker = scope.spawn(move || {
+                let _permit = limiter.acquire(host);
+                acquired.send(()).unwrap();
+                let _ = wait_for_finish.recv();
+            });
+            wait_for_acquired.recv().unwrap();
+
+            limiter.abandon(worker.thread().id());
+            // Would wait for the worker forever if its slot were still held.
+            drop(limiter.acquire(host));
+            drop(finish);
+        });
+    }
+}
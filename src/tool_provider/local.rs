@@ -0,0 +1,209 @@
+//! Tool provider reading releases from a directory on disk, like a mirror on
+//! a network share.
+//!
+//! Each tool has its own directory under the host's `file://` URL, named
+//! after the tool's path. That directory contains a `releases.json` manifest
+//! listing its releases:
+//!
+//! ```json
+//! [
+//!     {
+//!         "tag_name": "v1.0.0",
+//!         "assets": [{ "name": "tool-1.0.0-linux.zip" }]
+//!     }
+//! ]
+//! ```
+//!
+//! Assets are read from `<tag_name>/<name>` next to the manifest, unless they
+//! specify another `path` relative to the tool's directory.
+
+use std::path::{Path, PathBuf};
+
+use serde::{Deserialize, Serialize};
+use url::Url;
+
+use super::{DownloadOptions, Release, ReleaseAsset, ToolProviderImpl};
+use crate::{
+    error::{ForemanError, ForemanResult},
+    fs,
+};
+
+const MANIFEST_NAME: &str = "releases.json";
+
+#[derive(Debug, Default)]
+pub struct LocalProvider;
+
+impl ToolProviderImpl for LocalProvider {
+    fn get_releases(&self, repo: &str, host: &Url) -> ForemanResult<Vec<Release>> {
+        let tool_directory = directory_from_url(host)?.join(repo);
+        let manifest_path = tool_directory.join(MANIFEST_NAME);
+
+        log::debug!("Reading local releases from {}", manifest_path.display());
+        let contents = fs::try_read_to_string(&manifest_path)?.ok_or_else(|| {
+            ForemanError::io_error_with_context(
+                std::io::ErrorKind::NotFound.into(),
+                format!(
+                    "unable to find release manifest {}",
+                    manifest_path.display()
+                ),
+            )
+        })?;
+
+        let releases: Vec<LocalRelease> = serde_json::from_str(&contents).map_err(|err| {
+            ForemanError::unexpected_response_body(
+                err.to_string(),
+                contents.clone(),
+                manifest_path.display().to_string(),
+            )
+        })?;
+
+        releases
+            .into_iter()
+            .map(|release| release.into_release(&tool_directory))
+            .collect()
+    }
+
+    fn download_asset(&self, url: &str, options: &DownloadOptions) -> ForemanResult<Vec<u8>> {
+        let parsed_url = Url::parse(url).map_err(|err| invalid_url(url, err))?;
+        let path = directory_from_url(&parsed_url)?;
+
+        if let (Some(max_size), Ok(metadata)) = (options.max_size, std::fs::metadata(&path)) {
+            if metadata.len() > max_size {
+                return Err(ForemanError::AssetTooLarge {
+                    url: url.to_owned(),
+                    size: Some(metadata.len()),
+                    max_size,
+                });
+            }
+        }
+
+        log::debug!("Reading release asset {}", path.display());
+        fs::try_read(&path)?.ok_or_else(|| {
+            ForemanError::io_error_with_context(
+                std::io::ErrorKind::NotFound.into(),
+                format!("unable to find release asset {}", path.display()),
+            )
+        })
+    }
+}
+
+fn directory_from_url(url: &Url) -> ForemanResult<PathBuf> {
+    url.to_file_path()
+        .map_err(|()| invalid_url(url, "expected a `file://` URL"))
+}
+
+fn invalid_url<U: std::fmt::Display, E: std::fmt::Display>(url: U, err: E) -> ForemanError {
+    ForemanError::io_error_with_context(
+        std::io::ErrorKind::InvalidInput.into(),
+        format!("invalid local mirror location `{}`: {}", url, err),
+    )
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+struct LocalRelease {
+    tag_name: String,
+    #[serde(default)]
+    prerelease: bool,
+    assets: Vec<LocalAsset>,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+struct LocalAsset {
+    name: String,
+    path: Option<String>,
+}
+
+impl LocalRelease {
+    fn into_release(self, tool_directory: &Path) -> ForemanResult<Release> {
+        let tag_name = self.tag_name;
+        let assets = self
+            .assets
+            .into_iter()
+            .map(|asset| {
+                let path = match asset.path {
+                    Some(path) => tool_directory.join(path),
+                    None => tool_directory.join(&tag_name).join(&asset.name),
+                };
+                let url = Url::from_file_path(&path)
+                    .map_err(|()| invalid_url(path.display(), "expected an absolute path"))?;
+
+                Ok(ReleaseAsset {
+                    name: asset.name,
+                    url: url.to_string(),
+                })
+            })
+            .collect::<ForemanResult<_>>()?;
+
+        Ok(Release {
+            tag_name,
+            prerelease: self.prerelease,
+            assets,
+            source_tarball: None,
+        })
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use tempfile::tempdir;
+
+    use super::*;
+
+    #[test]
+    fn reads_releases_and_assets_from_directory() {
+        let mirror = tempdir().unwrap();
+        let tool_directory = mirror.path().join("owner/tool");
+        std::fs::create_dir_all(tool_directory.join("v1.0.0")).unwrap();
+        std::fs::create_dir_all(tool_directory.join("shared")).unwrap();
+        std::fs::write(
+            tool_directory.join(MANIFEST_NAME),
+            r#"[
+                {
+                    "tag_name": "v1.0.0",
+                    "assets": [
+                        { "name": "tool-linux.zip" },
+                        { "name": "tool-win64.zip", "path": "shared/tool-win64.zip" }
+                    ]
+                }
+            ]"#,
+        )
+        .unwrap();
+        std::fs::write(tool_directory.join("v1.0.0/tool-linux.zip"), b"linux").unwrap();
+        std::fs::write(tool_directory.join("shared/tool-win64.zip"), b"windows").unwrap();
+
+        let host = Url::from_directory_path(mirror.path()).unwrap();
+        let releases = LocalProvider.get_releases("owner/tool", &host).unwrap();
+
+        assert_eq!(releases.len(), 1);
+        assert_eq!(releases[0].tag_name, "v1.0.0");
+        assert!(!releases[0].prerelease);
+        let names: Vec<_> = releases[0]
+            .assets
+            .iter()
+            .map(|asset| asset.name.as_str())
+            .collect();
+        assert_eq!(names, vec!["tool-linux.zip", "tool-win64.zip"]);
+
+        assert_eq!(
+            LocalProvider
+                .download_asset(&releases[0].assets[0].url, &DownloadOptions::default())
+                .unwrap(),
+            b"linux"
+        );
+        assert_eq!(
+            LocalProvider
+                .download_asset(&releases[0].assets[1].url, &DownloadOptions::default())
+                .unwrap(),
+            b"windows"
+        );
+    }
+
+    #[test]
+    fn missing_manifest_is_an_error() {
+        let mirror = tempdir().unwrap();
+        let host = Url::from_directory_path(mirror.path()).unwrap();
+
+        let err = LocalProvider.get_releases("owner/tool", &host).unwrap_err();
+        assert!(err.to_string().contains(MANIFEST_NAME));
+    }
+}
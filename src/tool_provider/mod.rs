@@ -1,25 +1,106 @@
 mod artifactory;
+mod bitbucket;
+mod direct;
+#[cfg(test)]
+pub mod fixed_releases;
 mod github;
 mod gitlab;
+mod host_limiter;
+mod http;
+mod local;
+mod release_cache;
 
-use crate::{error::ForemanResult, paths::ForemanPaths};
+use crate::{
+    config::{ConfigFile, ToolSpec},
+    error::ForemanResult,
+    paths::ForemanPaths,
+};
 use artifactory::ArtifactoryProvider;
+use bitbucket::BitbucketProvider;
+use direct::DirectProvider;
 use github::GithubProvider;
 use gitlab::GitlabProvider;
-use std::{collections::HashMap, fmt};
+use host_limiter::HostLimiter;
+use local::LocalProvider;
+pub(crate) use release_cache::ReleaseCache;
+use serde::{Deserialize, Serialize};
+use std::{
+    collections::HashMap,
+    env, fmt,
+    thread::{self, ThreadId},
+    time::{Duration, SystemTime},
+};
 use url::Url;
 
-pub trait ToolProviderImpl: fmt::Debug {
+#[cfg(test)]
+pub use http::test_server;
+
+/// Environment variable setting the largest release asset, in bytes, that
+/// Foreman downloads. The `--max-download-size` option takes precedence.
+const MAX_DOWNLOAD_SIZE_ENV_VARIABLE: &str = "FOREMAN_MAX_DOWNLOAD_SIZE";
+
+/// How many more times the releases of a tool known to have some are listed
+/// when the host returns none, which hosts sometimes do for a moment, for
+/// example while their caches catch up.
+const EMPTY_RELEASES_RETRIES: u32 = 2;
+
+const EMPTY_RELEASES_RETRY_DELAY: Duration = Duration::from_secs(2);
+
+pub trait ToolProviderImpl: fmt::Debug + Send + Sync {
     fn get_releases(&self, repo: &str, host: &Url) -> ForemanResult<Vec<Release>>;
 
-    fn download_asset(&self, url: &str) -> ForemanResult<Vec<u8>>;
+    /// Whether `get_releases` leaves out the assets of releases, which are
+    /// then listed by `get_release_assets` for the releases that need them.
+    fn lists_assets_on_demand(&self) -> bool {
+        false
+    }
+
+    /// Lists the assets of the release tagged `tag_name`, for providers that
+    /// list assets on demand.
+    fn get_release_assets(
+        &self,
+        _repo: &str,
+        _host: &Url,
+        _tag_name: &str,
+    ) -> ForemanResult<Vec<ReleaseAsset>> {
+        Ok(Vec::new())
+    }
+
+    /// Fetches the release tagged `tag_name` without listing the others, for
+    /// tools pinned to an exact version. Returns `None` when there is no such
+    /// release, or when the provider can only list releases, so that the
+    /// releases are listed instead.
+    fn get_release_by_tag(
+        &self,
+        _repo: &str,
+        _host: &Url,
+        _tag_name: &str,
+    ) -> ForemanResult<Option<Release>> {
+        Ok(None)
+    }
+
+    /// Downloads the asset at `url`, failing when it is larger than
+    /// `options.max_size` bytes.
+    fn download_asset(&self, url: &str, options: &DownloadOptions) -> ForemanResult<Vec<u8>>;
 }
 
-#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+/// How a release asset is downloaded.
+#[derive(Debug, Default, Clone)]
+pub struct DownloadOptions {
+    /// Size in bytes past which the download is refused.
+    pub max_size: Option<u64>,
+    /// What the download is for, shown next to its progress bar.
+    pub label: String,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
 pub enum Provider {
     Github,
     Gitlab,
+    Bitbucket,
     Artifactory,
+    Local,
+    Direct,
 }
 
 impl fmt::Display for Provider {
@@ -30,7 +111,10 @@ impl fmt::Display for Provider {
             match self {
                 Provider::Github => "GitHub",
                 Provider::Gitlab => "GitLab",
+                Provider::Bitbucket => "Bitbucket",
                 Provider::Artifactory => "Artifactory",
+                Provider::Local => "Local mirror",
+                Provider::Direct => "Direct URL",
             }
         )
     }
@@ -39,24 +123,202 @@ impl fmt::Display for Provider {
 #[derive(Debug)]
 pub struct ToolProvider {
     providers: HashMap<Provider, Box<dyn ToolProviderImpl>>,
+    host_limiter: HostLimiter,
+    /// The largest release asset, in bytes, that may be downloaded.
+    max_download_size: Option<u64>,
+    empty_releases_retries: u32,
+    empty_releases_retry_delay: Duration,
+    /// Where listed releases are kept for a while, unless turned off.
+    release_cache: Option<ReleaseCache>,
+    /// List releases even when fresh ones are cached.
+    refresh_releases: bool,
 }
 
 impl ToolProvider {
-    pub fn new(paths: &ForemanPaths) -> Self {
+    /// Creates the providers for `config`. Tokens from its `[auth]` table
+    /// are used when the auth store has none, and Artifactory tokens are
+    /// placed in requests as its hosts' `auth_in` keys say.
+    pub fn new(paths: &ForemanPaths, config: &ConfigFile) -> Self {
+        let config_auth = config.auth.clone().unwrap_or_default();
+
         let mut providers: HashMap<Provider, Box<dyn ToolProviderImpl>> = HashMap::default();
         providers.insert(
             Provider::Github,
-            Box::new(GithubProvider::new(paths.clone())),
+            Box::new(GithubProvider::new(paths.clone(), config_auth.clone())),
         );
         providers.insert(
             Provider::Gitlab,
-            Box::new(GitlabProvider::new(paths.clone())),
+            Box::new(GitlabProvider::new(paths.clone(), config_auth.clone())),
+        );
+        providers.insert(
+            Provider::Bitbucket,
+            Box::new(BitbucketProvider::new(paths.clone(), config_auth)),
         );
         providers.insert(
             Provider::Artifactory,
-            Box::new(ArtifactoryProvider::new(paths.clone())),
+            Box::new(ArtifactoryProvider::new(
+                paths.clone(),
+                config.auth_placements(),
+            )),
         );
-        Self { providers }
+        providers.insert(Provider::Local, Box::new(LocalProvider));
+        providers.insert(Provider::Direct, Box::new(DirectProvider));
+        Self {
+            providers,
+            host_limiter: HostLimiter::from_env(),
+            max_download_size: max_download_size_from_env(),
+            empty_releases_retries: EMPTY_RELEASES_RETRIES,
+            empty_releases_retry_delay: EMPTY_RELEASES_RETRY_DELAY,
+            release_cache: ReleaseCache::from_env(paths.release_cache_dir()),
+            refresh_releases: false,
+        }
+    }
+
+    /// Creates a `ToolProvider` that only knows about a single provider.
+    #[cfg(test)]
+    pub fn with_provider(provider: Provider, implementation: Box<dyn ToolProviderImpl>) -> Self {
+        Self {
+            providers: HashMap::from([(provider, implementation)]),
+            host_limiter: HostLimiter::new(1),
+            max_download_size: None,
+            empty_releases_retries: EMPTY_RELEASES_RETRIES,
+            empty_releases_retry_delay: Duration::ZERO,
+            release_cache: None,
+            refresh_releases: false,
+        }
+    }
+
+    pub fn set_max_download_size(&mut self, max_download_size: u64) {
+        self.max_download_size = Some(max_download_size);
+    }
+
+    /// Sets how many more times the releases of a tool known to have some are
+    /// listed when the host returns none.
+    pub fn set_empty_releases_retries(&mut self, retries: u32) {
+        self.empty_releases_retries = retries;
+    }
+
+    /// Lists releases again instead of using the cached ones, which are still
+    /// replaced by the new listing.
+    pub fn set_refresh_releases(&mut self, refresh: bool) {
+        self.refresh_releases = refresh;
+    }
+
+    /// Keeps listed releases in `release_cache`.
+    #[cfg(test)]
+    pub fn set_release_cache(&mut self, release_cache: ReleaseCache) {
+        self.release_cache = Some(release_cache);
+    }
+
+    /// The releases of `tool` listed recently, from the release cache. Local
+    /// mirrors are never cached, since they are read from disk anyway.
+    pub fn cached_releases(&self, tool: &ToolSpec) -> Option<Vec<Release>> {
+        if self.refresh_releases {
+            return None;
+        }
+        self.release_cache(tool)?.get(tool, SystemTime::now())
+    }
+
+    fn release_cache(&self, tool: &ToolSpec) -> Option<&ReleaseCache> {
+        self.release_cache
+            .as_ref()
+            .filter(|_| tool.provider() != Provider::Local)
+    }
+
+    /// Lists the releases of `tool`, waiting for a free request slot for its
+    /// host first, and keeps them in the release cache.
+    ///
+    /// A host returning no releases cannot be told apart from a repository
+    /// without any. When `expect_releases` says the tool is known to have
+    /// some, for example because a version of it was installed before, the
+    /// releases are listed again a few times before giving up. Local mirrors
+    /// are read from disk and never retried.
+    pub fn list_releases(
+        &self,
+        tool: &ToolSpec,
+        expect_releases: bool,
+    ) -> ForemanResult<Vec<Release>> {
+        let provider = tool.provider();
+        let retries = match provider {
+            Provider::Local => 0,
+            _ if !expect_releases => 0,
+            _ => self.empty_releases_retries,
+        };
+        let release_cache = self.release_cache(tool);
+
+        let mut retry = 0;
+        loop {
+            let releases = {
+                let _permit = self.host_limiter.acquire(tool.host());
+                self.get(&provider).get_releases(tool.path(), tool.host())?
+            };
+            if !releases.is_empty() {
+                if let Some(release_cache) = release_cache {
+                    release_cache.put(tool, &releases, SystemTime::now());
+                }
+                return Ok(releases);
+            }
+            if retry == retries {
+                return Ok(releases);
+            }
+
+            retry += 1;
+            log::warn!(
+                "No releases found for {}, listing them again in {} seconds (retry {} of {})",
+                tool.source(),
+                self.empty_releases_retry_delay.as_secs(),
+                retry,
+                retries
+            );
+            thread::sleep(self.empty_releases_retry_delay);
+        }
+    }
+
+    /// Whether the releases of `tool` come without their assets, which must
+    /// be listed with `get_release_assets`.
+    pub fn lists_assets_on_demand(&self, tool: &ToolSpec) -> bool {
+        self.get(&tool.provider()).lists_assets_on_demand()
+    }
+
+    /// Lists the assets of the release of `tool` tagged `tag_name`, waiting
+    /// for a free request slot for its host first.
+    pub fn get_release_assets(
+        &self,
+        tool: &ToolSpec,
+        tag_name: &str,
+    ) -> ForemanResult<Vec<ReleaseAsset>> {
+        let _permit = self.host_limiter.acquire(tool.host());
+        self.get(&tool.provider())
+            .get_release_assets(tool.path(), tool.host(), tag_name)
+    }
+
+    /// Fetches the release of `tool` tagged `tag_name` alone, if its provider
+    /// can, waiting for a free request slot for its host first.
+    pub fn get_release_by_tag(
+        &self,
+        tool: &ToolSpec,
+        tag_name: &str,
+    ) -> ForemanResult<Option<Release>> {
+        let _permit = self.host_limiter.acquire(tool.host());
+        self.get(&tool.provider())
+            .get_release_by_tag(tool.path(), tool.host(), tag_name)
+    }
+
+    /// Downloads a release asset of `tool`, waiting for a free request slot
+    /// for its host first.
+    pub fn download_asset(&self, tool: &ToolSpec, url: &str) -> ForemanResult<Vec<u8>> {
+        let _permit = self.host_limiter.acquire(tool.host());
+        let options = DownloadOptions {
+            max_size: self.max_download_size,
+            label: tool.source(),
+        };
+        self.get(&tool.provider()).download_asset(url, &options)
+    }
+
+    /// Frees the request slots held by `thread`, whose work was given up on,
+    /// so that it does not hold up requests to the same hosts.
+    pub fn abandon(&self, thread: ThreadId) {
+        self.host_limiter.abandon(thread);
     }
 
     pub fn get(&self, provider: &Provider) -> &dyn ToolProviderImpl {
@@ -72,14 +334,30 @@ impl ToolProvider {
     }
 }
 
-#[derive(Debug, Clone, PartialEq, Eq)]
+fn max_download_size_from_env() -> Option<u64> {
+    let value = env::var(MAX_DOWNLOAD_SIZE_ENV_VARIABLE).ok()?;
+
+    value.parse().map(Some).unwrap_or_else(|_| {
+        log::warn!(
+            "ignoring {}={}: expected a number of bytes",
+            MAX_DOWNLOAD_SIZE_ENV_VARIABLE,
+            value
+        );
+        None
+    })
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
 pub struct Release {
     pub tag_name: String,
     pub prerelease: bool,
     pub assets: Vec<ReleaseAsset>,
+    /// URL of the release's generated source tarball, for tools that opt
+    /// into `source_fallback`.
+    pub source_tarball: Option<String>,
 }
 
-#[derive(Debug, Clone, PartialEq, Eq)]
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
 pub struct ReleaseAsset {
     pub url: String,
     pub name: String,
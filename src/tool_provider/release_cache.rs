@@ -0,0 +1,231 @@
+//! Keeps the releases listed for each tool on disk for a while, so that
+//! installing tools again soon after does not list their releases again,
+//! which is slow and counts against the rate limits of hosts.
+
+use std::{
+    env,
+    path::PathBuf,
+    time::{Duration, SystemTime, UNIX_EPOCH},
+};
+
+use serde::{Deserialize, Serialize};
+
+use super::{Provider, Release};
+use crate::{config::ToolSpec, fs};
+
+/// Environment variable setting how many seconds listed releases are reused
+/// for. `0` turns the cache off.
+const RELEASE_CACHE_TTL_ENV_VARIABLE: &str = "FOREMAN_RELEASE_CACHE_TTL";
+const DEFAULT_RELEASE_CACHE_TTL: Duration = Duration::from_secs(60 * 60);
+
+#[derive(Debug)]
+pub struct ReleaseCache {
+    directory: PathBuf,
+    ttl: Duration,
+}
+
+/// The contents of a cache file.
+#[derive(Debug, Serialize, Deserialize)]
+struct CachedReleases {
+    /// When the releases were listed, in seconds since the Unix epoch.
+    listed_at: u64,
+    releases: Vec<Release>,
+}
+
+impl ReleaseCache {
+    pub fn new(directory: PathBuf, ttl: Duration) -> Self {
+        Self { directory, ttl }
+    }
+
+    /// A cache in `directory` with the lifetime set by
+    /// `FOREMAN_RELEASE_CACHE_TTL`, or `None` when it is turned off.
+    pub fn from_env(directory: PathBuf) -> Option<Self> {
+        let ttl = match env::var(RELEASE_CACHE_TTL_ENV_VARIABLE) {
+            Ok(value) => value
+                .trim()
+                .parse()
+                .map(Duration::from_secs)
+                .unwrap_or_else(|_| {
+                    log::warn!(
+                        "ignoring {}={}: expected a number of seconds",
+                        RELEASE_CACHE_TTL_ENV_VARIABLE,
+                        value
+                    );
+                    DEFAULT_RELEASE_CACHE_TTL
+                }),
+            Err(_) => DEFAULT_RELEASE_CACHE_TTL,
+        };
+
+        (!ttl.is_zero()).then(|| Self::new(directory, ttl))
+    }
+
+    /// The releases of `tool` listed less than the cache's lifetime before
+    /// `now`, if any. Unreadable cache files are treated as missing.
+    pub fn get(&self, tool: &ToolSpec, now: SystemTime) -> Option<Vec<Release>> {
+        let path = self.path(tool);
+        let contents = match fs::try_read(&path) {
+            Ok(contents) => contents?,
+            Err(err) => {
+                log::debug!("{}", err);
+                return None;
+            }
+        };
+        let cached: CachedReleases = match serde_json::from_slice(&contents) {
+            Ok(cached) => cached,
+            Err(err) => {
+                log::debug!("Ignoring cached releases {}: {}", path.display(), err);
+                return None;
+            }
+        };
+
+        let age = now
+            .duration_since(UNIX_EPOCH + Duration::from_secs(cached.listed_at))
+            .ok()?;
+        if age >= self.ttl {
+            log::debug!("Cached releases of {} are outdated", tool.source());
+            return None;
+        }
+
+        log::debug!(
+            "Using the releases of {} listed {} seconds ago",
+            tool.source(),
+            age.as_secs()
+        );
+        Some(cached.releases)
+    }
+
+    /// Stores the `releases` of `tool` as listed at `now`. Failing to store
+    /// them only means they are listed again next time, so it is not an
+    /// error.
+    pub fn put(&self, tool: &ToolSpec, releases: &[Release], now: SystemTime) {
+        let cached = CachedReleases {
+            listed_at: now.duration_since(UNIX_EPOCH).unwrap_or_default().as_secs(),
+            releases: releases.to_vec(),
+        };
+        let contents = serde_json::to_vec(&cached).expect("unable to serialize releases");
+
+        // Written aside then renamed, so that concurrent installs never read
+        // a partially written file.
+        let path = self.path(tool);
+        let temp_path = fs::temp_path(&path);
+        let stored = path
+            .parent()
+            .map_or(Ok(()), fs::create_dir_all)
+            .and_then(|_| fs::write(&temp_path, contents))
+            .and_then(|_| fs::rename(&temp_path, &path));
+        if let Err(err) = stored {
+            log::warn!("Unable to cache the releases of {}: {}", tool.source(), err);
+        }
+    }
+
+    /// Where the releases of `tool` are cached: a directory per provider,
+    /// with a file per tool named after its cache key.
+    fn path(&self, tool: &ToolSpec) -> PathBuf {
+        let provider = match tool.provider() {
+            Provider::Github => "github",
+            Provider::Gitlab => "gitlab",
+            Provider::Bitbucket => "bitbucket",
+            Provider::Artifactory => "artifactory",
+            Provider::Local => "local",
+            Provider::Direct => "direct",
+        };
+        let file_name: String = tool
+            .cache_key()
+            .0
+            .to_lowercase()
+            .chars()
+            .map(|c| match c {
+                '/' | '\\' => '_',
+                c if c.is_ascii_alphanumeric() || "-_.@".contains(c) => c,
+                _ => '-',
+            })
+            .collect();
+
+        self.directory
+            .join(provider)
+            .join(format!("{}.json", file_name))
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use tempfile::tempdir;
+
+    use super::*;
+    use crate::{config::ConfigFile, tool_provider::fixed_releases::FixedReleases};
+
+    fn tool(declaration: &str) -> ToolSpec {
+        ToolSpec::from_value(
+            &toml::from_str(declaration).unwrap(),
+            &ConfigFile::new_with_defaults().hosts,
+        )
+        .unwrap()
+    }
+
+    fn releases() -> Vec<Release> {
+        FixedReleases::new(vec!["v7.3.0"]).releases(true)
+    }
+
+    #[test]
+    fn releases_are_reused_until_they_expire() {
+        let directory = tempdir().unwrap();
+        let cache = ReleaseCache::new(directory.path().to_owned(), Duration::from_secs(60));
+        let rojo = tool("github = \"rojo-rbx/rojo\"\nversion = \"7.3.0\"");
+        let listed_at = UNIX_EPOCH + Duration::from_secs(1_700_000_000);
+
+        assert_eq!(cache.get(&rojo, listed_at), None);
+        cache.put(&rojo, &releases(), listed_at);
+        assert!(directory
+            .path()
+            .join("github")
+            .join("rojo-rbx_rojo.json")
+            .is_file());
+
+        assert_eq!(
+            cache.get(&rojo, listed_at + Duration::from_secs(59)),
+            Some(releases())
+        );
+        assert_eq!(cache.get(&rojo, listed_at + Duration::from_secs(60)), None);
+    }
+
+    #[test]
+    fn providers_cache_releases_until_refreshed() {
+        let directory = tempdir().unwrap();
+        let (mut providers, requests) = FixedReleases::new(vec!["v7.3.0"]).logged_providers();
+        providers.set_release_cache(ReleaseCache::new(
+            directory.path().to_owned(),
+            DEFAULT_RELEASE_CACHE_TTL,
+        ));
+        let rojo = tool("github = \"rojo-rbx/rojo\"\nversion = \"7.3.0\"");
+
+        assert_eq!(providers.cached_releases(&rojo), None);
+        assert_eq!(providers.list_releases(&rojo, true).unwrap(), releases());
+        assert_eq!(providers.cached_releases(&rojo), Some(releases()));
+        assert_eq!(requests.listings(), 1);
+
+        providers.set_refresh_releases(true);
+        assert_eq!(providers.cached_releases(&rojo), None);
+        assert_eq!(providers.list_releases(&rojo, true).unwrap(), releases());
+        assert_eq!(requests.listings(), 2);
+    }
+
+    #[test]
+    fn tools_are_cached_apart() {
+        let directory = tempdir().unwrap();
+        let cache = ReleaseCache::new(directory.path().to_owned(), Duration::from_secs(60));
+        let now = SystemTime::now();
+
+        cache.put(
+            &tool("github = \"rojo-rbx/rojo\"\nversion = \"7.3.0\""),
+            &releases(),
+            now,
+        );
+
+        for other in [
+            "gitlab = \"rojo-rbx/rojo\"\nversion = \"7.3.0\"",
+            "github = \"rojo-rbx/other\"\nversion = \"7.3.0\"",
+        ] {
+            assert_eq!(cache.get(&tool(other), now), None, "{}", other);
+        }
+    }
+}
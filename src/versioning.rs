@@ -0,0 +1,185 @@
+//! How the tags of a tool's releases map to versions, and how its version
+//! requirement is read.
+//!
+//! Most tools tag releases with semantic versions. Tools tagged by date, like
+//! `2024.03.01`, opt into calendar versioning with `versioning = "calver"`:
+//! their tags are read as a year, month and day compared chronologically, and
+//! their requirements, like `>=2024.01.01`, are written with the same dates.
+//! Dates are stored as the semantic version with the same numbers, so
+//! `2024.03.01` is installed as `2024.3.1`.
+
+use semver::{Version, VersionReq};
+
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub enum Versioning {
+    #[default]
+    Semver,
+    Calver,
+}
+
+impl Versioning {
+    /// The versioning scheme named `name` in a tool's `versioning` key.
+    pub fn from_name(name: &str) -> Option<Self> {
+        match name {
+            "semver" => Some(Self::Semver),
+            "calver" => Some(Self::Calver),
+            _ => None,
+        }
+    }
+
+    /// The version a release's tag name stands for, with or without a leading
+    /// `v`.
+    pub fn tag_version(self, tag_name: &str) -> Option<Version> {
+        match self {
+            Self::Semver => Version::parse(tag_name).ok().or_else(|| {
+                if !tag_name.starts_with('v') {
+                    log::debug!("Release tag name did not start with 'v'! {}", tag_name);
+                    return None;
+                }
+
+                Version::parse(&tag_name[1..]).ok()
+            }),
+            Self::Calver => {
+                let date = tag_name.strip_prefix('v').unwrap_or(tag_name);
+                let parts = date
+                    .split('.')
+                    .map(|part| {
+                        part.parse::<u64>()
+                            .ok()
+                            .filter(|_| part.chars().all(|c| c.is_ascii_digit()))
+                    })
+                    .collect::<Option<Vec<_>>>();
+
+                match parts.as_deref() {
+                    Some([year, month]) => Some(Version::new(*year, *month, 0)),
+                    Some([year, month, day]) => Some(Version::new(*year, *month, *day)),
+                    _ => {
+                        log::debug!("Release tag name is not a date! {}", tag_name);
+                        None
+                    }
+                }
+            }
+        }
+    }
+
+    /// Parses a tool's version requirement. Calendar versions without an
+    /// operator are exact, so that `2024.03` matches the releases of March
+    /// 2024 and `2024.03.01` only that day's.
+    pub fn parse_requirement(self, requirement: &str) -> Option<VersionReq> {
+        match self {
+            Self::Semver => VersionReq::parse(requirement).ok(),
+            Self::Calver => {
+                let comparators = requirement
+                    .split(',')
+                    .map(calver_comparator)
+                    .collect::<Option<Vec<_>>>()?;
+                VersionReq::parse(&comparators.join(", ")).ok()
+            }
+        }
+    }
+
+    /// Parses a version written in a tool's declaration, like a yanked
+    /// version.
+    pub fn parse_version(self, version: &str) -> Option<Version> {
+        match self {
+            Self::Semver => Version::parse(version).ok(),
+            Self::Calver => self.tag_version(version),
+        }
+    }
+}
+
+/// Rewrites a calendar version comparator, like `>=2024.01.01`, as the
+/// semantic version comparator `>=2024.1.1`.
+fn calver_comparator(comparator: &str) -> Option<String> {
+    let comparator = comparator.trim();
+    let date_start = comparator.find(|c: char| !"=<>~^ ".contains(c))?;
+    let operator = comparator[..date_start].trim();
+
+    let date = comparator[date_start..]
+        .split('.')
+        .map(|part| match part {
+            "*" | "x" | "X" => Some(part.to_owned()),
+            _ if !part.is_empty() && part.chars().all(|c| c.is_ascii_digit()) => {
+                part.parse::<u64>().ok().map(|number| number.to_string())
+            }
+            _ => None,
+        })
+        .collect::<Option<Vec<_>>>()?
+        .join(".");
+
+    let operator = match operator {
+        "" if date != "*" => "=",
+        operator => operator,
+    };
+    Some(format!("{}{}", operator, date))
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn calver_tags_are_dates() {
+        assert_eq!(
+            Versioning::Calver.tag_version("2024.03.01"),
+            Some(Version::new(2024, 3, 1))
+        );
+        assert_eq!(
+            Versioning::Calver.tag_version("v2024.12"),
+            Some(Version::new(2024, 12, 0))
+        );
+        assert_eq!(Versioning::Calver.tag_version("2024.03.01-rc"), None);
+        assert_eq!(Versioning::Calver.tag_version("2024.03.01.1"), None);
+        assert_eq!(Versioning::Calver.tag_version("nightly"), None);
+
+        // Semantic versions reject the leading zeros of dates.
+        assert_eq!(Versioning::Semver.tag_version("2024.03.01"), None);
+        assert_eq!(
+            Versioning::Semver.tag_version("v1.2.3"),
+            Some(Version::new(1, 2, 3))
+        );
+    }
+
+    #[test]
+    fn calver_tags_compare_chronologically() {
+        let mut versions: Vec<_> = ["2024.10.02", "2024.09.30", "2023.12.31", "2024.10.01"]
+            .iter()
+            .map(|tag| Versioning::Calver.tag_version(tag).unwrap())
+            .collect();
+        versions.sort();
+
+        assert_eq!(
+            versions,
+            vec![
+                Version::new(2023, 12, 31),
+                Version::new(2024, 9, 30),
+                Version::new(2024, 10, 1),
+                Version::new(2024, 10, 2),
+            ]
+        );
+    }
+
+    #[test]
+    fn calver_ranges() {
+        let matches = |requirement: &str, tag: &str| {
+            Versioning::Calver
+                .parse_requirement(requirement)
+                .unwrap()
+                .matches(&Versioning::Calver.tag_version(tag).unwrap())
+        };
+
+        assert!(matches(">=2024.01.01", "2024.03.01"));
+        assert!(!matches(">=2024.01.01", "2023.12.31"));
+        assert!(matches(">=2024.01.01, <2024.06", "2024.05.31"));
+        assert!(!matches(">=2024.01.01, <2024.06", "2024.06.01"));
+
+        // Dates without an operator are exact, down to the part written.
+        assert!(matches("2024.03.01", "2024.03.01"));
+        assert!(!matches("2024.03.01", "2024.03.02"));
+        assert!(matches("2024.03", "2024.03.15"));
+        assert!(!matches("2024.03", "2024.04.01"));
+        assert!(matches("*", "2024.04.01"));
+
+        assert_eq!(Versioning::Calver.parse_requirement(">=2024.Jan"), None);
+    }
+}
@@ -0,0 +1,236 @@
+//! Support for `foreman watch`, which keeps the installed tools in sync with
+//! the configuration files as they are edited.
+
+use std::{
+    collections::{BTreeMap, BTreeSet},
+    io,
+    path::PathBuf,
+    sync::mpsc::{self, Receiver, RecvTimeoutError},
+    time::{Duration, Instant},
+};
+
+use notify::{Event, EventKind, RecommendedWatcher, RecursiveMode, Watcher};
+
+use crate::{
+    config::ToolSpec,
+    error::{ForemanError, ForemanResult},
+};
+
+/// How long the configuration files must stay untouched after a change
+/// before it is acted upon, so that editors saving in several steps only
+/// trigger a single reload.
+const DEBOUNCE: Duration = Duration::from_millis(200);
+
+/// Watches configuration files, including ones that do not exist yet.
+pub struct ConfigWatcher {
+    // Events stop coming once the watcher is dropped.
+    _watcher: RecommendedWatcher,
+    events: Receiver<notify::Result<Event>>,
+    config_paths: BTreeSet<PathBuf>,
+}
+
+impl ConfigWatcher {
+    /// Watches the directories of `config_paths` for changes to these files.
+    /// Files whose directory does not exist are skipped.
+    pub fn new<I>(config_paths: I) -> ForemanResult<Self>
+    where
+        I: IntoIterator<Item = PathBuf>,
+    {
+        let (sender, events) = mpsc::channel();
+        let mut watcher = notify::recommended_watcher(sender).map_err(watch_error)?;
+
+        let mut watched = BTreeSet::new();
+        for config_path in config_paths {
+            // Events carry canonical paths, so match against those.
+            let (directory, file_name) = match (config_path.parent(), config_path.file_name()) {
+                (Some(directory), Some(file_name)) => (directory, file_name),
+                _ => continue,
+            };
+            let directory = match directory.canonicalize() {
+                Ok(directory) => directory,
+                Err(_) => continue,
+            };
+
+            log::debug!("Watching {}", config_path.display());
+            watcher
+                .watch(&directory, RecursiveMode::NonRecursive)
+                .map_err(watch_error)?;
+            watched.insert(directory.join(file_name));
+        }
+
+        Ok(Self {
+            _watcher: watcher,
+            events,
+            config_paths: watched,
+        })
+    }
+
+    /// Waits for some of the watched files to change, then for them to
+    /// settle, and returns the files that changed. Returns `None` when
+    /// `timeout` elapses first.
+    pub fn next_change(
+        &self,
+        timeout: Option<Duration>,
+    ) -> ForemanResult<Option<BTreeSet<PathBuf>>> {
+        let deadline = timeout.map(|timeout| Instant::now() + timeout);
+        let mut changed = BTreeSet::new();
+
+        loop {
+            let wait = if changed.is_empty() {
+                match deadline {
+                    Some(deadline) => deadline.saturating_duration_since(Instant::now()),
+                    None => Duration::MAX,
+                }
+            } else {
+                DEBOUNCE
+            };
+
+            match self.events.recv_timeout(wait) {
+                Ok(event) => changed.extend(self.changed_config_paths(event.map_err(watch_error)?)),
+                Err(RecvTimeoutError::Timeout) if !changed.is_empty() => return Ok(Some(changed)),
+                Err(RecvTimeoutError::Timeout) => return Ok(None),
+                Err(RecvTimeoutError::Disconnected) => {
+                    return Err(watch_error(notify::Error::generic(
+                        "the file watcher stopped unexpectedly",
+                    )))
+                }
+            }
+        }
+    }
+
+    fn changed_config_paths(&self, event: Event) -> impl Iterator<Item = PathBuf> + '_ {
+        let relevant = matches!(
+            event.kind,
+            EventKind::Create(_) | EventKind::Modify(_) | EventKind::Remove(_)
+        );
+
+        event
+            .paths
+            .into_iter()
+            .filter(move |path| relevant && self.config_paths.contains(path))
+    }
+}
+
+fn watch_error(err: notify::Error) -> ForemanError {
+    ForemanError::io_error_with_context(
+        io::Error::other(err),
+        "unable to watch the configuration files",
+    )
+}
+
+/// How a declared tool changed between two versions of the configuration.
+#[derive(Debug, PartialEq, Eq)]
+pub enum ToolChange<'a> {
+    /// The tool is new, or its declaration changed.
+    Declared(&'a str),
+    Removed(&'a str),
+}
+
+/// The tools that need to be installed again or that went away between the
+/// `old` and `new` declared tools, keyed by alias, sorted by alias.
+pub fn tool_changes<'a>(
+    old: &'a BTreeMap<String, ToolSpec>,
+    new: &'a BTreeMap<String, ToolSpec>,
+) -> Vec<ToolChange<'a>> {
+    let declared = new
+        .iter()
+        .filter(|(alias, tool)| old.get(*alias) != Some(*tool))
+        .map(|(alias, _)| ToolChange::Declared(alias));
+    let removed = old
+        .keys()
+        .filter(|alias| !new.contains_key(*alias))
+        .map(|alias| ToolChange::Removed(alias));
+
+    let mut changes: Vec<ToolChange> = declared.chain(removed).collect();
+    changes.sort_by_key(|change| match change {
+        ToolChange::Declared(alias) | ToolChange::Removed(alias) => *alias,
+    });
+    changes
+}
+
+#[cfg(test)]
+mod test {
+    use std::path::Path;
+
+    use tempfile::tempdir;
+
+    use super::*;
+    use crate::config::ConfigFile;
+
+    /// Whether `path` is one of the files `watcher` reports changes for.
+    fn is_watched(watcher: &ConfigWatcher, path: &Path) -> bool {
+        watcher.config_paths.contains(&path.canonicalize().unwrap())
+    }
+
+    fn declared(contents: &str) -> BTreeMap<String, ToolSpec> {
+        ConfigFile::from_value(toml::from_str(contents).unwrap())
+            .unwrap()
+            .tools
+    }
+
+    #[test]
+    fn editing_a_watched_config_triggers_a_reload() {
+        let directory = tempdir().unwrap();
+        let config_path = directory.path().join("foreman.toml");
+        let before = "[tools]\nrojo = { github = \"rojo-rbx/rojo\", version = \"7.3.0\" }\n";
+        std::fs::write(&config_path, before).unwrap();
+
+        let watcher = ConfigWatcher::new(vec![config_path.clone()]).unwrap();
+        assert!(is_watched(&watcher, &config_path));
+
+        let after = "[tools]\nrojo = { github = \"rojo-rbx/rojo\", version = \"7.4.0\" }\n";
+        std::fs::write(&config_path, after).unwrap();
+
+        let changed = watcher
+            .next_change(Some(Duration::from_secs(10)))
+            .unwrap()
+            .expect("editing the configuration was not noticed");
+        assert_eq!(
+            changed.into_iter().collect::<Vec<_>>(),
+            vec![config_path.canonicalize().unwrap()]
+        );
+
+        let contents = std::fs::read_to_string(&config_path).unwrap();
+        assert_eq!(
+            tool_changes(&declared(before), &declared(&contents)),
+            vec![ToolChange::Declared("rojo")]
+        );
+    }
+
+    #[test]
+    fn other_files_are_ignored() {
+        let directory = tempdir().unwrap();
+        let config_path = directory.path().join("foreman.toml");
+
+        let watcher = ConfigWatcher::new(vec![config_path]).unwrap();
+        std::fs::write(directory.path().join("README.md"), "# Project\n").unwrap();
+
+        assert_eq!(
+            watcher
+                .next_change(Some(Duration::from_millis(500)))
+                .unwrap(),
+            None
+        );
+    }
+
+    #[test]
+    fn changes_of_declared_tools() {
+        let old = declared(
+            "[tools]\nrojo = { github = \"rojo-rbx/rojo\", version = \"7.3.0\" }\n\
+            selene = { github = \"Kampfkarren/selene\", version = \"0.25.0\" }\n",
+        );
+        let new = declared(
+            "[tools]\nrojo = { github = \"rojo-rbx/rojo\", version = \"7.3.0\" }\n\
+            stylua = { github = \"JohnnyMorganz/StyLua\", version = \"0.11.3\" }\n",
+        );
+
+        assert_eq!(
+            tool_changes(&old, &new),
+            vec![
+                ToolChange::Removed("selene"),
+                ToolChange::Declared("stylua")
+            ]
+        );
+        assert!(tool_changes(&new, &new).is_empty());
+    }
+}
@@ -216,3 +216,1341 @@ also-not-a-real-tool = { github = "Roblox/NotARepository", version = "0.1.0" }
 
     context.snapshot_command("install_all_tools_before_failing");
 }
+
+#[test]
+fn snapshot_list_declared_tools() {
+    let mut context = TestContext::foreman().arg("list").arg("--declared");
+    let config_path = context.path_from_working_directory("foreman.toml");
+    write_file(
+        &config_path,
+        r#"
+[hosts]
+artifactory = { source = "https://artifactory.com", protocol = "artifactory" }
+
+[tools]
+stylua = { github = "JohnnyMorganz/StyLua", version = "0.11.3" }
+darklua = { gitlab = "seaofvoices/darklua", version = "0.7.0" }
+rojo = { source = "rojo-rbx/rojo", version = "^7.2" }
+tool = { artifactory = "path/to/tool", version = "1.0.0" }
+
+[dev-tools]
+selene = { github = "Kampfkarren/selene", version = "0.25.0" }
+    "#,
+    );
+    context.snapshot_command("list_declared_tools");
+}
+
+#[test]
+fn install_skips_tools_for_other_platforms() {
+    let other_platform = if cfg!(target_os = "windows") {
+        "linux"
+    } else {
+        "windows"
+    };
+
+    let mut context = TestContext::foreman().arg("install");
+    let config_path = context.path_from_working_directory("foreman.toml");
+    write_file(
+        &config_path,
+        &format!(
+            r#"
+[tools]
+not-a-real-tool = {{ github = "Roblox/VeryFakeRepository", version = "0.1.0", platforms = ["{}"] }}
+    "#,
+            other_platform
+        ),
+    );
+
+    let output = context.output();
+    assert!(
+        output.contains("Skipping not-a-real-tool because it is only available on:"),
+        "unexpected output: {}",
+        output
+    );
+    context.expect_success();
+}
+
+#[test]
+fn invoking_undeclared_alias_exits_with_dedicated_code() {
+    let home_directory = tempdir().expect("unable to create temporary directory");
+    let working_directory = tempdir().expect("unable to create temporary directory");
+
+    let alias_path = home_directory
+        .path()
+        .join(format!("undeclared-tool{}", std::env::consts::EXE_SUFFIX));
+    std::fs::copy(
+        assert_cmd::cargo::cargo_bin(env!("CARGO_PKG_NAME")),
+        &alias_path,
+    )
+    .expect("unable to copy foreman binary");
+
+    let mut command = Command::new(&alias_path);
+    command.env("FOREMAN_HOME", home_directory.path().as_os_str());
+    command.current_dir(working_directory.path());
+
+    let output = command.output().expect("unable to run command");
+    let stderr = std::str::from_utf8(&output.stderr).expect("unable to read output");
+
+    assert_eq!(output.status.code(), Some(127));
+    assert!(
+        stderr.contains("'undeclared-tool' is not a known Foreman tool"),
+        "unexpected output: {}",
+        stderr
+    );
+    assert!(stderr.contains("no tool was run"));
+}
+
+/// Runs a copy of Foreman installed as the alias `undeclared-tool` in the
+/// bin directory, with `path_dirs` as `PATH`.
+#[cfg(unix)]
+fn run_undeclared_alias(home_directory: &Path, path_dirs: &[&Path]) -> std::process::Output {
+    let working_directory = tempdir().expect("unable to create temporary directory");
+    let bin_dir = home_directory.join("bin");
+    std::fs::create_dir_all(&bin_dir).unwrap();
+    let alias_path = bin_dir.join("undeclared-tool");
+    std::fs::copy(
+        assert_cmd::cargo::cargo_bin(env!("CARGO_PKG_NAME")),
+        &alias_path,
+    )
+    .expect("unable to copy foreman binary");
+
+    let path = std::env::join_paths(path_dirs.iter().copied().chain([bin_dir.as_path()])).unwrap();
+    Command::new(&alias_path)
+        .env("FOREMAN_HOME", home_directory)
+        .env("PATH", path)
+        .current_dir(working_directory.path())
+        .output()
+        .expect("unable to run command")
+}
+
+#[cfg(unix)]
+#[test]
+fn passthrough_runs_undeclared_alias_from_path() {
+    use std::os::unix::fs::PermissionsExt;
+
+    let home_directory = tempdir().expect("unable to create temporary directory");
+    write_file(
+        &home_directory.path().join("foreman.toml"),
+        "passthrough = true\n[tools]\n",
+    );
+
+    let system_dir = tempdir().expect("unable to create temporary directory");
+    let system_tool = system_dir.path().join("undeclared-tool");
+    write_file(
+        &system_tool,
+        "#!/bin/sh\necho \"system tool $FOREMAN_PASSTHROUGH\"\n",
+    );
+    std::fs::set_permissions(&system_tool, std::fs::Permissions::from_mode(0o755)).unwrap();
+
+    let output = run_undeclared_alias(home_directory.path(), &[system_dir.path()]);
+    assert!(output.status.success(), "{:?}", output);
+    assert_eq!(
+        String::from_utf8(output.stdout).unwrap(),
+        "system tool undeclared-tool\n"
+    );
+}
+
+#[cfg(unix)]
+#[test]
+fn passthrough_does_not_recurse_into_other_aliases() {
+    let home_directory = tempdir().expect("unable to create temporary directory");
+    write_file(
+        &home_directory.path().join("foreman.toml"),
+        "passthrough = true\n[tools]\n",
+    );
+
+    // Another copy of Foreman outside the bin directory, like the alias of a
+    // second Foreman installation.
+    let other_bin_dir = tempdir().expect("unable to create temporary directory");
+    std::fs::copy(
+        assert_cmd::cargo::cargo_bin(env!("CARGO_PKG_NAME")),
+        other_bin_dir.path().join("undeclared-tool"),
+    )
+    .expect("unable to copy foreman binary");
+
+    let output = run_undeclared_alias(home_directory.path(), &[other_bin_dir.path()]);
+    let stderr = String::from_utf8(output.stderr).unwrap();
+    assert_eq!(output.status.code(), Some(127), "{}", stderr);
+    assert!(
+        stderr.contains("'undeclared-tool' is not a known Foreman tool"),
+        "{}",
+        stderr
+    );
+}
+
+#[test]
+fn install_skips_dev_tools_by_default() {
+    let mut context = TestContext::foreman().arg("install");
+    let config_path = context.path_from_working_directory("foreman.toml");
+    write_file(
+        &config_path,
+        r#"
+[tools]
+
+[dev-tools]
+not-a-real-tool = { github = "Roblox/VeryFakeRepository", version = "0.1.0" }
+    "#,
+    );
+
+    let output = context.output();
+    assert!(
+        output.contains("Skipping development tools (not-a-real-tool), use --dev to install them"),
+        "unexpected output: {}",
+        output
+    );
+    context.expect_success();
+}
+
+/// Installs a shell script as version 1.0.0 of the GitHub tool
+/// `Roblox/fake-tool`, so that it can be run without network access.
+#[cfg(unix)]
+fn install_fake_tool(context: &TestContext, script: &str) {
+    use std::os::unix::fs::PermissionsExt;
+
+    std::fs::create_dir_all(context.path_from_home("tools")).unwrap();
+    let tool_path = context.path_from_home("tools/Roblox__fake-tool-1.0.0");
+    write_file(&tool_path, script);
+    std::fs::set_permissions(&tool_path, std::fs::Permissions::from_mode(0o755)).unwrap();
+    write_file(
+        &context.path_from_home("tool-cache.json"),
+        r#"{ "tools": { "Roblox/fake-tool": { "versions": ["1.0.0"] } } }"#,
+    );
+    write_file(
+        &context.path_from_working_directory("foreman.toml"),
+        r#"
+[tools]
+fake-tool = { github = "Roblox/fake-tool", version = "1.0.0" }
+    "#,
+    );
+}
+
+#[cfg(unix)]
+#[test]
+fn run_captures_tool_output() {
+    let context = TestContext::foreman()
+        .arg("run")
+        .arg("fake-tool")
+        .arg("--capture")
+        .arg("--output-file")
+        .arg("captured.txt")
+        .arg("--");
+    install_fake_tool(&context, "#!/bin/sh\necho \"hello from $1\"\n");
+    let context = context.arg("--flag").expect_success();
+
+    let captured = read_to_string(context.path_from_working_directory("captured.txt")).unwrap();
+    assert_eq!(captured, "hello from --flag\n");
+}
+
+#[cfg(unix)]
+#[test]
+fn status_summarizes_installed_and_missing_tools() {
+    let mut context = TestContext::foreman().arg("status").arg("--offline");
+    install_fake_tool(&context, "#!/bin/sh\n");
+    write_file(
+        &context.path_from_working_directory("foreman.toml"),
+        r#"
+[tools]
+fake-tool = { github = "Roblox/fake-tool", version = "1.0.0" }
+missing-tool = { github = "Roblox/missing-tool", version = "2.0" }
+    "#,
+    );
+    std::fs::create_dir_all(context.path_from_home("bin")).unwrap();
+    std::fs::copy(
+        assert_cmd::cargo::cargo_bin(env!("CARGO_PKG_NAME")),
+        context.path_from_home("bin/fake-tool"),
+    )
+    .unwrap();
+
+    assert_eq!(
+        context.output(),
+        "TOOL          REQUIREMENT  INSTALLED  LATEST  ALIAS\n\
+        fake-tool     ^1.0.0       1.0.0      -       ok\n\
+        missing-tool  ^2.0         no         -       missing\n\
+        \n\
+        Run `foreman install` to install the missing tools and aliases.\n\n"
+    );
+}
+
+#[cfg(unix)]
+#[test]
+fn diff_reports_changed_added_and_stale_tools() {
+    let mut context = TestContext::foreman().arg("diff").arg("--exit-code");
+    install_fake_tool(&context, "#!/bin/sh\n");
+    write_file(
+        &context.path_from_working_directory("foreman.toml"),
+        r#"
+[tools]
+fake-tool = { github = "Roblox/fake-tool", version = "=1.1.0" }
+new-tool = { github = "Roblox/new-tool", version = "2.0" }
+    "#,
+    );
+    write_file(
+        &context.path_from_home("tool-cache.json"),
+        r#"{ "tools": {
+            "Roblox/fake-tool": { "versions": ["1.0.0"] },
+            "Roblox/old-tool": { "versions": ["0.3.0"] }
+        } }"#,
+    );
+
+    let output = context.command.output().unwrap();
+    assert!(!output.status.success());
+    assert_eq!(
+        String::from_utf8(output.stdout).unwrap(),
+        "~ fake-tool 1.0.0 -> =1.1.0\n\
+        + new-tool ^2.0\n\
+        - Roblox/old-tool 0.3.0\n\
+        1 changed, 1 added, 1 stale\n"
+    );
+}
+
+#[cfg(unix)]
+#[test]
+fn run_detach_outlives_foreman() {
+    let mut context = TestContext::foreman()
+        .arg("run")
+        .arg("--detach")
+        .arg("fake-tool");
+    // The tool waits for the test to create `go`, so it is still running when
+    // Foreman returns, then reports its PID in `done`.
+    install_fake_tool(
+        &context,
+        "#!/bin/sh
+        for _ in $(seq 100); do [ -f go ] && break; sleep 0.1; done
+        echo $$ > done
+",
+    );
+
+    let output = context.output();
+    let pid = output.trim();
+    assert!(
+        !pid.is_empty() && pid.chars().all(|c| c.is_ascii_digit()),
+        "expected a PID, got {:?}",
+        output
+    );
+    assert!(!context.path_from_working_directory("done").exists());
+
+    write_file(&context.path_from_working_directory("go"), "");
+    for _ in 0..100 {
+        if let Ok(contents) = read_to_string(context.path_from_working_directory("done")) {
+            if contents.ends_with('\n') {
+                assert_eq!(contents.trim(), pid);
+                return;
+            }
+        }
+        std::thread::sleep(std::time::Duration::from_millis(100));
+    }
+    panic!("the detached tool did not keep running after Foreman exited");
+}
+
+#[cfg(unix)]
+#[test]
+fn max_concurrent_tools_serializes_runs() {
+    let context = TestContext::foreman();
+    // Each run logs when it starts and ends, slowly enough that unlimited
+    // runs would interleave.
+    install_fake_tool(
+        &context,
+        "#!/bin/sh
+        echo start >> log
+        sleep 0.3
+        echo end >> log
+",
+    );
+
+    let runs: Vec<_> = (0..3)
+        .map(|_| {
+            std::process::Command::new(assert_cmd::cargo::cargo_bin(env!("CARGO_PKG_NAME")))
+                .env("FOREMAN_HOME", context.home_directory.path())
+                .env("FOREMAN_MAX_CONCURRENT_TOOLS", "1")
+                .current_dir(context.working_directory.path())
+                .arg("run")
+                .arg("fake-tool")
+                .spawn()
+                .unwrap()
+        })
+        .collect();
+    for mut run in runs {
+        assert!(run.wait().unwrap().success());
+    }
+
+    let log = read_to_string(context.path_from_working_directory("log")).unwrap();
+    assert_eq!(log, "start\nend\n".repeat(3));
+}
+
+#[cfg(unix)]
+#[test]
+fn run_sets_configured_and_cli_environment() {
+    let mut context = TestContext::foreman()
+        .arg("run")
+        .arg("--env")
+        .arg("SHARED=from cli")
+        .arg("--env")
+        .arg("CLI_ONLY=cli")
+        .arg("fake-tool");
+    install_fake_tool(
+        &context,
+        "#!/bin/sh\necho \"$CONFIG_ONLY $CLI_ONLY $SHARED\"\n",
+    );
+    write_file(
+        &context.path_from_working_directory("foreman.toml"),
+        r#"
+[tools]
+fake-tool = { github = "Roblox/fake-tool", version = "1.0.0", env = { CONFIG_ONLY = "config", SHARED = "from config" } }
+    "#,
+    );
+
+    assert_eq!(context.output(), "config cli from cli\n\n");
+}
+
+#[cfg(unix)]
+#[test]
+fn run_in_given_working_directory() {
+    let tool_directory = tempdir().unwrap();
+    let mut context = TestContext::foreman()
+        .arg("run")
+        .arg("--cwd")
+        .arg(tool_directory.path())
+        .arg("fake-tool");
+    install_fake_tool(&context, "#!/bin/sh\npwd -P\n");
+
+    assert_eq!(
+        context.output(),
+        format!(
+            "{}\n\n",
+            tool_directory.path().canonicalize().unwrap().display()
+        )
+    );
+}
+
+#[cfg(unix)]
+#[test]
+fn run_prints_command_without_running_it() {
+    let mut context = TestContext::foreman()
+        .arg("run")
+        .arg("--print-command")
+        .arg("--env")
+        .arg("GREETING=hello world")
+        .arg("fake-tool")
+        .arg("--")
+        .arg("serve")
+        .arg("--port")
+        .arg("8000");
+    install_fake_tool(&context, "#!/bin/sh\ntouch ran\n");
+
+    assert_eq!(
+        context.output(),
+        format!(
+            "GREETING='hello world' {} serve --port 8000\n\n",
+            context
+                .path_from_home("tools/Roblox__fake-tool-1.0.0")
+                .display()
+        )
+    );
+    assert!(!context.path_from_working_directory("ran").exists());
+}
+
+#[cfg(unix)]
+#[test]
+fn run_prints_captured_output() {
+    let mut context = TestContext::foreman()
+        .arg("run")
+        .arg("--capture")
+        .arg("fake-tool");
+    install_fake_tool(&context, "#!/bin/sh\necho captured\n");
+
+    assert_eq!(context.output(), "captured\n\n");
+}
+
+#[test]
+fn complete_tool_aliases_by_prefix() {
+    let mut context = TestContext::foreman().arg("complete").arg("r");
+    write_file(
+        &context.path_from_working_directory("foreman.toml"),
+        r#"
+[tools]
+rojo = { github = "rojo-rbx/rojo", version = "7.3.0" }
+remodel = { github = "rojo-rbx/remodel", version = "0.11.0" }
+stylua = { github = "JohnnyMorganz/StyLua", version = "0.18.0" }
+    "#,
+    );
+
+    assert_eq!(context.output(), "remodel\nrojo\n\n");
+}
+
+#[test]
+fn complete_installed_tool_versions() {
+    let mut context = TestContext::foreman().arg("complete").arg("rojo").arg("7.");
+    write_file(
+        &context.path_from_working_directory("foreman.toml"),
+        r#"
+[tools]
+rojo = { github = "rojo-rbx/rojo", version = "7.3.0" }
+    "#,
+    );
+    write_file(
+        &context.path_from_home("tool-cache.json"),
+        r#"{ "tools": { "rojo-rbx/rojo": { "versions": ["6.2.0", "7.2.1", "7.3.0"] } } }"#,
+    );
+
+    assert_eq!(context.output(), "7.3.0\n7.2.1\n\n");
+}
+
+#[test]
+fn snapshot_debug_paths() {
+    let mut context = TestContext::foreman().arg("debug").arg("paths");
+    write_file(
+        &context.path_from_working_directory("foreman.toml"),
+        "[tools]\n",
+    );
+
+    // Configuration files above the working directory depend on where the
+    // temporary directories live, so they are left out of the snapshot.
+    let working_directory = context.working_directory_display.clone();
+    let home_directory = context.home_directory_display.clone();
+    let root_dir = context.home_directory.path().display().to_string();
+    let content: String = context
+        .output()
+        .lines()
+        .filter(|line| {
+            !line.contains("foreman.toml")
+                || line.contains(&working_directory)
+                || line.contains(&home_directory)
+        })
+        .map(|line| {
+            format!(
+                "{}\n",
+                line.replace(&format!("{} ", root_dir), "{{FOREMAN_HOME}} ")
+            )
+        })
+        .collect();
+    context.snapshot_string("debug_paths", content);
+}
+
+/// Creates a local mirror in `mirror` with a single release of `tool`, whose
+/// asset is picked on every platform by the `fake-platform` keyword.
+fn write_local_mirror(mirror: &Path, tool: &str, version: &str) {
+    use std::io::Write;
+
+    let tool_directory = mirror.join(tool);
+    let release_directory = tool_directory.join(format!("v{}", version));
+    std::fs::create_dir_all(&release_directory).unwrap();
+    write_file(
+        &tool_directory.join("releases.json"),
+        &format!(
+            r#"[{{ "tag_name": "v{}", "assets": [{{ "name": "tool-fake-platform.zip" }}] }}]"#,
+            version
+        ),
+    );
+
+    let asset = std::fs::File::create(release_directory.join("tool-fake-platform.zip")).unwrap();
+    let mut writer = zip::ZipWriter::new(asset);
+    writer
+        .start_file(tool, zip::write::FileOptions::default())
+        .unwrap();
+    writer.write_all(b"not really a tool").unwrap();
+    writer.finish().unwrap();
+}
+
+/// A configuration declaring `tools` from a local mirror in `mirror`.
+fn local_mirror_config(mirror: &Path, tools: &[&str]) -> String {
+    let mut config = format!(
+        "platform_keywords = [\"fake-platform\"]\n\n[hosts]\nmirror = {{ source = \"{}\", protocol = \"local\" }}\n\n[tools]\n",
+        url::Url::from_directory_path(mirror).unwrap()
+    );
+    for tool in tools {
+        config.push_str(&format!(
+            "{} = {{ mirror = \"{}\", version = \"1.0.0\" }}\n",
+            tool, tool
+        ));
+    }
+    config
+}
+
+#[test]
+fn install_only_named_tools() {
+    let mirror = tempdir().unwrap();
+    for tool in ["first", "second", "third"] {
+        write_local_mirror(mirror.path(), tool, "1.0.0");
+    }
+
+    let context = TestContext::foreman()
+        .arg("install")
+        .arg("first")
+        .arg("third");
+    write_file(
+        &context.path_from_working_directory("foreman.toml"),
+        &local_mirror_config(mirror.path(), &["first", "second", "third"]),
+    );
+    let context = context.expect_success();
+
+    let alias = |name: &str| {
+        context.path_from_home(format!("bin/{}{}", name, std::env::consts::EXE_SUFFIX))
+    };
+    assert!(alias("first").exists());
+    assert!(!alias("second").exists());
+    assert!(alias("third").exists());
+
+    let cache = read_to_string(context.path_from_home("tool-cache.json")).unwrap();
+    assert!(cache.contains("first"));
+    assert!(!cache.contains("second"));
+}
+
+#[test]
+fn install_prints_status_lines_per_tool() {
+    let mirror = tempdir().unwrap();
+    for tool in ["first", "second"] {
+        write_local_mirror(mirror.path(), tool, "1.0.0");
+    }
+
+    let mut context = TestContext::foreman().arg("install");
+    write_file(
+        &context.path_from_working_directory("foreman.toml"),
+        &local_mirror_config(mirror.path(), &["first", "second"]),
+    );
+
+    // The output is not a terminal, so lines are printed one after another.
+    let output = context.output();
+    let lines: Vec<&str> = output.lines().collect();
+    assert_eq!(lines.len(), 4, "unexpected output: {}", output);
+    assert_eq!(lines[0], "Installing first (^1.0.0)...");
+    assert!(lines[1].starts_with("Installed first 1.0.0 in "));
+    assert_eq!(lines[2], "Installing second (^1.0.0)...");
+    assert!(lines[3].starts_with("Installed second 1.0.0 in "));
+
+    let output = context.output();
+    assert!(output.contains("Installing first (^1.0.0)...\nfirst 1.0.0 is already installed\n"));
+    assert!(output.contains("second 1.0.0 is already installed"));
+}
+
+#[test]
+fn install_writes_resolution_report() {
+    let mirror = tempdir().unwrap();
+    for tool in ["first", "second"] {
+        write_local_mirror(mirror.path(), tool, "1.0.0");
+    }
+
+    let context = TestContext::foreman()
+        .arg("install")
+        .arg("--resolution-report")
+        .arg("report.json");
+    write_file(
+        &context.path_from_working_directory("foreman.toml"),
+        &local_mirror_config(mirror.path(), &["first", "second"]),
+    );
+    let context = context.expect_success();
+
+    let read_report = || -> serde_json::Value {
+        let report = read_to_string(context.path_from_working_directory("report.json")).unwrap();
+        serde_json::from_str(&report).unwrap()
+    };
+
+    let report = read_report();
+    let tools = report["tools"].as_array().unwrap();
+    assert_eq!(tools.len(), 2);
+    for (entry, alias) in tools.iter().zip(["first", "second"]) {
+        assert_eq!(entry["alias"], alias);
+        assert_eq!(entry["version_req"], "^1.0.0");
+        assert_eq!(entry["version"], "1.0.0");
+        assert_eq!(entry["asset"], "tool-fake-platform.zip");
+        assert!(entry["url"]
+            .as_str()
+            .unwrap()
+            .ends_with("/v1.0.0/tool-fake-platform.zip"));
+        let checksum = entry["checksum"].as_str().unwrap();
+        assert!(checksum.starts_with("sha256:"), "{}", checksum);
+        assert_eq!(checksum.len(), "sha256:".len() + 64);
+        assert_eq!(entry["cache_hit"], false);
+        assert!(entry["error"].is_null());
+    }
+
+    Command::cargo_bin(env!("CARGO_PKG_NAME"))
+        .unwrap()
+        .env("FOREMAN_HOME", context.home_directory.path())
+        .current_dir(context.working_directory.path())
+        .arg("install")
+        .arg("--resolution-report")
+        .arg("report.json")
+        .assert()
+        .success();
+
+    let report = read_report();
+    for entry in report["tools"].as_array().unwrap() {
+        assert_eq!(entry["version"], "1.0.0");
+        assert_eq!(entry["cache_hit"], true);
+        assert!(entry["checksum"].is_null());
+    }
+}
+
+#[test]
+fn install_unknown_named_tool() {
+    let mut context = TestContext::foreman().arg("install").arg("missing");
+    write_file(
+        &context.path_from_working_directory("foreman.toml"),
+        "[tools]\n",
+    );
+
+    let output = context.output();
+    assert!(
+        output.contains("not declared by any configuration file"),
+        "unexpected output: {}",
+        output
+    );
+    assert!(output.contains("missing"));
+}
+
+#[test]
+fn install_refuses_assets_over_max_download_size() {
+    let mirror = tempdir().unwrap();
+    write_local_mirror(mirror.path(), "tool", "1.0.0");
+
+    let mut context = TestContext::foreman()
+        .arg("install")
+        .arg("--max-download-size")
+        .arg("16");
+    write_file(
+        &context.path_from_working_directory("foreman.toml"),
+        &local_mirror_config(mirror.path(), &["tool"]),
+    );
+
+    let output = context.output();
+    assert!(
+        output.contains("exceeds the maximum download size of 16 bytes"),
+        "unexpected output: {}",
+        output
+    );
+    assert!(!context
+        .path_from_home(format!("bin/tool{}", std::env::consts::EXE_SUFFIX))
+        .exists());
+}
+
+#[test]
+fn ignore_cache_downloads_installed_tool_again() {
+    let mirror = tempdir().unwrap();
+    write_local_mirror(mirror.path(), "tool", "1.0.0");
+
+    let context = TestContext::foreman().arg("install");
+    write_file(
+        &context.path_from_working_directory("foreman.toml"),
+        &local_mirror_config(mirror.path(), &["tool"]),
+    );
+    let context = context.expect_success();
+
+    let tools_dir = context.path_from_home("tools");
+    let mut installed = std::fs::read_dir(&tools_dir).unwrap();
+    let tool_path = installed.next().unwrap().unwrap().path();
+    assert!(installed.next().is_none());
+    write_file(&tool_path, "stale");
+
+    let install = |extra_args: &[&str]| {
+        Command::cargo_bin(env!("CARGO_PKG_NAME"))
+            .unwrap()
+            .env("FOREMAN_HOME", context.home_directory.path())
+            .current_dir(context.working_directory.path())
+            .arg("install")
+            .args(extra_args)
+            .assert()
+            .success();
+        read_to_string(&tool_path).unwrap()
+    };
+
+    assert_eq!(install(&[]), "stale");
+    assert_eq!(install(&["--ignore-cache"]), "not really a tool");
+}
+
+/// Installs version 1.0.0 of a tool, publishes version 1.1.0 and installs
+/// again with the given update policy, returning the resulting tool cache.
+fn install_with_newer_release(update_policy: &str) -> String {
+    let mirror = tempdir().unwrap();
+    write_local_mirror(mirror.path(), "tool", "1.0.0");
+
+    let context = TestContext::foreman().arg("install");
+    write_file(
+        &context.path_from_working_directory("foreman.toml"),
+        &local_mirror_config(mirror.path(), &["tool"]),
+    );
+    let context = context.expect_success();
+
+    write_local_mirror(mirror.path(), "tool", "1.1.0");
+    Command::cargo_bin(env!("CARGO_PKG_NAME"))
+        .unwrap()
+        .env("FOREMAN_HOME", context.home_directory.path())
+        .current_dir(context.working_directory.path())
+        .arg("install")
+        .arg("--update-policy")
+        .arg(update_policy)
+        .assert()
+        .success();
+
+    read_to_string(context.path_from_home("tool-cache.json")).unwrap()
+}
+
+#[test]
+fn cached_update_policy_keeps_installed_version() {
+    let cache = install_with_newer_release("cached");
+    assert!(cache.contains("1.0.0"), "{}", cache);
+    assert!(!cache.contains("1.1.0"), "{}", cache);
+}
+
+#[test]
+fn latest_update_policy_downloads_newer_release() {
+    let cache = install_with_newer_release("latest");
+    assert!(cache.contains("1.0.0"), "{}", cache);
+    assert!(cache.contains("1.1.0"), "{}", cache);
+}
+
+#[cfg(unix)]
+#[test]
+fn install_writes_launcher_script_aliases() {
+    let mirror = tempdir().unwrap();
+    write_local_mirror(mirror.path(), "tool", "1.0.0");
+
+    let context = TestContext::foreman()
+        .arg("install")
+        .arg("--alias-style")
+        .arg("script");
+    write_file(
+        &context.path_from_working_directory("foreman.toml"),
+        &local_mirror_config(mirror.path(), &["tool"]),
+    );
+    let context = context.expect_success();
+
+    let launcher = read_to_string(context.path_from_home("bin/tool")).unwrap();
+    assert!(launcher.starts_with("#!/bin/sh\n"));
+    assert!(launcher.ends_with(" run 'tool' -- \"$@\"\n"));
+}
+
+#[cfg(unix)]
+#[test]
+fn symlink_aliases_run_their_tool() {
+    let context = TestContext::foreman()
+        .arg("install")
+        .arg("--alias-style")
+        .arg("symlink");
+    install_fake_tool(&context, "#!/bin/sh\necho \"fake-tool ran with $1\"\n");
+    let context = context.expect_success();
+
+    let alias_path = context.path_from_home("bin/fake-tool");
+    assert!(std::fs::symlink_metadata(&alias_path)
+        .unwrap()
+        .file_type()
+        .is_symlink());
+
+    let output = Command::new(&alias_path)
+        .env("FOREMAN_HOME", context.home_directory.path())
+        .current_dir(context.working_directory.path())
+        .arg("--version")
+        .output()
+        .unwrap();
+    assert!(output.status.success());
+    assert_eq!(
+        String::from_utf8(output.stdout).unwrap(),
+        "fake-tool ran with --version\n"
+    );
+}
+
+#[test]
+fn list_stale_tools() {
+    let mirror = tempdir().unwrap();
+    write_local_mirror(mirror.path(), "kept", "1.0.0");
+    write_local_mirror(mirror.path(), "removed", "1.0.0");
+
+    let context = TestContext::foreman().arg("install");
+    let config_path = context.path_from_working_directory("foreman.toml");
+    write_file(
+        &config_path,
+        &local_mirror_config(mirror.path(), &["kept", "removed"]),
+    );
+    let context = context.expect_success();
+
+    write_file(&config_path, &local_mirror_config(mirror.path(), &["kept"]));
+
+    let output = Command::cargo_bin(env!("CARGO_PKG_NAME"))
+        .unwrap()
+        .env("FOREMAN_HOME", context.home_directory.path())
+        .current_dir(context.working_directory.path())
+        .arg("list")
+        .arg("--stale")
+        .output()
+        .unwrap();
+    let stdout = String::from_utf8(output.stdout).unwrap();
+
+    assert!(stdout.starts_with("Stale tools:\n"), "{}", stdout);
+    assert!(stdout.contains("@removed\n    - 1.0.0 ("), "{}", stdout);
+    assert!(!stdout.contains("@kept"), "{}", stdout);
+    assert!(stdout.contains(" bytes in 1 version\n"), "{}", stdout);
+}
+
+#[test]
+fn config_root_only_reads_its_own_config() {
+    let context = TestContext::foreman();
+    let project = context.path_from_working_directory("project");
+    std::fs::create_dir(&project).unwrap();
+    write_file(
+        &project.join("foreman.toml"),
+        "[tools]\nrojo = { github = \"rojo-rbx/rojo\", version = \"7.3.0\" }\n",
+    );
+    write_file(
+        &context.path_from_working_directory("foreman.toml"),
+        "[tools]\nselene = { github = \"Kampfkarren/selene\", version = \"0.25.0\" }\n",
+    );
+    let output = context
+        .arg("list")
+        .arg("--declared")
+        .arg("--config-root")
+        .arg(&project)
+        .expect_success()
+        .output();
+
+    assert!(output.contains("rojo => rojo-rbx/rojo"), "{}", output);
+    assert!(!output.contains("selene"), "{}", output);
+}
+
+#[test]
+fn install_copies_binaries_to_output_directory() {
+    let mirror = tempdir().unwrap();
+    for tool in ["first", "second"] {
+        write_local_mirror(mirror.path(), tool, "1.0.0");
+    }
+
+    let context = TestContext::foreman()
+        .arg("install")
+        .arg("--output")
+        .arg("vendor/bin");
+    write_file(
+        &context.path_from_working_directory("foreman.toml"),
+        &local_mirror_config(mirror.path(), &["first", "second"]),
+    );
+    let context = context.expect_success();
+
+    for tool in ["first", "second"] {
+        let binary = context
+            .path_from_working_directory("vendor/bin")
+            .join(format!("{}{}", tool, std::env::consts::EXE_SUFFIX));
+        assert_eq!(read_to_string(&binary).unwrap(), "not really a tool");
+
+        #[cfg(unix)]
+        {
+            use std::os::unix::fs::PermissionsExt;
+
+            let mode = std::fs::metadata(&binary).unwrap().permissions().mode();
+            assert_ne!(mode & 0o111, 0, "{} is not executable", binary.display());
+        }
+    }
+}
+
+#[test]
+fn update_prints_a_line_per_tool() {
+    let mirror = tempdir().unwrap();
+    write_local_mirror(mirror.path(), "moved", "1.0.0");
+    write_local_mirror(mirror.path(), "kept", "1.0.0");
+
+    let context = TestContext::foreman().arg("install");
+    write_file(
+        &context.path_from_working_directory("foreman.toml"),
+        &local_mirror_config(mirror.path(), &["moved", "kept"]),
+    );
+    let context = context.expect_success();
+
+    write_local_mirror(mirror.path(), "moved", "1.1.0");
+    let output = Command::cargo_bin(env!("CARGO_PKG_NAME"))
+        .unwrap()
+        .env("FOREMAN_HOME", context.home_directory.path())
+        .current_dir(context.working_directory.path())
+        .arg("update")
+        .output()
+        .unwrap();
+    let stdout = String::from_utf8(output.stdout).unwrap();
+
+    assert_eq!(stdout, "kept: 1.0.0 is up to date\nmoved: 1.0.0 -> 1.1.0\n");
+
+    // The previous version stays installed.
+    let cache = read_to_string(context.path_from_home("tool-cache.json")).unwrap();
+    assert!(cache.contains("\"1.0.0\""), "{}", cache);
+    assert!(cache.contains("\"1.1.0\""), "{}", cache);
+}
+
+#[test]
+fn uninstall_removes_tool_and_alias() {
+    let mirror = tempdir().unwrap();
+    write_local_mirror(mirror.path(), "tool", "1.0.0");
+
+    let context = TestContext::foreman().arg("install");
+    write_file(
+        &context.path_from_working_directory("foreman.toml"),
+        &local_mirror_config(mirror.path(), &["tool"]),
+    );
+    let context = context.expect_success();
+    let alias = context.path_from_home(format!("bin/tool{}", std::env::consts::EXE_SUFFIX));
+    assert!(alias.exists());
+
+    let uninstall = |tool: &str| {
+        Command::cargo_bin(env!("CARGO_PKG_NAME"))
+            .unwrap()
+            .env("FOREMAN_HOME", context.home_directory.path())
+            .current_dir(context.working_directory.path())
+            .arg("uninstall")
+            .arg(tool)
+            .assert()
+    };
+
+    uninstall("tool").success().stdout("tool: removed 1.0.0\n");
+    assert!(!alias.exists());
+    let cache = read_to_string(context.path_from_home("tool-cache.json")).unwrap();
+    assert!(!cache.contains("1.0.0"), "{}", cache);
+
+    uninstall("unknown").failure();
+}
+
+#[test]
+fn uninstall_keeps_alias_declared_by_another_project() {
+    let mirror = tempdir().unwrap();
+    write_local_mirror(mirror.path(), "tool", "1.0.0");
+
+    let context = TestContext::foreman().arg("install");
+    write_file(
+        &context.path_from_working_directory("foreman.toml"),
+        &local_mirror_config(mirror.path(), &["tool"]),
+    );
+    let context = context.expect_success();
+
+    // Another project declares the same alias.
+    let other_project = tempdir().unwrap();
+    write_file(
+        &other_project.path().join("foreman.toml"),
+        &local_mirror_config(mirror.path(), &["tool"]),
+    );
+    Command::cargo_bin(env!("CARGO_PKG_NAME"))
+        .unwrap()
+        .env("FOREMAN_HOME", context.home_directory.path())
+        .current_dir(other_project.path())
+        .arg("install")
+        .assert()
+        .success();
+
+    Command::cargo_bin(env!("CARGO_PKG_NAME"))
+        .unwrap()
+        .env("FOREMAN_HOME", context.home_directory.path())
+        .current_dir(context.working_directory.path())
+        .arg("uninstall")
+        .arg("tool")
+        .assert()
+        .success();
+    let alias = context.path_from_home(format!("bin/tool{}", std::env::consts::EXE_SUFFIX));
+    assert!(alias.exists());
+}
+
+#[test]
+fn missing_auth_file_is_an_error() {
+    let mut context = TestContext::foreman()
+        .arg("--auth-file")
+        .arg("missing-auth.toml")
+        .arg("list");
+    let output = context.command.assert().failure().get_output().clone();
+    assert!(String::from_utf8(output.stderr)
+        .unwrap()
+        .contains("the auth file set with --auth-file or FOREMAN_AUTH_FILE does not exist"));
+
+    let mut context = TestContext::foreman().arg("list");
+    context
+        .command
+        .env("FOREMAN_AUTH_FILE", "missing-auth.toml");
+    context.command.assert().failure();
+}
+
+#[test]
+fn generate_artifactory_path_for_all_platforms() {
+    let home = tempdir().unwrap();
+    let generate = |args: &[&str]| {
+        Command::cargo_bin(env!("CARGO_PKG_NAME"))
+            .unwrap()
+            .env("FOREMAN_HOME", home.path())
+            .args(["generate-artifactory-path", "repo", "tool", "v1.0.0"])
+            .args(args)
+            .output()
+            .unwrap()
+    };
+
+    let single = generate(&["linux", "x86_64"]);
+    assert!(single.status.success());
+    assert_eq!(
+        String::from_utf8(single.stdout).unwrap(),
+        "artifactory/repo/tool/v1.0.0/tool-v1.0.0-linux-x86_64.zip\n"
+    );
+
+    let all = generate(&["--all-platforms"]);
+    assert!(all.status.success());
+    let lines = String::from_utf8(all.stdout).unwrap();
+    assert_eq!(lines.lines().count(), 12);
+    assert!(lines.contains("artifactory/repo/tool/v1.0.0/tool-v1.0.0-windows-i686.zip\n"));
+
+    let json = generate(&["--all-platforms", "--json"]);
+    assert!(json.status.success());
+    let paths: Vec<String> = serde_json::from_slice(&json.stdout).unwrap();
+    assert_eq!(paths, lines.lines().collect::<Vec<_>>());
+
+    assert!(!generate(&["linux", "--all-platforms"]).status.success());
+    assert!(!generate(&[]).status.success());
+}
+
+#[test]
+fn prune_dry_run_keeps_everything() {
+    let mirror = tempdir().unwrap();
+    write_local_mirror(mirror.path(), "tool", "1.0.0");
+
+    let context = TestContext::foreman().arg("install");
+    write_file(
+        &context.path_from_working_directory("foreman.toml"),
+        &local_mirror_config(mirror.path(), &["tool"]),
+    );
+    let context = context.expect_success();
+    write_file(
+        &context.path_from_working_directory("foreman.toml"),
+        "[tools]\n",
+    );
+
+    let prune = |dry_run: bool| {
+        let mut command = Command::cargo_bin(env!("CARGO_PKG_NAME")).unwrap();
+        command
+            .env("FOREMAN_HOME", context.home_directory.path())
+            .current_dir(context.working_directory.path())
+            .arg("prune");
+        if dry_run {
+            command.arg("--dry-run");
+        }
+        let output = command.output().unwrap();
+        assert!(output.status.success());
+        String::from_utf8(output.stdout).unwrap()
+    };
+    let cache_path = context.path_from_home("tool-cache.json");
+
+    let output = prune(true);
+    assert!(output.starts_with("Would remove:\n"), "{}", output);
+    assert!(output.contains(" 1.0.0\n"), "{}", output);
+    assert!(read_to_string(&cache_path).unwrap().contains("1.0.0"));
+
+    let output = prune(false);
+    assert!(output.starts_with("Removing:\n"), "{}", output);
+    assert!(!read_to_string(&cache_path).unwrap().contains("1.0.0"));
+
+    assert_eq!(prune(false), "Nothing to prune.\n");
+}
+
+#[test]
+fn prune_keeps_tools_of_other_projects() {
+    let mirror = tempdir().unwrap();
+    write_local_mirror(mirror.path(), "tool", "1.0.0");
+
+    let context = TestContext::foreman().arg("install");
+    let config_path = context.path_from_working_directory("foreman.toml");
+    write_file(&config_path, &local_mirror_config(mirror.path(), &["tool"]));
+    let context = context.expect_success();
+
+    // Pruned from a directory without any foreman.toml.
+    let other_directory = tempdir().unwrap();
+    let prune = || {
+        let output = Command::cargo_bin(env!("CARGO_PKG_NAME"))
+            .unwrap()
+            .env("FOREMAN_HOME", context.home_directory.path())
+            .current_dir(other_directory.path())
+            .arg("prune")
+            .output()
+            .unwrap();
+        assert!(output.status.success());
+        String::from_utf8(output.stdout).unwrap()
+    };
+    let cache_path = context.path_from_home("tool-cache.json");
+
+    assert_eq!(prune(), "Nothing to prune.\n");
+    assert!(read_to_string(&cache_path).unwrap().contains("1.0.0"));
+
+    // Once the project is gone, its tools are not kept anymore.
+    std::fs::remove_file(&config_path).unwrap();
+    let output = prune();
+    assert!(output.starts_with("Removing:\n"), "{}", output);
+    assert!(!read_to_string(&cache_path).unwrap().contains("1.0.0"));
+}
+
+#[test]
+fn prune_skips_projects_that_no_longer_parse() {
+    let mirror = tempdir().unwrap();
+    write_local_mirror(mirror.path(), "tool", "1.0.0");
+
+    let context = TestContext::foreman().arg("install");
+    let config_path = context.path_from_working_directory("foreman.toml");
+    write_file(&config_path, &local_mirror_config(mirror.path(), &["tool"]));
+    let context = context.expect_success();
+
+    write_file(&config_path, "[tools\n");
+
+    let other_directory = tempdir().unwrap();
+    let output = Command::cargo_bin(env!("CARGO_PKG_NAME"))
+        .unwrap()
+        .env("FOREMAN_HOME", context.home_directory.path())
+        .current_dir(other_directory.path())
+        .arg("prune")
+        .arg("--dry-run")
+        .output()
+        .unwrap();
+    assert!(output.status.success());
+    assert!(String::from_utf8(output.stderr)
+        .unwrap()
+        .contains("Skipping project"));
+}
+
+#[cfg(unix)]
+#[test]
+fn install_sends_events_to_socket() {
+    use std::{io::Read, os::unix::net::UnixListener};
+
+    let mirror = tempdir().unwrap();
+    write_local_mirror(mirror.path(), "tool", "1.0.0");
+
+    let socket_directory = tempdir().unwrap();
+    let socket_path = socket_directory.path().join("events.sock");
+    let listener = UnixListener::bind(&socket_path).unwrap();
+    let receiver = std::thread::spawn(move || {
+        let (mut stream, _) = listener.accept().unwrap();
+        let mut events = String::new();
+        stream.read_to_string(&mut events).unwrap();
+        events
+    });
+
+    // `missing` is not in the mirror, so installing it fails.
+    let mut context = TestContext::foreman()
+        .arg("install")
+        .arg("--events-socket")
+        .arg(&socket_path);
+    write_file(
+        &context.path_from_working_directory("foreman.toml"),
+        &local_mirror_config(mirror.path(), &["missing", "tool"]),
+    );
+    context.output();
+
+    let events: Vec<serde_json::Value> = receiver
+        .join()
+        .unwrap()
+        .lines()
+        .map(|line| serde_json::from_str(line).unwrap())
+        .collect();
+    let summary: Vec<String> = events
+        .iter()
+        .map(|event| {
+            let field = |name: &str| event[name].as_str().unwrap_or_default().to_owned();
+            match field("event").as_str() {
+                "progress" => format!("progress {}/{}", event["completed"], event["total"]),
+                "done" => format!("done {} {}", field("tool"), field("version")),
+                kind => format!("{} {}", kind, field("tool")),
+            }
+        })
+        .collect();
+
+    assert_eq!(
+        summary,
+        vec![
+            "start missing",
+            "error missing",
+            "progress 1/2",
+            "start tool",
+            "done tool 1.0.0",
+            "progress 2/2",
+        ]
+    );
+}
+
+#[test]
+fn which_prints_installed_binary_path() {
+    let mirror = tempdir().unwrap();
+    write_local_mirror(mirror.path(), "tool", "1.0.0");
+
+    let context = TestContext::foreman().arg("install");
+    write_file(
+        &context.path_from_working_directory("foreman.toml"),
+        &local_mirror_config(mirror.path(), &["tool"]),
+    );
+    let context = context.expect_success();
+
+    let which = |update_policy: &str| {
+        Command::cargo_bin(env!("CARGO_PKG_NAME"))
+            .unwrap()
+            .env("FOREMAN_HOME", context.home_directory.path())
+            .current_dir(context.working_directory.path())
+            .arg("which")
+            .arg("tool")
+            .arg("--update-policy")
+            .arg(update_policy)
+            .output()
+            .unwrap()
+    };
+
+    let output = which("cached");
+    assert!(output.status.success());
+    let tool_path = PathBuf::from(String::from_utf8(output.stdout).unwrap().trim_end());
+    assert!(tool_path.is_absolute(), "{}", tool_path.display());
+    assert_eq!(read_to_string(&tool_path).unwrap(), "not really a tool");
+
+    // With the latest policy, the tool resolves to a release that is not
+    // installed yet.
+    write_local_mirror(mirror.path(), "tool", "1.1.0");
+    let output = which("latest");
+    assert!(!output.status.success());
+    assert!(output.stdout.is_empty());
+    let stderr = String::from_utf8(output.stderr).unwrap();
+    assert!(stderr.contains("version 1.1.0 of "), "{}", stderr);
+    assert!(stderr.contains("is not installed"), "{}", stderr);
+}
+
+#[test]
+fn cache_size_sums_tools_directory() {
+    let context = TestContext::foreman();
+    let tools_dir = context.path_from_home("tools");
+    std::fs::create_dir_all(&tools_dir).unwrap();
+    std::fs::write(tools_dir.join("user__tool-1.0.0"), [0; 1024]).unwrap();
+    std::fs::write(tools_dir.join("user__tool-1.1.0"), [0; 512]).unwrap();
+
+    let output = Command::cargo_bin(env!("CARGO_PKG_NAME"))
+        .unwrap()
+        .env("FOREMAN_HOME", context.home_directory.path())
+        .current_dir(context.working_directory.path())
+        .arg("cache")
+        .arg("size")
+        .output()
+        .unwrap();
+    assert!(output.status.success());
+
+    let stdout = String::from_utf8(output.stdout).unwrap();
+    assert!(
+        stdout.starts_with("Total: 1.5 KiB (1536 bytes) in "),
+        "{}",
+        stdout
+    );
+}
+
+#[test]
+fn cache_clean_temp_removes_stale_temp_files() {
+    let context = TestContext::foreman();
+    let tools_dir = context.path_from_home("tools");
+    std::fs::create_dir_all(&tools_dir).unwrap();
+    std::fs::write(tools_dir.join("user__tool-1.0.0"), [0; 1024]).unwrap();
+    std::fs::write(tools_dir.join("user__tool-1.1.0.4242.tmp"), [0; 512]).unwrap();
+
+    let output = Command::cargo_bin(env!("CARGO_PKG_NAME"))
+        .unwrap()
+        .env("FOREMAN_HOME", context.home_directory.path())
+        .current_dir(context.working_directory.path())
+        .args(["cache", "clean-temp", "--older-than", "0"])
+        .output()
+        .unwrap();
+    assert!(output.status.success());
+
+    let stdout = String::from_utf8(output.stdout).unwrap();
+    assert!(stdout.starts_with("Removing:\n"), "{}", stdout);
+    assert!(
+        stdout.contains("user__tool-1.1.0.4242.tmp (512 B)"),
+        "{}",
+        stdout
+    );
+    assert!(stdout.ends_with("Total: 512 B\n"), "{}", stdout);
+
+    assert!(!tools_dir.join("user__tool-1.1.0.4242.tmp").exists());
+    assert!(tools_dir.join("user__tool-1.0.0").exists());
+}